@@ -0,0 +1,653 @@
+// Arithmetic Expansion Spec:
+// - URL: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html#tag_18_06_04
+//
+// Only signed 64 bit integer math is supported, same as bash. Numbers
+// are decimal, octal with leading 0, hex with leading 0x or `base#n`
+
+use std::{fmt::Display, iter::Peekable, str::Chars};
+
+use crate::{engine::variables::Variables, errors::ShellError};
+
+use super::{Assignment, AssignmentValue};
+
+#[derive(Debug, Clone, PartialEq)]
+enum ArithToken {
+    Number(i64),
+    Name(String),
+    Plus,         // "+"
+    Minus,        // "-"
+    Star,         // "*"
+    Slash,        // "/"
+    Percent,      // "%"
+    Less,         // "<"
+    LessEqual,    // "<="
+    Greater,      // ">"
+    GreaterEqual, // ">="
+    Equal,        // "=="
+    NotEqual,     // "!="
+    Not,          // "!"
+    And,          // "&&"
+    Or,           // "||"
+    Question,     // "?"
+    Colon,        // ":"
+    LeftParen,    // "("
+    RightParen,   // ")"
+    // "=", "+=", "-=", "*=", "/=", "%=", holds the
+    // operator to apply before assigning, if any
+    Assign(Option<Box<ArithToken>>),
+}
+
+struct Evaluator<'a> {
+    tokens: Vec<ArithToken>,
+    idx: usize,
+    variables: &'a mut Variables,
+    // Set while evaluating operands which are short circuited, e.g.
+    // rhs of `0 && (i = 1)`, they are parsed but neither assign nor fail
+    skipping: bool,
+    // Variables holding expressions are evaluated too,
+    // e.g. `x=3+4; echo $((x*2))`, so `x=x` would never end
+    depth: usize,
+}
+
+const MAX_DEPTH: usize = 100;
+
+// Assignments within expression, e.g. `i += 1`,
+// are written back to variables
+pub fn evaluate(expr: &str, variables: &mut Variables) -> anyhow::Result<i64> {
+    evaluate_at_depth(expr, variables, 0)
+}
+
+fn evaluate_at_depth(expr: &str, variables: &mut Variables, depth: usize) -> anyhow::Result<i64> {
+    if depth > MAX_DEPTH {
+        return Err(arithmetic_error("expression recursion level exceeded"));
+    }
+
+    let tokens = tokenize(expr)?;
+
+    // `$(( ))` and `n=` on an integer variable both evaluate to 0
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+
+    let mut evaluator = Evaluator {
+        tokens,
+        idx: 0,
+        variables,
+        skipping: false,
+        depth,
+    };
+
+    let value = evaluator.assignment()?;
+
+    if let Some(token) = evaluator.peek() {
+        return Err(arithmetic_error(&format!(
+            "syntax error in expression (error token is \"{}\")",
+            token
+        )));
+    }
+
+    Ok(value)
+}
+
+fn tokenize(expr: &str) -> anyhow::Result<Vec<ArithToken>> {
+    let mut chars = expr.chars().peekable();
+    let mut tokens = vec![];
+
+    while let Some(ch) = chars.next() {
+        let mut followed_by = |next: char| chars.next_if_eq(&next).is_some();
+
+        let token = match ch {
+            ' ' | '\t' | '\n' => continue,
+            '=' if followed_by('=') => ArithToken::Equal,
+            '=' => ArithToken::Assign(None),
+            '!' if followed_by('=') => ArithToken::NotEqual,
+            '!' => ArithToken::Not,
+            '<' if followed_by('=') => ArithToken::LessEqual,
+            '<' => ArithToken::Less,
+            '>' if followed_by('=') => ArithToken::GreaterEqual,
+            '>' => ArithToken::Greater,
+            '&' if followed_by('&') => ArithToken::And,
+            '|' if followed_by('|') => ArithToken::Or,
+            '?' => ArithToken::Question,
+            ':' => ArithToken::Colon,
+            '+' => ArithToken::Plus,
+            '-' => ArithToken::Minus,
+            '*' => ArithToken::Star,
+            '/' => ArithToken::Slash,
+            '%' => ArithToken::Percent,
+            '(' => ArithToken::LeftParen,
+            ')' => ArithToken::RightParen,
+            ch if ch.is_ascii_digit() => {
+                let digits = take_while(ch, &mut chars, |ch| {
+                    ch.is_ascii_alphanumeric() || ch == '#' || ch == '@' || ch == '_'
+                });
+                ArithToken::Number(parse_number(&digits)?)
+            }
+            ch if ch.is_ascii_alphabetic() || ch == '_' => ArithToken::Name(take_while(
+                ch,
+                &mut chars,
+                |ch| ch.is_ascii_alphanumeric() || ch == '_',
+            )),
+            _ => {
+                return Err(arithmetic_error(&format!(
+                    "syntax error: operand expected (error token is \"{}\")",
+                    ch
+                )))
+            }
+        };
+
+        // Compound assignment operators
+        if matches!(
+            token,
+            ArithToken::Plus
+                | ArithToken::Minus
+                | ArithToken::Star
+                | ArithToken::Slash
+                | ArithToken::Percent
+        ) && chars.peek() == Some(&'=')
+        {
+            chars.next();
+            tokens.push(ArithToken::Assign(Some(Box::new(token))));
+            continue;
+        }
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+// Digits past 9 are a-z, then A-Z, then @ and _, letters are
+// case insensitive for bases up to 36 same as bash
+fn parse_number(number: &str) -> anyhow::Result<i64> {
+    let (base, digits) = if let Some((base, digits)) = number.split_once('#') {
+        let base = match base.parse::<u32>() {
+            Ok(base @ 2..=64) => base,
+            _ => return Err(arithmetic_error(&format!("{}: invalid arithmetic base", number))),
+        };
+        (base, digits)
+    } else if let Some(digits) =
+        number.strip_prefix("0x").or_else(|| number.strip_prefix("0X"))
+    {
+        (16, digits)
+    } else if number.len() > 1 && number.starts_with('0') {
+        (8, &number[1..])
+    } else {
+        (10, number)
+    };
+
+    if digits.is_empty() {
+        return Err(arithmetic_error(&format!("{}: invalid number", number)));
+    }
+
+    let mut value: i64 = 0;
+    for ch in digits.chars() {
+        let digit = match ch {
+            '0'..='9' => ch as u32 - '0' as u32,
+            'a'..='z' => ch as u32 - 'a' as u32 + 10,
+            'A'..='Z' if base <= 36 => ch as u32 - 'A' as u32 + 10,
+            'A'..='Z' => ch as u32 - 'A' as u32 + 36,
+            '@' => 62,
+            '_' => 63,
+            _ => return Err(arithmetic_error(&format!("{}: invalid number", number))),
+        };
+        if digit >= base {
+            return Err(arithmetic_error(&format!("{}: value too great for base", number)));
+        }
+
+        value = value
+            .checked_mul(base as i64)
+            .and_then(|value| value.checked_add(digit as i64))
+            .ok_or_else(|| arithmetic_error("integer overflow"))?;
+    }
+
+    Ok(value)
+}
+
+fn take_while(first: char, chars: &mut Peekable<Chars>, predicate: impl Fn(char) -> bool) -> String {
+    let mut word = String::from(first);
+    while let Some(ch) = chars.peek() {
+        if !predicate(*ch) {
+            break;
+        }
+
+        word.push(*ch);
+        chars.next();
+    }
+
+    word
+}
+
+// Grammar, lowest to highest precedence:
+// assignment     -> NAME ("=" | "+=" | "-=" | "*=" | "/=" | "%=") assignment | conditional
+// conditional    -> logical_or ("?" assignment ":" conditional)?
+// logical_or     -> logical_and ("||" logical_and)*
+// logical_and    -> equality ("&&" equality)*
+// equality       -> relational (("==" | "!=") relational)*
+// relational     -> additive (("<" | "<=" | ">" | ">=") additive)*
+// additive       -> multiplicative (("+" | "-") multiplicative)*
+// multiplicative -> unary (("*" | "/" | "%") unary)*
+// unary          -> ("+" | "-" | "!") unary | primary
+// primary        -> NUMBER | NAME | "(" assignment ")"
+impl<'a> Evaluator<'a> {
+    fn assignment(&mut self) -> anyhow::Result<i64> {
+        let (Some(ArithToken::Name(name)), Some(ArithToken::Assign(op))) =
+            (self.tokens.get(self.idx), self.tokens.get(self.idx + 1))
+        else {
+            return self.conditional();
+        };
+        let (name, op) = (name.clone(), op.clone());
+        self.idx += 2;
+
+        let rhs = self.assignment()?;
+        let value = match op {
+            Some(op) => {
+                let lhs = self.variable_value(&name)?;
+                self.apply(&op, lhs, rhs)?
+            }
+            None => rhs,
+        };
+
+        if !self.skipping {
+            self.variables.assign(&Assignment {
+                name,
+                value: AssignmentValue::Scalar(value.to_string()),
+                append: false,
+            })?;
+        }
+
+        Ok(value)
+    }
+
+    // Only the branch chosen by condition takes effect
+    fn conditional(&mut self) -> anyhow::Result<i64> {
+        let condition = self.logical_or()?;
+        if self.peek() != Some(&ArithToken::Question) {
+            return Ok(condition);
+        }
+        self.idx += 1;
+
+        let then_value = self.skipping_if(condition == 0, Self::assignment)?;
+        if self.peek() != Some(&ArithToken::Colon) {
+            return Err(arithmetic_error("`:' expected for conditional expression"));
+        }
+        self.idx += 1;
+        let else_value = self.skipping_if(condition != 0, Self::conditional)?;
+
+        Ok(if condition != 0 { then_value } else { else_value })
+    }
+
+    fn logical_or(&mut self) -> anyhow::Result<i64> {
+        let mut value = self.logical_and()?;
+
+        while self.peek() == Some(&ArithToken::Or) {
+            self.idx += 1;
+            let rhs = self.skipping_if(value != 0, Self::logical_and)?;
+            value = (value != 0 || rhs != 0) as i64;
+        }
+
+        Ok(value)
+    }
+
+    fn logical_and(&mut self) -> anyhow::Result<i64> {
+        let mut value = self.equality()?;
+
+        while self.peek() == Some(&ArithToken::And) {
+            self.idx += 1;
+            let rhs = self.skipping_if(value == 0, Self::equality)?;
+            value = (value != 0 && rhs != 0) as i64;
+        }
+
+        Ok(value)
+    }
+
+    fn equality(&mut self) -> anyhow::Result<i64> {
+        let mut value = self.relational()?;
+
+        while let Some(op @ (ArithToken::Equal | ArithToken::NotEqual)) = self.peek().cloned() {
+            self.idx += 1;
+            let rhs = self.relational()?;
+            value = self.apply(&op, value, rhs)?;
+        }
+
+        Ok(value)
+    }
+
+    fn relational(&mut self) -> anyhow::Result<i64> {
+        let mut value = self.additive()?;
+
+        while let Some(
+            op @ (ArithToken::Less
+            | ArithToken::LessEqual
+            | ArithToken::Greater
+            | ArithToken::GreaterEqual),
+        ) = self.peek().cloned()
+        {
+            self.idx += 1;
+            let rhs = self.additive()?;
+            value = self.apply(&op, value, rhs)?;
+        }
+
+        Ok(value)
+    }
+
+    fn additive(&mut self) -> anyhow::Result<i64> {
+        let mut value = self.multiplicative()?;
+
+        while let Some(op @ (ArithToken::Plus | ArithToken::Minus)) = self.peek().cloned() {
+            self.idx += 1;
+            let rhs = self.multiplicative()?;
+            value = self.apply(&op, value, rhs)?;
+        }
+
+        Ok(value)
+    }
+
+    fn multiplicative(&mut self) -> anyhow::Result<i64> {
+        let mut value = self.unary()?;
+
+        while let Some(op @ (ArithToken::Star | ArithToken::Slash | ArithToken::Percent)) =
+            self.peek().cloned()
+        {
+            self.idx += 1;
+            let rhs = self.unary()?;
+            value = self.apply(&op, value, rhs)?;
+        }
+
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<i64> {
+        match self.peek() {
+            Some(ArithToken::Plus) => {
+                self.idx += 1;
+                self.unary()
+            }
+            Some(ArithToken::Minus) => {
+                self.idx += 1;
+                let value = self.unary()?;
+                self.apply(&ArithToken::Minus, 0, value)
+            }
+            Some(ArithToken::Not) => {
+                self.idx += 1;
+                Ok((self.unary()? == 0) as i64)
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> anyhow::Result<i64> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(arithmetic_error("syntax error: operand expected"));
+        };
+        self.idx += 1;
+
+        match token {
+            ArithToken::Number(number) => Ok(number),
+            ArithToken::Name(name) => self.variable_value(&name),
+            ArithToken::LeftParen => {
+                let value = self.assignment()?;
+                if self.peek() != Some(&ArithToken::RightParen) {
+                    return Err(arithmetic_error("missing `)'"));
+                }
+                self.idx += 1;
+                Ok(value)
+            }
+            token => Err(arithmetic_error(&format!(
+                "syntax error: operand expected (error token is \"{}\")",
+                token
+            ))),
+        }
+    }
+
+    // Value of variable is an expression itself, plain
+    // numbers being simplest ones
+    fn variable_value(&mut self, name: &str) -> anyhow::Result<i64> {
+        // Unset and empty variables are treated as 0
+        let value = self.variables.get_str(name).unwrap_or_default();
+        if value.trim().is_empty() || self.skipping {
+            return Ok(0);
+        }
+
+        evaluate_at_depth(&value, self.variables, self.depth + 1)
+    }
+
+    fn skipping_if(
+        &mut self,
+        skip: bool,
+        operand: impl FnOnce(&mut Self) -> anyhow::Result<i64>,
+    ) -> anyhow::Result<i64> {
+        let was_skipping = self.skipping;
+        self.skipping |= skip;
+        let value = operand(self);
+        self.skipping = was_skipping;
+        value
+    }
+
+    // Skipped operands never fail, their value is not used anyway
+    fn apply(&self, op: &ArithToken, lhs: i64, rhs: i64) -> anyhow::Result<i64> {
+        match apply(op, lhs, rhs) {
+            Err(_) if self.skipping => Ok(0),
+            result => result,
+        }
+    }
+
+    fn peek(&self) -> Option<&ArithToken> {
+        self.tokens.get(self.idx)
+    }
+}
+
+impl Display for ArithToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let token_str = match self {
+            ArithToken::Number(number) => return write!(f, "{}", number),
+            ArithToken::Name(name) => return write!(f, "{}", name),
+            ArithToken::Assign(Some(op)) => return write!(f, "{}=", op),
+            ArithToken::Assign(None) => "=",
+            ArithToken::Plus => "+",
+            ArithToken::Minus => "-",
+            ArithToken::Star => "*",
+            ArithToken::Slash => "/",
+            ArithToken::Percent => "%",
+            ArithToken::Less => "<",
+            ArithToken::LessEqual => "<=",
+            ArithToken::Greater => ">",
+            ArithToken::GreaterEqual => ">=",
+            ArithToken::Equal => "==",
+            ArithToken::NotEqual => "!=",
+            ArithToken::Not => "!",
+            ArithToken::And => "&&",
+            ArithToken::Or => "||",
+            ArithToken::Question => "?",
+            ArithToken::Colon => ":",
+            ArithToken::LeftParen => "(",
+            ArithToken::RightParen => ")",
+        };
+
+        write!(f, "{}", token_str)
+    }
+}
+
+// Overflow is an error instead of wrapping around
+fn apply(op: &ArithToken, lhs: i64, rhs: i64) -> anyhow::Result<i64> {
+    if rhs == 0 && matches!(op, ArithToken::Slash | ArithToken::Percent) {
+        return Err(arithmetic_error("division by 0"));
+    }
+
+    let result = match op {
+        ArithToken::Plus => lhs.checked_add(rhs),
+        ArithToken::Minus => lhs.checked_sub(rhs),
+        ArithToken::Star => lhs.checked_mul(rhs),
+        ArithToken::Slash => lhs.checked_div(rhs),
+        ArithToken::Percent => lhs.checked_rem(rhs),
+        ArithToken::Less => Some((lhs < rhs) as i64),
+        ArithToken::LessEqual => Some((lhs <= rhs) as i64),
+        ArithToken::Greater => Some((lhs > rhs) as i64),
+        ArithToken::GreaterEqual => Some((lhs >= rhs) as i64),
+        ArithToken::Equal => Some((lhs == rhs) as i64),
+        ArithToken::NotEqual => Some((lhs != rhs) as i64),
+        _ => {
+            return Err(arithmetic_error(&format!(
+                "syntax error: invalid operator \"{}\"",
+                op
+            )))
+        }
+    };
+
+    result.ok_or_else(|| arithmetic_error("integer overflow"))
+}
+
+fn arithmetic_error(message: &str) -> anyhow::Error {
+    ShellError::ArithmeticError(message.to_string()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        command::{Assignment, AssignmentValue},
+        engine::variables::Variables,
+    };
+
+    use super::evaluate;
+
+    fn check(expr: &str) -> i64 {
+        let mut variables = Variables::new();
+        evaluate(expr, &mut variables).expect("evaluation should have succeeded")
+    }
+
+    #[test]
+    fn test_evaluation_of_literals() {
+        assert_eq!(check("42"), 42);
+        assert_eq!(check(""), 0);
+        assert_eq!(check("-7"), -7);
+        assert_eq!(check("0"), 0);
+        assert_eq!(check("010"), 8);
+        assert_eq!(check("0x10"), 16);
+        assert_eq!(check("0XfF"), 255);
+        assert_eq!(check("2#101"), 5);
+        assert_eq!(check("36#zZ"), 1295);
+        assert_eq!(check("64#_"), 63);
+    }
+
+    #[test]
+    fn test_evaluation_respects_precedence() {
+        assert_eq!(check("5+5"), 10);
+        assert_eq!(check("2+3*4"), 14);
+        assert_eq!(check("(2+3)*4"), 20);
+        assert_eq!(check("10 - 4 - 3"), 3);
+        assert_eq!(check("17 % 5 * 2"), 4);
+        assert_eq!(check("-(2+3)"), -5);
+    }
+
+    #[test]
+    fn test_evaluation_of_variables() {
+        let mut variables = Variables::new();
+        variables
+            .assign(&Assignment {
+                name: "n".into(),
+                value: AssignmentValue::Scalar("6".into()),
+                append: false,
+            })
+            .expect("assignment should have succeeded");
+
+        assert_eq!(evaluate("n*7", &mut variables).unwrap(), 42);
+        assert_eq!(evaluate("unset + 1", &mut variables).unwrap(), 1);
+
+        // Variables holding expressions are evaluated as well
+        variables
+            .assign(&Assignment {
+                name: "x".into(),
+                value: AssignmentValue::Scalar("3+n".into()),
+                append: false,
+            })
+            .expect("assignment should have succeeded");
+        assert_eq!(evaluate("x*2", &mut variables).unwrap(), 18);
+    }
+
+    #[test]
+    fn test_evaluation_of_assignments() {
+        let mut variables = Variables::new();
+
+        assert_eq!(evaluate("i = 5", &mut variables).unwrap(), 5);
+        assert_eq!(evaluate("i = i + 1", &mut variables).unwrap(), 6);
+        assert_eq!(evaluate("i *= 2", &mut variables).unwrap(), 12);
+        assert_eq!(evaluate("j = (i -= 2) + 1", &mut variables).unwrap(), 11);
+        assert_eq!(variables.get_str("i"), Some("10".into()));
+        assert_eq!(variables.get_str("j"), Some("11".into()));
+    }
+
+    #[test]
+    fn test_evaluation_errors() {
+        let mut variables = Variables::new();
+
+        assert!(evaluate("1/0", &mut variables).is_err());
+        assert!(evaluate("1 +", &mut variables).is_err());
+        assert!(evaluate("(1 + 2", &mut variables).is_err());
+        assert!(evaluate("9223372036854775807 + 1", &mut variables).is_err());
+        assert!(evaluate("1 = 2", &mut variables).is_err());
+        assert!(evaluate("i %= 0", &mut variables).is_err());
+        assert!(evaluate("1 ? 2", &mut variables).is_err());
+        assert!(evaluate("1 & 2", &mut variables).is_err());
+        assert!(evaluate("09", &mut variables).is_err());
+        assert!(evaluate("0x", &mut variables).is_err());
+        assert!(evaluate("65#1", &mut variables).is_err());
+        assert!(evaluate("2#102", &mut variables).is_err());
+
+        variables
+            .assign(&Assignment {
+                name: "self".into(),
+                value: AssignmentValue::Scalar("self + 1".into()),
+                append: false,
+            })
+            .expect("assignment should have succeeded");
+        let err = evaluate("self", &mut variables).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "dss: arithmetic error: expression recursion level exceeded\n"
+        );
+
+        let err = evaluate("1 2", &mut variables).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "dss: arithmetic error: syntax error in expression (error token is \"2\")\n"
+        );
+        let err = evaluate("-9223372036854775807 - 2", &mut variables).unwrap_err();
+        assert_eq!(err.to_string(), "dss: arithmetic error: integer overflow\n");
+    }
+
+    #[test]
+    fn test_evaluation_of_comparisons() {
+        assert_eq!(check("1 < 2"), 1);
+        assert_eq!(check("2 <= 1"), 0);
+        assert_eq!(check("3 > 2 == 1"), 1);
+        assert_eq!(check("2 >= 2"), 1);
+        assert_eq!(check("1 + 1 != 2"), 0);
+        assert_eq!(check("!5"), 0);
+        assert_eq!(check("!!5"), 1);
+    }
+
+    #[test]
+    fn test_evaluation_of_logical_ops() {
+        assert_eq!(check("2 && 3"), 1);
+        assert_eq!(check("0 || 0"), 0);
+        assert_eq!(check("1 || 0 && 0"), 1);
+
+        // Short circuited operands neither assign nor fail
+        let mut variables = Variables::new();
+        assert_eq!(evaluate("0 && (i = 1)", &mut variables).unwrap(), 0);
+        assert_eq!(evaluate("1 || 1 / 0", &mut variables).unwrap(), 1);
+        assert_eq!(variables.get_str("i"), None);
+    }
+
+    #[test]
+    fn test_evaluation_of_conditionals() {
+        let mut variables = Variables::new();
+
+        assert_eq!(evaluate("1 ? 2 : 3", &mut variables).unwrap(), 2);
+        assert_eq!(evaluate("0 ? 2 : 0 ? 3 : 4", &mut variables).unwrap(), 4);
+        assert_eq!(evaluate("0 ? (a = 1) : (b = 2)", &mut variables).unwrap(), 2);
+        assert_eq!(variables.get_str("a"), None);
+        assert_eq!(variables.get_str("b"), Some("2".into()));
+        assert_eq!(evaluate("x = 1 < 2 ? 10 : 1 / 0", &mut variables).unwrap(), 10);
+    }
+}
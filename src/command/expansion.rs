@@ -0,0 +1,1472 @@
+// Word Expansions Spec:
+// - URL: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html#tag_18_06
+//
+// Expansions are done in the order of:
+// tilde -> parameter -> command substitution -> arithmetic
+// -> field splitting -> pathname -> quote removal
+//
+// First four are done in a single pass from left to right,
+// every char produced remembers whether it was quoted, as
+// only unquoted ones take part in splitting and globbing
+
+use nix::unistd::User;
+
+use crate::{engine::variables::Variables, errors::ShellError, interrupt};
+
+use super::{
+    arithmetic,
+    glob::{self, GlobOptions},
+    Assignment, AssignmentValue,
+};
+
+// Chars fields are split on when IFS is unset
+const DEFAULT_IFS: &str = " \t\n";
+
+// IFS chars which are also whitespace, runs of them make one delimiter
+const IFS_WHITESPACE: &str = " \t\n";
+
+// Shell state needed while expanding words
+pub trait ExpansionContext {
+    fn variables(&mut self) -> &mut Variables;
+
+    fn glob_options(&self) -> GlobOptions;
+
+    // Bytes a word can expand to, None lifts the limit
+    fn max_expansion_size(&self) -> Option<usize>;
+
+    // `$1` onwards, of script being run
+    fn positional_params(&self) -> &[String];
+
+    // Exit status of the last command, for `$?`
+    fn last_status(&self) -> i32;
+
+    // Process id of the last pipeline started in background, for `$!`
+    fn background_pid(&self) -> Option<i32>;
+
+    // Unset parameters fail to expand, with `set -u`
+    fn nounset(&self) -> bool;
+
+    // Output of running command in a subshell, for `$(command)`
+    fn command_output(&mut self, command: &str) -> anyhow::Result<String>;
+
+    // Path output of command run in background can be read from,
+    // for `<(command)`
+    fn process_substitution(&mut self, command: &str) -> anyhow::Result<String>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ExpandedChar {
+    ch: char,
+    quoted: bool,
+    // Only chars produced by unquoted expansions are split
+    // on IFS, not the ones written literally in word
+    split: bool,
+    // Put between elements by `$@` and `${name[@]}`, always
+    // delimits a field irrespective of IFS
+    separator: bool,
+}
+
+// Word after first four expansions
+#[derive(Debug, Default, PartialEq)]
+struct Field {
+    chars: Vec<ExpandedChar>,
+    // Quoted empty words, e.g. `""`, still make a field
+    has_quotes: bool,
+    // Set by `$@` and `${name[@]}` with no elements, which make
+    // no field at all, quotes around them notwithstanding
+    no_elements: bool,
+}
+
+impl Field {
+    // Result of an expansion, it is quoted only if the
+    // expansion itself was inside double quotes
+    fn unquoted(value: &str) -> Self {
+        let mut field = Self::default();
+        field.push_str(value, false);
+        field
+    }
+
+    fn push_str(&mut self, value: &str, quoted: bool) {
+        self.chars.extend(value.chars().map(|ch| ExpandedChar {
+            ch,
+            quoted,
+            split: false,
+            separator: false,
+        }));
+    }
+
+    // Unquoted expansions take part in field splitting
+    fn push_expansion(&mut self, value: &str, in_double_quotes: bool) {
+        self.chars.extend(value.chars().map(|ch| ExpandedChar {
+            ch,
+            quoted: in_double_quotes,
+            split: !in_double_quotes,
+            separator: false,
+        }));
+    }
+
+    // Bytes text of field takes
+    fn size(&self) -> usize {
+        self.chars.iter().map(|expanded| expanded.ch.len_utf8()).sum()
+    }
+
+    // Quote removal, quotes are not part of chars anymore
+    fn to_text(&self) -> String {
+        self.chars.iter().map(|expanded| expanded.ch).collect()
+    }
+
+    // Quoted chars are quoted again for glob, so that they stay literal
+    fn to_pattern(&self) -> String {
+        let mut pattern = String::new();
+        for expanded in &self.chars {
+            match (expanded.quoted, expanded.ch) {
+                (false, ch) | (true, ch @ '/') => pattern.push(ch),
+                (true, '\'') => pattern.push_str("\"'\""),
+                (true, ch) => pattern.push_str(&format!("'{}'", ch)),
+            }
+        }
+        pattern
+    }
+}
+
+// Expands word into fields, pathname expansion is skipped if no
+// glob options are given, e.g. for commands prefixed with `noglob`
+pub fn expand_word(
+    word: &str,
+    glob_options: Option<&GlobOptions>,
+    ctx: &mut impl ExpansionContext,
+) -> anyhow::Result<Vec<String>> {
+    let field = expand_parts(word, ctx)?;
+    check_expansion_size(word, &field, ctx)?;
+    let ifs = field_separators(ctx);
+
+    let mut words = vec![];
+    for field in split_fields(field, &ifs) {
+        match glob_options {
+            Some(glob_options) => words.extend(expand_pathname(&field, glob_options)?),
+            None => words.push(field.to_text()),
+        }
+    }
+
+    Ok(words)
+}
+
+// Assignment values are neither split nor globbed
+pub fn expand_assignment_value(
+    value: &str,
+    ctx: &mut impl ExpansionContext,
+) -> anyhow::Result<String> {
+    let field = expand_parts(value, ctx)?;
+    check_expansion_size(value, &field, ctx)?;
+    Ok(field.to_text())
+}
+
+// Words can keep growing, e.g. `x=$x$x` run over and over
+fn check_expansion_size(
+    word: &str,
+    field: &Field,
+    ctx: &mut impl ExpansionContext,
+) -> anyhow::Result<()> {
+    match ctx.max_expansion_size() {
+        Some(max) if field.size() > max => Err(ShellError::ExpansionError(format!(
+            "{}: expands to more than {} bytes (expandmaxsize)",
+            word, max
+        ))
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+// Tilde, parameter, command substitution and arithmetic expansions
+fn expand_parts(word: &str, ctx: &mut impl ExpansionContext) -> anyhow::Result<Field> {
+    let chars: Vec<char> = word.chars().collect();
+    let mut field = Field::default();
+    let mut in_double_quotes = false;
+    let mut no_elements_in_quotes = false;
+
+    let mut idx = 0;
+    if let Some((home, len)) = expand_tilde(&chars, ctx) {
+        field.push_str(&home, true);
+        idx = len;
+    }
+
+    while idx < chars.len() {
+        let ch = chars[idx];
+        idx += 1;
+
+        match ch {
+            '\'' if !in_double_quotes => {
+                field.has_quotes = true;
+                while idx < chars.len() && chars[idx] != '\'' {
+                    field.push_str(&chars[idx].to_string(), true);
+                    idx += 1;
+                }
+                idx += 1;
+            }
+            '"' => {
+                field.has_quotes = true;
+                in_double_quotes = !in_double_quotes;
+            }
+            '\\' => {
+                let Some(&next_ch) = chars.get(idx) else {
+                    field.push_str("\\", in_double_quotes);
+                    continue;
+                };
+
+                // Within double quotes backslash only escapes these
+                if in_double_quotes && !matches!(next_ch, '$' | '`' | '"' | '\\') {
+                    field.push_str("\\", true);
+                    continue;
+                }
+
+                field.push_str(&next_ch.to_string(), true);
+                idx += 1;
+            }
+            '$' => {
+                let (value, len) = expand_dollar(&chars[idx..], in_double_quotes, ctx)?;
+                idx += len;
+                match value {
+                    Some(value) => {
+                        no_elements_in_quotes |= in_double_quotes && value.no_elements;
+                        field.has_quotes |= value.has_quotes;
+                        field
+                            .chars
+                            .extend(value.chars.into_iter().map(|expanded| ExpandedChar {
+                                ch: expanded.ch,
+                                quoted: expanded.quoted || in_double_quotes,
+                                split: !expanded.quoted && !in_double_quotes,
+                                separator: expanded.separator,
+                            }));
+                    }
+                    None => field.push_str("$", in_double_quotes),
+                }
+            }
+            '`' => {
+                let len = chars[idx..]
+                    .iter()
+                    .position(|ch| *ch == '`')
+                    .ok_or_else(|| bad_substitution(word))?;
+                let command: String = chars[idx..idx + len].iter().collect();
+                idx += len + 1;
+
+                let output = substitute_command(&command, ctx)?;
+                field.push_expansion(&output, in_double_quotes);
+            }
+            // Path is a single field, never split or globbed
+            '<' if !in_double_quotes && chars.get(idx) == Some(&'(') => {
+                let len = matching_bracket(&chars[idx..]).ok_or_else(|| bad_substitution(word))?;
+                let command: String = chars[idx + 1..idx + len].iter().collect();
+                idx += len + 1;
+
+                let path = ctx.process_substitution(&command)?;
+                field.push_str(&path, true);
+            }
+            ch => field.push_str(&ch.to_string(), in_double_quotes),
+        }
+    }
+
+    if no_elements_in_quotes && field.chars.is_empty() {
+        field.has_quotes = false;
+    }
+
+    Ok(field)
+}
+
+// Tilde Expansion Spec:
+// - URL: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html#tag_18_06_01
+//
+// Returns home directory along with length of tilde prefix, `~`
+// is expanded only if it is unquoted and starts the word. `~name`
+// is home directory of user name, left as is for unknown users
+fn expand_tilde(chars: &[char], ctx: &mut impl ExpansionContext) -> Option<(String, usize)> {
+    if chars.first() != Some(&'~') {
+        return None;
+    }
+
+    let len = chars
+        .iter()
+        .position(|ch| *ch == '/')
+        .unwrap_or(chars.len());
+    let login_name: String = chars[1..len].iter().collect();
+
+    if login_name.is_empty() {
+        let home = ctx.variables().get_str("HOME")?;
+        return Some((home, 1));
+    }
+
+    // Any quoting or expansion in prefix makes it a plain word
+    if !login_name
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || "._-".contains(ch))
+    {
+        return None;
+    }
+
+    let user = User::from_name(&login_name).ok()??;
+    Some((user.dir.to_string_lossy().into_owned(), len))
+}
+
+// Expansions starting with `$`, chars are the ones after it. Returns
+// value, if any, along with count of chars used up by expansion.
+fn expand_dollar(
+    chars: &[char],
+    in_double_quotes: bool,
+    ctx: &mut impl ExpansionContext,
+) -> anyhow::Result<(Option<Field>, usize)> {
+    let word = || format!("${}", chars.iter().collect::<String>());
+
+    match chars.first() {
+        // `$((expression))`
+        Some('(') if chars.get(1) == Some(&'(') => {
+            let len = matching_bracket(chars).ok_or_else(|| bad_substitution(&word()))?;
+            if chars[len - 1] != ')' {
+                return Err(bad_substitution(&word()));
+            }
+
+            let expr: String = chars[2..len - 1].iter().collect();
+            let value = expand_arithmetic(&expr, ctx)?;
+            Ok((Some(Field::unquoted(&value)), len + 1))
+        }
+        // `$(command)`
+        Some('(') => {
+            let len = matching_bracket(chars).ok_or_else(|| bad_substitution(&word()))?;
+            let command: String = chars[1..len].iter().collect();
+            let output = substitute_command(&command, ctx)?;
+            Ok((Some(Field::unquoted(&output)), len + 1))
+        }
+        // `${name}`, `${name:-word}` and other forms
+        Some('{') => {
+            let len = matching_bracket(chars).ok_or_else(|| bad_substitution(&word()))?;
+            let expr: String = chars[1..len].iter().collect();
+            let field = expand_braced_parameter(&expr, in_double_quotes, ctx)?;
+            Ok((Some(field), len + 1))
+        }
+        // `$name`
+        Some(ch) if ch.is_ascii_alphabetic() || *ch == '_' => {
+            let len = chars
+                .iter()
+                .position(|ch| !ch.is_ascii_alphanumeric() && *ch != '_')
+                .unwrap_or(chars.len());
+            let name: String = chars[..len].iter().collect();
+            let value = lookup_parameter(&name, ctx);
+            let value = value_or_unbound(&name, value, ctx)?;
+            Ok((Some(Field::unquoted(&value)), len))
+        }
+        // `$1` to `$9`, `$#`, `$?` and `$!`, later positional
+        // parameters need braces, e.g. `${10}`
+        Some(ch @ ('1'..='9' | '#' | '?' | '!')) => {
+            let name = ch.to_string();
+            let value = lookup_parameter(&name, ctx);
+            let value = value_or_unbound(&name, value, ctx)?;
+            Ok((Some(Field::unquoted(&value)), 1))
+        }
+        Some(ch @ ('@' | '*')) => {
+            let params = ctx.positional_params().to_vec();
+            Ok((Some(expand_elements(&params, *ch, in_double_quotes, ctx)), 1))
+        }
+        // Lone `$` stays as it is
+        _ => Ok((None, 0)),
+    }
+}
+
+// Parameter Expansion Spec:
+// - URL: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html#tag_18_06_02
+//
+// Expression is what is within braces, e.g. `name:-word`. With `:`
+// operators treat null parameters same as unset ones, without it
+// only unset ones. Word is expanded only when it gets used
+fn expand_braced_parameter(
+    expr: &str,
+    in_double_quotes: bool,
+    ctx: &mut impl ExpansionContext,
+) -> anyhow::Result<Field> {
+    // `${#name}`, length of value, `${#name[@]}` and `${#name[*]}`
+    // count of elements and `${#name[index]}` length of an element
+    if let Some(name) = expr.strip_prefix('#').filter(|name| !name.is_empty()) {
+        if let Some((name, subscript, "")) = split_subscript(name) {
+            let count = match subscript {
+                "@" | "*" => ctx.variables().get_array(name).unwrap_or_default().len(),
+                index => {
+                    let value = lookup_element(name, index, ctx)?;
+                    value_or_unbound(name, value, ctx)?.chars().count()
+                }
+            };
+            return Ok(Field::unquoted(&count.to_string()));
+        }
+
+        if !is_parameter_name(name) {
+            return Err(bad_substitution(&format!("${{{}}}", expr)));
+        }
+
+        let value = lookup_parameter(name, ctx);
+        let value = value_or_unbound(name, value, ctx)?;
+        return Ok(Field::unquoted(&value.chars().count().to_string()));
+    }
+
+    let name_len = match expr.chars().next() {
+        Some('#') if expr.len() == 1 => 1,
+        Some('?' | '!') => 1,
+        Some(ch) if ch.is_ascii_digit() => expr
+            .find(|ch: char| !ch.is_ascii_digit())
+            .unwrap_or(expr.len()),
+        _ => expr
+            .find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '_')
+            .unwrap_or(expr.len()),
+    };
+    let (name, subscript, rest) = match split_subscript(expr) {
+        Some((name, subscript, rest)) => (name, Some(subscript), rest),
+        None => (&expr[..name_len], None, &expr[name_len..]),
+    };
+
+    if !is_parameter_name(name) {
+        return Err(bad_substitution(&format!("${{{}}}", expr)));
+    }
+
+    // `${name[@]}` and `${name[*]}` are same as `$@` and `$*`,
+    // only over elements of array instead
+    if let Some(ch @ ('@' | '*')) = subscript.and_then(|subscript| subscript.parse().ok()) {
+        if !rest.is_empty() {
+            return Err(bad_substitution(&format!("${{{}}}", expr)));
+        }
+
+        let elements = ctx.variables().get_array(name).unwrap_or_default();
+        return Ok(expand_elements(&elements, ch, in_double_quotes, ctx));
+    }
+
+    let value = match subscript {
+        Some(index) => lookup_element(name, index, ctx)?,
+        None => lookup_parameter(name, ctx),
+    };
+    if rest.is_empty() {
+        return Ok(Field::unquoted(&value_or_unbound(name, value, ctx)?));
+    }
+
+    // `${name%pattern}` and `${name%%pattern}` remove suffix,
+    // `${name#pattern}` and `${name##pattern}` remove prefix
+    if let Some(side @ ('%' | '#')) = rest.chars().next() {
+        let longest = rest[1..].starts_with(side);
+        let pattern = expand_parts(&rest[1 + usize::from(longest)..], ctx)?.to_pattern();
+        let value = value_or_unbound(name, value, ctx)?;
+        return Ok(Field::unquoted(&remove_pattern(
+            &value, &pattern, side, longest,
+        )));
+    }
+
+    // `${name/pattern/string}` replaces first match of pattern,
+    // `//` replaces all of them, `/#` and `/%` only the ones
+    // at start and end of value. Without string matches get removed
+    if let Some(substitution) = rest.strip_prefix('/') {
+        let (mode, substitution) = match substitution.chars().next() {
+            Some(mode @ ('/' | '#' | '%')) => (Some(mode), &substitution[1..]),
+            _ => (None, substitution),
+        };
+        let (pattern, replacement) = split_unquoted(substitution, '/');
+
+        let pattern = expand_parts(pattern, ctx)?.to_pattern();
+        let replacement = match replacement {
+            Some(replacement) => expand_parts(replacement, ctx)?.to_text(),
+            None => String::new(),
+        };
+        let value = value_or_unbound(name, value, ctx)?;
+        return Ok(Field::unquoted(&replace_pattern(
+            &value,
+            &pattern,
+            &replacement,
+            mode,
+        )));
+    }
+
+    // `${name^pattern}` and `${name,pattern}` convert first char to
+    // upper and lower case, `^^` and `,,` all chars. Only chars matching
+    // pattern get converted, without it any char does
+    if let Some(conversion @ ('^' | ',')) = rest.chars().next() {
+        let all = rest[1..].starts_with(conversion);
+        let pattern = match &rest[1 + usize::from(all)..] {
+            "" => "?".to_string(),
+            pattern => expand_parts(pattern, ctx)?.to_pattern(),
+        };
+        let value = value_or_unbound(name, value, ctx)?;
+        return Ok(Field::unquoted(&convert_case(
+            &value,
+            &pattern,
+            conversion == '^',
+            all,
+        )));
+    }
+
+    // `${name:offset}` and `${name:offset:length}`
+    if let Some(range) = rest
+        .strip_prefix(':')
+        .filter(|range| !range.is_empty() && !range.starts_with(['-', '=', '?', '+']))
+    {
+        let value = value_or_unbound(name, value, ctx)?;
+        return Ok(Field::unquoted(&substring(&value, range, ctx)?));
+    }
+
+    let (check_null, rest) = match rest.strip_prefix(':') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+
+    let mut rest_chars = rest.chars();
+    let operator = rest_chars.next();
+    let word = rest_chars.as_str();
+
+    let value = value.filter(|value| !(check_null && value.is_empty()));
+
+    match (operator, value) {
+        (Some('-' | '=' | '?'), Some(value)) => Ok(Field::unquoted(&value)),
+        (Some('-'), None) => expand_parts(word, ctx),
+        // Elements are not assigned by expansions
+        (Some('='), None) if subscript.is_none() => {
+            let field = expand_parts(word, ctx)?;
+            let assignment = Assignment {
+                name: name.to_string(),
+                value: AssignmentValue::Scalar(field.to_text()),
+                append: false,
+            };
+            ctx.variables().assign(&assignment)?;
+            Ok(field)
+        }
+        (Some('?'), None) => {
+            let message = match expand_parts(word, ctx)?.to_text() {
+                message if message.is_empty() => "parameter null or not set".to_string(),
+                message => message,
+            };
+            Err(ShellError::ExpansionError(format!("{}: {}", name, message)).into())
+        }
+        (Some('+'), Some(_)) => expand_parts(word, ctx),
+        (Some('+'), None) => Ok(Field::default()),
+        _ => Err(bad_substitution(&format!("${{{}}}", expr))),
+    }
+}
+
+// Unset parameters expand to nothing, unless nounset is set
+fn value_or_unbound(
+    name: &str,
+    value: Option<String>,
+    ctx: &impl ExpansionContext,
+) -> anyhow::Result<String> {
+    match value {
+        Some(value) => Ok(value),
+        None if ctx.nounset() => {
+            Err(ShellError::ExpansionError(format!("{}: unbound variable", name)).into())
+        }
+        None => Ok(String::new()),
+    }
+}
+
+// Splits `name[subscript]rest` of an expression, only
+// variables can be subscripted
+fn split_subscript(expr: &str) -> Option<(&str, &str, &str)> {
+    let (name, rest) = expr.split_once('[')?;
+    let (subscript, rest) = rest.split_once(']')?;
+    super::is_valid_name(name).then_some((name, subscript, rest))
+}
+
+// Element of array at index, which is an arithmetic expression.
+// Negative indexes count from end of array
+fn lookup_element(
+    name: &str,
+    index: &str,
+    ctx: &mut impl ExpansionContext,
+) -> anyhow::Result<Option<String>> {
+    let index = evaluate_arithmetic(index, ctx)?;
+    let elements = ctx.variables().get_array(name).unwrap_or_default();
+    let index = match index {
+        index if index < 0 => elements.len().checked_sub(index.unsigned_abs() as usize),
+        index => Some(index as usize),
+    };
+    Ok(index.and_then(|index| elements.get(index).cloned()))
+}
+
+// Names of variables, positional parameters, `#` and `?`
+fn is_parameter_name(name: &str) -> bool {
+    super::is_valid_name(name)
+        || name == "#"
+        || name == "?"
+        || name == "!"
+        || name.parse::<usize>().is_ok_and(|position| position > 0)
+}
+
+// Removes shortest or longest prefix, with `#` as side, or suffix,
+// with `%` as side, of value matching pattern
+fn remove_pattern(value: &str, pattern: &str, side: char, longest: bool) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let mut lens: Vec<usize> = (0..=chars.len()).collect();
+    if longest {
+        lens.reverse();
+    }
+
+    for len in lens {
+        let (removed, rest) = match side {
+            '#' => (&chars[..len], &chars[len..]),
+            _ => (&chars[chars.len() - len..], &chars[..chars.len() - len]),
+        };
+
+        if glob::matches_text(pattern, &removed.iter().collect::<String>()) {
+            return rest.iter().collect();
+        }
+    }
+
+    value.to_string()
+}
+
+// Longest matches are replaced, anchored ones, with `#` or `%` as
+// mode, can be empty too, e.g. `${name/#/prefix}`
+fn replace_pattern(value: &str, pattern: &str, replacement: &str, mode: Option<char>) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let text = |start: usize, end: usize| chars[start..end].iter().collect::<String>();
+    let matches = |start: usize, end: usize| glob::matches_text(pattern, &text(start, end));
+
+    match mode {
+        Some('#') => (0..=chars.len())
+            .rev()
+            .find(|end| matches(0, *end))
+            .map(|end| format!("{}{}", replacement, text(end, chars.len()))),
+        Some('%') => (0..=chars.len())
+            .find(|start| matches(*start, chars.len()))
+            .map(|start| format!("{}{}", text(0, start), replacement)),
+        _ => {
+            let mut result = String::new();
+            let mut idx = 0;
+            while idx < chars.len() {
+                let Some(end) = (idx + 1..=chars.len()).rev().find(|end| matches(idx, *end)) else {
+                    result.push(chars[idx]);
+                    idx += 1;
+                    continue;
+                };
+
+                result.push_str(replacement);
+                idx = end;
+
+                if mode.is_none() {
+                    result.push_str(&text(idx, chars.len()));
+                    break;
+                }
+            }
+            Some(result)
+        }
+    }
+    .unwrap_or_else(|| value.to_string())
+}
+
+// Case mapping is of Unicode, so a char can become more
+// than one, e.g. `ß` becomes `SS` in upper case
+fn convert_case(value: &str, pattern: &str, upper: bool, all: bool) -> String {
+    let mut result = String::new();
+
+    for (idx, ch) in value.chars().enumerate() {
+        if (idx > 0 && !all) || !glob::matches_text(pattern, &ch.to_string()) {
+            result.push(ch);
+        } else if upper {
+            result.extend(ch.to_uppercase());
+        } else {
+            result.extend(ch.to_lowercase());
+        }
+    }
+
+    result
+}
+
+// Splits word at first occurrence of delimiter which is
+// neither quoted nor escaped with a backslash
+fn split_unquoted(word: &str, delimiter: char) -> (&str, Option<&str>) {
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (idx, ch) in word.char_indices() {
+        match (quote, ch) {
+            _ if escaped => escaped = false,
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (None, '"' | '\'') => quote = Some(ch),
+            (None, ch) if ch == delimiter => return (&word[..idx], Some(&word[idx + 1..])),
+            _ => {}
+        }
+    }
+
+    (word, None)
+}
+
+// Range is `offset` or `offset:length`, both arithmetic expressions.
+// Negative offset counts back from end of value, negative length
+// leaves out that many chars at the end. Chars are counted, not bytes
+fn substring(value: &str, range: &str, ctx: &mut impl ExpansionContext) -> anyhow::Result<String> {
+    let (offset, length) = match range.split_once(':') {
+        Some((offset, length)) => (offset, Some(length)),
+        None => (range, None),
+    };
+
+    let chars: Vec<char> = value.chars().collect();
+    let len = chars.len() as i64;
+
+    let offset = evaluate_arithmetic(offset, ctx)?;
+    let start = if offset < 0 { len + offset } else { offset };
+    if !(0..=len).contains(&start) {
+        return Ok(String::new());
+    }
+
+    let Some(length) = length else {
+        return Ok(chars[start as usize..].iter().collect());
+    };
+
+    let end = match evaluate_arithmetic(length, ctx)? {
+        value if value < 0 => len + value,
+        value => start.saturating_add(value).min(len),
+    };
+    if end < start {
+        let message = format!("{}: substring expression < 0", length);
+        return Err(ShellError::ExpansionError(message).into());
+    }
+
+    Ok(chars[start as usize..end as usize].iter().collect())
+}
+
+// Positional parameters, their count, `#`, status of the last
+// command, `?`, and process id of the last background pipeline, `!`,
+// are of the shell. For others shell variables are looked up first,
+// then environment of shell, unset parameters expand to nothing
+fn lookup_parameter(name: &str, ctx: &mut impl ExpansionContext) -> Option<String> {
+    if name == "#" {
+        return Some(ctx.positional_params().len().to_string());
+    }
+
+    if name == "?" {
+        return Some(ctx.last_status().to_string());
+    }
+
+    if name == "!" {
+        return ctx.background_pid().map(|pid| pid.to_string());
+    }
+
+    if let Ok(position) = name.parse::<usize>() {
+        return ctx
+            .positional_params()
+            .get(position.checked_sub(1)?)
+            .cloned();
+    }
+
+    ctx.variables().get_str(name)
+}
+
+// Special Parameters Spec:
+// - URL: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html#tag_18_05_02
+//
+// `$@` and `$*` make a field of every positional parameter, apart
+// from `"$*"` which joins them with first char of IFS instead.
+// Elements are positional parameters or those of an array
+fn expand_elements(
+    elements: &[String],
+    ch: char,
+    in_double_quotes: bool,
+    ctx: &mut impl ExpansionContext,
+) -> Field {
+    let ifs = field_separators(ctx);
+    let mut field = Field {
+        no_elements: ch == '@' && elements.is_empty(),
+        ..Field::default()
+    };
+
+    if ch == '*' && in_double_quotes {
+        let joiner = ifs.chars().next().map(String::from).unwrap_or_default();
+        field.push_str(&elements.join(&joiner), false);
+        return field;
+    }
+
+    // Separator char is what fields are joined with when they
+    // do not get split, e.g. in assignments
+    let separator = match ch {
+        '*' => ifs.chars().next().unwrap_or(' '),
+        _ => ' ',
+    };
+
+    for (idx, element) in elements.iter().enumerate() {
+        if idx > 0 {
+            field.chars.push(ExpandedChar {
+                ch: separator,
+                quoted: false,
+                split: false,
+                separator: true,
+            });
+        }
+        field.push_str(element, false);
+    }
+
+    field
+}
+
+// Unlike other variables, IFS is never taken from environment
+fn field_separators(ctx: &mut impl ExpansionContext) -> String {
+    ctx.variables()
+        .get_str("IFS")
+        .unwrap_or_else(|| DEFAULT_IFS.to_string())
+}
+
+// Command Substitution Spec:
+// - URL: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html#tag_18_06_03
+//
+// Trailing newlines of output are removed
+fn substitute_command(command: &str, ctx: &mut impl ExpansionContext) -> anyhow::Result<String> {
+    let output = ctx.command_output(command)?;
+    Ok(output.trim_end_matches('\n').to_string())
+}
+
+// Arithmetic Expansion Spec:
+// - URL: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html#tag_18_06_04
+//
+// Expression goes through parameter expansion first, same as
+// a word in double quotes
+fn expand_arithmetic(expr: &str, ctx: &mut impl ExpansionContext) -> anyhow::Result<String> {
+    Ok(evaluate_arithmetic(expr, ctx)?.to_string())
+}
+
+fn evaluate_arithmetic(expr: &str, ctx: &mut impl ExpansionContext) -> anyhow::Result<i64> {
+    let expr = expand_parts(&format!("\"{}\"", expr.replace('"', "\\\"")), ctx)?.to_text();
+    arithmetic::evaluate(&expr, ctx.variables())
+}
+
+// Index of `)` or `}` closing the bracket which chars start with.
+// Brackets within quotes or escaped ones do not count
+fn matching_bracket(chars: &[char]) -> Option<usize> {
+    let open = *chars.first()?;
+    let close = if open == '(' { ')' } else { '}' };
+    let mut depth = 0;
+
+    let mut idx = 0;
+    while idx < chars.len() {
+        match chars[idx] {
+            '\\' => idx += 1,
+            '\'' => idx += 1 + chars[idx + 1..].iter().position(|ch| *ch == '\'')?,
+            '"' => idx += closing_quote(&chars[idx..])?,
+            ch if ch == open => depth += 1,
+            ch if ch == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+            _ => {}
+        }
+        idx += 1;
+    }
+
+    None
+}
+
+// Index of `"` closing the double quotes which chars start with,
+// substitutions within them can have quotes of their own
+fn closing_quote(chars: &[char]) -> Option<usize> {
+    let mut idx = 1;
+    while idx < chars.len() {
+        match chars[idx] {
+            '\\' => idx += 1,
+            '"' => return Some(idx),
+            '$' if matches!(chars.get(idx + 1), Some('(' | '{')) => {
+                idx += 1 + matching_bracket(&chars[idx + 1..])?;
+            }
+            _ => {}
+        }
+        idx += 1;
+    }
+
+    None
+}
+
+// Field Splitting Spec:
+// - URL: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html#tag_18_06_05
+//
+// Only unquoted results of expansions get split. Runs of IFS
+// whitespace delimit fields and are ignored at both ends, every
+// other IFS char delimits a field along with whitespace around it,
+// so `a::b` with IFS=: makes an empty field in between. Fields
+// which end up empty are removed unless they had quotes in them
+fn split_fields(field: Field, ifs: &str) -> Vec<Field> {
+    let is_delimiter = |expanded: &ExpandedChar| {
+        expanded.split && !expanded.separator && ifs.contains(expanded.ch)
+    };
+    let is_whitespace = |expanded: &ExpandedChar| IFS_WHITESPACE.contains(expanded.ch);
+
+    let mut fields = vec![];
+    let mut current = Field {
+        has_quotes: field.has_quotes,
+        ..Field::default()
+    };
+
+    let mut chars = field.chars.into_iter().peekable();
+    while let Some(expanded) = chars.next() {
+        // Empty positional parameters stay only within `"$@"`
+        if expanded.separator {
+            if !current.chars.is_empty() || current.has_quotes {
+                fields.push(current);
+            }
+            current = Field {
+                has_quotes: expanded.quoted,
+                ..Field::default()
+            };
+            continue;
+        }
+
+        if !is_delimiter(&expanded) {
+            current.chars.push(expanded);
+            continue;
+        }
+
+        let mut non_whitespace = !is_whitespace(&expanded);
+        while chars
+            .next_if(|next| is_delimiter(next) && is_whitespace(next))
+            .is_some()
+        {}
+
+        if !non_whitespace
+            && chars
+                .next_if(|next| is_delimiter(next) && !is_whitespace(next))
+                .is_some()
+        {
+            non_whitespace = true;
+            while chars
+                .next_if(|next| is_delimiter(next) && is_whitespace(next))
+                .is_some()
+            {}
+        }
+
+        if non_whitespace || !current.chars.is_empty() || current.has_quotes {
+            fields.push(current);
+        }
+        current = Field::default();
+    }
+
+    if !current.chars.is_empty() || current.has_quotes {
+        fields.push(current);
+    }
+
+    fields
+}
+
+// Pathname Expansion Spec:
+// - URL: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html#tag_18_06_06
+//
+// Patterns matching nothing are kept as they are unless
+// nullglob or failglob is set
+fn expand_pathname(field: &Field, glob_options: &GlobOptions) -> anyhow::Result<Vec<String>> {
+    let text = field.to_text();
+    let expansion = glob::expand(&field.to_pattern(), glob_options);
+
+    // Paths found till Ctrl-C are not all of them
+    if interrupt::is_interrupted() {
+        return Err(ShellError::Interrupted.into());
+    }
+
+    match expansion {
+        Some(paths) if glob_options.exceeds_max_matches(paths.len()) => {
+            Err(ShellError::ExpansionError(format!(
+                "{}: matches more than {} paths (globmaxmatches)",
+                text,
+                glob_options.max_matches.unwrap_or_default()
+            ))
+            .into())
+        }
+        Some(paths) if paths.is_empty() => {
+            if glob_options.fail_glob {
+                return Err(ShellError::NoMatch(text).into());
+            }
+
+            if glob_options.null_glob {
+                return Ok(vec![]);
+            }
+
+            Ok(vec![text])
+        }
+        Some(paths) => Ok(paths),
+        None => Ok(vec![text]),
+    }
+}
+
+fn bad_substitution(word: &str) -> anyhow::Error {
+    ShellError::ExpansionError(format!("{}: bad substitution", word)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{
+        command::{glob::GlobOptions, Assignment, AssignmentValue},
+        engine::variables::Variables,
+    };
+
+    use super::{expand_assignment_value, expand_word, ExpansionContext};
+
+    struct TestContext {
+        variables: Variables,
+        positional_params: Vec<String>,
+        last_status: i32,
+        background_pid: Option<i32>,
+        nounset: bool,
+        // Commands substituted so far
+        commands: Vec<String>,
+        max_expansion_size: Option<usize>,
+    }
+
+    impl ExpansionContext for TestContext {
+        fn variables(&mut self) -> &mut Variables {
+            &mut self.variables
+        }
+
+        fn glob_options(&self) -> GlobOptions {
+            GlobOptions::default()
+        }
+
+        fn max_expansion_size(&self) -> Option<usize> {
+            self.max_expansion_size
+        }
+
+        fn positional_params(&self) -> &[String] {
+            &self.positional_params
+        }
+
+        fn last_status(&self) -> i32 {
+            self.last_status
+        }
+
+        fn background_pid(&self) -> Option<i32> {
+            self.background_pid
+        }
+
+        fn nounset(&self) -> bool {
+            self.nounset
+        }
+
+        // Echoes command back, with trailing newlines
+        // which should get stripped by substitution
+        fn command_output(&mut self, command: &str) -> anyhow::Result<String> {
+            self.commands.push(command.to_string());
+            Ok(format!("{}\n\n", command))
+        }
+
+        fn process_substitution(&mut self, command: &str) -> anyhow::Result<String> {
+            self.commands.push(command.to_string());
+            Ok(format!("/dev/fd/{}", self.commands.len() + 9))
+        }
+    }
+
+    fn context(variables: &[(&str, &str)]) -> TestContext {
+        let mut ctx = TestContext {
+            variables: Variables::new(),
+            positional_params: vec![],
+            last_status: 0,
+            background_pid: None,
+            nounset: false,
+            commands: vec![],
+            max_expansion_size: None,
+        };
+
+        for (name, value) in variables {
+            ctx.variables
+                .assign(&Assignment {
+                    name: name.to_string(),
+                    value: AssignmentValue::Scalar(value.to_string()),
+                    append: false,
+                })
+                .expect("assignment should have succeeded");
+        }
+
+        ctx
+    }
+
+    fn check(word: &str, ctx: &mut TestContext) -> Vec<String> {
+        expand_word(word, None, ctx).expect("expansion should have succeeded")
+    }
+
+    #[test]
+    fn test_tilde_expansion() {
+        let mut ctx = context(&[("HOME", "/home/dss")]);
+
+        assert_eq!(check("~", &mut ctx), vec!["/home/dss"]);
+        assert_eq!(check("~/src", &mut ctx), vec!["/home/dss/src"]);
+        assert_eq!(check("'~'", &mut ctx), vec!["~"]);
+        assert_eq!(check("a~", &mut ctx), vec!["a~"]);
+
+        assert_eq!(check("~root/src", &mut ctx), vec!["/root/src"]);
+        assert_eq!(check("~\"root\"", &mut ctx), vec!["~root"]);
+        assert_eq!(check("~no-such-user", &mut ctx), vec!["~no-such-user"]);
+    }
+
+    #[test]
+    fn test_parameter_expansion() {
+        let mut ctx = context(&[("name", "dss"), ("empty", "")]);
+
+        assert_eq!(check("$name", &mut ctx), vec!["dss"]);
+        assert_eq!(check("${name}rc", &mut ctx), vec!["dssrc"]);
+        assert_eq!(check("\"$name-$unset\"", &mut ctx), vec!["dss-"]);
+        assert_eq!(check("'$name'", &mut ctx), vec!["$name"]);
+        assert_eq!(check("\\$name", &mut ctx), vec!["$name"]);
+        assert_eq!(check("$", &mut ctx), vec!["$"]);
+        assert!(expand_word("${a b}", None, &mut ctx).is_err());
+    }
+
+    #[test]
+    fn test_last_status_expansion() {
+        let mut ctx = context(&[]);
+        assert_eq!(check("$?", &mut ctx), vec!["0"]);
+
+        ctx.last_status = 127;
+        assert_eq!(check("\"$?\"", &mut ctx), vec!["127"]);
+        assert_eq!(check("${?}x", &mut ctx), vec!["127x"]);
+        assert_eq!(check("${?:-none}", &mut ctx), vec!["127"]);
+    }
+
+    #[test]
+    fn test_background_pid_expansion() {
+        let mut ctx = context(&[]);
+        assert_eq!(check("\"$!\"", &mut ctx), vec![""]);
+        assert_eq!(check("${!:-none}", &mut ctx), vec!["none"]);
+
+        ctx.background_pid = Some(4242);
+        assert_eq!(check("$!", &mut ctx), vec!["4242"]);
+        assert_eq!(check("pid=${!}", &mut ctx), vec!["pid=4242"]);
+
+        ctx.nounset = true;
+        ctx.background_pid = None;
+        assert!(expand_word("$!", None, &mut ctx).is_err());
+    }
+
+    #[test]
+    fn test_expansion_with_nounset() {
+        let mut ctx = context(&[("empty", "")]);
+        ctx.nounset = true;
+
+        assert_eq!(check("$empty", &mut ctx), Vec::<String>::new());
+        assert_eq!(check("${unset:-none}", &mut ctx), vec!["none"]);
+        assert_eq!(check("${unset+set}", &mut ctx), Vec::<String>::new());
+        assert_eq!(check("\"$@\"$#", &mut ctx), vec!["0"]);
+        for word in ["$unset", "\"${unset}\"", "${#unset}", "${unset%x}", "$1"] {
+            let err = expand_word(word, None, &mut ctx).expect_err(word).to_string();
+            assert!(err.ends_with(": unbound variable\n"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_positional_parameter_expansion() {
+        let mut ctx = context(&[]);
+        assert_eq!(check("\"$@\"", &mut ctx), Vec::<String>::new());
+        assert_eq!(check("\"$*\"", &mut ctx), vec![""]);
+        assert_eq!(check("$#", &mut ctx), vec!["0"]);
+
+        ctx.positional_params = (1..=10).map(|position| position.to_string()).collect();
+        ctx.positional_params[1] = "b c".into();
+        ctx.positional_params[2] = "".into();
+        assert_eq!(check("$#", &mut ctx), vec!["10"]);
+        assert_eq!(check("$1$10", &mut ctx), vec!["110"]);
+        assert_eq!(check("${10}", &mut ctx), vec!["10"]);
+        assert_eq!(check("${11:-none}", &mut ctx), vec!["none"]);
+        assert_eq!(check("$0", &mut ctx), vec!["$0"]);
+
+        ctx.positional_params.truncate(4);
+        assert_eq!(check("\"$@\"", &mut ctx), vec!["1", "b c", "", "4"]);
+        assert_eq!(check("x\"$@\"y", &mut ctx), vec!["x1", "b c", "", "4y"]);
+        assert_eq!(check("$@", &mut ctx), vec!["1", "b", "c", "4"]);
+        assert_eq!(check("$*", &mut ctx), vec!["1", "b", "c", "4"]);
+        assert_eq!(check("\"$*\"", &mut ctx), vec!["1 b c  4"]);
+        assert_eq!(expand_assignment_value("$@", &mut ctx).unwrap(), "1 b c  4");
+
+        let mut ctx = context(&[("IFS", ":")]);
+        ctx.positional_params = vec!["a b".into(), "c".into()];
+        assert_eq!(check("\"$*\"", &mut ctx), vec!["a b:c"]);
+        assert_eq!(check("$*", &mut ctx), vec!["a b", "c"]);
+    }
+
+    #[test]
+    fn test_array_expansion() {
+        let mut ctx = context(&[("scalar", "s"), ("i", "1")]);
+        for (name, elements) in [("arr", vec!["a", "b c", ""]), ("empty", vec![])] {
+            ctx.variables
+                .assign(&Assignment {
+                    name: name.to_string(),
+                    value: AssignmentValue::Array(elements.into_iter().map(String::from).collect()),
+                    append: false,
+                })
+                .expect("assignment should have succeeded");
+        }
+
+        assert_eq!(check("$arr", &mut ctx), vec!["a"]);
+        assert_eq!(check("\"${arr[1]}\"", &mut ctx), vec!["b c"]);
+        assert_eq!(check("${arr[i - 1]}${arr[-2]}", &mut ctx), vec!["ab", "c"]);
+        assert_eq!(check("${arr[5]:-none}", &mut ctx), vec!["none"]);
+        assert_eq!(check("${scalar[0]}", &mut ctx), vec!["s"]);
+
+        assert_eq!(check("\"${arr[@]}\"", &mut ctx), vec!["a", "b c", ""]);
+        assert_eq!(check("${arr[@]}", &mut ctx), vec!["a", "b", "c"]);
+        assert_eq!(check("\"${arr[*]}\"", &mut ctx), vec!["a b c "]);
+        assert_eq!(check("\"${empty[@]}\"", &mut ctx), Vec::<String>::new());
+        assert_eq!(check("\"${empty[*]}\"", &mut ctx), vec![""]);
+
+        assert_eq!(check("${#arr[@]}${#arr[*]}", &mut ctx), vec!["33"]);
+        assert_eq!(check("${#arr[1]}${#empty[@]}", &mut ctx), vec!["30"]);
+        assert!(expand_word("${arr[@]:-x}", None, &mut ctx).is_err());
+        assert!(expand_word("${arr[5]=x}", None, &mut ctx).is_err());
+    }
+
+    #[test]
+    fn test_parameter_expansion_operators() {
+        let mut ctx = context(&[("name", "dss"), ("empty", "")]);
+
+        assert_eq!(check("${name:-x}", &mut ctx), vec!["dss"]);
+        assert_eq!(check("${empty:-x}", &mut ctx), vec!["x"]);
+        assert_eq!(check("${empty-x}", &mut ctx), Vec::<String>::new());
+        assert_eq!(check("${unset-a b}", &mut ctx), vec!["a", "b"]);
+        assert_eq!(check("${unset:-\"a b\"}", &mut ctx), vec!["a b"]);
+        assert_eq!(check("\"${unset:-$name rc}\"", &mut ctx), vec!["dss rc"]);
+
+        assert_eq!(check("${name:+set}", &mut ctx), vec!["set"]);
+        assert_eq!(check("${empty+set}", &mut ctx), vec!["set"]);
+        assert_eq!(check("${empty:+set}", &mut ctx), Vec::<String>::new());
+
+        // Word is expanded only when it is used
+        assert_eq!(
+            check("${name:-$(pwd)}${unset:+$(ls)}", &mut ctx),
+            vec!["dss"]
+        );
+        assert!(ctx.commands.is_empty());
+
+        assert_eq!(check("${new:=$name}", &mut ctx), vec!["dss"]);
+        assert_eq!(ctx.variables.get_str("new"), Some("dss".into()));
+        assert_eq!(check("${new:=other}", &mut ctx), vec!["dss"]);
+
+        assert_eq!(check("${name:?}", &mut ctx), vec!["dss"]);
+        let err = expand_word("${empty:?is $name}", None, &mut ctx).unwrap_err();
+        assert_eq!(err.to_string(), "dss: empty: is dss\n");
+        let err = expand_word("${unset?}", None, &mut ctx).unwrap_err();
+        assert_eq!(err.to_string(), "dss: unset: parameter null or not set\n");
+
+        assert!(expand_word("${name:}", None, &mut ctx).is_err());
+        assert!(expand_word("${name:-x", None, &mut ctx).is_err());
+    }
+
+    #[test]
+    fn test_length_and_substring_expansions() {
+        let mut ctx = context(&[("path", "/usr/src/main.tar.gz"), ("n", "2")]);
+
+        assert_eq!(check("${#path}", &mut ctx), vec!["20"]);
+        assert_eq!(check("${#unset}", &mut ctx), vec!["0"]);
+        assert!(expand_word("${#a b}", None, &mut ctx).is_err());
+
+        assert_eq!(check("${path%.*}", &mut ctx), vec!["/usr/src/main.tar"]);
+        assert_eq!(check("${path%%.*}", &mut ctx), vec!["/usr/src/main"]);
+        assert_eq!(check("${path#*/}", &mut ctx), vec!["usr/src/main.tar.gz"]);
+        assert_eq!(check("${path##*/}", &mut ctx), vec!["main.tar.gz"]);
+        assert_eq!(
+            check("${path%.[a-z]z}", &mut ctx),
+            vec!["/usr/src/main.tar"]
+        );
+        assert_eq!(
+            check("${path#\"/usr\"}", &mut ctx),
+            vec!["/src/main.tar.gz"]
+        );
+        // Quoted pattern chars match only themselves
+        assert_eq!(
+            check("${path%\"*\"}", &mut ctx),
+            vec!["/usr/src/main.tar.gz"]
+        );
+        assert_eq!(check("${unset#*}", &mut ctx), Vec::<String>::new());
+
+        assert_eq!(check("${path:5}", &mut ctx), vec!["src/main.tar.gz"]);
+        assert_eq!(check("${path:5:3}", &mut ctx), vec!["src"]);
+        assert_eq!(check("${path:$n*2+1:n}", &mut ctx), vec!["sr"]);
+        assert_eq!(check("${path: -6}", &mut ctx), vec!["tar.gz"]);
+        assert_eq!(check("${path:5:-7}", &mut ctx), vec!["src/main"]);
+        assert_eq!(check("${path:5:100}", &mut ctx), vec!["src/main.tar.gz"]);
+        assert_eq!(check("${path:100}", &mut ctx), Vec::<String>::new());
+        assert_eq!(check("${path: -100}", &mut ctx), Vec::<String>::new());
+
+        let err = expand_word("${path:5:-100}", None, &mut ctx).unwrap_err();
+        assert_eq!(err.to_string(), "dss: -100: substring expression < 0\n");
+    }
+
+    #[test]
+    fn test_pattern_substitution() {
+        let mut ctx = context(&[
+            ("path", "/usr/src/main.rs"),
+            ("r", "a b"),
+            ("HOME", "/home"),
+        ]);
+
+        assert_eq!(check("${path/s/S}", &mut ctx), vec!["/uSr/src/main.rs"]);
+        assert_eq!(check("${path//s/S}", &mut ctx), vec!["/uSr/Src/main.rS"]);
+        assert_eq!(check("${path//s}", &mut ctx), vec!["/ur/rc/main.r"]);
+        assert_eq!(
+            check("${path//[aeiou]/_}", &mut ctx),
+            vec!["/_sr/src/m__n.rs"]
+        );
+        assert_eq!(check("${path/*\\//}", &mut ctx), vec!["main.rs"]);
+        assert_eq!(
+            check("\"${path//\\//|}\"", &mut ctx),
+            vec!["|usr|src|main.rs"]
+        );
+        assert_eq!(check("${path/'/'/:}", &mut ctx), vec![":usr/src/main.rs"]);
+        assert_eq!(check("${path/s*/}", &mut ctx), vec!["/u"]);
+        assert_eq!(
+            check("${path//\"*\"/x}", &mut ctx),
+            vec!["/usr/src/main.rs"]
+        );
+        assert_eq!(
+            check("\"${path/main/$r}\"", &mut ctx),
+            vec!["/usr/src/a b.rs"]
+        );
+        assert_eq!(check("${path/}", &mut ctx), vec!["/usr/src/main.rs"]);
+
+        assert_eq!(
+            check("${path/#\\/usr/~}", &mut ctx),
+            vec!["/home/src/main.rs"]
+        );
+        assert_eq!(check("${path/#src/x}", &mut ctx), vec!["/usr/src/main.rs"]);
+        assert_eq!(check("${path/%.rs/.c}", &mut ctx), vec!["/usr/src/main.c"]);
+        assert_eq!(
+            check("${path/#/pre:}", &mut ctx),
+            vec!["pre:/usr/src/main.rs"]
+        );
+        assert_eq!(
+            check("${path/%/:post}", &mut ctx),
+            vec!["/usr/src/main.rs:post"]
+        );
+        assert_eq!(check("${unset/a/b}", &mut ctx), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_case_modification() {
+        let mut ctx = context(&[("s", "hello world"), ("u", "ÉCOLE straße")]);
+
+        assert_eq!(check("\"${s^}\"", &mut ctx), vec!["Hello world"]);
+        assert_eq!(check("\"${s^^}\"", &mut ctx), vec!["HELLO WORLD"]);
+        assert_eq!(check("\"${s^^[lo]}\"", &mut ctx), vec!["heLLO wOrLd"]);
+        assert_eq!(check("${s^[a-g]}", &mut ctx), vec!["hello", "world"]);
+        assert_eq!(check("\"${u,}\"", &mut ctx), vec!["éCOLE straße"]);
+        assert_eq!(check("\"${u,,}\"", &mut ctx), vec!["école straße"]);
+        assert_eq!(check("\"${u^^}\"", &mut ctx), vec!["ÉCOLE STRASSE"]);
+        assert_eq!(check("${unset^^}", &mut ctx), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_command_substitution() {
+        let mut ctx = context(&[]);
+
+        assert_eq!(check("$(pwd)", &mut ctx), vec!["pwd"]);
+        assert_eq!(check("a`ls`b", &mut ctx), vec!["alsb"]);
+        assert_eq!(check("\"$(echo (a))\"", &mut ctx), vec!["echo (a)"]);
+        assert_eq!(check("\"$(echo \")\" \\))\"", &mut ctx), vec!["echo \")\" \\)"]);
+        assert_eq!(ctx.commands, vec!["pwd", "ls", "echo (a)", "echo \")\" \\)"]);
+        assert!(expand_word("$(ls", None, &mut ctx).is_err());
+    }
+
+    #[test]
+    fn test_process_substitution() {
+        let mut ctx = context(&[("IFS", "/")]);
+
+        assert_eq!(check("<(sort a)", &mut ctx), vec!["/dev/fd/10"]);
+        assert_eq!(check("x<(echo (a))", &mut ctx), vec!["x/dev/fd/11"]);
+        assert_eq!(check("\"<(ls)\"", &mut ctx), vec!["<(ls)"]);
+        assert_eq!(ctx.commands, vec!["sort a", "echo (a)"]);
+        assert!(expand_word("<(ls", None, &mut ctx).is_err());
+    }
+
+    #[test]
+    fn test_arithmetic_expansion() {
+        let mut ctx = context(&[("n", "4")]);
+
+        assert_eq!(check("$((1 + 2 * 3))", &mut ctx), vec!["7"]);
+        assert_eq!(check("$(($n * (n + 1)))", &mut ctx), vec!["20"]);
+        assert_eq!(check("x$((i = 2))", &mut ctx), vec!["x2"]);
+        assert_eq!(ctx.variables.get_str("i"), Some("2".into()));
+        assert!(expand_word("$((1 / 0))", None, &mut ctx).is_err());
+    }
+
+    #[test]
+    fn test_field_splitting() {
+        let mut ctx = context(&[("list", " a  b\tc "), ("empty", "")]);
+
+        assert_eq!(check("$list", &mut ctx), vec!["a", "b", "c"]);
+        assert_eq!(check("x${list}y", &mut ctx), vec!["x", "a", "b", "c", "y"]);
+        assert_eq!(check("\"$list\"", &mut ctx), vec![" a  b\tc "]);
+        assert_eq!(check("$(a b)", &mut ctx), vec!["a", "b"]);
+        assert!(check("$empty", &mut ctx).is_empty());
+        assert_eq!(check("\"$empty\"", &mut ctx), vec![""]);
+        assert_eq!(check("''", &mut ctx), vec![""]);
+        assert_eq!(check("`a b`", &mut ctx), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_field_splitting_with_ifs() {
+        let mut ctx = context(&[("IFS", ":"), ("path", "a::b:"), ("spaced", " a : b ")]);
+
+        assert_eq!(check("$path", &mut ctx), vec!["a", "", "b"]);
+        assert_eq!(check("\"$path\"", &mut ctx), vec!["a::b:"]);
+        // Literal IFS chars in word are not delimiters
+        assert_eq!(check("x:y$path", &mut ctx), vec!["x:ya", "", "b"]);
+        assert_eq!(check("$spaced", &mut ctx), vec![" a ", " b "]);
+
+        let mut ctx = context(&[("IFS", " :"), ("path", "a::b:"), ("spaced", " a : b ")]);
+        assert_eq!(check("$spaced", &mut ctx), vec!["a", "b"]);
+        assert_eq!(check("$path", &mut ctx), vec!["a", "", "b"]);
+
+        // Empty IFS disables splitting
+        let mut ctx = context(&[("IFS", ""), ("spaced", " a : b ")]);
+        assert_eq!(check("$spaced", &mut ctx), vec![" a : b "]);
+    }
+
+    #[test]
+    fn test_pathname_expansion() {
+        let dir = std::env::temp_dir().join(format!("dss_expansion_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for file in ["a.rs", "b.rs", "*.rs"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let dir_str = dir.display().to_string();
+
+        let mut ctx = context(&[("dir", &dir_str), ("pattern", "*.rs")]);
+        let options = GlobOptions::default();
+
+        let expanded = expand_word("$dir/$pattern", Some(&options), &mut ctx).unwrap();
+        assert_eq!(
+            expanded,
+            vec![
+                format!("{}/*.rs", dir_str),
+                format!("{}/a.rs", dir_str),
+                format!("{}/b.rs", dir_str)
+            ]
+        );
+
+        // Quoted results are not patterns
+        let expanded = expand_word("\"$dir/$pattern\"", Some(&options), &mut ctx).unwrap();
+        assert_eq!(expanded, vec![format!("{}/*.rs", dir_str)]);
+
+        // Unmatched patterns are kept as they are
+        let expanded = expand_word("\"$dir\"/*.none", Some(&options), &mut ctx).unwrap();
+        assert_eq!(expanded, vec![format!("{}/*.none", dir_str)]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_quote_removal() {
+        let mut ctx = context(&[]);
+
+        assert_eq!(check("'a b'c\"d\\\"\"", &mut ctx), vec!["a bcd\""]);
+        assert_eq!(check("\"a\\nb\"", &mut ctx), vec!["a\\nb"]);
+        assert_eq!(
+            expand_assignment_value("\"$(a  b)\" c", &mut ctx).unwrap(),
+            "a  b c"
+        );
+    }
+
+    #[test]
+    fn test_expansion_size_limit() {
+        let mut ctx = context(&[("x", "0123456789")]);
+        ctx.max_expansion_size = Some(20);
+
+        assert_eq!(check("$x$x", &mut ctx), vec!["01234567890123456789"]);
+        assert!(expand_word("$x$x$x", None, &mut ctx).is_err());
+        assert!(expand_assignment_value("${x}$x", &mut ctx).is_ok());
+        assert!(expand_assignment_value("${x}-$x", &mut ctx).is_err());
+    }
+}
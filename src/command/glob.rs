@@ -0,0 +1,620 @@
+// Pattern Matching Notation Spec:
+// - URL: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html#tag_18_13
+//
+// Quoted parts of a word never act as pattern characters
+
+use std::{
+    cmp::Ordering,
+    fs,
+    os::unix::prelude::{MetadataExt, PermissionsExt},
+    path::Path,
+};
+
+use crate::interrupt;
+
+// Chars allowed within glob qualifiers, e.g. `*(/)`:
+// - `/`: directories
+// - `.`: plain files
+// - `@`: symbolic links
+// - `*`: executable plain files
+// - `^`: negates qualifiers after it
+pub const QUALIFIERS: &str = "/.@*^";
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GlobOptions {
+    // Sort matches case insensitively, set by
+    // `completion-ignore-case` in inputrc
+    pub ignore_case: bool,
+    // Enables qualifiers, set by `set -o extendedglob`
+    pub extended_glob: bool,
+    // Levels of directories `**` can descend into,
+    // set by `set -o globmaxdepth=N`
+    pub max_depth: Option<usize>,
+    // Patterns match hidden files too, set by `set -o dotglob`
+    pub dot_glob: bool,
+    // Patterns matching nothing are removed, set by `set -o nullglob`
+    pub null_glob: bool,
+    // Patterns matching nothing are an error, set by `set -o failglob`
+    pub fail_glob: bool,
+    // Paths pattern can match, search stops once there are more
+    // of them, set by `set -o globmaxmatches=N`
+    pub max_matches: Option<usize>,
+}
+
+impl GlobOptions {
+    pub fn exceeds_max_matches(&self, matches: usize) -> bool {
+        self.max_matches.is_some_and(|max| matches > max)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Qualifier {
+    Directory,
+    PlainFile,
+    Symlink,
+    Executable,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum PatternChar {
+    Literal(char),
+    Any,  // "?"
+    Star, // "*"
+    // "[...]", e.g. [a-z], [!0-9]
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+// Expands word to matching paths, None means word has no
+// pattern in it, empty paths means nothing matched it
+pub fn expand(word: &str, options: &GlobOptions) -> Option<Vec<String>> {
+    let (word, qualifiers) = match split_qualifiers(word) {
+        Some((pattern, qualifiers)) if options.extended_glob => (pattern, qualifiers),
+        _ => (word, vec![]),
+    };
+
+    let segments: Vec<Vec<PatternChar>> = split_segments(word)
+        .iter()
+        .map(|segment| parse_pattern(segment))
+        .collect();
+
+    if !segments.iter().any(|segment| is_pattern(segment)) {
+        return None;
+    }
+
+    // Paths matched so far, empty one being current directory
+    let mut paths = vec![String::new()];
+    for (idx, segment) in segments.iter().enumerate() {
+        let is_last = idx == segments.len() - 1;
+
+        paths = paths
+            .into_iter()
+            .flat_map(|path| {
+                if segment[..] == [PatternChar::Star, PatternChar::Star] {
+                    // Last `**` matches files too, e.g. `src/**`
+                    let mut found = vec![];
+                    descend(&path, 0, is_last, options, &mut vec![], &mut found);
+                    found
+                } else if is_pattern(segment) {
+                    matching_entries(&path, segment, is_last, options)
+                } else {
+                    let literal: String = segment
+                        .iter()
+                        .filter_map(|ch| match ch {
+                            PatternChar::Literal(ch) => Some(*ch),
+                            _ => None,
+                        })
+                        .collect();
+                    vec![join(&path, &literal, idx == 0)]
+                }
+            })
+            .collect();
+
+        if options.exceeds_max_matches(paths.len()) {
+            break;
+        }
+    }
+
+    // Literal segments after last pattern can still be missing
+    paths.retain(|path| {
+        qualifiers
+            .iter()
+            .all(|(qualifier, negated)| has_qualifier(path, *qualifier) != *negated)
+            && fs::symlink_metadata(path).is_ok()
+    });
+
+    sort_names(&mut paths, options.ignore_case);
+    Some(paths)
+}
+
+// Whether text as a whole matches pattern, `/` being an ordinary
+// char unlike in paths, e.g. for `${name#pattern}`
+pub fn matches_text(pattern: &str, text: &str) -> bool {
+    let chars: Vec<char> = text.chars().collect();
+    matches(&parse_pattern(pattern), &chars)
+}
+
+// Splits `pattern(qualifiers)` into pattern and qualifiers,
+// each with whether it is negated
+fn split_qualifiers(word: &str) -> Option<(&str, Vec<(Qualifier, bool)>)> {
+    let (pattern, qualifiers_str) = word.strip_suffix(')')?.rsplit_once('(')?;
+
+    let mut qualifiers = vec![];
+    let mut negated = false;
+    for ch in qualifiers_str.chars() {
+        let qualifier = match ch {
+            '/' => Qualifier::Directory,
+            '.' => Qualifier::PlainFile,
+            '@' => Qualifier::Symlink,
+            '*' => Qualifier::Executable,
+            '^' => {
+                negated = !negated;
+                continue;
+            }
+            _ => return None,
+        };
+        qualifiers.push((qualifier, negated));
+    }
+
+    Some((pattern, qualifiers))
+}
+
+fn has_qualifier(path: &str, qualifier: Qualifier) -> bool {
+    // Symlinks are followed for everything except `@`
+    let metadata = match qualifier {
+        Qualifier::Symlink => fs::symlink_metadata(path),
+        _ => fs::metadata(path),
+    };
+    let Ok(metadata) = metadata else {
+        return false;
+    };
+
+    match qualifier {
+        Qualifier::Directory => metadata.is_dir(),
+        Qualifier::PlainFile => metadata.is_file(),
+        Qualifier::Symlink => metadata.file_type().is_symlink(),
+        Qualifier::Executable => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+    }
+}
+
+// Unicode aware ordering, falling back to exact comparison
+// so that case insensitive order is still stable
+pub fn sort_names(names: &mut [String], ignore_case: bool) {
+    if !ignore_case {
+        names.sort();
+        return;
+    }
+
+    names.sort_by(|a, b| match compare_ignoring_case(a, b) {
+        Ordering::Equal => a.cmp(b),
+        ordering => ordering,
+    });
+}
+
+fn compare_ignoring_case(a: &str, b: &str) -> Ordering {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .cmp(b.chars().flat_map(char::to_lowercase))
+}
+
+fn matching_entries(
+    dir: &str,
+    segment: &[PatternChar],
+    is_last: bool,
+    options: &GlobOptions,
+) -> Vec<String> {
+    let dir_path = if dir.is_empty() { "." } else { dir };
+    let Ok(entries) = fs::read_dir(dir_path) else {
+        return vec![];
+    };
+
+    // Hidden files match only if pattern starts with a literal "."
+    let match_hidden = options.dot_glob || segment.first() == Some(&PatternChar::Literal('.'));
+
+    entries
+        .take_while(|_| !interrupt::is_interrupted())
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if name.starts_with('.') && !match_hidden {
+                return None;
+            }
+
+            // Intermediate segments can only match directories
+            if !is_last && !Path::new(dir_path).join(&name).is_dir() {
+                return None;
+            }
+
+            let chars: Vec<char> = name.chars().collect();
+            matches(segment, &chars).then(|| join(dir, &name, false))
+        })
+        .take(options.max_matches.map_or(usize::MAX, |max| max.saturating_add(1)))
+        .collect()
+}
+
+// Collects dir along with all directories under it for `**`,
+// ancestors are tracked to not get stuck in symlink loops
+fn descend(
+    dir: &str,
+    depth: usize,
+    include_files: bool,
+    options: &GlobOptions,
+    ancestors: &mut Vec<(u64, u64)>,
+    found: &mut Vec<String>,
+) {
+    let dir_path = if dir.is_empty() { "." } else { dir };
+    let Ok(metadata) = fs::metadata(dir_path) else {
+        return;
+    };
+
+    let id = (metadata.dev(), metadata.ino());
+    if ancestors.contains(&id) {
+        return;
+    }
+
+    found.push(dir.to_string());
+    if options
+        .max_depth
+        .is_some_and(|max_depth| depth >= max_depth)
+    {
+        return;
+    }
+
+    let Ok(entries) = fs::read_dir(dir_path) else {
+        return;
+    };
+
+    ancestors.push(id);
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        // Walk over a huge tree can take long, Ctrl-C cuts it short
+        if interrupt::is_interrupted() || options.exceeds_max_matches(found.len()) {
+            break;
+        }
+
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if name.starts_with('.') && !options.dot_glob {
+            continue;
+        }
+
+        let path = join(dir, &name, false);
+        if Path::new(&path).is_dir() {
+            descend(&path, depth + 1, include_files, options, ancestors, found);
+        } else if include_files {
+            found.push(path);
+        }
+    }
+    ancestors.pop();
+}
+
+fn join(dir: &str, name: &str, is_first: bool) -> String {
+    if is_first || dir.is_empty() {
+        return name.to_string();
+    }
+
+    if dir == "/" {
+        return format!("/{}", name);
+    }
+
+    format!("{}/{}", dir, name)
+}
+
+// Splits on unquoted "/", quotes are kept in segments
+fn split_segments(word: &str) -> Vec<String> {
+    let mut segments = vec![];
+    let mut segment = String::new();
+    let mut quote: Option<char> = None;
+
+    for ch in word.chars() {
+        match (quote, ch) {
+            (None, '"' | '\'') => quote = Some(ch),
+            (Some(quote_ch), ch) if quote_ch == ch => quote = None,
+            (None, '/') => {
+                segments.push(segment);
+                segment = String::new();
+                continue;
+            }
+            _ => {}
+        }
+
+        segment.push(ch);
+    }
+
+    segments.push(segment);
+
+    // `/*` should be read as root followed by pattern
+    if segments.len() > 1 && segments[0].is_empty() {
+        segments[0] = "/".to_string();
+    }
+
+    segments
+}
+
+fn parse_pattern(segment: &str) -> Vec<PatternChar> {
+    // Pair of char and whether it was quoted
+    let mut chars = vec![];
+    let mut quote: Option<char> = None;
+    let mut raw_chars = segment.chars();
+    while let Some(ch) = raw_chars.next() {
+        match (quote, ch) {
+            (None, '"' | '\'') => quote = Some(ch),
+            (Some(quote_ch), ch) if quote_ch == ch => quote = None,
+            (Some('"'), '\\') => match raw_chars.next() {
+                Some(next_ch @ ('$' | '`' | '"' | '\\')) => chars.push((next_ch, true)),
+                Some(next_ch) => {
+                    chars.push(('\\', true));
+                    chars.push((next_ch, true));
+                }
+                None => chars.push(('\\', true)),
+            },
+            (quote, ch) => chars.push((ch, quote.is_some())),
+        }
+    }
+
+    let mut pattern = vec![];
+    let mut idx = 0;
+    while idx < chars.len() {
+        let (ch, quoted) = chars[idx];
+        idx += 1;
+
+        if quoted {
+            pattern.push(PatternChar::Literal(ch));
+            continue;
+        }
+
+        match ch {
+            '*' => pattern.push(PatternChar::Star),
+            '?' => pattern.push(PatternChar::Any),
+            '[' => match parse_class(&chars[idx..]) {
+                Some((class, consumed)) => {
+                    pattern.push(class);
+                    idx += consumed;
+                }
+                // Unclosed "[" is matched literally
+                None => pattern.push(PatternChar::Literal('[')),
+            },
+            ch => pattern.push(PatternChar::Literal(ch)),
+        }
+    }
+
+    pattern
+}
+
+// Parses class after "[", returns it along with count of chars consumed
+fn parse_class(chars: &[(char, bool)]) -> Option<(PatternChar, usize)> {
+    let mut idx = 0;
+    let negated = matches!(chars.first(), Some(('!' | '^', false)));
+    if negated {
+        idx += 1;
+    }
+
+    let mut ranges = vec![];
+    // "]" right after "[" or "[!" is a literal
+    let mut first = true;
+    loop {
+        let (ch, quoted) = *chars.get(idx)?;
+        idx += 1;
+
+        if ch == ']' && !quoted && !first {
+            return Some((PatternChar::Class { negated, ranges }, idx));
+        }
+        first = false;
+
+        match (chars.get(idx), chars.get(idx + 1)) {
+            (Some(('-', false)), Some((end, _))) if *end != ']' => {
+                ranges.push((ch, *end));
+                idx += 2;
+            }
+            _ => ranges.push((ch, ch)),
+        }
+    }
+}
+
+fn is_pattern(segment: &[PatternChar]) -> bool {
+    segment
+        .iter()
+        .any(|ch| !matches!(ch, PatternChar::Literal(_)))
+}
+
+fn matches(pattern: &[PatternChar], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((PatternChar::Star, rest)) => {
+            (0..=name.len()).any(|skip| matches(rest, &name[skip..]))
+        }
+        Some((pattern_ch, rest)) => match name.split_first() {
+            Some((ch, name_rest)) => pattern_ch.matches(*ch) && matches(rest, name_rest),
+            None => false,
+        },
+    }
+}
+
+impl PatternChar {
+    fn matches(&self, ch: char) -> bool {
+        match self {
+            PatternChar::Literal(literal) => *literal == ch,
+            PatternChar::Any => true,
+            PatternChar::Star => true,
+            PatternChar::Class { negated, ranges } => {
+                ranges
+                    .iter()
+                    .any(|(start, end)| (*start..=*end).contains(&ch))
+                    != *negated
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{expand, matches_text, sort_names, GlobOptions};
+
+    fn check(pattern: &str, name: &str) -> bool {
+        matches_text(pattern, name)
+    }
+
+    #[test]
+    fn test_pattern_matching() {
+        assert!(check("*.rs", "main.rs"));
+        assert!(!check("*.rs", "main.rc"));
+        assert!(check("ma?n.*", "main.rs"));
+        assert!(check("[a-c]at", "bat"));
+        assert!(!check("[!a-c]at", "bat"));
+        assert!(check("[]]", "]"));
+        assert!(check("[", "["));
+        // Quoted pattern characters are literals
+        assert!(check("'*'.rs", "*.rs"));
+        assert!(!check("\"*\".rs", "main.rs"));
+        assert!(check("src/*", "src/glob.rs"));
+    }
+
+    #[test]
+    fn test_sorting_of_names() {
+        let mut names: Vec<String> = ["b", "Ä", "a", "B", "ä", "A"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+
+        sort_names(&mut names, false);
+        assert_eq!(names, vec!["A", "B", "a", "b", "Ä", "ä"]);
+
+        sort_names(&mut names, true);
+        assert_eq!(names, vec!["A", "a", "B", "b", "Ä", "ä"]);
+    }
+
+    #[test]
+    fn test_expansion_of_paths() {
+        let dir = std::env::temp_dir().join(format!("dss_glob_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        for file in ["b.txt", "A.txt", "c.md", ".hidden.txt", "sub/d.txt"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let dir_str = dir.display().to_string();
+
+        let options = GlobOptions {
+            ignore_case: true,
+            ..Default::default()
+        };
+        let expanded = expand(&format!("{}/*.txt", dir_str), &options);
+        assert_eq!(
+            expanded,
+            Some(vec![
+                format!("{}/A.txt", dir_str),
+                format!("{}/b.txt", dir_str)
+            ])
+        );
+
+        let expanded = expand(&format!("{}/*/d.txt", dir_str), &options);
+        assert_eq!(expanded, Some(vec![format!("{}/sub/d.txt", dir_str)]));
+
+        let expanded = expand(&format!("{}/.h*", dir_str), &options);
+        assert_eq!(expanded, Some(vec![format!("{}/.hidden.txt", dir_str)]));
+
+        assert_eq!(
+            expand(&format!("{}/*.none", dir_str), &options),
+            Some(vec![])
+        );
+        assert_eq!(expand(&format!("{}/'*'.txt", dir_str), &options), None);
+
+        let options = GlobOptions {
+            dot_glob: true,
+            ..Default::default()
+        };
+        let expanded = expand(&format!("{}/*.txt", dir_str), &options);
+        assert_eq!(
+            expanded,
+            Some(vec![
+                format!("{}/.hidden.txt", dir_str),
+                format!("{}/A.txt", dir_str),
+                format!("{}/b.txt", dir_str)
+            ])
+        );
+
+        // One more than the limit is enough to know it is exceeded
+        let options = GlobOptions {
+            max_matches: Some(1),
+            ..Default::default()
+        };
+        let expanded = expand(&format!("{}/*.txt", dir_str), &options).unwrap();
+        assert_eq!(expanded.len(), 2);
+        assert!(options.exceeds_max_matches(expanded.len()));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_expansion_with_qualifiers() {
+        let dir = std::env::temp_dir().join(format!("dss_glob_qual_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("file"), "").unwrap();
+        std::os::unix::fs::symlink(dir.join("file"), dir.join("link")).unwrap();
+        let dir_str = dir.display().to_string();
+
+        let mut options = GlobOptions::default();
+        // Qualifiers are part of pattern without extendedglob
+        assert_eq!(expand(&format!("{}/*(/)", dir_str), &options), Some(vec![]));
+
+        options.extended_glob = true;
+        assert_eq!(
+            expand(&format!("{}/*(/)", dir_str), &options),
+            Some(vec![format!("{}/sub", dir_str)])
+        );
+        assert_eq!(
+            expand(&format!("{}/*(.)", dir_str), &options),
+            Some(vec![
+                format!("{}/file", dir_str),
+                format!("{}/link", dir_str)
+            ])
+        );
+        assert_eq!(
+            expand(&format!("{}/*(.^@)", dir_str), &options),
+            Some(vec![format!("{}/file", dir_str)])
+        );
+        assert_eq!(expand(&format!("{}/*(*)", dir_str), &options), Some(vec![]));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_recursive_expansion() {
+        let dir = std::env::temp_dir().join(format!("dss_glob_rec_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("a/b/c")).unwrap();
+        for file in ["x.rs", "a/y.rs", "a/b/c/z.rs", "a/b/w.txt"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        // Loop back to the top, which should not be followed forever
+        std::os::unix::fs::symlink(&dir, dir.join("a/b/loop")).unwrap();
+        let dir_str = dir.display().to_string();
+
+        let mut options = GlobOptions::default();
+        assert_eq!(
+            expand(&format!("{}/**/*.rs", dir_str), &options),
+            Some(vec![
+                format!("{}/a/b/c/z.rs", dir_str),
+                format!("{}/a/y.rs", dir_str),
+                format!("{}/x.rs", dir_str),
+            ])
+        );
+
+        options.max_depth = Some(1);
+        assert_eq!(
+            expand(&format!("{}/**/*.rs", dir_str), &options),
+            Some(vec![
+                format!("{}/a/y.rs", dir_str),
+                format!("{}/x.rs", dir_str)
+            ])
+        );
+
+        options.max_depth = None;
+        let expanded = expand(&format!("{}/a/**", dir_str), &options).unwrap();
+        assert!(expanded.contains(&format!("{}/a/b/w.txt", dir_str)));
+        assert!(expanded.contains(&format!("{}/a/b/c", dir_str)));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
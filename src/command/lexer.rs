@@ -6,170 +6,490 @@ use std::{iter::Peekable, str::Chars};
 
 use crate::errors::{LexError, ShellError};
 
-use super::token::{Keyword, Operator, Token, TokenType, Word};
+use super::{
+    glob,
+    token::{Keyword, Operator, Token, TokenType, Word},
+};
 
+#[derive(Default)]
 pub struct Lexer {
     // TODO: Remove tokens field
     pub tokens: Vec<Token>,
+    // Lines scanned so far, continuation lines keep
+    // counting from here so that line changes show up
+    line: usize,
+    // Word with a quote still open, e.g. `echo 'foo`, it
+    // gets scanned again along with next line of input
+    pending: Option<PendingWord>,
+}
+
+struct PendingWord {
+    line: usize,
+    offset: usize,
+    input: String,
 }
 
 struct LexingContext<'a> {
     line: usize,
+    input: &'a str,
     chars: Peekable<Chars<'a>>,
+    // byte position of chars in input
+    pos: usize,
     // offset from new line
     offset: usize,
     tokens: Vec<Token>,
     word: String,
+    // line and offset of first char of word
+    word_start: (usize, usize),
+    // Set when commands with errors are to be skipped, errors
+    // are collected then instead of failing the whole input
+    errors: Option<Vec<LexError>>,
+    pending: Option<PendingWord>,
 }
 
 impl Lexer {
     pub fn new() -> Self {
         Self {
             tokens: vec![],
+            line: 0,
+            pending: None,
         }
     }
 
     pub fn scan(&mut self, input_str: &str) -> anyhow::Result<Vec<Token>> {
-        let mut ctx = LexingContext {
-            line: 0,
-            chars: input_str.chars().peekable(),
-            offset: 0,
-            tokens: vec![],
-            word: String::new(),
-        };
+        let mut ctx = LexingContext::new(self.line, 0, input_str, false);
 
-        ctx.scan()?;
+        ctx.scan().map_err(ShellError::LexError)?;
+        self.line = ctx.line;
+        self.tokens.extend(ctx.tokens.iter().cloned());
         Ok(ctx.tokens)
     }
 
-    pub fn complete_processing(&self, last_token: Token) -> bool {
+    // Same as `scan`, except that a command with a syntax error is
+    // skipped till next `;` or newline, instead of the whole input
+    // failing, and a word with a quote left open waits for next line
+    // to complete it. Returns errors found, rest of the input is kept
+    pub fn scan_recovering(&mut self, input_str: &str) -> Vec<LexError> {
+        let (line, offset, input) = match self.pending.take() {
+            Some(pending) => (pending.line, pending.offset, pending.input + input_str),
+            None => (self.line, 0, input_str.to_string()),
+        };
+        let mut ctx = LexingContext::new(line, offset, &input, true);
+
+        // Errors are only returned when not recovering from them
+        let _ = ctx.scan();
+        self.line = ctx.line;
+        self.pending = ctx.pending.take();
+        self.tokens.append(&mut ctx.tokens);
+        ctx.errors.unwrap_or_default()
+    }
+
+    // Nothing is scanned yet, not even part of a word
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty() && self.pending.is_none()
+    }
+
+    pub fn complete_processing(&self) -> bool {
+        // if nothing is scanned yet -> not completed processing
+        // if a quote is not closed yet -> not completed processing
         // if it's backslash -> not completed processing
-        // if it's any operator other than & -> not completed processing
+        // if it's any operator other than & or ! -> not completed processing
+        // if a compound command is not closed yet -> not completed processing
+
+        if self.pending.is_some() {
+            return false;
+        }
+
+        let Some(last_token) = self.tokens.last() else {
+            return false;
+        };
 
         if matches!(last_token.token_type, TokenType::Backslash) {
             return false;
         }
 
         if matches!(last_token.token_type, TokenType::Operator(_))
-            && !matches!(last_token.token_type, TokenType::Operator(Operator::And))
+            && !matches!(
+                last_token.token_type,
+                TokenType::Operator(Operator::And | Operator::Exclamation)
+            )
         {
             return false;
         }
 
-        return true;
+        !self.has_unterminated_compound()
+    }
+
+    // Compound commands waiting for their closing word, e.g. `if true; then`
+    // waits for `fi`, only words in command position are considered
+    fn has_unterminated_compound(&self) -> bool {
+        let mut depth: isize = 0;
+        let mut command_position = true;
+        let mut prev_token: Option<&Token> = None;
+
+        for token in &self.tokens {
+            // New line starts a new command, unless it got escaped
+            if prev_token.is_some_and(|prev_token| prev_token.ends_line_before(token)) {
+                command_position = true;
+            }
+            prev_token = Some(token);
+
+            command_position = match &token.token_type {
+                TokenType::LeftParen => {
+                    depth += 1;
+                    true
+                }
+                TokenType::RightParen => {
+                    depth -= 1;
+                    false
+                }
+                TokenType::Semicolon
+                | TokenType::Operator(
+                    Operator::And
+                    | Operator::AndIf
+                    | Operator::Or
+                    | Operator::OrIf
+                    | Operator::OrAnd
+                    | Operator::Exclamation,
+                ) => true,
+                TokenType::Word(_) if command_position => match token.lexeme.as_str() {
+                    "if" | "while" | "until" | "{" => {
+                        depth += 1;
+                        true
+                    }
+                    // Name comes after these, not a command
+                    "for" | "select" => {
+                        depth += 1;
+                        false
+                    }
+                    "fi" | "done" | "}" => {
+                        depth -= 1;
+                        false
+                    }
+                    "then" | "else" | "elif" | "do" | "time" => true,
+                    _ => false,
+                },
+                _ => false,
+            };
+        }
+
+        depth > 0
     }
 }
 
 impl<'a> LexingContext<'a> {
-    pub fn scan(&mut self) -> anyhow::Result<()> {
+    fn new(line: usize, offset: usize, input: &'a str, recover: bool) -> Self {
+        Self {
+            line,
+            input,
+            chars: input.chars().peekable(),
+            pos: 0,
+            offset,
+            tokens: vec![],
+            word: String::new(),
+            word_start: (line, offset),
+            errors: recover.then(Vec::new),
+            pending: None,
+        }
+    }
+
+    fn scan(&mut self) -> Result<(), LexError> {
         while let Some(ch) = self.eat() {
-            match ch {
-                ' ' => {
-                    // We want to clear the word cause otherwise it will
-                    // contain space as a char
+            let (line, offset) = self.word_start;
+            let start = self.pos - ch.len_utf8();
+
+            match self.scan_char(ch) {
+                // Input being read line by line is not over yet
+                Err(LexError::UnexpectedEof { .. }) if self.errors.is_some() => {
+                    self.pending = Some(PendingWord {
+                        line,
+                        offset,
+                        input: self.input[start..].to_string(),
+                    });
                     self.word = String::new();
                 }
-                '\n' => {
-                    self.line += 1;
-                    self.offset = 0;
-                    self.word = String::new();
+                Err(err) => self.recover(err)?,
+                Ok(()) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    // Skips rest of the erroneous command, along with commands chained
+    // to it, i.e. everything since last `;` or newline, till next ones
+    fn recover(&mut self, err: LexError) -> Result<(), LexError> {
+        let Some(errors) = &mut self.errors else {
+            return Err(err);
+        };
+        errors.push(err);
+
+        while self
+            .tokens
+            .last()
+            .is_some_and(|token| {
+                token.line == self.line && !matches!(token.token_type, TokenType::Semicolon)
+            })
+        {
+            self.tokens.pop();
+        }
+
+        while let Some(&ch) = self.chars.peek() {
+            if ch == '\n' {
+                break;
+            }
+            self.eat();
+            if ch == ';' {
+                break;
+            }
+        }
+        self.word = String::new();
+
+        Ok(())
+    }
+
+    fn scan_char(&mut self, ch: char) -> Result<(), LexError> {
+        match ch {
+            // Other blanks, e.g. tabs indenting a script, are the same as space
+            ' ' | '\t' | '\r' => {
+                // We want to clear the word cause otherwise it will
+                // contain space as a char
+                self.word = String::new();
+            }
+            '\n' => {
+                self.word = String::new();
+            }
+            '&' => {
+                let next_char = self.peek();
+                if next_char == Some(&'&') {
+                    self.eat();
+                    self.add_token(TokenType::Operator(Operator::AndIf));
+                } else if next_char == Some(&'>') {
+                    self.eat();
+                    self.add_token(TokenType::Operator(Operator::SquirrelOutput));
+                } else {
+                    self.add_token(TokenType::Operator(Operator::And));
                 }
-                '&' => {
-                    let next_char = self.peek();
-                    if next_char == Some(&'&') {
-                        self.eat();
-                        self.add_token(TokenType::Operator(Operator::AndIf));
-                    } else if next_char == Some(&'>') {
-                        self.eat();
-                        self.add_token(TokenType::Operator(Operator::SquirrelOutput));
-                    } else {
-                        self.add_token(TokenType::Operator(Operator::And));
-                    }
+            }
+            '|' => {
+                let next_char = self.peek();
+                if next_char == Some(&'|') {
+                    self.eat();
+                    self.add_token(TokenType::Operator(Operator::OrIf));
+                } else if next_char == Some(&'&') {
+                    self.eat();
+                    self.add_token(TokenType::Operator(Operator::OrAnd));
+                } else {
+                    self.add_token(TokenType::Operator(Operator::Or));
                 }
-                '|' => {
-                    if self.peek() == Some(&'|') {
-                        self.eat();
-                        self.add_token(TokenType::Operator(Operator::OrIf));
-                    } else {
-                        self.add_token(TokenType::Operator(Operator::Or));
-                    }
+            }
+            ';' => {
+                self.add_token(TokenType::Semicolon);
+            }
+            // Comment runs till end of line
+            '#' => {
+                while self.peek().is_some_and(|ch| *ch != '\n') {
+                    self.eat();
                 }
-                ';' => {
-                    self.add_token(TokenType::Semicolon);
+                self.word = String::new();
+            }
+            '!' => self.add_token(TokenType::Operator(Operator::Exclamation)),
+            '(' => self.add_token(TokenType::LeftParen),
+            ')' => {
+                self.add_token(TokenType::RightParen);
+            }
+            // Only before a newline `\` continues the line, otherwise
+            // it escapes next char, which is then part of a word
+            '\\' if self.peek() == Some(&'\n') => {
+                self.add_token(TokenType::Backslash);
+            }
+            '<' => {
+                let next_char = self.peek();
+                if next_char == Some(&'>') {
+                    self.eat();
+                   self.add_token(TokenType::Operator(Operator::DiamondPointyBrackets));
+                } else if next_char == Some(&'&') {
+                    self.eat();
+                    self.add_token(TokenType::Operator(Operator::SquirrelInput));
+                } else if next_char == Some(&'(') {
+                    // `<(command)` is a word, substituted by a path
+                    self.eat();
+                    self.eat_substitution('(')?;
+                    self.eat_word()?;
+                    self.add_token(TokenType::Word(Word::Text));
+                } else {
+                    self.add_token(TokenType::Operator(Operator::LeftPointyBracket));
                 }
-                '!' => self.add_token(TokenType::Operator(Operator::Exclamation)),
-                '(' => self.add_token(TokenType::LeftParen),
-                ')' => {
-                    self.add_token(TokenType::RightParen);
+            }
+            '>' => {
+                let next_char = self.peek();
+                if next_char == Some(&'>') {
+                    self.eat();
+                    self.add_token(TokenType::Operator(Operator::DoubleRightPointyBracket));
+                } else if next_char == Some(&'&') {
+                    // `>&` is same as `&>`, e.g. `2>&1`
+                    self.eat();
+                    self.add_token(TokenType::Operator(Operator::SquirrelOutput));
+                } else {
+                    self.add_token(TokenType::Operator(Operator::RightPointyBracket));
                 }
-                '\\' => {
-                    self.add_token(TokenType::Backslash);
+            }
+            ch if is_valid_name_char(ch) || is_quote(ch) || ch == '\\' => {
+                if is_quote(ch) {
+                    self.eat_quoted(ch)?;
+                } else if ch == '\\' {
+                    self.eat();
                 }
-                '<' => {
-                    let next_char = self.peek();
-                    if next_char == Some(&'>') {
-                        self.eat();
-                       self.add_token(TokenType::Operator(Operator::DiamondPointyBrackets));
-                    } else if next_char == Some(&'&') {
-                        self.eat();
-                        self.add_token(TokenType::Operator(Operator::SquirrelInput));
-                    } else {
-                        self.add_token(TokenType::Operator(Operator::LeftPointyBracket));
-                    }
+                self.eat_word()?;
+                let token_type = match self.word.as_str() {
+                    "exit" => TokenType::Word(Word::Keyword(Keyword::Exit)),
+                    _ => TokenType::Word(Word::Text),
+                };
+                self.add_token(token_type);
+            }
+            _ => {
+                return Err(LexError::SyntaxError {
+                    message: format!("syntax error near unexpected token `{}'", ch),
+                    line: self.line,
+                    range: (self.offset, self.offset + 1),
+                })
+            }
+        }
+
+        Ok(())
+    }
+
+    // Quoted parts are kept as it is in the word,
+    // they get removed only while executing
+    fn eat_word(&mut self) -> Result<(), LexError> {
+        while let Some(&ch) = self.chars.peek() {
+            if is_quote(ch) {
+                self.eat();
+                self.eat_quoted(ch)?;
+            } else if ch == '\\' {
+                if self.chars.clone().nth(1) == Some('\n') {
+                    break;
                 }
-                '>' => {
-                    let next_char = self.peek();
-                    if next_char == Some(&'>') {
-                        self.eat();
-                        self.add_token(TokenType::Operator(Operator::DoubleRightPointyBracket));
-                    } else {
-                        self.add_token(TokenType::Operator(Operator::RightPointyBracket));
+                self.eat();
+                self.eat();
+            } else if matches!(ch, '(' | '{') && self.word.ends_with('$') {
+                self.eat();
+                self.eat_substitution(ch)?;
+            } else if ch == '#' {
+                // `$#`, count of positional parameters, or `#` inside
+                // a word, which does not start a comment
+                self.eat();
+            } else if is_valid_name_char(ch) || ch == '!' {
+                // `!` is an operator only at start of a word
+                self.eat();
+            } else if ch == '(' && self.at_glob_qualifiers() {
+                while self.eat() != Some(')') {}
+                break;
+            } else {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Glob qualifiers like `*(/)` are part of the pattern
+    // before them, instead of starting a subshell
+    fn at_glob_qualifiers(&self) -> bool {
+        if !self.word.contains(['*', '?', ']']) {
+            return false;
+        }
+
+        let mut lookahead = self.chars.clone().skip(1);
+        let mut qualifiers = 0;
+        loop {
+            match lookahead.next() {
+                Some(')') => return qualifiers > 0,
+                Some(ch) if glob::QUALIFIERS.contains(ch) => qualifiers += 1,
+                _ => return false,
+            }
+        }
+    }
+
+    // Eats `$(command)`, `$((expression))` or `${parameter}` till the
+    // matching bracket, opening bracket is expected to be already eaten
+    fn eat_substitution(&mut self, open: char) -> Result<(), LexError> {
+        let start_offset = self.offset - 2;
+        let close = if open == '(' { ')' } else { '}' };
+        let mut depth = 1;
+
+        while let Some(ch) = self.eat() {
+            match ch {
+                ch if ch == open => depth += 1,
+                ch if ch == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
                     }
                 }
-                ch if is_valid_name_char(ch) => {
-                    self.eat_while(is_valid_name_char);
-                    let token_type = match self.word.as_str() {
-                        "exit" => TokenType::Word(Word::Keyword(Keyword::Exit)),
-                        _ => TokenType::Word(Word::Text),
-                    };
-                    self.add_token(token_type);
-                }
-                _ => {
-                    return Err(ShellError::LexError(LexError::SyntaxError {
-                        message: "unexpected character".to_string(),
-                        line: self.line,
-                        range: (self.offset, self.offset + 1),
-                    })
-                    .into())
+                ch if is_quote(ch) => self.eat_quoted(ch)?,
+                '\\' => {
+                    self.eat();
                 }
+                _ => {}
             }
         }
 
-        Ok(())
+        Err(LexError::UnexpectedEof {
+            expected: close,
+            line: self.line,
+            range: (start_offset, self.offset),
+        })
     }
 
-    fn eat_while(&mut self, predicate: impl Fn(char) -> bool) {
-        loop {
-            let Some(ch) = self.chars.peek() else {
-                break;
-            };
+    // Eats till closing quote, opening quote is expected
+    // to be already eaten
+    fn eat_quoted(&mut self, quote: char) -> Result<(), LexError> {
+        let start_offset = self.offset - 1;
 
-            if !predicate(*ch) {
-                break;
+        while let Some(ch) = self.eat() {
+            if ch == quote {
+                return Ok(());
             }
 
-            self.eat();
+            match ch {
+                // Single quotes do not support escaping
+                '\\' if quote != '\'' => {
+                    self.eat();
+                }
+                // Substitutions within double quotes can have quotes of their own
+                '$' if quote == '"' && matches!(self.peek(), Some('(' | '{')) => {
+                    let open = self.eat().expect("bracket should have been peeked");
+                    self.eat_substitution(open)?;
+                }
+                _ => {}
+            }
         }
+
+        Err(LexError::UnexpectedEof {
+            expected: quote,
+            line: self.line,
+            range: (start_offset, self.offset),
+        })
     }
 
     fn eat(&mut self) -> Option<char> {
-        let Some(ch) = self.chars.next() else {
-            return None;
-        };
+        let ch = self.chars.next()?;
 
+        if self.word.is_empty() {
+            self.word_start = (self.line, self.offset);
+        }
         self.word.push(ch);
-        self.offset += 1;
-        return Some(ch);
+        self.pos += ch.len_utf8();
+
+        // Quoted words can have new lines too
+        if ch == '\n' {
+            self.line += 1;
+            self.offset = 0;
+        } else {
+            self.offset += 1;
+        }
+        Some(ch)
     }
 
     fn peek(&mut self) -> Option<&char> {
@@ -177,16 +497,13 @@ impl<'a> LexingContext<'a> {
     }
 
     fn add_token(&mut self, token_type: TokenType) {
-        let lexeme = &self.word;
-        let len = lexeme.len();
-
-        let start_offset = self.offset - len;
+        let (line, start_offset) = self.word_start;
         let end_offset = self.offset - 1;
 
         let token = Token {
-            lexeme: lexeme.to_string(),
+            lexeme: self.word.to_string(),
             token_type,
-            line: self.line,
+            line,
             range: (start_offset, end_offset),
         };
         self.tokens.push(token);
@@ -194,31 +511,20 @@ impl<'a> LexingContext<'a> {
     }
 }
 
+// Anything but blanks and characters shell gives a meaning
+// to can be in a word, e.g. `a,b`, `^` or non-ASCII text
 fn is_valid_name_char(ch: char) -> bool {
-    is_alpha_numeric(ch) || is_valid_name_special_char(ch)
+    !ch.is_whitespace()
+        && !matches!(
+            ch,
+            '|' | '&' | ';' | '(' | ')' | '<' | '>' | '\\' | '#' | '!' | '"' | '\'' | '`'
+        )
 }
 
-fn is_valid_name_special_char(ch: char) -> bool {
-    ch == '_'
-        || ch == '-'
-        || ch == '.'
-        || ch == '/'
-        || ch == '"'
-        || ch == '$'
-        || ch == '{'
-        || ch == '}'
-}
-
-fn is_alpha_numeric(ch: char) -> bool {
-    return is_alpha(ch) || is_digit(ch);
-}
-
-fn is_digit(ch: char) -> bool {
-    return ch >= '0' && ch <= '9';
-}
-
-fn is_alpha(ch: char) -> bool {
-    return (ch >= 'a' && ch <= 'z') || (ch >= 'A' && ch <= 'Z') || ch == '_';
+// Backquotes are not quotes really, but command substitutions
+// within them are taken as a whole word in the same way
+fn is_quote(ch: char) -> bool {
+    ch == '"' || ch == '\'' || ch == '`'
 }
 
 #[cfg(test)]
@@ -228,9 +534,7 @@ mod tests {
     fn check(input_str: &str) -> Vec<Token> {
         let mut lexer = Lexer::new();
 
-        let tokens = lexer.scan(input_str).expect("lexing should have succeeded");
-
-        tokens
+        lexer.scan(input_str).expect("lexing should have succeeded")
     }
 
     // Do not keep insta::assert_debug_snapshot!(lexer.tokens)
@@ -320,12 +624,26 @@ mod tests {
         insta::assert_debug_snapshot!(tokens);
     }
 
+    #[test]
+    fn test_lexing_of_escaped_chars() {
+        let tokens = check("echo \\$HOME a\\ b\\;c \\* x\\\n\n");
+        let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        assert_eq!(lexemes, vec!["echo", "\\$HOME", "a\\ b\\;c", "\\*", "x", "\\"]);
+        assert!(matches!(tokens[5].token_type, TokenType::Backslash));
+    }
+
     #[test]
     fn test_lexing_of_pipe_op() {
         let tokens = check("echo foo | cat | cat\n");
         insta::assert_debug_snapshot!(tokens);
     }
 
+    #[test]
+    fn test_lexing_of_pipe_stderr_op() {
+        let tokens = check("ls doesnotexist |& cat\n");
+        insta::assert_debug_snapshot!(tokens);
+    }
+
     #[test]
     fn test_lexing_of_pipe_op_with_redirection_with_fd() {
         let tokens = check("ls -6 2> file.txt\n");
@@ -386,6 +704,134 @@ mod tests {
         insta::assert_debug_snapshot!(tokens);
     }
 
+    #[test]
+    fn test_lexing_of_assignments() {
+        let tokens = check("n=5+5 arr+=(a b)\n");
+        insta::assert_debug_snapshot!(tokens);
+    }
+
+    #[test]
+    fn test_lexing_of_quoted_words() {
+        let tokens = check("let \"i = i + 1\" 'a b'c\"d\\\"\"\n");
+        insta::assert_debug_snapshot!(tokens);
+    }
+
+    #[test]
+    fn test_lexing_of_glob_patterns() {
+        let tokens = check("ls *.rs src/?.rs [!a-c]* *(/) (ls)\n");
+        insta::assert_debug_snapshot!(tokens);
+    }
+
+    #[test]
+    fn test_lexing_of_substitutions() {
+        let tokens = check(
+            "echo $(ls -a | wc) `pwd`x \"$((1 + (2)))\" $(echo ')') ${x:-a b}c ${y:+'}'}\n",
+        );
+        let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        assert_eq!(
+            lexemes,
+            vec![
+                "echo",
+                "$(ls -a | wc)",
+                "`pwd`x",
+                "\"$((1 + (2)))\"",
+                "$(echo ')')",
+                "${x:-a b}c",
+                "${y:+'}'}"
+            ]
+        );
+
+        let tokens = check("echo $# $@ \"$*\" ${10} user@host\n");
+        let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        assert_eq!(
+            lexemes,
+            vec!["echo", "$#", "$@", "\"$*\"", "${10}", "user@host"]
+        );
+
+        let tokens = check("diff <(sort a) <(ls | sort) < b\n");
+        let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        assert_eq!(lexemes, vec!["diff", "<(sort a)", "<(ls | sort)", "<", "b"]);
+
+        let tokens = check("echo \"$(printf \"a\\n\")\" \"$(echo \"a\\\"b\")\" \"${x:-\"}\"}\"\n");
+        let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        assert_eq!(
+            lexemes,
+            vec![
+                "echo",
+                "\"$(printf \"a\\n\")\"",
+                "\"$(echo \"a\\\"b\")\"",
+                "\"${x:-\"}\"}\""
+            ]
+        );
+
+        let mut lexer = Lexer::new();
+        assert!(lexer.scan("echo $(ls\n").is_err());
+        assert!(lexer.scan("cat <(ls\n").is_err());
+        assert!(lexer.scan("echo ${x:-\n").is_err());
+    }
+
+    #[test]
+    fn test_lexing_of_comments() {
+        let tokens = check("# comment ; ls\necho a#b '#' # c \"d\n");
+        let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        assert_eq!(lexemes, vec!["echo", "a#b", "'#'"]);
+        assert_eq!(tokens[0].line, 1);
+    }
+
+    #[test]
+    fn test_lexing_of_unterminated_quotes() {
+        let mut lexer = Lexer::new();
+        assert!(lexer.scan("echo \"foo\n").is_err());
+        assert!(lexer.scan("echo 'foo\n").is_err());
+
+        // Line by line input waits for the quote to be closed
+        let mut lexer = Lexer::new();
+        assert!(lexer.scan_recovering("echo 'foo\n").is_empty());
+        assert!(!lexer.complete_processing());
+        assert!(lexer.scan_recovering("\n").is_empty());
+        assert!(lexer.scan_recovering("bar' baz\n").is_empty());
+        assert!(lexer.complete_processing());
+        let lexemes: Vec<String> = lexer.tokens.iter().map(|token| token.to_string()).collect();
+        assert_eq!(lexemes, ["echo", "'foo\n\nbar'", "baz"]);
+        assert_eq!(lexer.tokens[2].line, 2);
+    }
+
+    #[test]
+    fn test_recovering_from_syntax_errors() {
+        let mut lexer = Lexer::new();
+        assert_eq!(lexer.scan_recovering("echo a; echo \u{b}b; c\n").len(), 1);
+        assert!(lexer.scan_recovering("echo d\n").is_empty());
+        let lexemes: Vec<String> = lexer.tokens.iter().map(|token| token.to_string()).collect();
+        assert_eq!(lexemes, ["echo", "a", ";", "c", "echo", "d"]);
+
+        // Only blanks and operators split words
+        let mut lexer = Lexer::new();
+        assert!(lexer.scan_recovering("echo a^b é\n").is_empty());
+        assert_eq!(lexer.tokens.len(), 3);
+    }
+
+    #[test]
+    fn test_completion_of_compound_cmds() {
+        let mut lexer = Lexer::new();
+        lexer.scan("if true; then\n").unwrap();
+        assert!(!lexer.complete_processing());
+        lexer.scan("echo fi\n").unwrap();
+        assert!(!lexer.complete_processing());
+        lexer.scan("fi\n").unwrap();
+        assert!(lexer.complete_processing());
+        assert_eq!(lexer.tokens.last().map(|token| token.line), Some(2));
+
+        let mut lexer = Lexer::new();
+        lexer.scan("select f in a b; do\n").unwrap();
+        assert!(!lexer.complete_processing());
+        lexer.scan("(echo $f\n").unwrap();
+        assert!(!lexer.complete_processing());
+        lexer.scan(")\n").unwrap();
+        assert!(!lexer.complete_processing());
+        lexer.scan("done\n").unwrap();
+        assert!(lexer.complete_processing());
+    }
+
     #[test]
     fn test_lexing_of_bg_process_with_ampersand() {
         let tokens = check("ping google.com &\n");
@@ -1,34 +1,146 @@
+pub mod arithmetic;
+pub mod expansion;
+pub mod glob;
 pub mod lexer;
 pub mod parser;
+pub mod quote;
 pub mod token;
 
-use std::{ffi::CString, path::PathBuf};
+use std::path::{Path, PathBuf};
 
+use expansion::ExpansionContext;
 use token::Token;
 
 #[derive(Default, Clone, Debug)]
 pub struct Command {
     pub tokens: Vec<Token>,
     pub path: PathBuf,
-    pub negate_exit_status: bool,
-    // Unqualified path = A path not starting with "/" or "../" or "./"
-    pub is_unqualified_path: bool,
+    // Prefixed with `noglob`, words are not pathname expanded
+    pub no_glob: bool,
 }
 
 impl Command {
-    pub fn get_args(&self) -> Vec<CString> {
-        self.tokens
-            .iter()
-            .map(|token| {
-                CString::new(token.lexeme.clone()).expect(&format!(
-                    "expected covnersion of lexeme {} to cstring to be successful",
-                    token.lexeme
-                ))
-            })
-            .collect()
+    // Words of command after all expansions, see expansion module
+    pub fn expand_words(&self, ctx: &mut impl ExpansionContext) -> anyhow::Result<Vec<String>> {
+        let glob_options = ctx.glob_options();
+        let glob_options = (!self.no_glob).then_some(&glob_options);
+
+        let mut words = vec![];
+        for token in &self.tokens {
+            // Values of array assignment are expanded one by one, and
+            // quoted back into the word, e.g. `arr=(*.rs)` expands to
+            // `arr=(a.rs 'b c.rs')`
+            if let Some((lhs, values)) = split_array_assignment(&token.lexeme) {
+                let mut expanded = vec![];
+                for value in values {
+                    expanded.extend(expansion::expand_word(&value, glob_options, ctx)?);
+                }
+                words.push(format!("{}({})", lhs, quote::join(&expanded)));
+                continue;
+            }
+
+            words.extend(expansion::expand_word(&token.lexeme, glob_options, ctx)?);
+        }
+
+        Ok(words)
     }
 }
 
+// Array assignment parser kept as one word, e.g. `arr=(a "b c")`, split
+// into `arr=` and words of values as they are written. Such unquoted
+// word can only come from parser, as `(` is not a part of words
+fn split_array_assignment(word: &str) -> Option<(&str, Vec<String>)> {
+    Assignment::parse(word)?;
+    let (lhs, value) = word.split_at(word.find('=')? + 1);
+    let values = value.strip_prefix('(')?.strip_suffix(')')?;
+
+    let tokens = lexer::Lexer::new().scan(&format!("{}\n", values)).ok()?;
+    Some((lhs, tokens.into_iter().map(|token| token.lexeme).collect()))
+}
+
+// Unqualified path = A path not starting with "/" or "../" or "./"
+pub fn is_unqualified_path(path: &Path) -> bool {
+    !(path.starts_with("./") || path.starts_with("../") || path.starts_with("/"))
+}
+
+// Quote Removal Spec:
+// - URL: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html#tag_18_06_07
+pub fn unquote(word: &str) -> String {
+    let mut unquoted = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = word.chars();
+
+    while let Some(ch) = chars.next() {
+        match (quote, ch) {
+            (None, '"' | '\'') => quote = Some(ch),
+            (Some(quote_ch), ch) if quote_ch == ch => quote = None,
+            // Within double quotes backslash only escapes these
+            (Some('"'), '\\') => match chars.next() {
+                Some(next_ch @ ('$' | '`' | '"' | '\\')) => unquoted.push(next_ch),
+                Some(next_ch) => {
+                    unquoted.push('\\');
+                    unquoted.push(next_ch);
+                }
+                None => unquoted.push('\\'),
+            },
+            (_, ch) => unquoted.push(ch),
+        }
+    }
+
+    unquoted
+}
+
+// A `name=value` or `name+=value` word, arrays
+// are written as `name=(value1 value2)`
+#[derive(Clone, Debug)]
+pub struct Assignment {
+    pub name: String,
+    pub value: AssignmentValue,
+    // true for `+=`
+    pub append: bool,
+}
+
+#[derive(Clone, Debug)]
+pub enum AssignmentValue {
+    Scalar(String),
+    Array(Vec<String>),
+}
+
+impl Assignment {
+    // Returns None if word is not of form `name=value` or `name+=value`,
+    // where name follows the rules of:
+    // https://pubs.opengroup.org/onlinepubs/9699919799/basedefs/V1_chap03.html#tag_03_235
+    pub fn parse(word: &str) -> Option<Assignment> {
+        let (lhs, value) = word.split_once('=')?;
+
+        let (name, append) = match lhs.strip_suffix('+') {
+            Some(name) => (name, true),
+            None => (lhs, false),
+        };
+
+        if !is_valid_name(name) {
+            return None;
+        }
+
+        Some(Assignment {
+            name: name.to_string(),
+            value: AssignmentValue::Scalar(value.to_string()),
+            append,
+        })
+    }
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(ch) if ch.is_ascii_alphabetic() || ch == '_' => {}
+        _ => return false,
+    }
+
+    chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+}
+
 // Old Lexing + Parsing
 
 //#[derive(Default, Clone, Debug)]
@@ -3,14 +3,17 @@ use std::{fmt::Display, path::PathBuf, str::FromStr};
 use crate::errors::ShellError;
 
 use super::{
+    is_valid_name,
     token::{Keyword, Operator, Token, TokenType, Word},
-    Command,
+    unquote, Assignment, AssignmentValue, Command,
 };
 
 #[derive(Debug)]
 pub struct Parser<'a> {
     tokens: &'a Vec<Token>,
     idx: usize,
+    // Command being parsed is prefixed with `noglob`
+    no_glob: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -22,12 +25,9 @@ pub enum OpType {
     // RedirectSquirrelOutput(Option<i32>),
     RedirectSquirrelOutput {
         // 2nd argument
-        // None here means "minus"
-        // because otherwise it will
-        // be an error
         //
         // read this as source
-        source: Option<i32>,
+        source: FdSource,
         // 1st argument
         // Here None means nothing
         // is present and
@@ -38,25 +38,82 @@ pub enum OpType {
     },
     RedirectSquirrelInput {
         // same docs as output one
-        source: Option<i32>,
+        source: FdSource,
         target: Option<i32>,
     },
     OrIf,
     Pipe,
+    // `|&`, pipes stderr along with stdout
+    PipeWithStderr,
     AndIf,
     Semicolon,
     Background,
 }
 
-#[derive(Debug)]
+// What `>&` and `<&` make fd a duplicate of
+#[derive(Debug, Clone)]
+pub enum FdSource {
+    Fd(i32),
+    // `-`, fd gets closed instead
+    Close,
+    // Expanded when command runs, e.g. `$fd`
+    Word(String),
+}
+
+impl Display for FdSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FdSource::Fd(fd) => write!(f, "{}", fd),
+            FdSource::Close => write!(f, "-"),
+            FdSource::Word(word) => write!(f, "{}", word),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum ExecuteMode {
     Normal,
-    Subshell(Vec<Token>),
+    // Commands within `()`, which can have
+    // subshells nested in them too
+    Subshell(Vec<ParseResult>),
+    // `(( expr ))`, holds the expression
+    Arithmetic(String),
+    // `select name in words; do body; done`
+    Select {
+        name: String,
+        words: Vec<String>,
+        body: Vec<ParseResult>,
+    },
+    // `if list; then list; [elif list; then list;]... [else list;] fi`,
+    // conditions are kept along with bodies they guard
+    If {
+        branches: Vec<(Vec<ParseResult>, Vec<ParseResult>)>,
+        else_body: Option<Vec<ParseResult>>,
+    },
+    // `while list; do list; done`, `until` runs body till condition succeeds
+    Loop {
+        until: bool,
+        condition: Vec<ParseResult>,
+        body: Vec<ParseResult>,
+    },
+    // `for name [in words]; do list; done`, words are expanded when loop
+    // runs, without them it goes over positional parameters
+    For {
+        name: String,
+        words: Vec<Token>,
+        body: Vec<ParseResult>,
+    },
+    // `{ list; }`, runs in shell itself unlike subshell
+    Group(Vec<ParseResult>),
 }
 
 impl<'a> Parser<'a> {
     pub fn new(tokens: &'a Vec<Token>) -> Self {
-        Self { tokens, idx: 0 }
+        Self {
+            tokens,
+            idx: 0,
+            no_glob: false,
+        }
     }
 
     // There are two types of parsing modes:
@@ -75,38 +132,149 @@ impl<'a> Parser<'a> {
             return Ok(None);
         }
 
+        // Redirections return as soon as their file path is
+        // parsed, so `;` terminating them is left for us
+        let follows_redirection = self.idx > 0
+            && matches!(self.tokens[self.idx - 1].token_type, TokenType::Word(_));
+        if follows_redirection && matches!(self.tokens[self.idx].token_type, TokenType::Semicolon) {
+            self.idx += 1;
+            return self.get_command();
+        }
+
         let mut parse_result = ParseResult::new();
+        parse_result.line = self.tokens[self.idx].line;
+        self.no_glob = false;
+        let start_idx = self.idx;
 
         let mut tokens = vec![];
         let mut first_token = true;
         let mut cmd_path = None;
-        let mut negate_exit_status = false;
         let mut capture_only_tokens = false; // This is for subshell mode
+        // Count of `(` seen inside subshell which are not closed yet,
+        // so that only the matching `)` ends the subshell
+        let mut subshell_depth = 0;
 
         while self.idx < self.tokens.len() {
+            // New line ends command same as `;`
+            if !capture_only_tokens
+                && self.idx > start_idx
+                && self.tokens[self.idx - 1].ends_line_before(&self.tokens[self.idx])
+            {
+                parse_result.associated_operator = Some(OpType::Semicolon);
+                break;
+            }
+
             let token = self.tokens[self.idx].clone();
             self.idx += 1;
 
-            if capture_only_tokens && !matches!(token.token_type, TokenType::RightParen) {
-                tokens.push(token);
-                continue;
+            if capture_only_tokens {
+                let closes_subshell = match token.token_type {
+                    TokenType::LeftParen => {
+                        subshell_depth += 1;
+                        false
+                    }
+                    TokenType::RightParen if subshell_depth > 0 => {
+                        subshell_depth -= 1;
+                        false
+                    }
+                    TokenType::RightParen => true,
+                    _ => false,
+                };
+
+                if !closes_subshell {
+                    tokens.push(token);
+                    continue;
+                }
             }
 
             match &token.token_type {
                 TokenType::Word(Word::Text) => {
+                    if !matches!(
+                        parse_result.execute_mode,
+                        ExecuteMode::Normal | ExecuteMode::Subshell(_)
+                    ) {
+                        return Err(ShellError::ParseError(format!("unexpected {}", token)).into());
+                    }
+
+                    // `time` applies to the whole pipeline after it
+                    if first_token && token.lexeme == "time" && !parse_result.timed {
+                        parse_result.timed = true;
+                        continue;
+                    }
+
+                    // Words of command after `noglob` reach it unexpanded
+                    if first_token && token.lexeme == "noglob" && !self.no_glob {
+                        self.no_glob = true;
+                        continue;
+                    }
+
                     if first_token {
-                        cmd_path = Some(PathBuf::from_str(&token.lexeme).expect(&format!(
-                            "Could not construct path buf from token: {}",
-                            token.lexeme
-                        )));
+                        let compound = match token.lexeme.as_str() {
+                            "select" => Some(self.parse_select(&token)?),
+                            "if" => Some(self.parse_if()?),
+                            "while" | "until" => Some(self.parse_loop(&token)?),
+                            "for" => Some(self.parse_for(&token)?),
+                            "{" => Some(ExecuteMode::Group(self.parse_list_till("{", &["}"])?.0)),
+                            // Closing words of compound commands without one
+                            "then" | "elif" | "else" | "fi" | "do" | "done" | "}" => {
+                                return Err(ShellError::SyntaxError(token.lexeme).into())
+                            }
+                            _ => None,
+                        };
+
+                        if let Some(compound) = compound {
+                            parse_result.execute_mode = compound;
+                            continue;
+                        }
+                    }
+
+                    if first_token {
+                        if let Some(assignment) = self.parse_assignment(&token)? {
+                            parse_result.assignments.push(assignment);
+                            continue;
+                        }
+
+                        cmd_path = Some(PathBuf::from_str(&token.lexeme).unwrap_or_else(|_| {
+                            panic!("Could not construct path buf from token: {}", token.lexeme)
+                        }));
                         first_token = false;
+                    } else if Assignment::parse(&token.lexeme).is_some() {
+                        // Array assignments given to commands, e.g. `declare -a
+                        // arr=(a b)`, are kept as one word till expansion
+                        if let Some(values) = self.parse_array_values(&token)? {
+                            tokens.push(Token {
+                                lexeme: format!("{}({})", token.lexeme, values.join(" ")),
+                                ..token
+                            });
+                            continue;
+                        }
                     }
 
                     tokens.push(token);
                 }
+                // Keywords are plain words past command word, e.g. `type exit`
+                TokenType::Word(Word::Keyword(_)) if !first_token => tokens.push(token),
                 TokenType::Word(Word::Keyword(keyword)) => match keyword {
                     Keyword::Exit => {
                         parse_result.exit_term = true;
+
+                        // Optional status, e.g. `exit 3`
+                        while let Some(next_token) = self.tokens.get(self.idx) {
+                            if !matches!(next_token.token_type, TokenType::Word(Word::Text))
+                                || next_token.line != token.line
+                            {
+                                break;
+                            }
+
+                            if parse_result.exit_status.is_some() {
+                                return Err(ShellError::ParseError(
+                                    "exit: too many arguments".into(),
+                                )
+                                .into());
+                            }
+                            parse_result.exit_status = Some(next_token.lexeme.clone());
+                            self.idx += 1;
+                        }
                     }
                 },
                 TokenType::Operator(Operator::OrIf) => {
@@ -121,8 +289,23 @@ impl<'a> Parser<'a> {
                 //     parse_result.associated_operator = Some(OpType::Semicolon);
                 //     break;
                 // }
+                // Past command word it is a plain word, e.g. `[ ! -f x ]`
+                TokenType::Operator(Operator::Exclamation) if !first_token => {
+                    tokens.push(Token {
+                        token_type: TokenType::Word(Word::Text),
+                        ..token
+                    });
+                }
                 TokenType::Operator(Operator::Exclamation) => {
-                    if !first_token {
+                    // `!` negates a whole pipeline, so it can
+                    // only come before its first command
+                    let follows_pipe = self.idx >= 2
+                        && matches!(
+                            self.tokens[self.idx - 2].token_type,
+                            TokenType::Operator(Operator::Or | Operator::OrAnd)
+                        );
+
+                    if follows_pipe {
                         return Err(
                             ShellError::ParseError("! found in invalid place".into()).into()
                         );
@@ -130,25 +313,21 @@ impl<'a> Parser<'a> {
                         // in next loop cycle we want it to be parsed
                         // as pathbuf
                     }
-                    negate_exit_status = true;
+                    parse_result.negated = true;
                 }
                 // FIXME: use macro to remove this repeated code
                 // for redirect operators below
                 TokenType::Operator(Operator::LeftPointyBracket) => {
-                    if let Some(last_token) = tokens.last() {
-                        if let Ok(fd) = last_token.to_string().parse::<i32>() {
-                            tokens.pop();
-                            parse_result.associated_operator =
-                                Some(OpType::RedirectInput(Some(fd)));
-                        } else {
-                            parse_result.associated_operator = Some(OpType::RedirectInput(None));
-                        }
-                    }
+                    parse_result.fd_variable = take_fd_variable(&mut tokens, &token, &mut cmd_path);
+                    let fd = match parse_result.fd_variable {
+                        Some(_) => None,
+                        None => take_redirected_fd(&mut tokens, &token, &mut cmd_path),
+                    };
+                    parse_result.associated_operator = Some(OpType::RedirectInput(fd));
 
                     let cmds = self.handle_pointy_bracket_redirection_cmd_gen(
                         tokens,
-                        cmd_path.expect("expected command path to exist"),
-                        negate_exit_status,
+                        cmd_path.unwrap_or_default(),
                     );
 
                     for cmd in cmds.into_iter() {
@@ -158,20 +337,16 @@ impl<'a> Parser<'a> {
                     return Ok(Some(parse_result));
                 }
                 TokenType::Operator(Operator::RightPointyBracket) => {
-                    if let Some(last_token) = tokens.last() {
-                        if let Ok(fd) = last_token.to_string().parse::<i32>() {
-                            tokens.pop();
-                            parse_result.associated_operator =
-                                Some(OpType::RedirectOutput(Some(fd)));
-                        } else {
-                            parse_result.associated_operator = Some(OpType::RedirectOutput(None));
-                        }
-                    }
+                    parse_result.fd_variable = take_fd_variable(&mut tokens, &token, &mut cmd_path);
+                    let fd = match parse_result.fd_variable {
+                        Some(_) => None,
+                        None => take_redirected_fd(&mut tokens, &token, &mut cmd_path),
+                    };
+                    parse_result.associated_operator = Some(OpType::RedirectOutput(fd));
 
                     let cmds = self.handle_pointy_bracket_redirection_cmd_gen(
                         tokens,
-                        cmd_path.expect("expected command path to exist"),
-                        negate_exit_status,
+                        cmd_path.unwrap_or_default(),
                     );
 
                     for cmd in cmds.into_iter() {
@@ -181,21 +356,16 @@ impl<'a> Parser<'a> {
                     return Ok(Some(parse_result));
                 }
                 TokenType::Operator(Operator::DoubleRightPointyBracket) => {
-                    if let Some(last_token) = tokens.last() {
-                        if let Ok(fd) = last_token.to_string().parse::<i32>() {
-                            tokens.pop();
-                            parse_result.associated_operator =
-                                Some(OpType::RedirectAppendOutput(Some(fd)));
-                        } else {
-                            parse_result.associated_operator =
-                                Some(OpType::RedirectAppendOutput(None));
-                        }
-                    }
+                    parse_result.fd_variable = take_fd_variable(&mut tokens, &token, &mut cmd_path);
+                    let fd = match parse_result.fd_variable {
+                        Some(_) => None,
+                        None => take_redirected_fd(&mut tokens, &token, &mut cmd_path),
+                    };
+                    parse_result.associated_operator = Some(OpType::RedirectAppendOutput(fd));
 
                     let cmds = self.handle_pointy_bracket_redirection_cmd_gen(
                         tokens,
-                        cmd_path.expect("expected command path to exist"),
-                        negate_exit_status,
+                        cmd_path.unwrap_or_default(),
                     );
 
                     for cmd in cmds.into_iter() {
@@ -205,21 +375,16 @@ impl<'a> Parser<'a> {
                     return Ok(Some(parse_result));
                 }
                 TokenType::Operator(Operator::DiamondPointyBrackets) => {
-                    if let Some(last_token) = tokens.last() {
-                        if let Ok(fd) = last_token.to_string().parse::<i32>() {
-                            tokens.pop();
-                            parse_result.associated_operator =
-                                Some(OpType::RedirectReadWrite(Some(fd)));
-                        } else {
-                            parse_result.associated_operator =
-                                Some(OpType::RedirectReadWrite(None));
-                        }
-                    }
+                    parse_result.fd_variable = take_fd_variable(&mut tokens, &token, &mut cmd_path);
+                    let fd = match parse_result.fd_variable {
+                        Some(_) => None,
+                        None => take_redirected_fd(&mut tokens, &token, &mut cmd_path),
+                    };
+                    parse_result.associated_operator = Some(OpType::RedirectReadWrite(fd));
 
                     let cmds = self.handle_pointy_bracket_redirection_cmd_gen(
                         tokens,
-                        cmd_path.expect("expected command path to exist"),
-                        negate_exit_status,
+                        cmd_path.unwrap_or_default(),
                     );
 
                     for cmd in cmds.into_iter() {
@@ -229,143 +394,479 @@ impl<'a> Parser<'a> {
                     return Ok(Some(parse_result));
                 }
                 TokenType::Operator(Operator::SquirrelOutput) => {
-                    if let Some(last_token) = tokens.last() {
-                        let target_fd_opt = if let Ok(fd) = last_token.to_string().parse::<i32>() {
-                            tokens.pop();
-                            Some(fd)
-                        } else {
-                            None
-                        };
-
-                        let fd_or_minus_not_found_err = Err(ShellError::ParseError(
-                            "expected file descriptor or minus after squirrel redirection operator"
-                                .into(),
-                        )
-                        .into());
-
-                        let maybe_fd_or_minus_token = self.tokens[self.idx].clone();
-                        self.idx += 1;
-
-                        let t = maybe_fd_or_minus_token;
-                        let fd_or_minus_token = if t.to_string() == "-" {
-                            None
-                        } else if let Ok(fd) = t.to_string().parse::<i32>() {
-                            Some(fd)
-                        } else {
-                            return fd_or_minus_not_found_err;
-                        };
-
-                        parse_result.associated_operator = Some(OpType::RedirectSquirrelOutput {
-                            source: fd_or_minus_token,
-                            target: target_fd_opt,
-                        });
-                    }
-
-                    let cmd = make_command(
-                        tokens,
-                        cmd_path.expect("expected command path to exist"),
-                        negate_exit_status,
-                    );
+                    parse_result.fd_variable = take_fd_variable(&mut tokens, &token, &mut cmd_path);
+                    let target_fd_opt = match parse_result.fd_variable {
+                        Some(_) => None,
+                        None => take_redirected_fd(&mut tokens, &token, &mut cmd_path),
+                    };
+                    let source = self.parse_fd_source()?;
+
+                    parse_result.associated_operator = Some(OpType::RedirectSquirrelOutput {
+                        source,
+                        target: target_fd_opt,
+                    });
+
+                    let cmd = self.make_command(tokens, cmd_path.unwrap_or_default());
                     parse_result.cmds.push(cmd);
 
                     return Ok(Some(parse_result));
                 }
                 TokenType::Operator(Operator::SquirrelInput) => {
-                    if let Some(last_token) = tokens.last() {
-                        let target_fd_opt = if let Ok(fd) = last_token.to_string().parse::<i32>() {
-                            tokens.pop();
-                            Some(fd)
-                        } else {
-                            None
-                        };
-
-                        let fd_or_minus_not_found_err = Err(ShellError::ParseError(
-                            "expected file descriptor or minus after squirrel redirection operator"
-                                .into(),
-                        )
-                        .into());
-
-                        let maybe_fd_or_minus_token = self.tokens[self.idx].clone();
-                        self.idx += 1;
-
-                        let t = maybe_fd_or_minus_token;
-                        let fd_or_minus_token = if t.to_string() == "-" {
-                            None
-                        } else if let Ok(fd) = t.to_string().parse::<i32>() {
-                            Some(fd)
-                        } else {
-                            return fd_or_minus_not_found_err;
-                        };
-
-                        parse_result.associated_operator = Some(OpType::RedirectSquirrelInput {
-                            source: fd_or_minus_token,
-                            target: target_fd_opt,
-                        });
-                    }
-
-                    let cmd = make_command(
-                        tokens,
-                        cmd_path.expect("expected command path to exist"),
-                        negate_exit_status,
-                    );
+                    parse_result.fd_variable = take_fd_variable(&mut tokens, &token, &mut cmd_path);
+                    let target_fd_opt = match parse_result.fd_variable {
+                        Some(_) => None,
+                        None => take_redirected_fd(&mut tokens, &token, &mut cmd_path),
+                    };
+                    let source = self.parse_fd_source()?;
+
+                    parse_result.associated_operator = Some(OpType::RedirectSquirrelInput {
+                        source,
+                        target: target_fd_opt,
+                    });
+
+                    let cmd = self.make_command(tokens, cmd_path.unwrap_or_default());
                     parse_result.cmds.push(cmd);
 
                     return Ok(Some(parse_result));
                 }
                 TokenType::LeftParen => {
+                    // `((` starts an arithmetic command instead of nested subshells
+                    let is_arithmetic = first_token
+                        && self.tokens.get(self.idx).is_some_and(|next_token| {
+                            matches!(next_token.token_type, TokenType::LeftParen)
+                                && is_adjacent(&token, next_token)
+                        });
+
+                    if is_arithmetic {
+                        self.idx += 1;
+                        parse_result.execute_mode =
+                            ExecuteMode::Arithmetic(self.parse_arithmetic()?);
+                        first_token = false;
+                        continue;
+                    }
+
                     capture_only_tokens = true;
                 }
                 TokenType::RightParen => {
-                    parse_result.execute_mode = ExecuteMode::Subshell(tokens.clone());
+                    if !capture_only_tokens {
+                        return Err(ShellError::ParseError("unexpected )".into()).into());
+                    }
+
+                    let mut subshell_parser = Parser::new(&tokens);
+                    let mut subshell_parse_results = vec![];
+                    while let Some(subshell_parse_result) = subshell_parser.get_command()? {
+                        subshell_parse_results.push(subshell_parse_result);
+                    }
+
+                    parse_result.execute_mode = ExecuteMode::Subshell(subshell_parse_results);
                     capture_only_tokens = false;
                 }
                 TokenType::Operator(Operator::Or) => {
                     parse_result.associated_operator = Some(OpType::Pipe);
                     break;
                 }
+                TokenType::Operator(Operator::OrAnd) => {
+                    parse_result.associated_operator = Some(OpType::PipeWithStderr);
+                    break;
+                }
                 TokenType::Operator(Operator::And) => {
                     parse_result.associated_operator = Some(OpType::Background);
                     break;
                 },
                 TokenType::Backslash => {}
-                TokenType::Semicolon => todo!(),
+                TokenType::Semicolon => {
+                    parse_result.associated_operator = Some(OpType::Semicolon);
+                    break;
+                }
             }
         }
 
-        if matches!(parse_result.execute_mode, ExecuteMode::Subshell(_)) {
+        if capture_only_tokens {
+            return Err(ShellError::ParseError("expected ) at the end of subshell".into()).into());
+        }
+
+        if !matches!(parse_result.execute_mode, ExecuteMode::Normal) {
             return Ok(Some(parse_result));
         }
 
         match cmd_path {
             Some(cmd_path) => {
-                let cmd = make_command(tokens, cmd_path, negate_exit_status);
+                let cmd = self.make_command(tokens, cmd_path);
 
                 parse_result.cmds.push(cmd);
 
-                return Ok(Some(parse_result));
+                Ok(Some(parse_result))
+            }
+            None => {
+                if !parse_result.exit_term && parse_result.assignments.is_empty() {
+                    // Operator ended command before it had any word,
+                    // e.g. `; echo`, `a && && b`, or input ended
+                    let unexpected = self
+                        .tokens
+                        .get(self.idx - 1)
+                        .filter(|token| !matches!(token.token_type, TokenType::Word(_)))
+                        .map_or("newline".to_string(), |token| token.to_string());
+                    return Err(ShellError::SyntaxError(unexpected).into());
+                }
+
+                Ok(Some(parse_result))
+            }
+        }
+    }
+
+    // Skips rest of the erroneous command till next `;` or
+    // newline, so that parsing can carry on after a parse error
+    pub fn recover(&mut self) {
+        let Some(last_token) = self.idx.checked_sub(1).and_then(|idx| self.tokens.get(idx)) else {
+            return;
+        };
+
+        // Error was found after command got terminated already
+        if matches!(last_token.token_type, TokenType::Semicolon) {
+            return;
+        }
+
+        let mut prev_token = last_token;
+        while let Some(token) = self.tokens.get(self.idx) {
+            if prev_token.ends_line_before(token) {
+                break;
+            }
+            prev_token = token;
+
+            self.idx += 1;
+            if matches!(token.token_type, TokenType::Semicolon) {
+                break;
+            }
+        }
+    }
+
+    // Grammar: select NAME in WORD* (";" | newline) do LIST done
+    fn parse_select(&mut self, select_token: &Token) -> anyhow::Result<ExecuteMode> {
+        let (name, words) = self.parse_loop_words(select_token)?;
+        let Some(words) = words else {
+            return Err(ShellError::ParseError("select: expected in after name".into()).into());
+        };
+        let words = words.iter().map(|token| unquote(&token.lexeme)).collect();
+        let (body, _) = self.parse_list_till("select", &["done"])?;
+
+        Ok(ExecuteMode::Select { name, words, body })
+    }
+
+    // Grammar: for NAME [in WORD*] (";" | newline) do LIST done
+    fn parse_for(&mut self, for_token: &Token) -> anyhow::Result<ExecuteMode> {
+        let (name, words) = self.parse_loop_words(for_token)?;
+        // Same as `for name in "$@"`
+        let words = words.unwrap_or_else(|| {
+            vec![Token {
+                lexeme: "\"$@\"".to_string(),
+                token_type: TokenType::Word(Word::Text),
+                ..for_token.clone()
+            }]
+        });
+        let (body, _) = self.parse_list_till("for", &["done"])?;
+
+        Ok(ExecuteMode::For { name, words, body })
+    }
+
+    // Parses `NAME [in WORD*] (";" | newline) do` of `for` and `select`,
+    // words are None if there is no `in`
+    fn parse_loop_words(
+        &mut self,
+        keyword: &Token,
+    ) -> anyhow::Result<(String, Option<Vec<Token>>)> {
+        let name = match self.next_token() {
+            Some(token) if is_valid_name(&token.lexeme) => token.clone(),
+            Some(token) => {
+                return Err(ShellError::ParseError(format!(
+                    "{}: `{}': not a valid identifier",
+                    keyword, token
+                ))
+                .into())
             }
             None => {
-                if !parse_result.exit_term {
-                    return Err(ShellError::InternalError("could not find cmd_path".into()).into());
+                return Err(ShellError::ParseError(format!("{}: expected name", keyword)).into())
+            }
+        };
+
+        // Words run till `;` or end of line
+        let mut words = None;
+        let mut prev_token = name.clone();
+        while let Some(token) = self.tokens.get(self.idx).cloned() {
+            if prev_token.ends_line_before(&token) {
+                break;
+            }
+            self.idx += 1;
+
+            match (&token.token_type, &mut words) {
+                (TokenType::Semicolon, _) => break,
+                (TokenType::Word(_), None) if token.lexeme == "in" => words = Some(vec![]),
+                (TokenType::Word(_), Some(words)) => words.push(token.clone()),
+                _ => {
+                    return Err(ShellError::ParseError(format!(
+                        "{}: unexpected {}",
+                        keyword, token
+                    ))
+                    .into())
                 }
+            }
+            prev_token = token;
+        }
 
-                return Ok(Some(parse_result));
+        match self.next_token() {
+            Some(token) if token.lexeme == "do" => {}
+            _ => return Err(ShellError::ParseError(format!("{}: expected do", keyword)).into()),
+        }
+
+        Ok((name.lexeme, words))
+    }
+
+    // Grammar: if LIST then LIST (elif LIST then LIST)* [else LIST] fi
+    fn parse_if(&mut self) -> anyhow::Result<ExecuteMode> {
+        let mut branches = vec![];
+        loop {
+            let (condition, _) = self.parse_list_till("if", &["then"])?;
+            let (body, end) = self.parse_list_till("if", &["elif", "else", "fi"])?;
+            branches.push((condition, body));
+
+            match end.as_str() {
+                "elif" => continue,
+                "else" => {
+                    let (else_body, _) = self.parse_list_till("if", &["fi"])?;
+                    return Ok(ExecuteMode::If {
+                        branches,
+                        else_body: Some(else_body),
+                    });
+                }
+                _ => {
+                    return Ok(ExecuteMode::If {
+                        branches,
+                        else_body: None,
+                    })
+                }
             }
         }
     }
 
+    // Grammar: (while | until) LIST do LIST done
+    fn parse_loop(&mut self, keyword: &Token) -> anyhow::Result<ExecuteMode> {
+        let (condition, _) = self.parse_list_till(&keyword.lexeme, &["do"])?;
+        let (body, _) = self.parse_list_till(&keyword.lexeme, &["done"])?;
+
+        Ok(ExecuteMode::Loop {
+            until: keyword.lexeme == "until",
+            condition,
+            body,
+        })
+    }
+
+    // Parses commands till one of `ends` words, which is eaten and
+    // returned too. Only words in command position count, so that
+    // `echo done` works as expected, and compound commands nested
+    // in between are taken whole along with their closing words
+    fn parse_list_till(
+        &mut self,
+        keyword: &str,
+        ends: &[&str],
+    ) -> anyhow::Result<(Vec<ParseResult>, String)> {
+        let mut tokens: Vec<Token> = vec![];
+        // Closing words of nested compound commands
+        let mut closers = vec![];
+        let mut command_position = true;
+
+        let end = loop {
+            let Some(token) = self.next_token().cloned() else {
+                return Err(ShellError::ParseError(format!(
+                    "{}: expected {}",
+                    keyword,
+                    ends.join(" or ")
+                ))
+                .into());
+            };
+
+            command_position = command_position
+                || tokens.last().is_some_and(|prev_token| prev_token.ends_line_before(&token));
+
+            if command_position && matches!(token.token_type, TokenType::Word(_)) {
+                let lexeme = token.lexeme.as_str();
+                if closers.is_empty() && ends.contains(&lexeme) {
+                    break token.lexeme;
+                }
+
+                match lexeme {
+                    "if" => closers.push("fi"),
+                    "while" | "until" | "for" | "select" => closers.push("done"),
+                    "{" => closers.push("}"),
+                    _ if closers.last() == Some(&lexeme) => {
+                        closers.pop();
+                    }
+                    _ => {}
+                }
+            }
+
+            // Words after these start a command too
+            command_position = match token.token_type {
+                TokenType::Semicolon | TokenType::Operator(_) | TokenType::LeftParen => true,
+                TokenType::Backslash => command_position,
+                TokenType::Word(_) => {
+                    command_position
+                        && matches!(
+                            token.lexeme.as_str(),
+                            "if" | "then" | "elif" | "else" | "while" | "until" | "do" | "{"
+                        )
+                }
+                TokenType::RightParen => false,
+            };
+            tokens.push(token);
+        };
+
+        // Lists can not be empty, e.g. `if then`
+        if tokens.is_empty() {
+            return Err(ShellError::SyntaxError(end).into());
+        }
+
+        let mut parser = Parser::new(&tokens);
+        let mut list = vec![];
+        while let Some(parse_result) = parser.get_command()? {
+            list.push(parse_result);
+        }
+
+        Ok((list, end))
+    }
+
+    // Collects expression till matching `))`, `((` is
+    // expected to be already eaten
+    fn parse_arithmetic(&mut self) -> anyhow::Result<String> {
+        let mut expr = vec![];
+        let mut depth = 0;
+
+        loop {
+            let Some(token) = self.next_token().cloned() else {
+                return Err(ShellError::ParseError(
+                    "expected )) at the end of arithmetic command".into(),
+                )
+                .into());
+            };
+
+            match token.token_type {
+                TokenType::LeftParen => depth += 1,
+                TokenType::RightParen if depth > 0 => depth -= 1,
+                TokenType::RightParen => {
+                    return match self.next_token() {
+                        Some(next_token)
+                            if matches!(next_token.token_type, TokenType::RightParen)
+                                && is_adjacent(&token, next_token) =>
+                        {
+                            Ok(expr.join(" "))
+                        }
+                        _ => Err(ShellError::ParseError(
+                            "expected )) at the end of arithmetic command".into(),
+                        )
+                        .into()),
+                    };
+                }
+                _ => {}
+            }
+
+            expr.push(unquote(&token.lexeme));
+        }
+    }
+
+    // Word after `>&` or `<&`, either a fd, `-` or a word
+    // expanding to one of them
+    fn parse_fd_source(&mut self) -> anyhow::Result<FdSource> {
+        let source = match self.next_token() {
+            Some(token) if token.lexeme == "-" => FdSource::Close,
+            Some(token) if token.lexeme.parse::<i32>().is_ok() => {
+                FdSource::Fd(token.lexeme.parse::<i32>()?)
+            }
+            Some(token)
+                if matches!(token.token_type, TokenType::Word(_))
+                    && token.lexeme.contains('$') =>
+            {
+                FdSource::Word(token.lexeme.clone())
+            }
+            _ => {
+                return Err(ShellError::ParseError(
+                    "expected file descriptor or minus after squirrel redirection operator".into(),
+                )
+                .into())
+            }
+        };
+
+        Ok(source)
+    }
+
+    fn next_token(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.idx)?;
+        self.idx += 1;
+        Some(token)
+    }
+
+    fn parse_assignment(&mut self, token: &Token) -> anyhow::Result<Option<Assignment>> {
+        let Some(mut assignment) = Assignment::parse(&token.lexeme) else {
+            return Ok(None);
+        };
+
+        if let Some(values) = self.parse_array_values(token)? {
+            assignment.value = AssignmentValue::Array(values);
+        }
+        Ok(Some(assignment))
+    }
+
+    // Values of array assignment `token` starts, if any
+    fn parse_array_values(&mut self, token: &Token) -> anyhow::Result<Option<Vec<String>>> {
+        // `name=(` starts an array assignment, both have to be
+        // adjacent, otherwise `(` would start a subshell instead
+        let is_array_assignment = token.lexeme.ends_with('=')
+            && self.tokens.get(self.idx).is_some_and(|next_token| {
+                matches!(next_token.token_type, TokenType::LeftParen)
+                    && is_adjacent(token, next_token)
+            });
+
+        if !is_array_assignment {
+            return Ok(None);
+        }
+
+        // Skip `(`
+        self.idx += 1;
+
+        let mut values = vec![];
+        loop {
+            let Some(value_token) = self.tokens.get(self.idx) else {
+                return Err(ShellError::ParseError(
+                    "expected ) at the end of array assignment".into(),
+                )
+                .into());
+            };
+            self.idx += 1;
+
+            match value_token.token_type {
+                TokenType::RightParen => break,
+                TokenType::Word(_) => values.push(value_token.lexeme.clone()),
+                _ => {
+                    return Err(ShellError::ParseError(format!(
+                        "unexpected {} in array assignment",
+                        value_token
+                    ))
+                    .into())
+                }
+            }
+        }
+
+        Ok(Some(values))
+    }
+
     fn handle_pointy_bracket_redirection_cmd_gen(
         &mut self,
         tokens: Vec<Token>,
         cmd_path: PathBuf,
-        negate_exit_status: bool,
     ) -> Vec<Command> {
         // Construct command before redirect operator
-        let cmd = make_command(tokens, cmd_path, negate_exit_status);
+        let cmd = self.make_command(tokens, cmd_path);
 
         let file_path_cmd = self.make_file_path_cmd();
 
-        return vec![cmd, file_path_cmd];
+        vec![cmd, file_path_cmd]
     }
 
     fn make_file_path_cmd(&mut self) -> Command {
@@ -373,38 +874,95 @@ impl<'a> Parser<'a> {
         let file_path_token = self.tokens[self.idx].clone();
         self.idx += 1;
 
-        let file_path = PathBuf::from_str(&file_path_token.lexeme).expect(&format!(
-            "Could not construct path buf from token: {}",
-            file_path_token.lexeme
-        ));
+        let file_path = PathBuf::from_str(&file_path_token.lexeme).unwrap_or_else(|_| {
+            panic!(
+                "Could not construct path buf from token: {}",
+                file_path_token.lexeme
+            )
+        });
+
+        self.make_command(vec![file_path_token], file_path)
+    }
+
+    fn make_command(&self, tokens: Vec<Token>, cmd_path: PathBuf) -> Command {
+        Command {
+            tokens,
+            path: cmd_path,
+            no_glob: self.no_glob,
+        }
+    }
+}
 
-        let file_path_cmd = make_command(vec![file_path_token], file_path, false);
-        return file_path_cmd;
+// Fd redirected is the number right before redirection operator, if
+// any, e.g. `2` of `cmd 2> file`, while `echo 2 > file` just echoes it.
+// Without a command word there are no tokens left, e.g. `2> file` just
+// creates the file
+fn take_redirected_fd(
+    tokens: &mut Vec<Token>,
+    operator: &Token,
+    cmd_path: &mut Option<PathBuf>,
+) -> Option<i32> {
+    let last_token = tokens.last()?;
+    if !is_adjacent(last_token, operator) {
+        return None;
     }
+    let fd = last_token.lexeme.parse::<i32>().ok()?;
+    tokens.pop();
+    if tokens.is_empty() {
+        *cmd_path = None;
+    }
+    Some(fd)
 }
 
-fn make_command(tokens: Vec<Token>, cmd_path: PathBuf, negate_exit_status: bool) -> Command {
-    let mut is_unqualified_path = true;
-    if cmd_path.starts_with("./") || cmd_path.starts_with("../") || cmd_path.starts_with("/") {
-        is_unqualified_path = false;
+// `{name}` right before redirection operator, e.g. `exec {fd}> file`,
+// has shell pick fd to redirect, which gets stored in name
+fn take_fd_variable(
+    tokens: &mut Vec<Token>,
+    operator: &Token,
+    cmd_path: &mut Option<PathBuf>,
+) -> Option<String> {
+    let last_token = tokens.last()?;
+    let name = last_token.lexeme.strip_prefix('{')?.strip_suffix('}')?;
+    if !is_valid_name(name) || !is_adjacent(last_token, operator) {
+        return None;
     }
 
-    return Command {
-        tokens,
-        path: cmd_path,
-        negate_exit_status,
-        is_unqualified_path,
-    };
+    let name = name.to_string();
+    tokens.pop();
+    if tokens.is_empty() {
+        *cmd_path = None;
+    }
+    Some(name)
 }
 
-#[derive(Debug)]
+// Whether there is no whitespace between both tokens
+fn is_adjacent(token: &Token, next_token: &Token) -> bool {
+    next_token.line == token.line && next_token.range.0 == token.range.1 + 1
+}
+
+
+#[derive(Debug, Clone)]
 pub struct ParseResult {
     // cmds is only needed because of subshell commands
     // this would otherwise only be 1 element otherwise
     pub cmds: Vec<Command>,
     pub execute_mode: ExecuteMode,
     pub exit_term: bool,
+    // Argument given to `exit`, if any
+    pub exit_status: Option<String>,
+    // Leading `name=value` words, if there is no command
+    // after them, they get assigned in the shell itself
+    pub assignments: Vec<Assignment>,
     pub associated_operator: Option<OpType>,
+    // Name given as `{name}` to redirection, see `take_fd_variable`
+    pub fd_variable: Option<String>,
+    // Prefixed with `time`
+    pub timed: bool,
+    // Prefixed with `!`, negates exit status of the
+    // pipeline starting with this command
+    pub negated: bool,
+    // Input line command starts on, counted from where lexing started
+    pub line: usize,
 }
 
 impl ParseResult {
@@ -413,7 +971,13 @@ impl ParseResult {
             cmds: vec![],
             execute_mode: ExecuteMode::Normal,
             exit_term: false,
+            exit_status: None,
+            assignments: vec![],
             associated_operator: None,
+            fd_variable: None,
+            timed: false,
+            negated: false,
+            line: 0,
         }
     }
 }
@@ -426,6 +990,7 @@ impl Display for OpType {
             OpType::Semicolon => ";".into(),
             // OpType::And => "&".into(),
             OpType::Pipe => "|".into(),
+            OpType::PipeWithStderr => "|&".into(),
             // OpType::Exclamation => "!".into(),
             OpType::RedirectOutput(fd_opt) => match fd_opt {
                 Some(fd) => format!("{}, <", fd),
@@ -449,12 +1014,7 @@ impl Display for OpType {
                     None => "-".into(),
                 };
 
-                let source_fd_str = match source {
-                    Some(fd) => format!("{}", fd),
-                    None => "".into(),
-                };
-
-                format!("{}&>{}", target_fd_str, source_fd_str)
+                format!("{}&>{}", target_fd_str, source)
             }
             OpType::RedirectSquirrelInput { source, target } => {
                 let target_fd_str = match target {
@@ -462,15 +1022,10 @@ impl Display for OpType {
                     None => "-".into(),
                 };
 
-                let source_fd_str = match source {
-                    Some(fd) => format!("{}", fd),
-                    None => "".into(),
-                };
-
-                format!("{}&>{}", target_fd_str, source_fd_str)
+                format!("{}&>{}", target_fd_str, source)
             }
             OpType::Background => {
-                format!("&")
+                "&".to_string()
             },
         };
 
@@ -557,6 +1112,31 @@ mod tests {
         insta::assert_debug_snapshot!(results);
     }
 
+    #[test]
+    fn test_cmd_parsing_of_noglob_prefix() {
+        let lexer = get_tokens("noglob find . -name *.rs | grep x\n")
+            .expect("lexer failed, check lexer tests");
+        let results = check(&lexer.tokens).expect("parser failed :(");
+        assert_eq!(results.len(), 2);
+        assert!(results[0].cmds[0].no_glob);
+        assert_eq!(results[0].cmds[0].tokens[0].lexeme, "find");
+        // Prefix applies to a single command only
+        assert!(!results[1].cmds[0].no_glob);
+    }
+
+    #[test]
+    fn test_cmd_parsing_of_exit_status() {
+        let lexer = get_tokens("exit 3\n").expect("lexer failed, check lexer tests");
+        let results = check(&lexer.tokens).expect("parser failed :(");
+        assert_eq!(results.len(), 1);
+        assert!(results[0].exit_term);
+        assert!(results[0].cmds.is_empty());
+        assert_eq!(results[0].exit_status, Some("3".into()));
+
+        let lexer = get_tokens("exit 3 4\n").expect("lexer failed, check lexer tests");
+        assert!(check(&lexer.tokens).is_err());
+    }
+
     #[test]
     fn test_cmd_parsing_of_subshell() {
         let lexer = get_tokens("(ls && exit)\n").expect("lexer failed, check lexer tests");
@@ -568,6 +1148,20 @@ mod tests {
         insta::assert_debug_snapshot!(results);
     }
 
+    #[test]
+    fn test_cmd_parsing_of_nested_subshell() {
+        let lexer = get_tokens("( (echo a) && (echo b) ) || echo c\n")
+            .expect("lexer failed, check lexer tests");
+        let results = check(&lexer.tokens).expect("parser failed :(");
+        insta::assert_debug_snapshot!(results);
+
+        let lexer = get_tokens("(ls && (pwd)\n").expect("lexer failed, check lexer tests");
+        assert!(check(&lexer.tokens).is_err());
+
+        let lexer = get_tokens("ls )\n").expect("lexer failed, check lexer tests");
+        assert!(check(&lexer.tokens).is_err());
+    }
+
     #[test]
     fn test_cmd_parsing_of_pipe_ops() {
         let lexer = get_tokens("echo foo | cat | cat\n").expect("lexer failed, check lexer tests");
@@ -575,6 +1169,14 @@ mod tests {
         insta::assert_debug_snapshot!(results);
     }
 
+    #[test]
+    fn test_cmd_parsing_of_pipe_stderr_ops() {
+        let lexer =
+            get_tokens("ls doesnotexist |& cat\n").expect("lexer failed, check lexer tests");
+        let results = check(&lexer.tokens).expect("parser failed :(");
+        insta::assert_debug_snapshot!(results);
+    }
+
     #[test]
     fn test_cmd_parsing_of_redirection_ops_with_fd() {
         let lexer = get_tokens("ls -6 2> file.txt\n").expect("lexer failed, check lexer tests");
@@ -636,7 +1238,7 @@ mod tests {
     #[test]
     fn test_cmd_parsing_of_redirection_squirrel_output_ops_with_minus() {
         let lexer =
-            get_tokens("ls /tmp/ doesnotexist &>1\n").expect("lexer failed, check lexer tests");
+            get_tokens("ls /tmp/ doesnotexist 2>&-\n").expect("lexer failed, check lexer tests");
         let results = check(&lexer.tokens).expect("parser failed :(");
         insta::assert_debug_snapshot!(results);
     }
@@ -655,10 +1257,121 @@ mod tests {
         insta::assert_debug_snapshot!(results);
     }
 
+    #[test]
+    fn test_cmd_parsing_of_assignments() {
+        let lexer = get_tokens("n=5+5 ; s+=foo ; arr=(a b) && arr+=(c)\n")
+            .expect("lexer failed, check lexer tests");
+        let results = check(&lexer.tokens).expect("parser failed :(");
+        insta::assert_debug_snapshot!(results);
+    }
+
     #[test]
     fn test_cmd_parsing_for_bg_process_invocation() {
         let lexer = get_tokens("ping google.com &\n").expect("lexer failed, check lexer tests");
         let results = check(&lexer.tokens).expect("parser failed :(");
         insta::assert_debug_snapshot!(results);
     }
+
+    #[test]
+    fn test_cmd_parsing_recovers_after_errors() {
+        let lexer = get_tokens("echo a ) echo b ; echo c ;\necho d )\necho e\n")
+            .expect("lexer failed, check lexer tests");
+        let mut parser = Parser::new(&lexer.tokens);
+
+        let mut cmds = vec![];
+        let mut errors = 0;
+        loop {
+            match parser.get_command() {
+                Ok(Some(parse_result)) => cmds.push(parse_result.cmds[0].tokens[1].lexeme.clone()),
+                Ok(None) => break,
+                Err(_) => {
+                    errors += 1;
+                    parser.recover();
+                }
+            }
+        }
+
+        assert_eq!(errors, 2);
+        assert_eq!(cmds, vec!["c", "e"]);
+    }
+
+    #[test]
+    fn test_cmd_parsing_of_fd_variable_redirection() {
+        let lexer = get_tokens("exec {logfd}>out.log; echo {a} >b; cat {1x}<c\n")
+            .expect("lexer failed, check lexer tests");
+        let results = check(&lexer.tokens).expect("parser failed :(");
+        let fd_variables: Vec<_> =
+            results.iter().map(|result| result.fd_variable.clone()).collect();
+        assert_eq!(fd_variables, vec![Some("logfd".to_string()), None, None]);
+        assert_eq!(results[0].cmds[0].tokens.len(), 1);
+        assert_eq!(results[1].cmds[0].tokens.len(), 2);
+    }
+
+    #[test]
+    fn test_cmd_parsing_of_semicolon_after_redirection() {
+        let lexer = get_tokens("ls > file ; echo a\n").expect("lexer failed, check lexer tests");
+        let results = check(&lexer.tokens).expect("parser failed :(");
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn test_cmd_parsing_of_redirections_without_cmd() {
+        let lexer = get_tokens("> file.txt; 2> log.txt; exec 3< data.txt\n")
+            .expect("lexer failed, check lexer tests");
+        let results = check(&lexer.tokens).expect("parser failed :(");
+        insta::assert_debug_snapshot!(results);
+    }
+
+    #[test]
+    fn test_cmd_parsing_of_select() {
+        let lexer = get_tokens("select f in a 'b c' ; do echo done ; break ; done && ls\n")
+            .expect("lexer failed, check lexer tests");
+        let results = check(&lexer.tokens).expect("parser failed :(");
+        insta::assert_debug_snapshot!(results);
+    }
+
+    #[test]
+    fn test_cmd_parsing_of_invalid_select() {
+        for input in [
+            "select 1 in a ; do ls ; done\n",
+            "select f a ; do ls ; done\n",
+            "select f in a ; ls ; done\n",
+            "select f in a ; do ls\n",
+            "select f in a ; do ls ; done ls\n",
+        ] {
+            let lexer = get_tokens(input).expect("lexer failed, check lexer tests");
+            assert!(check(&lexer.tokens).is_err(), "expected error for: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_cmd_parsing_of_arithmetic_cmd() {
+        let lexer = get_tokens("(( i += (2+3)*4 )) && ((i))\n")
+            .expect("lexer failed, check lexer tests");
+        let results = check(&lexer.tokens).expect("parser failed :(");
+        insta::assert_debug_snapshot!(results);
+
+        for input in ["(( i + 1 )\n", "(( i + 1\n", "(( i + 1 )) ls\n"] {
+            let lexer = get_tokens(input).expect("lexer failed, check lexer tests");
+            assert!(check(&lexer.tokens).is_err(), "expected error for: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_cmd_parsing_of_timed_pipeline() {
+        let lexer = get_tokens("time ls -la | wc -l\n").expect("lexer failed, check lexer tests");
+        let results = check(&lexer.tokens).expect("parser failed :(");
+        insta::assert_debug_snapshot!(results);
+    }
+
+    #[test]
+    fn test_cmd_parsing_of_negated_pipeline() {
+        let lexer = get_tokens("! ls | wc -l\n").expect("lexer failed, check lexer tests");
+        let results = check(&lexer.tokens).expect("parser failed :(");
+        assert!(results[0].negated);
+        assert!(!results[1].negated);
+
+        let lexer = get_tokens("ls | ! wc -l\n").expect("lexer failed, check lexer tests");
+        assert!(check(&lexer.tokens).is_err());
+    }
 }
@@ -0,0 +1,85 @@
+// Quoting Spec:
+// - URL: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/V3_chap02.html#tag_18_02
+//
+// Reverse of quote removal, used while displaying words so
+// that they read unambiguously and can be run again as is
+
+use super::{lexer::Lexer, unquote};
+
+// Chars which never need quoting anywhere in a word
+fn is_safe_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || "_-.,/:@%+=".contains(ch)
+}
+
+// Quotes word only if it needs it, single quotes are preferred
+// as nothing is special within them, double quotes are used
+// for words having single quotes in them
+pub fn quote(word: &str) -> String {
+    if word.is_empty() {
+        return "''".to_string();
+    }
+
+    if word.chars().all(is_safe_char) {
+        return word.to_string();
+    }
+
+    if !word.contains('\'') {
+        return format!("'{}'", word);
+    }
+
+    let mut quoted = String::from('"');
+    for ch in word.chars() {
+        // Chars still special within double quotes
+        if matches!(ch, '$' | '`' | '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+// Recombines words into a line which splits back into the same words
+pub fn join(words: &[String]) -> String {
+    words
+        .iter()
+        .map(|word| quote(word))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Reverse of `join`, splits line back into words
+pub fn split(line: &str) -> anyhow::Result<Vec<String>> {
+    let tokens = Lexer::new().scan(&format!("{}\n", line))?;
+    Ok(tokens.iter().map(|token| unquote(&token.lexeme)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{join, quote, split};
+
+    #[test]
+    fn test_quoting_of_words() {
+        assert_eq!(quote("ls"), "ls");
+        assert_eq!(quote("src/main.rs"), "src/main.rs");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("*.rs"), "'*.rs'");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+        assert_eq!(quote("it's $x"), "\"it's \\$x\"");
+    }
+
+    #[test]
+    fn test_joined_words_lex_back_to_same_words() {
+        let words: Vec<String> = ["echo", "a b", "", "it's", "\"q\"", "x;y", "&"]
+            .iter()
+            .map(|word| word.to_string())
+            .collect();
+
+        let line = join(&words);
+        assert_eq!(line, "echo 'a b' '' \"it's\" '\"q\"' 'x;y' '&'");
+
+        assert_eq!(split(&line).expect("lexing should have succeeded"), words);
+    }
+}
@@ -8,6 +8,19 @@ pub struct Token {
     pub range: (usize, usize), // (start, end)
 }
 
+impl Token {
+    // Line token ends on, quoted words can span lines
+    pub fn end_line(&self) -> usize {
+        self.line + self.lexeme.matches('\n').count()
+    }
+
+    // Token after this one is on a line of its own, i.e. new line
+    // between them was not escaped
+    pub fn ends_line_before(&self, next_token: &Token) -> bool {
+        next_token.line != self.end_line() && !matches!(self.token_type, TokenType::Backslash)
+    }
+}
+
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.lexeme)
@@ -77,6 +90,7 @@ pub enum Operator {
     AndIf, // "&&"
     Or,  // "|"
     OrIf,  // "||"
+    OrAnd,  // "|&"
     Exclamation,  // "!"
     LeftPointyBracket,  // "<"
     RightPointyBracket,  // ">"
@@ -93,6 +107,7 @@ impl Display for Operator {
             Operator::OrIf => "||",
             Operator::And => "&",
             Operator::Or => "|",
+            Operator::OrAnd => "|&",
             Operator::Exclamation => "!",
             Operator::LeftPointyBracket => "<",
             Operator::RightPointyBracket => ">",
@@ -0,0 +1,119 @@
+// Entry point for running pieces of external POSIX shell test suites,
+// started with `dss --posix-test-adapter`. Script is read from stdin
+// and these environment variables control how it runs:
+// - DSS_TEST_NAME: name of script used in messages, `stdin` by default
+// - DSS_TEST_OPTIONS: space separated options enabled as with `set -o`
+// - DSS_TEST_STDERR: `stdout` sends stderr to stdout, for suites
+//   which compare both together
+//
+// Commands in script see stdin at EOF, as it is used up by the script.
+
+use std::{
+    env,
+    io::{self, Read},
+    path::Path,
+};
+
+use nix::unistd::dup2;
+
+use crate::frontend::write_to_stderr;
+
+use super::Engine;
+
+#[derive(Debug, PartialEq)]
+struct Modes {
+    name: String,
+    options: Vec<String>,
+    merge_stderr: bool,
+}
+
+impl Modes {
+    fn from_env() -> Result<Self, String> {
+        Self::parse(
+            env::var("DSS_TEST_NAME").ok(),
+            env::var("DSS_TEST_OPTIONS").ok(),
+            env::var("DSS_TEST_STDERR").ok(),
+        )
+    }
+
+    fn parse(
+        name: Option<String>,
+        options: Option<String>,
+        stderr: Option<String>,
+    ) -> Result<Self, String> {
+        let merge_stderr = match stderr.as_deref() {
+            None | Some("") | Some("stderr") => false,
+            Some("stdout") => true,
+            Some(stderr) => return Err(format!("DSS_TEST_STDERR: {}: invalid mode", stderr)),
+        };
+
+        Ok(Self {
+            name: name.unwrap_or_else(|| "stdin".to_string()),
+            options: options
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+            merge_stderr,
+        })
+    }
+}
+
+// Invalid modes exit with 2, same as invalid options to dss
+pub fn run(engine: &mut Engine) -> anyhow::Result<()> {
+    let modes = match Modes::from_env() {
+        Ok(modes) => modes,
+        Err(problem) => {
+            write_to_stderr(&format!("dss: {}\n", problem))?;
+            std::process::exit(2);
+        }
+    };
+
+    for option in &modes.options {
+        if let Err(err) = engine.options.set(option, true) {
+            write_to_stderr(&err.to_string())?;
+            std::process::exit(2);
+        }
+    }
+
+    if modes.merge_stderr {
+        dup2(1, 2)?;
+    }
+
+    let mut script = String::new();
+    io::stdin().read_to_string(&mut script)?;
+
+    engine.execute_script(Path::new(&modes.name), &script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Modes;
+
+    #[test]
+    fn test_parsing_of_adapter_modes() {
+        assert_eq!(
+            Modes::parse(None, None, None),
+            Ok(Modes {
+                name: "stdin".into(),
+                options: vec![],
+                merge_stderr: false,
+            })
+        );
+
+        assert_eq!(
+            Modes::parse(
+                Some("case.sh".into()),
+                Some(" nullglob  cmdtimeout=5 ".into()),
+                Some("stdout".into())
+            ),
+            Ok(Modes {
+                name: "case.sh".into(),
+                options: vec!["nullglob".into(), "cmdtimeout=5".into()],
+                merge_stderr: true,
+            })
+        );
+
+        assert!(Modes::parse(None, None, Some("file".into())).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::BTreeMap;
+
+use crate::command::{
+    is_valid_name,
+    lexer::Lexer,
+    quote::quote,
+    token::{Operator, Token, TokenType},
+};
+
+// Aliases defined with `alias`, listed in order of their names
+#[derive(Clone, Debug, Default)]
+pub struct Aliases {
+    aliases: BTreeMap<String, String>,
+}
+
+impl Aliases {
+    // Value is kept as it is given, it is lexed each time alias gets
+    // expanded. It can be any command line, e.g. `ls -l | less`, as
+    // long as it is complete, i.e. quotes in it are closed
+    pub fn define(&mut self, name: &str, value: &str) -> Result<(), String> {
+        if name.is_empty()
+            || name
+                .chars()
+                .any(|ch| ch.is_whitespace() || "/$`'\"\\=|&;<>()".contains(ch))
+        {
+            return Err(format!("{}: invalid alias name", name));
+        }
+
+        if lex_value(value).is_none() {
+            return Err(format!("{}: value is not a complete command", name));
+        }
+
+        self.aliases.insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    // Returns whether alias was defined
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.aliases.remove(name).is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.aliases.clear();
+    }
+
+    pub fn names(&self) -> Vec<&String> {
+        self.aliases.keys().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.aliases.get(name)
+    }
+
+    // Formats alias the way `alias` lists it, so that
+    // output can be fed back to the shell
+    pub fn definition(&self, name: &str) -> Option<String> {
+        let value = self.aliases.get(name)?;
+        Some(format!("alias {}={}", name, quote(value)))
+    }
+
+    // Every command word of line naming an alias is replaced by tokens
+    // of its value, before line gets parsed. Those are scanned again,
+    // so that commands in value get expanded too, except for the alias
+    // itself, e.g. `alias ls='ls -F'` runs `ls`. Quoted command words
+    // are not looked up, as lexemes keep their quotes.
+    pub fn expand(&self, tokens: &[Token]) -> Vec<Token> {
+        let mut tokens = tokens.to_vec();
+        // Aliases being expanded, along with where their tokens end
+        let mut expanding: Vec<(String, usize)> = vec![];
+        let mut command_position = true;
+        let mut idx = 0;
+
+        while idx < tokens.len() {
+            while expanding.last().is_some_and(|(_, end)| idx >= *end) {
+                expanding.pop();
+            }
+
+            let token = &tokens[idx];
+            command_position =
+                command_position || idx > 0 && tokens[idx - 1].ends_line_before(token);
+
+            if command_position && matches!(token.token_type, TokenType::Word(_)) {
+                let name = &token.lexeme;
+                let value = self
+                    .aliases
+                    .get(name)
+                    .filter(|_| !expanding.iter().any(|(expanded, _)| expanded == name));
+
+                if let Some(value) = value {
+                    let replacement = value_tokens(value, token);
+                    let len = replacement.len();
+                    for (_, end) in expanding.iter_mut() {
+                        *end = *end + len - 1;
+                    }
+                    expanding.push((name.clone(), idx + len));
+                    tokens.splice(idx..idx + 1, replacement);
+                    continue;
+                }
+            }
+
+            command_position = starts_command(&tokens[idx], command_position);
+            idx += 1;
+        }
+
+        tokens
+    }
+}
+
+// Whether word after token is a command word. Assignments and
+// keywords opening compound commands leave it at command position
+fn starts_command(token: &Token, command_position: bool) -> bool {
+    match &token.token_type {
+        TokenType::Semicolon | TokenType::LeftParen => true,
+        TokenType::Operator(operator) => matches!(
+            operator,
+            Operator::And
+                | Operator::AndIf
+                | Operator::Or
+                | Operator::OrIf
+                | Operator::OrAnd
+                | Operator::Exclamation
+        ),
+        TokenType::Backslash => command_position,
+        TokenType::Word(_) => {
+            let lexeme = token.lexeme.as_str();
+            let assignment = lexeme
+                .split_once('=')
+                .is_some_and(|(name, _)| is_valid_name(name.trim_end_matches('+')));
+
+            command_position
+                && (assignment
+                    || matches!(
+                        lexeme,
+                        "if" | "then"
+                            | "elif"
+                            | "else"
+                            | "while"
+                            | "until"
+                            | "do"
+                            | "{"
+                            | "time"
+                            | "noglob"
+                    ))
+        }
+        TokenType::RightParen => false,
+    }
+}
+
+// Tokens of value placed where command word was. Lines of value are
+// separated by `;`, as line of command word goes on after them
+fn value_tokens(value: &str, word: &Token) -> Vec<Token> {
+    let tokens = lex_value(value).unwrap_or_default();
+
+    let mut replacement = vec![];
+    let mut prev_line = None;
+    for token in tokens {
+        if prev_line.is_some_and(|line| line != token.line) {
+            replacement.push(Token {
+                lexeme: ";".into(),
+                token_type: TokenType::Semicolon,
+                line: word.line,
+                range: word.range,
+            });
+        }
+
+        prev_line = Some(token.end_line());
+        replacement.push(Token {
+            line: word.line,
+            range: (word.range.0 + token.range.0, word.range.0 + token.range.1),
+            ..token
+        });
+    }
+    replacement
+}
+
+// Tokens of value, if it lexes completely
+fn lex_value(value: &str) -> Option<Vec<Token>> {
+    let mut lexer = Lexer::new();
+    let tokens = lexer.scan(&format!("{}\n", value)).ok()?;
+
+    // Value can be empty too, command word is then just dropped
+    (tokens.is_empty() || lexer.complete_processing()).then_some(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::lexer::Lexer;
+
+    use super::Aliases;
+
+    fn expand(aliases: &Aliases, line: &str) -> Vec<String> {
+        let tokens = Lexer::new()
+            .scan(&format!("{}\n", line))
+            .expect("lexing should have succeeded");
+        aliases
+            .expand(&tokens)
+            .into_iter()
+            .map(|token| token.lexeme)
+            .collect()
+    }
+
+    #[test]
+    fn test_alias_expansion() {
+        let mut aliases = Aliases::default();
+        aliases.define("ll", "ls -la").unwrap();
+        aliases.define("l", "ll 'my dir'").unwrap();
+        aliases.define("ls", "ls -F").unwrap();
+
+        assert_eq!(expand(&aliases, "ll src"), ["ls", "-F", "-la", "src"]);
+        assert_eq!(expand(&aliases, "l"), ["ls", "-F", "-la", "'my dir'"]);
+        assert_eq!(expand(&aliases, "'ll'"), ["'ll'"]);
+        assert_eq!(expand(&aliases, "echo ll"), ["echo", "ll"]);
+
+        assert_eq!(
+            aliases.definition("l"),
+            Some("alias l=\"ll 'my dir'\"".into())
+        );
+        assert!(aliases.define("open", "echo 'a").is_err());
+        assert!(aliases.define("a/b", "ls").is_err());
+
+        // Commands after operators in line or in value are expanded too
+        aliases.define("count", "ll | wc -l").unwrap();
+        aliases.define("up", "cd .. && ls").unwrap();
+        assert_eq!(
+            expand(&aliases, "up; count && ll"),
+            [
+                "cd", "..", "&&", "ls", "-F", ";", "ls", "-F", "-la", "|", "wc", "-l", "&&", "ls",
+                "-F", "-la"
+            ]
+        );
+        assert_eq!(
+            expand(&aliases, "x=1 ll > ll"),
+            ["x=1", "ls", "-F", "-la", ">", "ll"]
+        );
+
+        aliases.define("nothing", "").unwrap();
+        assert_eq!(expand(&aliases, "nothing echo"), ["echo"]);
+
+        assert!(aliases.remove("ll"));
+        assert!(!aliases.remove("ll"));
+        assert_eq!(expand(&aliases, "ll"), ["ll"]);
+    }
+}
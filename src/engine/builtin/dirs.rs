@@ -0,0 +1,212 @@
+use std::path::{Component, Path, PathBuf};
+
+use super::{Io, ShellBuiltin};
+use crate::engine::{variables, Engine};
+
+// Supported usage: cd [dir | -]
+pub struct Cd;
+
+impl ShellBuiltin for Cd {
+    const NAME: &'static str = "cd";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        if engine.forbidden_in_restricted("cd")? {
+            return Ok(1);
+        }
+
+        // `~` is already expanded, only bare `cd` goes home
+        let (dir, var_name) = match args {
+            [] => (engine.variables.get_str("HOME"), "HOME"),
+            [dir] if dir == "-" => (engine.variables.get_str("OLDPWD"), "OLDPWD"),
+            [dir] => (Some(dir.clone()), ""),
+            _ => {
+                io.write_err("dss: cd: too many arguments\n")?;
+                return Ok(1);
+            }
+        };
+
+        let Some(mut dir) = dir else {
+            io.write_err(&format!("dss: cd: {} not set\n", var_name))?;
+            return Ok(1);
+        };
+
+        // `cd -` and directories found through CDPATH show where they went
+        let mut print = var_name == "OLDPWD";
+        if let (true, Some(found)) = (var_name.is_empty(), search_cdpath(engine, &dir)) {
+            dir = found;
+            print = true;
+        }
+
+        if let Err(err) = engine.change_directory(Path::new(&dir))? {
+            io.write_err(&format!("dss: cd: {}: {}\n", dir, err))?;
+            return Ok(1);
+        }
+
+        if print {
+            io.write_out(&format!("{}\n", engine.current_dir()?.display()))?;
+        }
+
+        Ok(0)
+    }
+}
+
+// Relative directories not found in current one are looked up in
+// colon separated directories of CDPATH, unless they start with `.`
+fn search_cdpath(engine: &Engine, dir: &str) -> Option<String> {
+    let path = Path::new(dir);
+    let explicitly_relative = matches!(
+        path.components().next(),
+        Some(Component::CurDir | Component::ParentDir)
+    );
+    if path.is_absolute() || explicitly_relative {
+        return None;
+    }
+
+    if engine.current_dir().ok()?.join(path).is_dir() {
+        return None;
+    }
+
+    engine
+        .variables
+        .get_str("CDPATH")?
+        .split(':')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| Path::new(entry).join(path))
+        .find(|candidate| candidate.is_dir())
+        .map(|candidate| candidate.display().to_string())
+}
+
+// Supported usage: pwd [-L | -P]
+// Logical path is the one `cd` keeps in $PWD, it is not
+// used if PWD got assigned some other directory
+pub struct Pwd;
+
+impl ShellBuiltin for Pwd {
+    const NAME: &'static str = "pwd";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        let mut physical = false;
+        for arg in args {
+            match arg.as_str() {
+                "-L" => physical = false,
+                "-P" => physical = true,
+                _ => {
+                    io.write_err("dss: pwd: usage: pwd [-L | -P]\n")?;
+                    return Ok(1);
+                }
+            }
+        }
+
+        let cwd = match std::env::current_dir() {
+            Ok(cwd) => cwd,
+            Err(err) => {
+                io.write_err(&format!("dss: pwd: {}\n", err))?;
+                return Ok(1);
+            }
+        };
+
+        let dir = match engine.current_dir() {
+            Ok(dir) if !physical && variables::is_same_file(&dir, &cwd) => dir,
+            _ => cwd,
+        };
+        io.write_out(&format!("{}\n", dir.display()))?;
+
+        Ok(0)
+    }
+}
+
+// Supported usage: pushd [dir]
+// Without dir, top two directories are swapped
+pub struct Pushd;
+
+impl ShellBuiltin for Pushd {
+    const NAME: &'static str = "pushd";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        if engine.forbidden_in_restricted("pushd")? {
+            return Ok(1);
+        }
+
+        let cwd = engine.current_dir()?;
+        let dir = match args {
+            [] => match engine.dirstack.pop() {
+                Some(dir) => dir,
+                None => {
+                    io.write_err("dss: pushd: no other directory\n")?;
+                    return Ok(1);
+                }
+            },
+            [dir] => PathBuf::from(dir),
+            _ => {
+                io.write_err("dss: pushd: too many arguments\n")?;
+                return Ok(1);
+            }
+        };
+
+        if let Err(err) = engine.change_directory(&dir)? {
+            io.write_err(&format!("dss: pushd: {}: {}\n", dir.display(), err))?;
+            // Swapped directory goes back where it was
+            if args.is_empty() {
+                engine.dirstack.push(dir);
+            }
+            return Ok(1);
+        }
+
+        engine.dirstack.push(cwd);
+        print_dirstack(engine, io)?;
+        Ok(0)
+    }
+}
+
+// Supported usage: popd
+pub struct Popd;
+
+impl ShellBuiltin for Popd {
+    const NAME: &'static str = "popd";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        if engine.forbidden_in_restricted("popd")? {
+            return Ok(1);
+        }
+
+        if !args.is_empty() {
+            io.write_err("dss: popd: too many arguments\n")?;
+            return Ok(1);
+        }
+
+        let Some(dir) = engine.dirstack.pop() else {
+            io.write_err("dss: popd: directory stack empty\n")?;
+            return Ok(1);
+        };
+
+        if let Err(err) = engine.change_directory(&dir)? {
+            io.write_err(&format!("dss: popd: {}: {}\n", dir.display(), err))?;
+            return Ok(1);
+        }
+
+        print_dirstack(engine, io)?;
+        Ok(0)
+    }
+}
+
+// Current directory first, then stack from top, same as `dirs`
+fn print_dirstack(engine: &Engine, io: &mut Io) -> anyhow::Result<()> {
+    let mut dirs = vec![engine.current_dir()?];
+    dirs.extend(engine.dirstack.iter().rev().cloned());
+
+    let dirs: Vec<String> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+    io.write_out(&format!("{}\n", dirs.join(" ")))?;
+    Ok(())
+}
+
+// Supported usage: dirs
+pub struct Dirs;
+
+impl ShellBuiltin for Dirs {
+    const NAME: &'static str = "dirs";
+
+    fn run(&self, engine: &mut Engine, _: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        print_dirstack(engine, io)?;
+        Ok(0)
+    }
+}
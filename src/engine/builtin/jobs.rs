@@ -0,0 +1,248 @@
+use nix::{sys::signal::Signal, unistd::Pid};
+
+use super::{Io, ShellBuiltin};
+use crate::engine::{
+    exit_status,
+    jobs::{JobState, TerminalHandover},
+    Engine, COMMAND_NOT_FOUND_EXIT_CODE,
+};
+
+// Supported usage: jobs [-o %job]
+pub struct Jobs;
+
+impl ShellBuiltin for Jobs {
+    const NAME: &'static str = "jobs";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        engine.reap_children();
+
+        let spec = match args {
+            [] => {
+                for job in engine.jobs.iter() {
+                    io.write_out(&engine.jobs.status_line(job))?;
+                }
+                // Done jobs are not listed again once reported
+                engine.jobs.remove_done();
+                return Ok(0);
+            }
+            [flag, spec] if flag == "-o" => spec,
+            _ => {
+                io.write_err("dss: jobs: usage: jobs [-o %job]\n")?;
+                return Ok(1);
+            }
+        };
+
+        let job = engine.jobs.resolve(spec).and_then(|id| engine.jobs.get(id));
+        let Some(job) = job else {
+            io.write_err(&format!("dss: jobs: {}: no such job\n", spec))?;
+            return Ok(1);
+        };
+
+        let Some(log) = &job.log else {
+            io.write_err(&format!("dss: jobs: {}: output was not captured\n", spec))?;
+            return Ok(1);
+        };
+
+        match std::fs::read_to_string(log) {
+            Ok(output) => io.write_out(&output)?,
+            Err(err) => {
+                io.write_err(&format!("dss: jobs: {}: {}\n", log.display(), err))?;
+                return Ok(1);
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+// Supported usage: fg [%job]
+// Job gets the terminal till it stops or exits, status of
+// `fg` is then that of the job
+pub struct Fg;
+
+impl ShellBuiltin for Fg {
+    const NAME: &'static str = "fg";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        engine.reap_children();
+
+        let (id, spec) = match args {
+            [] => (engine.jobs.current_and_previous().0, "current"),
+            [spec] => (engine.jobs.resolve(spec), spec.as_str()),
+            _ => {
+                io.write_err("dss: fg: usage: fg [%job]\n")?;
+                return Ok(1);
+            }
+        };
+        let Some(job) = id.and_then(|id| engine.jobs.get(id)) else {
+            io.write_err(&format!("dss: fg: {}: no such job\n", spec))?;
+            return Ok(1);
+        };
+
+        let id = job.id;
+        io.write_out(&format!("{}\n", job.command))?;
+
+        // Job done before being brought back still has its status taken
+        if job.state == JobState::Done {
+            let status = job.exit_code().unwrap_or(1);
+            engine.jobs.remove(id);
+            return Ok(status);
+        }
+
+        let handover = if engine.interactive {
+            TerminalHandover::give(job.pgid)?
+        } else {
+            None
+        };
+
+        let result = engine.jobs.resume(id).and_then(|_| engine.jobs.wait(id));
+        if let Some(handover) = handover {
+            handover.take_back()?;
+        }
+        result?;
+
+        let Some(job) = engine.jobs.get(id) else {
+            return Ok(1);
+        };
+        if job.state == JobState::Stopped {
+            io.write_err(&format!("\n{}", engine.jobs.status_line(job)))?;
+            return Ok(128 + Signal::SIGTSTP as i32);
+        }
+
+        // Killed job has status of the signal, e.g. 130 for Ctrl-C
+        let status = job.exit_code().unwrap_or(1);
+        engine.jobs.remove(id);
+        Ok(status)
+    }
+}
+
+// Supported usage: disown [-h] [-a | %job ...]
+// Current job is disowned if none are named. With `-h` jobs
+// are kept, but are not sent SIGHUP when shell is hung up.
+pub struct Disown;
+
+impl ShellBuiltin for Disown {
+    const NAME: &'static str = "disown";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        engine.reap_children();
+
+        let (keep, args) = match args {
+            [flag, rest @ ..] if flag == "-h" => (true, rest),
+            _ => (false, args),
+        };
+
+        let ids: Vec<Option<usize>> = match args {
+            [] => vec![engine.jobs.current_and_previous().0],
+            [flag] if flag == "-a" => engine.jobs.iter().map(|job| Some(job.id)).collect(),
+            specs if specs.iter().all(|spec| spec.starts_with('%')) => {
+                specs.iter().map(|spec| engine.jobs.resolve(spec)).collect()
+            }
+            _ => {
+                io.write_err("dss: disown: usage: disown [-h] [-a | %job ...]\n")?;
+                return Ok(1);
+            }
+        };
+
+        let mut successful = true;
+        for (idx, id) in ids.into_iter().enumerate() {
+            let Some(id) = id else {
+                let spec = args.get(idx).map_or("current", String::as_str);
+                io.write_err(&format!("dss: disown: {}: no such job\n", spec))?;
+                successful = false;
+                continue;
+            };
+
+            match engine.jobs.get_mut(id) {
+                Some(job) if keep => job.hangup = false,
+                _ => engine.jobs.disown(id),
+            }
+        }
+
+        Ok(exit_status(successful))
+    }
+}
+
+// Supported usage: wait [pid | %job ...]
+// Without arguments all jobs are waited for. Otherwise status is
+// that of the last one named, 127 if it is not a job of shell.
+pub struct Wait;
+
+impl ShellBuiltin for Wait {
+    const NAME: &'static str = "wait";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        engine.reap_children();
+
+        if args.is_empty() {
+            let ids: Vec<usize> = engine.jobs.iter().map(|job| job.id).collect();
+            for id in ids {
+                engine.jobs.wait(id)?;
+            }
+            return Ok(0);
+        }
+
+        let mut status = 0;
+        for arg in args {
+            let (id, pid) = match arg.parse::<i32>() {
+                Ok(pid) => {
+                    let pid = Pid::from_raw(pid);
+                    (engine.jobs.find_by_pid(pid), Some(pid))
+                }
+                Err(_) if arg.starts_with('%') => (engine.jobs.resolve(arg), None),
+                Err(_) => {
+                    io.write_err(&format!("dss: wait: {}: not a pid or job spec\n", arg))?;
+                    status = 2;
+                    continue;
+                }
+            };
+            let Some(id) = id else {
+                let problem = match pid {
+                    Some(pid) => format!("pid {} is not a child of this shell", pid),
+                    None => format!("{}: no such job", arg),
+                };
+                io.write_err(&format!("dss: wait: {}\n", problem))?;
+                status = COMMAND_NOT_FOUND_EXIT_CODE;
+                continue;
+            };
+
+            engine.jobs.wait(id)?;
+            let Some(job) = engine.jobs.get(id) else {
+                continue;
+            };
+
+            // Process named by pid has a status of its own
+            let process = job
+                .processes
+                .iter()
+                .find(|process| Some(process.pid) == pid)
+                .or(job.processes.last());
+            status = match process.and_then(|process| process.exit_code) {
+                Some(exit_code) => exit_code,
+                // Stopped instead, same as by Ctrl-Z in foreground
+                None => 128 + Signal::SIGTSTP as i32,
+            };
+
+            // Job waited for is not reported once done
+            if job.state == JobState::Done {
+                engine.jobs.remove(id);
+            }
+        }
+
+        Ok(status)
+    }
+}
+
+// Supported usage: pipestatus
+// Prints PIPESTATUS, same as `echo "${PIPESTATUS[@]}"`
+pub struct Pipestatus;
+
+impl ShellBuiltin for Pipestatus {
+    const NAME: &'static str = "pipestatus";
+
+    fn run(&self, engine: &mut Engine, _: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        let statuses = engine.variables.get_array("PIPESTATUS").unwrap_or_default();
+        io.write_out(&format!("{}\n", statuses.join(" ")))?;
+        Ok(0)
+    }
+}
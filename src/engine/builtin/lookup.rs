@@ -0,0 +1,189 @@
+use super::{Io, ShellBuiltin};
+use crate::engine::{exit_status, CommandKind, Engine};
+
+// Supported usage: hash [-r] [name ...]
+pub struct Hash;
+
+impl ShellBuiltin for Hash {
+    const NAME: &'static str = "hash";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        let (clear, names) = match args {
+            [flag, names @ ..] if flag == "-r" => (true, names),
+            names => (false, names),
+        };
+
+        let path_var = engine.variables.get_str("PATH").unwrap_or_default();
+        engine.command_hash.forget_if_changed(&path_var);
+        if clear {
+            engine.command_hash.clear();
+        }
+
+        // Names are searched for and remembered, without running them
+        let mut successful = true;
+        for name in names {
+            if engine.command_hash.remember(name, &path_var).is_none() {
+                io.write_err(&format!("dss: hash: {}: not found\n", name))?;
+                successful = false;
+            }
+        }
+
+        if !clear && names.is_empty() {
+            if engine.command_hash.is_empty() {
+                io.write_err("dss: hash: hash table empty\n")?;
+            } else {
+                io.write_out(&engine.command_hash.list())?;
+            }
+        }
+
+        Ok(exit_status(successful))
+    }
+}
+
+// Supported usage: command [-v | -V] name ...
+//                  command name [arg ...]
+pub struct Command;
+
+impl ShellBuiltin for Command {
+    const NAME: &'static str = "command";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        match args.first().map(String::as_str) {
+            None => return Ok(0),
+            Some("-v") => return describe_commands(engine, "command", &args[1..], false, io),
+            Some("-V") => return describe_commands(engine, "command", &args[1..], true, io),
+            Some(_) => {}
+        }
+
+        engine.execute_words(args.to_vec())?;
+        Ok(engine.last_status)
+    }
+}
+
+// Supported usage: type name ...
+pub struct Type;
+
+impl ShellBuiltin for Type {
+    const NAME: &'static str = "type";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        describe_commands(engine, "type", args, true, io)
+    }
+}
+
+// Verbose description is the one `type` prints, otherwise it is
+// the path, name or alias definition, as `command -v` prints it
+fn describe_commands(
+    engine: &mut Engine,
+    builtin: &str,
+    names: &[String],
+    verbose: bool,
+    io: &mut Io,
+) -> anyhow::Result<i32> {
+    let mut successful = true;
+
+    for name in names {
+        let description = match (engine.find_command(name), verbose) {
+            (None, _) => {
+                // `command -v` is used to check if a command exists,
+                // it stays quiet about ones which do not
+                if verbose {
+                    io.write_err(&format!("dss: {}: {}: not found\n", builtin, name))?;
+                }
+                successful = false;
+                continue;
+            }
+            (Some(CommandKind::Keyword), true) => format!("{} is a shell keyword", name),
+            (Some(CommandKind::Alias(value)), true) => {
+                format!("{} is aliased to `{}'", name, value)
+            }
+            (Some(CommandKind::Builtin), true) => format!("{} is a shell builtin", name),
+            (Some(CommandKind::CustomBuiltin), true) => {
+                format!("{} is an application builtin", name)
+            }
+            (Some(CommandKind::File { path, hashed: true }), true) => {
+                format!("{} is hashed ({})", name, path.display())
+            }
+            (Some(CommandKind::File { path, .. }), true) => {
+                format!("{} is {}", name, path.display())
+            }
+            (Some(CommandKind::Alias(_)), false) => {
+                engine.aliases.definition(name).unwrap_or_default()
+            }
+            (Some(CommandKind::File { path, .. }), false) => path.display().to_string(),
+            (Some(_), false) => name.to_string(),
+        };
+
+        io.write_out(&format!("{}\n", description))?;
+    }
+
+    Ok(exit_status(successful))
+}
+
+// Supported usage: alias [name[=value] ...]
+pub struct Alias;
+
+impl ShellBuiltin for Alias {
+    const NAME: &'static str = "alias";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        if args.is_empty() {
+            for name in engine.aliases.names() {
+                let definition = engine.aliases.definition(name).unwrap_or_default();
+                io.write_out(&format!("{}\n", definition))?;
+            }
+            return Ok(0);
+        }
+
+        let mut successful = true;
+        for arg in args {
+            let problem = match arg.split_once('=') {
+                Some((name, value)) => engine.aliases.define(name, value).err(),
+                None => match engine.aliases.definition(arg) {
+                    Some(definition) => {
+                        io.write_out(&format!("{}\n", definition))?;
+                        None
+                    }
+                    None => Some(format!("{}: not found", arg)),
+                },
+            };
+
+            if let Some(problem) = problem {
+                io.write_err(&format!("dss: alias: {}\n", problem))?;
+                successful = false;
+            }
+        }
+
+        Ok(exit_status(successful))
+    }
+}
+
+// Supported usage: unalias -a | name ...
+pub struct Unalias;
+
+impl ShellBuiltin for Unalias {
+    const NAME: &'static str = "unalias";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        match args {
+            [] => {
+                io.write_err("dss: unalias: usage: unalias -a | name ...\n")?;
+                Ok(1)
+            }
+            [flag] if flag == "-a" => {
+                engine.aliases.clear();
+                Ok(0)
+            }
+            names => {
+                let mut successful = true;
+                for name in names {
+                    if !engine.aliases.remove(name) {
+                        io.write_err(&format!("dss: unalias: {}: not found\n", name))?;
+                        successful = false;
+                    }
+                }
+                Ok(exit_status(successful))
+            }
+        }
+    }
+}
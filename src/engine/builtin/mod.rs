@@ -0,0 +1,233 @@
+mod dirs;
+mod jobs;
+mod lookup;
+mod shell;
+mod test;
+mod vars;
+
+use std::{collections::HashMap, fmt, os::unix::prelude::RawFd, rc::Rc};
+
+use nix::{errno::Errno, unistd::write};
+
+use super::Engine;
+use crate::errors::ShellError;
+
+// Command run in the shell itself instead of a forked process. Ones
+// of shell are registered by engine, applications embedding dss add
+// theirs with `Engine::register_builtin`
+pub trait Builtin {
+    // Words are of the command, name of builtin included. Returns
+    // exit status, same as a command run in a process would
+    fn exec(&self, engine: &mut Engine, argv: &[String], io: &mut Io) -> i32;
+}
+
+impl<F> Builtin for F
+where
+    F: Fn(&mut Engine, &[String], &mut Io) -> i32,
+{
+    fn exec(&self, engine: &mut Engine, argv: &[String], io: &mut Io) -> i32 {
+        self(engine, argv, io)
+    }
+}
+
+// Standard streams of a builtin, redirections of the
+// command are already applied to them when it runs
+pub struct Io {
+    pub stdin: RawFd,
+    pub stdout: RawFd,
+    pub stderr: RawFd,
+}
+
+impl Default for Io {
+    fn default() -> Self {
+        Self {
+            stdin: 0,
+            stdout: 1,
+            stderr: 2,
+        }
+    }
+}
+
+impl Io {
+    pub fn write_out(&mut self, output: &str) -> anyhow::Result<()> {
+        write_all(self.stdout, output)
+    }
+
+    pub fn write_err(&mut self, output: &str) -> anyhow::Result<()> {
+        write_all(self.stderr, output)
+    }
+}
+
+fn write_all(fd: RawFd, output: &str) -> anyhow::Result<()> {
+    let mut bytes = output.as_bytes();
+    while !bytes.is_empty() {
+        match write(fd, bytes) {
+            Ok(written) => bytes = &bytes[written..],
+            Err(Errno::EINTR) => {}
+            Err(errno) => return Err(errno.into()),
+        }
+    }
+
+    Ok(())
+}
+
+// Builtins of shell, each of them registered under its name
+trait ShellBuiltin {
+    const NAME: &'static str;
+
+    // Args come without name of builtin. Errors are
+    // reported, builtin fails with status 1 then
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32>;
+}
+
+struct Shell<T>(T);
+
+impl<T: ShellBuiltin> Builtin for Shell<T> {
+    fn exec(&self, engine: &mut Engine, argv: &[String], io: &mut Io) -> i32 {
+        let err = match self.0.run(engine, &argv[1..], io) {
+            Ok(status) => return status,
+            Err(err) => err,
+        };
+
+        let message = match err.downcast_ref::<ShellError>() {
+            Some(err) => err.to_string(),
+            None => format!("dss: {}: {}\n", T::NAME, err),
+        };
+        let _ = io.write_err(&message);
+        1
+    }
+}
+
+// Who registered a builtin, `type` tells them apart
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Origin {
+    Shell,
+    Application,
+}
+
+#[derive(Clone)]
+struct Entry {
+    origin: Origin,
+    builtin: Rc<dyn Builtin>,
+}
+
+#[derive(Clone, Default)]
+pub struct Builtins {
+    builtins: HashMap<String, Entry>,
+}
+
+impl Builtins {
+    // Registry holding every builtin of shell
+    pub fn with_shell_builtins() -> Self {
+        let mut builtins = Self::default();
+
+        builtins.register_shell(dirs::Cd);
+        builtins.register_shell(dirs::Pwd);
+        builtins.register_shell(dirs::Pushd);
+        builtins.register_shell(dirs::Popd);
+        builtins.register_shell(dirs::Dirs);
+
+        builtins.register_shell(vars::Declare);
+        builtins.register_shell(vars::Export);
+        builtins.register_shell(vars::Readonly);
+        builtins.register_shell(vars::Unset);
+        builtins.register_shell(vars::Let);
+
+        builtins.register_shell(jobs::Jobs);
+        builtins.register_shell(jobs::Fg);
+        builtins.register_shell(jobs::Disown);
+        builtins.register_shell(jobs::Wait);
+        builtins.register_shell(jobs::Pipestatus);
+
+        builtins.register_shell(lookup::Hash);
+        builtins.register_shell(lookup::Type);
+        builtins.register_shell(lookup::Command);
+        builtins.register_shell(lookup::Alias);
+        builtins.register_shell(lookup::Unalias);
+
+        builtins.register_shell(shell::Exec);
+        builtins.register_shell(shell::Source);
+        builtins.register_shell(shell::Dot);
+        builtins.register_shell(shell::Set);
+        builtins.register_shell(shell::Break);
+        builtins.register_shell(shell::Trap);
+        builtins.register_shell(shell::Bind);
+        builtins.register_shell(shell::Reload);
+        builtins.register_shell(shell::Inspect);
+        builtins.register_shell(shell::History);
+        builtins.register_shell(shell::Fc);
+        builtins.register_shell(shell::True);
+        builtins.register_shell(shell::Colon);
+        builtins.register_shell(shell::False);
+
+        builtins.register_shell(test::Test);
+        builtins.register_shell(test::Bracket);
+
+        builtins
+    }
+
+    fn register_shell<T: ShellBuiltin + 'static>(&mut self, builtin: T) {
+        self.register(T::NAME, Origin::Shell, Shell(builtin));
+    }
+
+    // Registering a name again replaces the builtin, except that builtins
+    // of shell take precedence over application ones. Returns whether
+    // builtin got registered
+    pub fn register(
+        &mut self,
+        name: &str,
+        origin: Origin,
+        builtin: impl Builtin + 'static,
+    ) -> bool {
+        if origin == Origin::Application && self.origin(name) == Some(Origin::Shell) {
+            return false;
+        }
+
+        let builtin = Rc::new(builtin);
+        self.builtins
+            .insert(name.to_string(), Entry { origin, builtin });
+        true
+    }
+
+    // Builtin is shared, so that it can be run with engine it is a part of
+    pub fn get(&self, name: &str) -> Option<Rc<dyn Builtin>> {
+        self.builtins.get(name).map(|entry| entry.builtin.clone())
+    }
+
+    pub fn origin(&self, name: &str) -> Option<Origin> {
+        self.builtins.get(name).map(|entry| entry.origin)
+    }
+}
+
+impl fmt::Debug for Builtins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.builtins.keys().collect();
+        names.sort();
+        f.debug_list().entries(names).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_builtins_take_precedence() {
+        let mut builtins = Builtins::with_shell_builtins();
+        assert_eq!(builtins.origin("cd"), Some(Origin::Shell));
+        assert_eq!(builtins.origin("deploy"), None);
+
+        let registered = builtins.register(
+            "cd",
+            Origin::Application,
+            |_: &mut Engine, _: &[String], _: &mut Io| 1,
+        );
+        assert!(!registered);
+        assert_eq!(builtins.origin("cd"), Some(Origin::Shell));
+
+        let deploy = |_: &mut Engine, _: &[String], _: &mut Io| 0;
+        assert!(builtins.register("deploy", Origin::Application, deploy));
+        assert_eq!(builtins.origin("deploy"), Some(Origin::Application));
+        assert!(builtins.get("deploy").is_some());
+    }
+}
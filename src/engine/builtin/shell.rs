@@ -0,0 +1,602 @@
+use std::path::{Path, PathBuf};
+
+use nix::unistd::getpid;
+
+use super::{Io, ShellBuiltin};
+use crate::{
+    command::quote,
+    engine::{exit_status, history, inspect, profile::StartupProfile, trap::Condition, Engine},
+    errors::ShellError,
+    interrupt,
+};
+
+// Supported usage: exec [command [arg ...]]
+// Without a command it is handled before builtins run, its
+// redirections then stay applied to shell
+pub struct Exec;
+
+impl ShellBuiltin for Exec {
+    const NAME: &'static str = "exec";
+
+    fn run(&self, engine: &mut Engine, args: &[String], _: &mut Io) -> anyhow::Result<i32> {
+        engine.execute_words(args.to_vec())?;
+        Ok(engine.last_status)
+    }
+}
+
+// Supported usage: set [-eux] [+eux] [-o [option[=value]]] [+o option] [--] [arg ...]
+pub struct Set;
+
+impl ShellBuiltin for Set {
+    const NAME: &'static str = "set";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let (enable, flags) = match arg.as_str() {
+                // Rest of the args replace positional params, none clears them
+                "--" | "-" => {
+                    engine.positional_params = args.cloned().collect();
+                    return Ok(0);
+                }
+                arg => match (arg.strip_prefix('-'), arg.strip_prefix('+')) {
+                    (Some(flags), _) => (true, flags),
+                    (_, Some(flags)) => (false, flags),
+                    _ => {
+                        engine.positional_params = std::iter::once(arg.to_string())
+                            .chain(args.cloned())
+                            .collect();
+                        return Ok(0);
+                    }
+                },
+            };
+
+            for flag in flags.chars() {
+                if flag != 'o' {
+                    engine.options.set_flag(flag, enable)?;
+                    continue;
+                }
+
+                match args.next() {
+                    Some(option) => engine.options.set(option, enable)?,
+                    // `set -o` lists current options
+                    None => io.write_out(&engine.options.list())?,
+                }
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+// Supported usage: break [n]
+pub struct Break;
+
+impl ShellBuiltin for Break {
+    const NAME: &'static str = "break";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        if engine.loop_depth == 0 {
+            io.write_err("dss: break: only meaningful in a loop\n")?;
+            return Ok(0);
+        }
+
+        let count = match args.first() {
+            Some(arg) => match arg.parse::<usize>() {
+                Ok(count) if count > 0 => count,
+                _ => {
+                    return Err(ShellError::BuiltinError(
+                        "break",
+                        format!("{}: loop count out of range", arg),
+                    )
+                    .into())
+                }
+            },
+            None => 1,
+        };
+
+        // Breaking out of more loops than enclosing ones breaks all
+        engine.pending_loop_breaks = count.min(engine.loop_depth);
+        Ok(0)
+    }
+}
+
+// Supported usage: trap [-p [condition ...]]
+//                  trap [action | - | ''] condition ...
+// Single condition alone is reset, same as with `-`
+pub struct Trap;
+
+impl ShellBuiltin for Trap {
+    const NAME: &'static str = "trap";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        let (action, names) = match args {
+            [] => return print_traps(engine, &[], io),
+            [flag, names @ ..] if flag == "-p" => return print_traps(engine, names, io),
+            [_] => (None, args),
+            [action, names @ ..] => ((action != "-").then_some(action.as_str()), names),
+        };
+
+        let mut successful = true;
+        for name in names {
+            let Some(condition) = Condition::parse(name) else {
+                io.write_err(&format!(
+                    "dss: trap: {}: invalid signal specification\n",
+                    name
+                ))?;
+                successful = false;
+                continue;
+            };
+
+            if let Err(errno) = set_trap(engine, condition, action) {
+                io.write_err(&format!("dss: trap: {}: {}\n", name, errno.desc()))?;
+                successful = false;
+            }
+        }
+
+        Ok(exit_status(successful))
+    }
+}
+
+// No action resets trap, an empty one ignores the signal
+fn set_trap(engine: &mut Engine, condition: Condition, action: Option<&str>) -> nix::Result<()> {
+    if let Condition::Signal(signal) = condition {
+        match action {
+            None => interrupt::restore(signal)?,
+            Some("") => interrupt::ignore(signal)?,
+            Some(_) => interrupt::catch(signal)?,
+        }
+    }
+
+    match action {
+        Some(action) => engine.traps.set(condition, action),
+        None => engine.traps.reset(condition),
+    }
+    Ok(())
+}
+
+// All traps are printed if no conditions are named
+fn print_traps(engine: &Engine, names: &[String], io: &mut Io) -> anyhow::Result<i32> {
+    let conditions = match names {
+        [] => engine.traps.conditions(),
+        _ => {
+            let mut conditions = vec![];
+            for name in names {
+                match Condition::parse(name) {
+                    Some(condition) => conditions.push(condition),
+                    None => {
+                        io.write_err(&format!(
+                            "dss: trap: {}: invalid signal specification\n",
+                            name
+                        ))?;
+                        return Ok(1);
+                    }
+                }
+            }
+            conditions
+        }
+    };
+
+    for condition in conditions {
+        if let Some(definition) = engine.traps.definition(condition) {
+            io.write_out(&format!("{}\n", definition))?;
+        }
+    }
+
+    Ok(0)
+}
+
+// Supported usage: bind [-pv]
+pub struct Bind;
+
+impl ShellBuiltin for Bind {
+    const NAME: &'static str = "bind";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        for arg in args {
+            match arg.as_str() {
+                "-p" => {
+                    for key_binding in &engine.inputrc.key_bindings {
+                        io.write_out(&format!("{}\n", key_binding))?;
+                    }
+                }
+                "-v" => io.write_out(&format!(
+                    "set completion-ignore-case {}\nset editing-mode {}\n",
+                    if engine.inputrc.completion_ignore_case {
+                        "on"
+                    } else {
+                        "off"
+                    },
+                    engine.inputrc.editing_mode
+                ))?,
+                _ => {
+                    return Err(ShellError::BuiltinError(
+                        "bind",
+                        format!("{}: invalid option", arg),
+                    )
+                    .into())
+                }
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+// Supported usage: reload [-f]
+pub struct Reload;
+
+impl ShellBuiltin for Reload {
+    const NAME: &'static str = "reload";
+
+    fn run(&self, engine: &mut Engine, args: &[String], _: &mut Io) -> anyhow::Result<i32> {
+        let mut force = false;
+        for arg in args {
+            match arg.as_str() {
+                "-f" => force = true,
+                _ => {
+                    return Err(ShellError::BuiltinError(
+                        "reload",
+                        format!("{}: invalid option", arg),
+                    )
+                    .into())
+                }
+            }
+        }
+
+        engine.load_startup_files(&mut StartupProfile::default(), force)?;
+        Ok(0)
+    }
+}
+
+// Supported usage: inspect [jobs | variables | options | traps | fds ...]
+// Prints all sections if none are named
+pub struct Inspect;
+
+impl ShellBuiltin for Inspect {
+    const NAME: &'static str = "inspect";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        let sections: Vec<&str> = match args {
+            [] => inspect::SECTIONS.to_vec(),
+            _ => args.iter().map(String::as_str).collect(),
+        };
+
+        if let Some(section) = sections
+            .iter()
+            .find(|section| !inspect::SECTIONS.contains(section))
+        {
+            io.write_err(&format!("dss: inspect: {}: no such section\n", section))?;
+            return Ok(1);
+        }
+
+        engine.reap_children();
+        for section in sections {
+            let output = match section {
+                "jobs" => inspect::jobs(&engine.jobs),
+                "variables" => inspect::variables(&engine.variables),
+                "options" => inspect::options(&engine.options),
+                "traps" => inspect::traps(&engine.traps),
+                _ => inspect::fds(),
+            };
+            io.write_out(&output)?;
+        }
+
+        Ok(0)
+    }
+}
+
+// Supported usage: history [-c | count]
+// Clearing history empties history file too
+pub struct History;
+
+impl ShellBuiltin for History {
+    const NAME: &'static str = "history";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        let end = engine.history.len();
+        let count = match args {
+            [] => Some(end),
+            [flag] if flag == "-c" => {
+                engine.history.clear();
+                if let Some(path) = engine.history_file().filter(|path| path.exists()) {
+                    if let Err(err) = std::fs::write(&path, "") {
+                        io.write_err(&format!("dss: history: {}: {}\n", path.display(), err))?;
+                        return Ok(1);
+                    }
+                }
+                return Ok(0);
+            }
+            [count] => count.parse::<usize>().ok(),
+            _ => None,
+        };
+        let Some(count) = count else {
+            io.write_err("dss: history: usage: history [-c | count]\n")?;
+            return Ok(1);
+        };
+
+        for number in end - count.min(end) + 1..=end {
+            if let Some(command) = engine.history.get(number) {
+                io.write_out(&format!("{:>5}  {}\n", number, command))?;
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+// Supported usage: fc [-e editor] [first [last]]
+//                  fc -l [first [last]]
+//                  fc -s [old=new] [first]
+// Command being run is already in history when reading from
+// terminal, it is not one of the commands `fc` refers to
+pub struct Fc;
+
+impl ShellBuiltin for Fc {
+    const NAME: &'static str = "fc";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        let end =
+            engine.history.len() - usize::from(engine.interactive && !engine.history.is_empty());
+        // Negative numbers are operands rather than options
+        let is_operand = |arg: &String| !arg.starts_with('-') || arg.parse::<i64>().is_ok();
+
+        match args {
+            [flag, operands @ ..] if flag == "-l" && operands.len() <= 2 => {
+                list_history(engine, operands, end, io)
+            }
+            [flag, operands @ ..] if flag == "-s" => reexecute_history(engine, operands, end, io),
+            [flag, editor, operands @ ..] if flag == "-e" && operands.len() <= 2 => {
+                edit_history(engine, Some(editor), operands, end, io)
+            }
+            operands if operands.len() <= 2 && operands.iter().all(is_operand) => {
+                edit_history(engine, None, operands, end, io)
+            }
+            _ => {
+                io.write_err(
+                    "dss: fc: usage: fc [-e editor] [first [last]] or fc -l [first [last]] \
+                     or fc -s [old=new] [first]\n",
+                )?;
+                Ok(1)
+            }
+        }
+    }
+}
+
+// Last 16 commands are listed if no range is given
+fn list_history(
+    engine: &Engine,
+    operands: &[String],
+    end: usize,
+    io: &mut Io,
+) -> anyhow::Result<i32> {
+    if end == 0 {
+        return Ok(0);
+    }
+
+    let mut range = vec![];
+    for operand in operands {
+        let Some(number) = engine.history.resolve_clamped(operand, end) else {
+            io.write_err(&format!("dss: fc: {}: no command found\n", operand))?;
+            return Ok(1);
+        };
+        range.push(number);
+    }
+
+    let (first, last) = match range[..] {
+        [] => (end.saturating_sub(15).max(1), end),
+        [first] => (first, end),
+        [first, last, ..] => (first, last),
+    };
+
+    for (number, command) in engine.history.range(first, last) {
+        io.write_out(&format!("{}\t{}\n", number, command))?;
+    }
+
+    Ok(0)
+}
+
+// Command is echoed before it runs, and takes place of
+// `fc` in history so that running it again is easy
+fn reexecute_history(
+    engine: &mut Engine,
+    operands: &[String],
+    end: usize,
+    io: &mut Io,
+) -> anyhow::Result<i32> {
+    let (replacement, operands) = match operands {
+        [replacement, rest @ ..] if replacement.contains('=') => (Some(replacement), rest),
+        _ => (None, operands),
+    };
+
+    let number = match operands {
+        [] => (end > 0).then_some(end),
+        [first] => engine.history.resolve(first, end),
+        _ => {
+            io.write_err("dss: fc: too many arguments\n")?;
+            return Ok(1);
+        }
+    };
+    let Some(command) = number.and_then(|number| engine.history.get(number)) else {
+        io.write_err("dss: fc: no command found\n")?;
+        return Ok(1);
+    };
+
+    let command = match replacement {
+        Some(replacement) => match history::substitute(command, replacement) {
+            Some(command) => command,
+            None => {
+                io.write_err(&format!("dss: fc: {}: invalid substitution\n", replacement))?;
+                return Ok(1);
+            }
+        },
+        None => command.to_string(),
+    };
+
+    io.write_err(&format!("{}\n", command))?;
+    if engine.interactive {
+        engine.history.replace_last(&command);
+    }
+
+    engine.execute_script(Path::new("fc"), &command)?;
+    Ok(engine.last_status)
+}
+
+// Commands are written to a file which editor is run on, same
+// as a command, and what is left in it afterwards gets run.
+// Editor is `-e` one, else $FCEDIT, $EDITOR or vi
+fn edit_history(
+    engine: &mut Engine,
+    editor: Option<&String>,
+    operands: &[String],
+    end: usize,
+    io: &mut Io,
+) -> anyhow::Result<i32> {
+    let mut range = vec![];
+    for operand in operands {
+        let Some(number) = engine.history.resolve(operand, end) else {
+            io.write_err(&format!("dss: fc: {}: no command found\n", operand))?;
+            return Ok(1);
+        };
+        range.push(number);
+    }
+
+    let (first, last) = match range[..] {
+        [] if end > 0 => (end, end),
+        [] => {
+            io.write_err("dss: fc: no command found\n")?;
+            return Ok(1);
+        }
+        [first] => (first, first),
+        [first, last, ..] => (first, last),
+    };
+
+    let mut commands = String::new();
+    for (_, command) in engine.history.range(first, last) {
+        commands.push_str(&format!("{}\n", command));
+    }
+
+    let path = std::env::temp_dir().join(format!("dss_fc_{}", getpid()));
+    std::fs::write(&path, commands)?;
+
+    let editor = editor
+        .cloned()
+        .or_else(|| engine.variables.get_str("FCEDIT"))
+        .or_else(|| engine.variables.get_str("EDITOR"))
+        .filter(|editor| !editor.is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let path_str = path.to_string_lossy().to_string();
+    engine.execute_script(
+        Path::new("fc"),
+        &format!("{} {}", editor, quote::quote(&path_str)),
+    )?;
+
+    let edited = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+    if engine.last_status != 0 {
+        io.write_err(&format!("dss: fc: {}: editor failed\n", editor))?;
+        return Ok(1);
+    }
+    let edited = edited?;
+
+    // Nothing is run if editor left file empty
+    if edited.trim().is_empty() {
+        return Ok(0);
+    }
+
+    io.write_err(&edited)?;
+    if engine.interactive {
+        engine.history.replace_last(&edited);
+    }
+
+    engine.execute_script(Path::new("fc"), &edited)?;
+    Ok(engine.last_status)
+}
+
+// Supported usage: source file [args]
+pub struct Source;
+
+impl ShellBuiltin for Source {
+    const NAME: &'static str = "source";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        source(engine, Self::NAME, args, io)
+    }
+}
+
+// Supported usage: . file [args]
+pub struct Dot;
+
+impl ShellBuiltin for Dot {
+    const NAME: &'static str = ".";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        source(engine, Self::NAME, args, io)
+    }
+}
+
+fn source(engine: &mut Engine, name: &str, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+    let Some((file, args)) = args.split_first() else {
+        io.write_err(&format!("dss: {}: filename argument required\n", name))?;
+        return Ok(1);
+    };
+
+    if file.contains('/') && engine.forbidden_in_restricted(file)? {
+        return Ok(1);
+    }
+
+    // Names without a slash are searched for in PATH, falling
+    // back to current directory same as bash
+    let mut path = PathBuf::from(file);
+    if !file.contains('/') {
+        let path_var = engine.variables.get_str("PATH").unwrap_or_default();
+        if let Some(found) = std::env::split_paths(&path_var)
+            .map(|dir| dir.join(file))
+            .find(|path| path.is_file())
+        {
+            path = found;
+        }
+    }
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            io.write_err(&format!("dss: {}: {}: {}\n", name, file, err))?;
+            return Ok(1);
+        }
+    };
+
+    engine.source(&path, &contents, args)?;
+    Ok(engine.last_status)
+}
+
+// Redirections of `:` are still done, e.g. `: > file` truncates
+pub struct Colon;
+
+impl ShellBuiltin for Colon {
+    const NAME: &'static str = ":";
+
+    fn run(&self, _: &mut Engine, _: &[String], _: &mut Io) -> anyhow::Result<i32> {
+        Ok(0)
+    }
+}
+
+pub struct True;
+
+impl ShellBuiltin for True {
+    const NAME: &'static str = "true";
+
+    fn run(&self, _: &mut Engine, _: &[String], _: &mut Io) -> anyhow::Result<i32> {
+        Ok(0)
+    }
+}
+
+pub struct False;
+
+impl ShellBuiltin for False {
+    const NAME: &'static str = "false";
+
+    fn run(&self, _: &mut Engine, _: &[String], _: &mut Io) -> anyhow::Result<i32> {
+        Ok(1)
+    }
+}
@@ -0,0 +1,42 @@
+use super::{Io, ShellBuiltin};
+use crate::engine::{condition, exit_status, Engine};
+
+// Misuse, e.g. `[ 1 -eq`, is told apart from a false expression
+const USAGE_ERROR_STATUS: i32 = 2;
+
+// Supported usage: test expr
+pub struct Test;
+
+impl ShellBuiltin for Test {
+    const NAME: &'static str = "test";
+
+    fn run(&self, _: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        test(Self::NAME, args, io)
+    }
+}
+
+// Supported usage: [ expr ]
+pub struct Bracket;
+
+impl ShellBuiltin for Bracket {
+    const NAME: &'static str = "[";
+
+    fn run(&self, _: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        let Some((_, args)) = args.split_last().filter(|(last, _)| *last == "]") else {
+            io.write_err("dss: [: missing `]'\n")?;
+            return Ok(USAGE_ERROR_STATUS);
+        };
+
+        test(Self::NAME, args, io)
+    }
+}
+
+fn test(name: &str, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+    match condition::evaluate(args) {
+        Ok(result) => Ok(exit_status(result)),
+        Err(problem) => {
+            io.write_err(&format!("dss: {}: {}\n", name, problem))?;
+            Ok(USAGE_ERROR_STATUS)
+        }
+    }
+}
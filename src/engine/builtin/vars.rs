@@ -0,0 +1,319 @@
+use super::{Io, ShellBuiltin};
+use crate::{
+    command::{arithmetic, is_valid_name, quote, Assignment, AssignmentValue},
+    engine::{exit_status, Engine},
+    errors::ShellError,
+};
+
+// Supported usage: declare [-aiprx] [+ix] [name[=value] ...]
+pub struct Declare;
+
+impl ShellBuiltin for Declare {
+    const NAME: &'static str = "declare";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        let mut integer = None;
+        let mut exported = None;
+        let mut readonly = false;
+        let mut array = false;
+        let mut print = false;
+        let mut names = vec![];
+
+        for arg in args {
+            let lexeme = arg.as_str();
+            if let Some(flags) = lexeme.strip_prefix('-') {
+                for flag in flags.chars() {
+                    match flag {
+                        'a' => array = true,
+                        'i' => integer = Some(true),
+                        'x' => exported = Some(true),
+                        'r' => readonly = true,
+                        'p' => print = true,
+                        _ => {
+                            return Err(ShellError::BuiltinError(
+                                "declare",
+                                format!("-{}: invalid option", flag),
+                            )
+                            .into())
+                        }
+                    }
+                }
+            } else if let Some(flags) = lexeme.strip_prefix('+') {
+                for flag in flags.chars() {
+                    match flag {
+                        'i' => integer = Some(false),
+                        'x' => exported = Some(false),
+                        _ => {
+                            return Err(ShellError::BuiltinError(
+                                "declare",
+                                format!("+{}: invalid option", flag),
+                            )
+                            .into())
+                        }
+                    }
+                }
+            } else {
+                names.push(lexeme);
+            }
+        }
+
+        // `declare` and `declare -p` list all variables
+        if print || names.is_empty() {
+            let names: Vec<String> = if names.is_empty() {
+                engine.variables.names().into_iter().cloned().collect()
+            } else {
+                names.iter().map(|name| name.to_string()).collect()
+            };
+
+            for name in names {
+                let Some(declaration) = engine.variables.declaration(&name) else {
+                    let message = format!("{}: not found", name);
+                    return Err(ShellError::BuiltinError("declare", message).into());
+                };
+                io.write_out(&format!("{}\n", declaration))?;
+            }
+
+            return Ok(0);
+        }
+
+        for name in names {
+            let assignment = Assignment::parse(name);
+            let var_name = assignment
+                .as_ref()
+                .map_or(name.to_string(), |assignment| assignment.name.clone());
+
+            if !is_valid_name(&var_name) {
+                return Err(ShellError::BuiltinError(
+                    "declare",
+                    format!("`{}': not a valid identifier", name),
+                )
+                .into());
+            }
+
+            if let Some(integer) = integer {
+                engine.variables.set_integer_attribute(&var_name, integer);
+            }
+
+            if let Some(exported) = exported {
+                engine.variables.set_exported_attribute(&var_name, exported);
+            }
+
+            if array {
+                engine.variables.set_array_attribute(&var_name);
+            }
+
+            if let Some(mut assignment) = assignment {
+                if let AssignmentValue::Scalar(value) = &assignment.value {
+                    // `arr=(a b)` reaches here with its values quoted, see
+                    // `Command::expand_words`, `declare -a arr=value` is
+                    // same as `arr=(value)`
+                    let values = value
+                        .strip_prefix('(')
+                        .and_then(|value| value.strip_suffix(')'));
+                    if let Some(values) = values {
+                        assignment.value = AssignmentValue::Array(quote::split(values)?);
+                    } else if array {
+                        assignment.value = AssignmentValue::Array(vec![value.clone()]);
+                    }
+                }
+
+                engine.variables.assign(&assignment)?;
+            }
+
+            // Value given along is assigned first
+            if readonly {
+                engine.variables.set_readonly_attribute(&var_name);
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+// Supported usage: export [-np] [name[=value] ...]
+pub struct Export;
+
+impl ShellBuiltin for Export {
+    const NAME: &'static str = "export";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        let mut unexport = false;
+        let mut names = vec![];
+
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !names.is_empty() || flags.is_empty() => names.push(arg),
+                Some(flags) => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'n' => unexport = true,
+                            'p' => {}
+                            _ => {
+                                io.write_err(&format!("dss: export: -{}: invalid option\n", flag))?;
+                                return Ok(1);
+                            }
+                        }
+                    }
+                }
+                None => names.push(arg),
+            }
+        }
+
+        // `export` and `export -p` list exported variables
+        if names.is_empty() {
+            for name in engine.variables.names() {
+                if engine.variables.get(name).is_some_and(|var| var.exported) {
+                    if let Some(declaration) = engine.variables.declaration(name) {
+                        io.write_out(&format!("{}\n", declaration))?;
+                    }
+                }
+            }
+
+            return Ok(0);
+        }
+
+        let mut successful = true;
+        for name in names {
+            let assignment = Assignment::parse(name);
+            let var_name = assignment
+                .as_ref()
+                .map_or(name.as_str(), |assignment| assignment.name.as_str());
+
+            if !is_valid_name(var_name) {
+                io.write_err(&format!(
+                    "dss: export: `{}': not a valid identifier\n",
+                    name
+                ))?;
+                successful = false;
+                continue;
+            }
+
+            if let Some(assignment) = &assignment {
+                if let Err(err) = engine.variables.assign(assignment) {
+                    io.write_err(&err.to_string())?;
+                    successful = false;
+                    continue;
+                }
+            }
+
+            engine.variables.set_exported_attribute(var_name, !unexport);
+        }
+
+        Ok(exit_status(successful))
+    }
+}
+
+// Supported usage: readonly [-p] [name[=value] ...]
+pub struct Readonly;
+
+impl ShellBuiltin for Readonly {
+    const NAME: &'static str = "readonly";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        let names = match args {
+            [flag, names @ ..] if flag == "-p" => names,
+            [flag, ..] if flag.starts_with('-') => {
+                io.write_err(&format!("dss: readonly: {}: invalid option\n", flag))?;
+                return Ok(1);
+            }
+            names => names,
+        };
+
+        // `readonly` and `readonly -p` list readonly variables
+        if names.is_empty() {
+            for name in engine.variables.names() {
+                if engine.variables.get(name).is_some_and(|var| var.readonly) {
+                    if let Some(declaration) = engine.variables.declaration(name) {
+                        io.write_out(&format!("{}\n", declaration))?;
+                    }
+                }
+            }
+
+            return Ok(0);
+        }
+
+        let mut successful = true;
+        for name in names {
+            let assignment = Assignment::parse(name);
+            let var_name = assignment
+                .as_ref()
+                .map_or(name.as_str(), |assignment| assignment.name.as_str());
+
+            if !is_valid_name(var_name) {
+                io.write_err(&format!(
+                    "dss: readonly: `{}': not a valid identifier\n",
+                    name
+                ))?;
+                successful = false;
+                continue;
+            }
+
+            if let Some(assignment) = &assignment {
+                if let Err(err) = engine.variables.assign(assignment) {
+                    io.write_err(&err.to_string())?;
+                    successful = false;
+                    continue;
+                }
+            }
+
+            engine.variables.set_readonly_attribute(var_name);
+        }
+
+        Ok(exit_status(successful))
+    }
+}
+
+// Supported usage: unset [-v] name ...
+pub struct Unset;
+
+impl ShellBuiltin for Unset {
+    const NAME: &'static str = "unset";
+
+    fn run(&self, engine: &mut Engine, args: &[String], io: &mut Io) -> anyhow::Result<i32> {
+        let names = match args {
+            [flag, names @ ..] if flag == "-v" => names,
+            [flag, ..] if flag.starts_with('-') => {
+                io.write_err(&format!("dss: unset: {}: invalid option\n", flag))?;
+                return Ok(1);
+            }
+            names => names,
+        };
+
+        let mut successful = true;
+        for name in names {
+            if !is_valid_name(name) {
+                io.write_err(&format!("dss: unset: `{}': not a valid identifier\n", name))?;
+                successful = false;
+                continue;
+            }
+
+            if let Err(err) = engine.variables.unset(name) {
+                io.write_err(&err.to_string())?;
+                successful = false;
+            }
+        }
+
+        Ok(exit_status(successful))
+    }
+}
+
+// Supported usage: let expr [expr ...]
+// Exit status is failure if the last expression evaluates to 0
+pub struct Let;
+
+impl ShellBuiltin for Let {
+    const NAME: &'static str = "let";
+
+    fn run(&self, engine: &mut Engine, args: &[String], _: &mut Io) -> anyhow::Result<i32> {
+        if args.is_empty() {
+            return Err(ShellError::BuiltinError("let", "expression expected".into()).into());
+        }
+
+        let mut value = 0;
+        for arg in args {
+            value = arithmetic::evaluate(arg, &mut engine.variables)?;
+        }
+
+        Ok(exit_status(value != 0))
+    }
+}
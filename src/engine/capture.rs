@@ -0,0 +1,174 @@
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    os::unix::prelude::{FromRawFd, RawFd},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use nix::{
+    fcntl::{fcntl, FcntlArg, FdFlag, OFlag},
+    pty::{openpty, Winsize},
+    sys::termios::{tcgetattr, OutputFlags},
+    unistd::{isatty, pipe2},
+};
+
+// How long stderr is read after command exits, processes started
+// by it can keep stderr open for much longer than that
+pub const DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
+
+const RED: &[u8] = b"\x1b[31m";
+const RESET: &[u8] = b"\x1b[0m";
+
+// Fds stderr of a foreground command is captured with, for
+// `failhint` and `colorstderr`
+pub struct StderrCapture {
+    pub read_fd: RawFd,
+    pub write_fd: RawFd,
+    // Output is passed on in red
+    pub colorize: bool,
+}
+
+impl StderrCapture {
+    // When stderr of shell is a terminal, command writes to a pseudo
+    // terminal, so that it still sees a terminal, to a pipe otherwise.
+    // Both ends are closed on exec, stderr of command is a copy of
+    // write end which is not
+    pub fn open(colorize: bool) -> anyhow::Result<Self> {
+        if !isatty(2).unwrap_or(false) {
+            let (read_fd, write_fd) = pipe2(OFlag::O_CLOEXEC)?;
+            return Ok(Self {
+                read_fd,
+                write_fd,
+                colorize,
+            });
+        }
+
+        // Output is passed on as it is, terminal of shell translates it
+        let mut termios = tcgetattr(2)?;
+        termios.output_flags.remove(OutputFlags::OPOST);
+
+        let pty = openpty(window_size().as_ref(), &termios)?;
+        for fd in [pty.master, pty.slave] {
+            fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+        }
+
+        Ok(Self {
+            read_fd: pty.master,
+            write_fd: pty.slave,
+            colorize,
+        })
+    }
+}
+
+fn window_size() -> Option<Winsize> {
+    let mut size = Winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    let res = unsafe { libc::ioctl(2, libc::TIOCGWINSZ, &mut size) };
+    (res == 0).then_some(size)
+}
+
+// Copies what a command writes to its stderr on to stderr of
+// shell, remembering the last line of it
+pub struct StderrTee {
+    last_line: Arc<Mutex<String>>,
+    done: mpsc::Receiver<()>,
+}
+
+impl StderrTee {
+    // Takes ownership of read end of capture, write end
+    // should already be closed in shell
+    pub fn start(capture: &StderrCapture) -> Self {
+        let last_line = Arc::new(Mutex::new(String::new()));
+        let (done_sender, done) = mpsc::channel();
+
+        let tee_last_line = Arc::clone(&last_line);
+        let read_fd = capture.read_fd;
+        let colorize = capture.colorize;
+        thread::spawn(move || {
+            let mut reader = unsafe { File::from_raw_fd(read_fd) };
+            let mut buf = [0; 4096];
+            let mut line = vec![];
+
+            // Pseudo terminal gives an error instead of EOF once
+            // all of its writers are gone
+            while let Ok(len) = reader.read(&mut buf) {
+                if len == 0 {
+                    break;
+                }
+
+                let output = if colorize {
+                    [RED, &buf[..len], RESET].concat()
+                } else {
+                    buf[..len].to_vec()
+                };
+                let _ = io::stderr().write_all(&output);
+
+                for &byte in &buf[..len] {
+                    if byte != b'\n' {
+                        line.push(byte);
+                        continue;
+                    }
+
+                    remember_line(&tee_last_line, &line);
+                    line.clear();
+                }
+            }
+
+            remember_line(&tee_last_line, &line);
+            let _ = done_sender.send(());
+        });
+
+        Self { last_line, done }
+    }
+
+    // Last non blank line written to stderr, waiting a bit for
+    // the output still in pipe once command has exited
+    pub fn finish(self) -> Option<String> {
+        let _ = self.done.recv_timeout(DRAIN_TIMEOUT);
+        let line = self.last_line.lock().ok()?.clone();
+        (!line.is_empty()).then_some(line)
+    }
+}
+
+// Only text after the last `\r` shows on terminal, e.g. for progress bars
+fn remember_line(last_line: &Mutex<String>, line: &[u8]) {
+    let line = String::from_utf8_lossy(line);
+    let line = line.rsplit('\r').next().unwrap_or_default().trim();
+    if line.is_empty() {
+        return;
+    }
+
+    if let Ok(mut last_line) = last_line.lock() {
+        *last_line = line.to_string();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::unistd::{close, pipe, write};
+
+    use super::{StderrCapture, StderrTee};
+
+    #[test]
+    fn test_stderr_tee_keeps_last_line() {
+        let (read_fd, write_fd) = pipe().unwrap();
+        let capture = StderrCapture {
+            read_fd,
+            write_fd,
+            colorize: false,
+        };
+        let tee = StderrTee::start(&capture);
+
+        write(write_fd, b"first\nsecond\n\n  \r50%\r100% done  ").unwrap();
+        close(write_fd).unwrap();
+
+        assert_eq!(tee.finish(), Some("100% done".into()));
+    }
+}
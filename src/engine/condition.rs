@@ -0,0 +1,262 @@
+// Test Spec:
+// - URL: https://pubs.opengroup.org/onlinepubs/9699919799/utilities/test.html
+//
+// Expressions of `test` and `[` builtins. Besides the forms POSIX
+// specifies for up to 4 arguments, longer ones are parsed with `-o`
+// binding looser than `-a`, which binds looser than `!`, as in XSI
+
+use std::{
+    fs::{self, Metadata},
+    os::unix::prelude::{FileTypeExt, MetadataExt, PermissionsExt},
+    path::Path,
+};
+
+use nix::unistd::{access, isatty, AccessFlags};
+
+// Returns result of expression, or problem with it
+pub fn evaluate(args: &[String]) -> Result<bool, String> {
+    if args.is_empty() {
+        return Ok(false);
+    }
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let mut parser = Parser {
+        args: &args,
+        idx: 0,
+    };
+
+    let result = parser.or_expr()?;
+    match parser.args.get(parser.idx) {
+        Some(arg) => Err(format!("{}: unexpected argument", arg)),
+        None => Ok(result),
+    }
+}
+
+struct Parser<'a> {
+    args: &'a [&'a str],
+    idx: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn remaining(&self) -> usize {
+        self.args.len() - self.idx
+    }
+
+    fn peek(&self, offset: usize) -> Option<&'a str> {
+        self.args.get(self.idx + offset).copied()
+    }
+
+    fn next(&mut self) -> Result<&'a str, String> {
+        let arg = self.peek(0).ok_or("argument expected")?;
+        self.idx += 1;
+        Ok(arg)
+    }
+
+    fn or_expr(&mut self) -> Result<bool, String> {
+        let mut result = self.and_expr()?;
+        while self.peek(0) == Some("-o") && self.remaining() > 1 {
+            self.idx += 1;
+            // Both sides are parsed, so that errors are found in either
+            result |= self.and_expr()?;
+        }
+        Ok(result)
+    }
+
+    fn and_expr(&mut self) -> Result<bool, String> {
+        let mut result = self.not_expr()?;
+        while self.peek(0) == Some("-a") && self.remaining() > 1 {
+            self.idx += 1;
+            result &= self.not_expr()?;
+        }
+        Ok(result)
+    }
+
+    // `!` alone is a non-empty string, as is `!` followed by a
+    // binary operator, e.g. `[ ! = ! ]`
+    fn not_expr(&mut self) -> Result<bool, String> {
+        if self.peek(0) == Some("!")
+            && self.remaining() > 1
+            && !self.peek(1).is_some_and(is_binary_op)
+        {
+            self.idx += 1;
+            return Ok(!self.not_expr()?);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<bool, String> {
+        let arg = self.next()?;
+
+        if let Some(op) = self.peek(0).filter(|op| is_binary_op(op)) {
+            if self.remaining() > 1 {
+                self.idx += 1;
+                let rhs = self.next()?;
+                return binary(arg, op, rhs);
+            }
+        }
+
+        if arg == "(" && self.remaining() > 1 {
+            let result = self.or_expr()?;
+            return match self.next() {
+                Ok(")") => Ok(result),
+                _ => Err("missing `)'".to_string()),
+            };
+        }
+
+        if is_unary_op(arg) && self.remaining() > 0 {
+            let operand = self.next()?;
+            return unary(arg, operand);
+        }
+
+        Ok(!arg.is_empty())
+    }
+}
+
+fn is_unary_op(op: &str) -> bool {
+    matches!(
+        op,
+        "-b" | "-c"
+            | "-d"
+            | "-e"
+            | "-f"
+            | "-g"
+            | "-h"
+            | "-L"
+            | "-n"
+            | "-p"
+            | "-r"
+            | "-s"
+            | "-S"
+            | "-t"
+            | "-u"
+            | "-w"
+            | "-x"
+            | "-z"
+    )
+}
+
+fn is_binary_op(op: &str) -> bool {
+    matches!(
+        op,
+        "=" | "=="
+            | "!="
+            | "<"
+            | ">"
+            | "-eq"
+            | "-ne"
+            | "-gt"
+            | "-ge"
+            | "-lt"
+            | "-le"
+            | "-nt"
+            | "-ot"
+            | "-ef"
+    )
+}
+
+fn unary(op: &str, operand: &str) -> Result<bool, String> {
+    let path = Path::new(operand);
+    let metadata = || fs::metadata(path).ok();
+    let has_type = |is_type: fn(&Metadata) -> bool| metadata().is_some_and(|m| is_type(&m));
+
+    let result = match op {
+        "-n" => !operand.is_empty(),
+        "-z" => operand.is_empty(),
+        "-e" => metadata().is_some(),
+        "-f" => has_type(|m| m.is_file()),
+        "-d" => has_type(|m| m.is_dir()),
+        "-b" => has_type(|m| m.file_type().is_block_device()),
+        "-c" => has_type(|m| m.file_type().is_char_device()),
+        "-p" => has_type(|m| m.file_type().is_fifo()),
+        "-S" => has_type(|m| m.file_type().is_socket()),
+        "-s" => has_type(|m| m.size() > 0),
+        "-g" => has_type(|m| m.permissions().mode() & 0o2000 != 0),
+        "-u" => has_type(|m| m.permissions().mode() & 0o4000 != 0),
+        "-h" | "-L" => fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()),
+        "-r" => access(path, AccessFlags::R_OK).is_ok(),
+        "-w" => access(path, AccessFlags::W_OK).is_ok(),
+        "-x" => access(path, AccessFlags::X_OK).is_ok(),
+        _ => isatty(parse_integer(operand)? as i32).unwrap_or(false),
+    };
+
+    Ok(result)
+}
+
+fn binary(lhs: &str, op: &str, rhs: &str) -> Result<bool, String> {
+    let result = match op {
+        "=" | "==" => lhs == rhs,
+        "!=" => lhs != rhs,
+        "<" => lhs < rhs,
+        ">" => lhs > rhs,
+        "-nt" | "-ot" => {
+            let modified = |path: &str| fs::metadata(path).and_then(|m| m.modified()).ok();
+            match (modified(lhs), modified(rhs), op) {
+                (Some(lhs), Some(rhs), "-nt") => lhs > rhs,
+                (Some(lhs), Some(rhs), _) => lhs < rhs,
+                // Existing file is newer than a missing one
+                (lhs, rhs, "-nt") => lhs.is_some() && rhs.is_none(),
+                (lhs, rhs, _) => lhs.is_none() && rhs.is_some(),
+            }
+        }
+        "-ef" => match (fs::metadata(lhs), fs::metadata(rhs)) {
+            (Ok(lhs), Ok(rhs)) => lhs.dev() == rhs.dev() && lhs.ino() == rhs.ino(),
+            _ => false,
+        },
+        _ => {
+            let (lhs, rhs) = (parse_integer(lhs)?, parse_integer(rhs)?);
+            match op {
+                "-eq" => lhs == rhs,
+                "-ne" => lhs != rhs,
+                "-gt" => lhs > rhs,
+                "-ge" => lhs >= rhs,
+                "-lt" => lhs < rhs,
+                _ => lhs <= rhs,
+            }
+        }
+    };
+
+    Ok(result)
+}
+
+fn parse_integer(arg: &str) -> Result<i64, String> {
+    arg.trim()
+        .parse()
+        .map_err(|_| format!("{}: integer expression expected", arg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate;
+
+    fn check(expr: &str) -> Result<bool, String> {
+        let args: Vec<String> = expr.split_whitespace().map(str::to_string).collect();
+        evaluate(&args)
+    }
+
+    #[test]
+    fn test_evaluation_of_conditions() {
+        assert_eq!(check(""), Ok(false));
+        assert_eq!(check("x"), Ok(true));
+        assert_eq!(evaluate(&["".to_string()]), Ok(false));
+        assert_eq!(check("-n"), Ok(true));
+        assert_eq!(check("!"), Ok(true));
+        assert_eq!(check("! x"), Ok(false));
+        assert_eq!(check("! = !"), Ok(true));
+
+        assert_eq!(check("abc = abc"), Ok(true));
+        assert_eq!(check("abc != abc"), Ok(false));
+        assert_eq!(check("a < b"), Ok(true));
+        assert_eq!(check("10 -gt 9"), Ok(true));
+        assert_eq!(check("-3 -le -4"), Ok(false));
+        assert!(check("ten -gt 9").is_err());
+
+        assert_eq!(check("-d / -a -f /"), Ok(false));
+        assert_eq!(check("-d / -o -f /"), Ok(true));
+        assert_eq!(check("x -o y -a ! z"), Ok(true));
+        assert_eq!(check("( x = y -o y = y ) -a ! -e /no/such/file"), Ok(true));
+        assert_eq!(check("/ -ef /."), Ok(true));
+
+        assert!(check("( x").is_err());
+        assert!(check("x y").is_err());
+    }
+}
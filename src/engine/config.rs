@@ -0,0 +1,297 @@
+// TOML Spec:
+// - URL: https://toml.io/en/v1.0.0
+//
+// Only a subset is supported: tables, bare and quoted keys, strings,
+// integers and booleans. Config looks like:
+//
+//   prompt = "% "
+//
+//   [options]
+//   extendedglob = true
+//   cmdtimeout = 10
+//
+//   [keybindings]
+//   Control-a = "end-of-line"
+//   '\C-x\C-r' = "reverse-search-history"
+
+use std::{env, fs, path::PathBuf};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    pub prompt: Option<String>,
+    // Option names with values, applied same as `set -o`
+    pub options: Vec<(String, ConfigValue)>,
+    // Key names or key sequences with functions bound to them
+    pub key_bindings: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigValue {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Table {
+    Root,
+    Options,
+    KeyBindings,
+    // Entries of unknown tables are skipped
+    Unknown,
+}
+
+impl Config {
+    // Reads $DSS_CONFIG or dss/config.toml in $XDG_CONFIG_HOME or
+    // ~/.config, problems found in the file are returned along
+    // with parsed config
+    pub fn load() -> (Self, Vec<String>) {
+        let path = match (
+            env::var_os("DSS_CONFIG"),
+            env::var_os("XDG_CONFIG_HOME"),
+            env::var_os("HOME"),
+        ) {
+            (Some(path), _, _) => PathBuf::from(path),
+            (None, Some(config_home), _) => PathBuf::from(config_home).join("dss/config.toml"),
+            (None, None, Some(home)) => PathBuf::from(home).join(".config/dss/config.toml"),
+            (None, None, None) => return (Self::default(), vec![]),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let (config, problems) = Self::parse(&contents);
+                let problems = problems
+                    .into_iter()
+                    .map(|problem| format!("{}: {}", path.display(), problem))
+                    .collect();
+                (config, problems)
+            }
+            Err(_) => (Self::default(), vec![]),
+        }
+    }
+
+    pub fn parse(contents: &str) -> (Self, Vec<String>) {
+        let mut config = Self::default();
+        let mut problems = vec![];
+        let mut table = Table::Root;
+
+        for (line_no, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let result = if line.starts_with('[') {
+                parse_table_header(line).map(|header| table = header)
+            } else if table == Table::Unknown {
+                Ok(())
+            } else {
+                parse_key_value(line)
+                    .and_then(|(key, value)| config.handle_entry(table, key, value))
+            };
+
+            if let Err(problem) = result {
+                problems.push(format!("line {}: {}", line_no + 1, problem));
+            }
+        }
+
+        (config, problems)
+    }
+
+    fn handle_entry(
+        &mut self,
+        table: Table,
+        key: String,
+        value: ConfigValue,
+    ) -> Result<(), String> {
+        match (table, key.as_str(), value) {
+            (Table::Root, "prompt", ConfigValue::String(prompt)) => self.prompt = Some(prompt),
+            (Table::Root, "prompt", _) => return Err("prompt: expected a string".to_string()),
+            (Table::Root, _, _) => return Err(format!("unknown key: {}", key)),
+            (Table::Options, _, ConfigValue::String(_)) => {
+                return Err(format!("{}: expected a boolean or an integer", key))
+            }
+            (Table::Options, _, value) => {
+                // Later entries for same option win
+                self.options.retain(|(name, _)| *name != key);
+                self.options.push((key, value));
+            }
+            (Table::KeyBindings, _, ConfigValue::String(function)) => {
+                self.key_bindings.retain(|(key_seq, _)| *key_seq != key);
+                self.key_bindings.push((key, function));
+            }
+            (Table::KeyBindings, _, _) => return Err(format!("{}: expected a function name", key)),
+            (Table::Unknown, _, _) => {}
+        }
+
+        Ok(())
+    }
+
+    pub fn option(&self, name: &str) -> Option<&ConfigValue> {
+        self.options
+            .iter()
+            .find(|(option, _)| option == name)
+            .map(|(_, value)| value)
+    }
+}
+
+impl ConfigValue {
+    // Formats value the way `set -o` lists it, so that it
+    // can be compared against value of an option
+    pub fn to_option_value(&self) -> String {
+        match self {
+            ConfigValue::String(value) => value.clone(),
+            ConfigValue::Integer(value) => value.to_string(),
+            ConfigValue::Boolean(true) => "on".to_string(),
+            ConfigValue::Boolean(false) => "off".to_string(),
+        }
+    }
+}
+
+fn parse_table_header(line: &str) -> Result<Table, String> {
+    let Some((name, rest)) = line[1..].split_once(']') else {
+        return Err(format!("unterminated table header: {}", line));
+    };
+
+    if !is_blank(rest) {
+        return Err(format!(
+            "unexpected text after table header: {}",
+            rest.trim()
+        ));
+    }
+
+    Ok(match name.trim() {
+        "options" => Table::Options,
+        "keybindings" => Table::KeyBindings,
+        _ => Table::Unknown,
+    })
+}
+
+fn parse_key_value(line: &str) -> Result<(String, ConfigValue), String> {
+    let (key, rest) = match line.chars().next() {
+        Some(quote @ ('"' | '\'')) => parse_string(line, quote)?,
+        _ => {
+            let end = line
+                .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_' || ch == '-'))
+                .unwrap_or(line.len());
+            (line[..end].to_string(), &line[end..])
+        }
+    };
+
+    if key.is_empty() {
+        return Err(format!("expected a key: {}", line));
+    }
+
+    let Some(rest) = rest.trim_start().strip_prefix('=') else {
+        return Err(format!("expected `=` after key: {}", key));
+    };
+
+    let rest = rest.trim_start();
+    let (value, rest) = match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => {
+            let (value, rest) = parse_string(rest, quote)?;
+            (ConfigValue::String(value), rest)
+        }
+        _ => {
+            let end = rest
+                .find(|ch: char| ch.is_whitespace() || ch == '#')
+                .unwrap_or(rest.len());
+            let value = match &rest[..end] {
+                "true" => ConfigValue::Boolean(true),
+                "false" => ConfigValue::Boolean(false),
+                number => number
+                    .replace('_', "")
+                    .parse::<i64>()
+                    .map(ConfigValue::Integer)
+                    .map_err(|_| format!("{}: invalid value: {}", key, number))?,
+            };
+            (value, &rest[end..])
+        }
+    };
+
+    if !is_blank(rest) {
+        return Err(format!("unexpected text after value: {}", rest.trim()));
+    }
+
+    Ok((key, value))
+}
+
+// Parses string starting at beginning of text, returning it
+// along with the remaining text. Escapes are only recognised
+// in double quoted strings, same as TOML
+fn parse_string(text: &str, quote: char) -> Result<(String, &str), String> {
+    let mut string = String::new();
+    let mut chars = text.char_indices().skip(1);
+
+    while let Some((idx, ch)) = chars.next() {
+        match ch {
+            ch if ch == quote => return Ok((string, &text[idx + 1..])),
+            '\\' if quote == '"' => {
+                let escaped = match chars.next() {
+                    Some((_, 'n')) => '\n',
+                    Some((_, 't')) => '\t',
+                    Some((_, 'r')) => '\r',
+                    Some((_, 'e')) => '\x1b',
+                    Some((_, ch @ ('"' | '\\'))) => ch,
+                    _ => return Err(format!("invalid escape in string: {}", text)),
+                };
+                string.push(escaped);
+            }
+            ch => string.push(ch),
+        }
+    }
+
+    Err(format!("unterminated string: {}", text))
+}
+
+// Whether only whitespace or a comment is left
+fn is_blank(text: &str) -> bool {
+    let text = text.trim_start();
+    text.is_empty() || text.starts_with('#')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, ConfigValue};
+
+    #[test]
+    fn test_parsing_of_config() {
+        let (config, problems) = Config::parse(
+            "# comment\nprompt = \"dss \\\"% \" # trailing comment\n\n[options]\nextendedglob = true\ncmdtimeout = 10\nextendedglob = false\n\n[keybindings]\nControl-a = \"end-of-line\"\n'\\C-x\\C-r' = 'reverse-search-history'\n\n[plugins]\nanything = \"goes\"\n",
+        );
+
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(config.prompt, Some("dss \"% ".into()));
+        assert_eq!(
+            config.options,
+            vec![
+                ("cmdtimeout".into(), ConfigValue::Integer(10)),
+                ("extendedglob".into(), ConfigValue::Boolean(false)),
+            ]
+        );
+        assert_eq!(
+            config.key_bindings,
+            vec![
+                ("Control-a".into(), "end-of-line".into()),
+                ("\\C-x\\C-r".into(), "reverse-search-history".into()),
+            ]
+        );
+        assert_eq!(
+            config
+                .option("cmdtimeout")
+                .map(ConfigValue::to_option_value),
+            Some("10".into())
+        );
+    }
+
+    #[test]
+    fn test_parsing_reports_problems() {
+        let (config, problems) = Config::parse(
+            "prompt = 5\ncolor = \"red\"\n[options\n[options]\ndotglob = \"yes\"\nnullglob = maybe\nfailglob true\nprogress = 2 3\n[keybindings]\nControl-a = \"unterminated\n",
+        );
+
+        assert_eq!(problems.len(), 8);
+        assert!(problems[0].starts_with("line 1: "));
+        assert_eq!(config, Config::default());
+    }
+}
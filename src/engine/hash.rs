@@ -0,0 +1,165 @@
+use std::{
+    collections::HashMap,
+    os::unix::prelude::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+// Full paths of commands found in PATH, so that PATH is searched only
+// the first time a command runs. Shown and cleared by `hash`
+#[derive(Clone, Debug, Default)]
+pub struct CommandHash {
+    // PATH commands were found in, changing it drops all of them
+    path_var: String,
+    commands: HashMap<String, HashedCommand>,
+}
+
+#[derive(Clone, Debug)]
+struct HashedCommand {
+    path: PathBuf,
+    // Times command was run from the cache
+    hits: usize,
+}
+
+impl CommandHash {
+    // Cache for commands of PATH shell starts with
+    pub fn new(path_var: &str) -> Self {
+        Self {
+            path_var: path_var.to_string(),
+            commands: HashMap::new(),
+        }
+    }
+
+    pub fn lookup(&mut self, name: &str, path_var: &str) -> Option<PathBuf> {
+        self.forget_if_changed(path_var);
+
+        // Command removed since it was found is searched for again
+        if let Some(command) = self.commands.get_mut(name) {
+            if is_executable(&command.path) {
+                command.hits += 1;
+                return Some(command.path.clone());
+            }
+        }
+
+        let path = self.remember(name, path_var)?;
+        if let Some(command) = self.commands.get_mut(name) {
+            command.hits += 1;
+        }
+        Some(path)
+    }
+
+    // Searches PATH for command without running it, for `hash name`
+    pub fn remember(&mut self, name: &str, path_var: &str) -> Option<PathBuf> {
+        self.forget_if_changed(path_var);
+
+        let Some(path) = search_path(name, path_var) else {
+            self.commands.remove(name);
+            return None;
+        };
+
+        self.commands.insert(
+            name.to_string(),
+            HashedCommand {
+                path: path.clone(),
+                hits: 0,
+            },
+        );
+        Some(path)
+    }
+
+    // Path command is remembered at, if it is still there. Not
+    // counted as a hit, for `type` which only reports it
+    pub fn hashed(&mut self, name: &str, path_var: &str) -> Option<PathBuf> {
+        self.forget_if_changed(path_var);
+
+        let command = self.commands.get(name)?;
+        is_executable(&command.path).then(|| command.path.clone())
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    // Formats cache in the way `hash` lists it
+    pub fn list(&self) -> String {
+        let mut names: Vec<&String> = self.commands.keys().collect();
+        names.sort();
+
+        let mut list = String::from("hits\tcommand\n");
+        for name in names {
+            let command = &self.commands[name];
+            list.push_str(&format!(
+                "{:>4}\t{}\n",
+                command.hits,
+                command.path.display()
+            ));
+        }
+        list
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    // Commands found in any other PATH are dropped
+    pub fn forget_if_changed(&mut self, path_var: &str) {
+        if self.path_var != path_var {
+            self.path_var = path_var.to_string();
+            self.clear();
+        }
+    }
+}
+
+// First executable file named so in directories of PATH, an
+// empty entry in it being current directory
+pub fn search_path(name: &str, path_var: &str) -> Option<PathBuf> {
+    path_var
+        .split(':')
+        .map(|dir| if dir.is_empty() { "." } else { dir })
+        .map(|dir| Path::new(dir).join(name))
+        .find(|path| is_executable(path))
+}
+
+pub fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::prelude::PermissionsExt, path::PathBuf};
+
+    use super::CommandHash;
+
+    #[test]
+    fn test_command_hash() {
+        let dir = std::env::temp_dir().join(format!("dss_hash_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("bin")).unwrap();
+        fs::write(dir.join("bin/tool"), "").unwrap();
+        fs::write(dir.join("notes"), "").unwrap();
+        fs::set_permissions(dir.join("bin/tool"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        let path_var = format!("{}:{}/bin", dir.display(), dir.display());
+        let mut hash = CommandHash::default();
+
+        // Files which are not executable are skipped
+        assert_eq!(hash.lookup("notes", &path_var), None);
+        assert_eq!(hash.lookup("tool", &path_var), Some(dir.join("bin/tool")));
+        hash.lookup("tool", &path_var);
+        assert_eq!(
+            hash.list(),
+            format!("hits\tcommand\n   2\t{}\n", dir.join("bin/tool").display())
+        );
+        assert_eq!(hash.hashed("tool", &path_var), Some(dir.join("bin/tool")));
+        assert!(hash.list().contains("   2\t"));
+
+        // Changing PATH drops what was found in the old one
+        assert_eq!(hash.remember("tool", "/no/such/dir"), None::<PathBuf>);
+        assert!(hash.is_empty());
+
+        hash.lookup("tool", &path_var);
+        fs::remove_file(dir.join("bin/tool")).unwrap();
+        assert_eq!(hash.lookup("tool", &path_var), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
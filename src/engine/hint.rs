@@ -0,0 +1,89 @@
+use crate::frontend::truncate_to_width;
+
+// Failures in a row after which a hint is shown
+const HINT_THRESHOLD: usize = 2;
+
+// Hints longer than this are cut, so that they fit on one line
+const HINT_WIDTH: usize = 80;
+
+// Consecutive failures of a foreground command, for `failhint`
+#[derive(Clone, Debug, Default)]
+pub struct FailureStreak {
+    command: String,
+    count: usize,
+    // Last line command wrote to stderr in any of the failures
+    last_line: Option<String>,
+    // Set for every failure past the threshold, until hint is shown
+    pending: bool,
+}
+
+impl FailureStreak {
+    // Any command succeeding ends the streak
+    pub fn record(&mut self, command: &str, successful: bool, stderr_line: Option<String>) {
+        if successful || self.command != command {
+            *self = Self {
+                command: command.to_string(),
+                ..Self::default()
+            };
+        }
+
+        if successful {
+            return;
+        }
+
+        self.count += 1;
+        self.last_line = stderr_line.or(self.last_line.take());
+        self.pending = self.count >= HINT_THRESHOLD;
+    }
+
+    // Hint to show above next prompt, if last failure asks for one
+    pub fn take_hint(&mut self) -> Option<String> {
+        if !std::mem::take(&mut self.pending) {
+            return None;
+        }
+
+        let hint = match &self.last_line {
+            Some(line) => format!(
+                "hint: {} failed {} times in a row: {}",
+                self.command, self.count, line
+            ),
+            None => format!(
+                "hint: {} failed {} times in a row",
+                self.command, self.count
+            ),
+        };
+
+        Some(truncate_to_width(&hint, HINT_WIDTH))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FailureStreak;
+
+    #[test]
+    fn test_hints_for_failure_streaks() {
+        let mut streak = FailureStreak::default();
+
+        streak.record("make", false, Some("make: *** No targets.  Stop.".into()));
+        assert_eq!(streak.take_hint(), None);
+
+        streak.record("make", false, None);
+        assert_eq!(
+            streak.take_hint(),
+            Some("hint: make failed 2 times in a row: make: *** No targets.  Stop.".into())
+        );
+        assert_eq!(streak.take_hint(), None);
+
+        streak.record("ls", false, Some("x".repeat(100)));
+        streak.record("ls", false, None);
+        let hint = streak.take_hint().unwrap();
+        assert_eq!(hint.chars().count(), 80);
+        assert!(hint.starts_with("hint: ls failed 2 times in a row: xxx"));
+        assert!(hint.ends_with("x..."));
+
+        streak.record("ls", true, None);
+        streak.record("ls", false, None);
+        assert_eq!(streak.take_hint(), None);
+    }
+}
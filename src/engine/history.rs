@@ -0,0 +1,490 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::command::lexer::Lexer;
+
+// Commands entered interactively, numbered from 1 in the order
+// they were entered, which is how `fc` and `history` refer to them.
+// Entries of earlier sessions are loaded from history file first
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+}
+
+// Line after history expansion
+#[derive(Debug, PartialEq)]
+pub struct Expansion {
+    pub line: String,
+    // Set by `:p`, line is to be shown instead of being run
+    pub print_only: bool,
+}
+
+impl History {
+    // Trailing newline of the command as read is not kept
+    pub fn push(&mut self, command: &str) {
+        let command = command.trim_end();
+        if !command.is_empty() {
+            self.entries.push(command.to_string());
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    // Missing file is same as an empty one
+    pub fn load(&mut self, path: &Path) {
+        if let Ok(contents) = fs::read_to_string(path) {
+            self.entries.extend(contents.lines().map(unescape));
+        }
+    }
+
+    // One entry per line, backslashes and newlines of multiline
+    // commands being escaped
+    pub fn append_to_file(path: &Path, command: &str) -> io::Result<()> {
+        let command = command.trim_end();
+        if command.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let escaped = command.replace('\\', "\\\\").replace('\n', "\\n");
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(format!("{}\n", escaped).as_bytes())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, number: usize) -> Option<&str> {
+        self.entries.get(number.checked_sub(1)?).map(String::as_str)
+    }
+
+    pub fn replace_last(&mut self, command: &str) {
+        if let Some(last) = self.entries.last_mut() {
+            *last = command.trim_end().to_string();
+        }
+    }
+
+    // Finds number of entry an operand of `fc` refers to, looking
+    // only at entries up to `end`. Operand is one of:
+    // - positive number: entry with that number
+    // - negative number: offset back from `end`, `-1` being `end`
+    // - anything else: most recent entry starting with it
+    pub fn resolve(&self, operand: &str, end: usize) -> Option<usize> {
+        let end = end.min(self.len());
+
+        let number = match operand.parse::<i64>() {
+            Ok(number) if number > 0 => usize::try_from(number).ok()?,
+            Ok(number) if number < 0 => {
+                (end + 1).checked_sub(usize::try_from(number.unsigned_abs()).ok()?)?
+            }
+            Ok(_) => return None,
+            Err(_) => {
+                return self.entries[..end]
+                    .iter()
+                    .rposition(|entry| entry.starts_with(operand))
+                    .map(|idx| idx + 1)
+            }
+        };
+
+        (1..=end).contains(&number).then_some(number)
+    }
+
+    // Same as `resolve`, but numbers outside of entries up to `end`
+    // refer to the oldest or the most recent one, as in `fc -l`
+    pub fn resolve_clamped(&self, operand: &str, end: usize) -> Option<usize> {
+        let end = end.min(self.len());
+        if end == 0 {
+            return None;
+        }
+
+        match operand.parse::<i64>() {
+            Ok(number) => {
+                Some(
+                    self.resolve(operand, end)
+                        .unwrap_or(if number > 0 { end } else { 1 }),
+                )
+            }
+            Err(_) => self.resolve(operand, end),
+        }
+    }
+
+    // Entries from `first` to `last` both included, going
+    // backwards if `first` is the later one
+    pub fn range(&self, first: usize, last: usize) -> Vec<(usize, &str)> {
+        let numbers: Vec<usize> = if first <= last {
+            (first..=last).collect()
+        } else {
+            (last..=first).rev().collect()
+        };
+
+        numbers
+            .into_iter()
+            .filter_map(|number| Some((number, self.get(number)?)))
+            .collect()
+    }
+
+    // History Expansion Spec, same as bash:
+    // - URL: https://www.gnu.org/software/bash/manual/html_node/History-Interaction.html
+    //
+    // Expands events in line, e.g. `!!`, `!3`, `!-2` or `!cmd`, along
+    // with their word designators and `:p`. Single quotes and backslash
+    // stop expansion. Returns None if line has no events in it
+    pub fn expand(&self, line: &str) -> Result<Option<Expansion>, String> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut expansion = Expansion {
+            line: String::new(),
+            print_only: false,
+        };
+        let mut expanded = false;
+        let mut in_single_quotes = false;
+        let mut in_double_quotes = false;
+
+        let mut idx = 0;
+        while idx < chars.len() {
+            let ch = chars[idx];
+            idx += 1;
+
+            match ch {
+                '\'' if !in_double_quotes => in_single_quotes = !in_single_quotes,
+                '"' if !in_single_quotes => in_double_quotes = !in_double_quotes,
+                // Escaped chars are left for the lexer to unquote
+                '\\' if !in_single_quotes => {
+                    expansion.line.push(ch);
+                    if let Some(&next_ch) = chars.get(idx) {
+                        expansion.line.push(next_ch);
+                        idx += 1;
+                    }
+                    continue;
+                }
+                '!' if !in_single_quotes && starts_event(&chars[..idx - 1], chars.get(idx)) => {
+                    let (entry, len) = self.event(&chars[idx..])?;
+                    idx += len;
+
+                    let (words, len) = select_words(entry, &chars[idx..])?;
+                    idx += len;
+
+                    while chars[idx..].starts_with(&[':', 'p']) {
+                        expansion.print_only = true;
+                        idx += 2;
+                    }
+
+                    if chars.get(idx) == Some(&':')
+                        && chars
+                            .get(idx + 1)
+                            .is_some_and(|ch| ch.is_ascii_alphabetic())
+                    {
+                        return Err(format!(
+                            ":{}: unrecognized history modifier",
+                            chars[idx + 1]
+                        ));
+                    }
+
+                    expansion.line.push_str(&words);
+                    expanded = true;
+                    continue;
+                }
+                _ => {}
+            }
+
+            expansion.line.push(ch);
+        }
+
+        Ok(expanded.then_some(expansion))
+    }
+
+    // Entry which event refers to, along with count of chars of event
+    // designator. Chars are the ones after `!`
+    fn event(&self, chars: &[char]) -> Result<(&str, usize), String> {
+        let numeric_len = match chars.first() {
+            Some('-') => 1 + count_digits(&chars[1..]),
+            _ => count_digits(chars),
+        };
+
+        let (spec, len) = match chars.first() {
+            Some('!') => ("-1".to_string(), 1),
+            // Word designators without event, e.g. `!$`, refer to
+            // the previous command
+            Some(':' | '^' | '$' | '*') => ("-1".to_string(), 0),
+            _ if numeric_len > 0 && chars[numeric_len - 1].is_ascii_digit() => {
+                (chars[..numeric_len].iter().collect(), numeric_len)
+            }
+            _ => {
+                let len = chars
+                    .iter()
+                    .position(|ch| ch.is_whitespace() || ":;&|()<>\"'".contains(*ch))
+                    .unwrap_or(chars.len());
+                (chars[..len].iter().collect(), len)
+            }
+        };
+
+        self.resolve(&spec, self.len())
+            .and_then(|number| self.get(number))
+            .map(|entry| (entry, len))
+            .ok_or_else(|| {
+                let event: String = chars[..len.max(1)].iter().collect();
+                format!("!{}: event not found", event)
+            })
+    }
+}
+
+// `!` is kept as it is when followed by blanks, `=` or `(`, and
+// in `$!` and `[!`, which are not events
+fn starts_event(before: &[char], next: Option<&char>) -> bool {
+    if matches!(before.last(), Some('$' | '[')) {
+        return false;
+    }
+
+    next.is_some_and(|next| !next.is_whitespace() && !"=()\";|&<>".contains(*next))
+}
+
+// Word Designators: `:n`, `:x-y`, `:x-`, `:-y`, `:x*`, `:*`, `:^` and
+// `:$`, words being numbered from 0. Colon is optional before `^`, `$`
+// and `*`. `x-` leaves out the last word, same as bash. Returns
+// selected words along with count of chars of designator
+fn select_words(entry: &str, chars: &[char]) -> Result<(String, usize), String> {
+    let mut idx = match chars {
+        [':', next, ..] if next.is_ascii_digit() || "^$*-".contains(*next) => 1,
+        [next, ..] if "^$*".contains(*next) => 0,
+        _ => return Ok((entry.to_string(), 0)),
+    };
+
+    let words = split_words(entry);
+    let last = words.len().saturating_sub(1);
+
+    let (first, end, may_be_empty) = if chars[idx] == '*' {
+        idx += 1;
+        (1, last, true)
+    } else {
+        let first = parse_word_number(chars, &mut idx, last);
+        match (first, chars.get(idx)) {
+            (Some(first), Some('*')) => {
+                idx += 1;
+                (first, last, true)
+            }
+            (first, Some('-')) => {
+                idx += 1;
+                let end = parse_word_number(chars, &mut idx, last).or(last.checked_sub(1));
+                (first.unwrap_or(0), end.unwrap_or(0), false)
+            }
+            (Some(first), _) => (first, first, false),
+            (None, _) => (1, 0, false),
+        }
+    };
+
+    if may_be_empty && first == last + 1 {
+        return Ok((String::new(), idx));
+    }
+
+    if first > end || end > last {
+        let designator: String = chars[..idx].iter().collect();
+        return Err(format!("{}: bad word specifier", designator));
+    }
+
+    Ok((words[first..=end].join(" "), idx))
+}
+
+// Number of word given by `^`, `$` or digits at idx, moving past it
+fn parse_word_number(chars: &[char], idx: &mut usize, last: usize) -> Option<usize> {
+    let number = match chars.get(*idx)? {
+        '^' => 1,
+        '$' => last,
+        _ => {
+            let len = count_digits(&chars[*idx..]);
+            let digits: String = chars[*idx..*idx + len].iter().collect();
+            *idx += len;
+            return digits.parse().ok();
+        }
+    };
+
+    *idx += 1;
+    Some(number)
+}
+
+fn count_digits(chars: &[char]) -> usize {
+    chars.iter().take_while(|ch| ch.is_ascii_digit()).count()
+}
+
+// Entry is split into words same as the lexer does, so
+// that quoted words and operators count as one word each
+fn split_words(entry: &str) -> Vec<String> {
+    match Lexer::new().scan(&format!("{}\n", entry)) {
+        Ok(tokens) => tokens.into_iter().map(|token| token.lexeme).collect(),
+        Err(_) => entry.split_whitespace().map(String::from).collect(),
+    }
+}
+
+fn unescape(line: &str) -> String {
+    let mut command = String::new();
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                command.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                command.push('\\');
+                chars.next();
+            }
+            _ => command.push(ch),
+        }
+    }
+    command
+}
+
+// Applies `old=new` of `fc -s`, replacing the first occurrence of old
+pub fn substitute(command: &str, replacement: &str) -> Option<String> {
+    let (old, new) = replacement.split_once('=')?;
+    if old.is_empty() {
+        return None;
+    }
+
+    Some(command.replacen(old, new, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{substitute, Expansion, History};
+
+    fn history() -> History {
+        let mut history = History::default();
+        for command in ["ls -l\n", "  \n", "echo one\n", "echo two", "make test\n"] {
+            history.push(command);
+        }
+        history
+    }
+
+    #[test]
+    fn test_resolving_history_operands() {
+        let history = history();
+        assert_eq!(history.len(), 4);
+
+        assert_eq!(history.resolve("2", 4), Some(2));
+        assert_eq!(history.resolve("-1", 4), Some(4));
+        assert_eq!(history.resolve("-4", 4), Some(1));
+        assert_eq!(history.resolve("-1", 3), Some(3));
+        assert_eq!(history.resolve("echo", 4), Some(3));
+        assert_eq!(history.resolve("ls", 4), Some(1));
+
+        assert_eq!(history.resolve("0", 4), None);
+        assert_eq!(history.resolve("5", 4), None);
+        assert_eq!(history.resolve("4", 3), None);
+        assert_eq!(history.resolve("-5", 4), None);
+        assert_eq!(history.resolve("cargo", 4), None);
+        assert_eq!(history.resolve("make", 3), None);
+
+        assert_eq!(history.resolve_clamped("10", 4), Some(4));
+        assert_eq!(history.resolve_clamped("-10", 4), Some(1));
+        assert_eq!(history.resolve_clamped("0", 4), Some(1));
+        assert_eq!(history.resolve_clamped("echo", 4), Some(3));
+        assert_eq!(history.resolve_clamped("cargo", 4), None);
+        assert_eq!(History::default().resolve_clamped("1", 4), None);
+    }
+
+    #[test]
+    fn test_history_file() {
+        let path = std::env::temp_dir().join(format!("dss_history_test_{}", std::process::id()));
+        for command in ["ls -l\n", "for x in a b\ndo echo $x\ndone\n", "echo \\n\n"] {
+            History::append_to_file(&path, command).unwrap();
+        }
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "ls -l\nfor x in a b\\ndo echo $x\\ndone\necho \\\\n\n"
+        );
+
+        let mut history = History::default();
+        history.load(&path);
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.get(2), Some("for x in a b\ndo echo $x\ndone"));
+        assert_eq!(history.get(3), Some("echo \\n"));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_history_ranges() {
+        let history = history();
+        assert_eq!(history.range(2, 3), vec![(2, "echo one"), (3, "echo two")]);
+        assert_eq!(history.range(4, 3), vec![(4, "make test"), (3, "echo two")]);
+        assert_eq!(history.range(3, 7), vec![(3, "echo two"), (4, "make test")]);
+    }
+
+    #[test]
+    fn test_history_expansion() {
+        let mut history = history();
+        history.push("cp 'a b' src/ && ls");
+
+        let expand = |line: &str| {
+            history
+                .expand(line)
+                .map(|expansion| expansion.map(|expansion| expansion.line))
+        };
+
+        assert_eq!(expand("echo a"), Ok(None));
+        assert_eq!(expand("!!"), Ok(Some("cp 'a b' src/ && ls".into())));
+        assert_eq!(expand("!1 -a"), Ok(Some("ls -l -a".into())));
+        assert_eq!(expand("!-3x"), Ok(Some("echo twox".into())));
+        assert_eq!(expand("!ec;!ma"), Ok(Some("echo two;make test".into())));
+
+        assert_eq!(
+            expand("echo !:0 !^ !$"),
+            Ok(Some("echo cp 'a b' ls".into()))
+        );
+        assert_eq!(expand("echo !!:2-$"), Ok(Some("echo src/ && ls".into())));
+        assert_eq!(expand("echo !!:2-"), Ok(Some("echo src/ &&".into())));
+        assert_eq!(expand("echo !:-1"), Ok(Some("echo cp 'a b'".into())));
+        assert_eq!(expand("echo !ls:*"), Ok(Some("echo -l".into())));
+        assert_eq!(expand("echo !ls:1*"), Ok(Some("echo -l".into())));
+        assert_eq!(expand("echo !ls:2*"), Ok(Some("echo ".into())));
+
+        // Neither quoted nor special `!`s are events
+        assert_eq!(expand("echo '!!' \\!! ! != $! [!a] !("), Ok(None));
+        assert_eq!(
+            expand("echo \"!!\""),
+            Ok(Some("echo \"cp 'a b' src/ && ls\"".into()))
+        );
+
+        assert_eq!(
+            history.expand("!make:p"),
+            Ok(Some(Expansion {
+                line: "make test".into(),
+                print_only: true,
+            }))
+        );
+
+        assert_eq!(expand("!cargo"), Err("!cargo: event not found".into()));
+        assert_eq!(expand("!9"), Err("!9: event not found".into()));
+        assert_eq!(expand("!!:9"), Err(":9: bad word specifier".into()));
+        assert_eq!(expand("!!:3-1"), Err(":3-1: bad word specifier".into()));
+        assert_eq!(
+            expand("!!:s/a/b/"),
+            Err(":s: unrecognized history modifier".into())
+        );
+        assert_eq!(
+            History::default().expand("!!"),
+            Err("!!: event not found".into())
+        );
+    }
+
+    #[test]
+    fn test_history_substitution() {
+        assert_eq!(substitute("echo a a", "a=b"), Some("echo b a".into()));
+        assert_eq!(substitute("echo a", "a="), Some("echo ".into()));
+        assert_eq!(substitute("echo a", "x=y"), Some("echo a".into()));
+        assert_eq!(substitute("echo a", "=y"), None);
+        assert_eq!(substitute("echo a", "ay"), None);
+    }
+}
@@ -0,0 +1,135 @@
+// Sections of shell state printed by `inspect`, each as a
+// title line followed by its entries indented under it
+
+use std::{fs, os::unix::prelude::RawFd, path::Path};
+
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+
+use super::{jobs::Jobs, options::Options, trap::Traps, variables::Variables};
+
+pub const SECTIONS: [&str; 5] = ["jobs", "variables", "options", "traps", "fds"];
+
+fn section(title: &str, entries: &[String]) -> String {
+    let mut section = format!("{}:\n", title);
+    for entry in entries {
+        section.push_str(&format!("  {}\n", entry));
+    }
+    section
+}
+
+pub fn jobs(jobs: &Jobs) -> String {
+    let entries: Vec<String> = jobs
+        .iter()
+        .map(|job| {
+            let mut entry = format!(
+                "[{}]  pgid {}  {:<8}{}",
+                job.id,
+                job.pgid,
+                job.state.name(),
+                job.command
+            );
+            if let Some(log) = &job.log {
+                entry.push_str(&format!("  (log: {})", log.display()));
+            }
+            entry
+        })
+        .collect();
+
+    section("jobs", &entries)
+}
+
+pub fn variables(variables: &Variables) -> String {
+    let entries: Vec<String> = variables
+        .names()
+        .into_iter()
+        .filter_map(|name| variables.declaration(name))
+        .collect();
+
+    section("variables", &entries)
+}
+
+pub fn options(options: &Options) -> String {
+    let entries: Vec<String> = options.list().lines().map(str::to_string).collect();
+    section("options", &entries)
+}
+
+pub fn traps(traps: &Traps) -> String {
+    let entries: Vec<String> = traps
+        .conditions()
+        .into_iter()
+        .filter_map(|condition| traps.definition(condition))
+        .collect();
+
+    section("traps", &entries)
+}
+
+// Open fds of shell with what they refer to
+pub fn fds() -> String {
+    // Directory being read is an fd too, it is closed
+    // by the time links get read so it is left out
+    let mut fds: Vec<RawFd> = match fs::read_dir("/proc/self/fd") {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .collect(),
+        Err(_) => vec![],
+    };
+    fds.sort_unstable();
+
+    let mut entries = vec![];
+    for fd in fds {
+        let Ok(target) = fs::read_link(Path::new("/proc/self/fd").join(fd.to_string())) else {
+            continue;
+        };
+
+        let cloexec = fcntl(fd, FcntlArg::F_GETFD)
+            .is_ok_and(|flags| FdFlag::from_bits_truncate(flags).contains(FdFlag::FD_CLOEXEC));
+        entries.push(format!(
+            "{}  {}{}",
+            fd,
+            target.display(),
+            if cloexec { "  (cloexec)" } else { "" }
+        ));
+    }
+
+    section("fds", &entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use nix::{sys::wait::WaitStatus, unistd::Pid};
+
+    use crate::engine::{
+        jobs::{JobState, Jobs},
+        variables::Variables,
+    };
+
+    use super::{fds, jobs, variables};
+
+    #[test]
+    fn test_inspect_sections() {
+        let pid = Pid::from_raw;
+        let mut table = Jobs::default();
+        table.add(pid(42), &[pid(42)], "sleep 10", None);
+        table.add(
+            pid(43),
+            &[pid(43)],
+            "make",
+            Some(PathBuf::from("/tmp/2.log")),
+        );
+        table.record_status(WaitStatus::Exited(pid(43), 0));
+        assert_eq!(table.get(2).map(|job| job.state), Some(JobState::Done));
+        assert_eq!(
+            jobs(&table),
+            "jobs:\n  [1]  pgid 42  Running sleep 10\n  \
+             [2]  pgid 43  Done    make  (log: /tmp/2.log)\n"
+        );
+        assert_eq!(jobs(&Jobs::default()), "jobs:\n");
+
+        assert_eq!(variables(&Variables::new()), "variables:\n");
+
+        let fds = fds();
+        assert!(fds.starts_with("fds:\n  0  "), "{}", fds);
+    }
+}
@@ -0,0 +1,543 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use nix::{
+    sys::{
+        signal::{kill, killpg, signal, SigHandler, Signal},
+        termios::{tcgetattr, tcsetattr, SetArg, Termios},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::{getpgrp, getpid, isatty, setpgid, tcgetpgrp, tcsetpgrp, Pid},
+};
+
+use crate::interrupt;
+
+use super::session;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    Stopped,
+    Done,
+}
+
+impl JobState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Running => "Running",
+            Self::Stopped => "Stopped",
+            Self::Done => "Done",
+        }
+    }
+}
+
+// One command of the pipeline a job runs
+#[derive(Clone, Debug)]
+pub struct Process {
+    pub pid: Pid,
+    pub state: JobState,
+    // Set once done, 128 + signal number if killed by one
+    pub exit_code: Option<i32>,
+}
+
+// Pipeline started in background with `&`, all of its
+// processes are in a process group of their own
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub id: usize,
+    pub pgid: Pid,
+    pub processes: Vec<Process>,
+    pub command: String,
+    pub state: JobState,
+    // Where stdout and stderr of job go with `joblogs` set
+    pub log: Option<PathBuf>,
+    // Cleared by `disown -h`
+    pub hangup: bool,
+}
+
+impl Job {
+    // Status of a pipeline is that of its last command
+    pub fn exit_code(&self) -> Option<i32> {
+        self.processes.last()?.exit_code
+    }
+
+    // Job is stopped as soon as one of its processes is, and
+    // is done only once all of them are
+    fn update_state(&mut self) {
+        let states = || self.processes.iter().map(|process| process.state);
+        self.state = if states().any(|state| state == JobState::Stopped) {
+            JobState::Stopped
+        } else if states().all(|state| state == JobState::Done) {
+            JobState::Done
+        } else {
+            JobState::Running
+        };
+    }
+}
+
+// Jobs started in this session, keyed by their ids
+#[derive(Clone, Debug, Default)]
+pub struct Jobs {
+    jobs: BTreeMap<usize, Job>,
+    // Processes of disowned jobs, still reaped once they exit
+    disowned: Vec<Pid>,
+}
+
+impl Jobs {
+    // Ids keep growing, one of a finished job is not reused
+    pub fn next_id(&self) -> usize {
+        self.jobs.keys().next_back().map_or(1, |id| id + 1)
+    }
+
+    pub fn add(&mut self, pgid: Pid, pids: &[Pid], command: &str, log: Option<PathBuf>) -> usize {
+        let id = self.next_id();
+        let processes = pids
+            .iter()
+            .map(|pid| Process {
+                pid: *pid,
+                state: JobState::Running,
+                exit_code: None,
+            })
+            .collect();
+
+        self.jobs.insert(
+            id,
+            Job {
+                id,
+                pgid,
+                processes,
+                command: command.to_string(),
+                state: JobState::Running,
+                log,
+                hangup: true,
+            },
+        );
+        id
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.get_mut(&id)
+    }
+
+    // Job pid is a process of
+    pub fn find_by_pid(&self, pid: Pid) -> Option<usize> {
+        self.jobs
+            .values()
+            .find(|job| job.processes.iter().any(|process| process.pid == pid))
+            .map(|job| job.id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.values()
+    }
+
+    // Records what happened to a process of some job, returns
+    // whether the process belonged to one
+    pub fn record_status(&mut self, status: WaitStatus) -> bool {
+        let (pid, state, exit_code) = match status {
+            WaitStatus::Exited(pid, _) | WaitStatus::Signaled(pid, _, _) => {
+                (pid, JobState::Done, exit_code_of(status))
+            }
+            WaitStatus::Stopped(pid, _) => (pid, JobState::Stopped, None),
+            WaitStatus::Continued(pid) => (pid, JobState::Running, None),
+            _ => return false,
+        };
+
+        // Disowned processes are only reaped
+        if let Some(idx) = self.disowned.iter().position(|disowned| *disowned == pid) {
+            if state == JobState::Done {
+                self.disowned.remove(idx);
+            }
+            return true;
+        }
+
+        for job in self.jobs.values_mut() {
+            if let Some(process) = job.processes.iter_mut().find(|process| process.pid == pid) {
+                process.state = state;
+                process.exit_code = exit_code;
+                job.update_state();
+                return true;
+            }
+        }
+        false
+    }
+
+    // Processes still to be reaped, be it of jobs or disowned ones
+    pub fn pids(&self) -> Vec<Pid> {
+        self.jobs
+            .values()
+            .flat_map(|job| &job.processes)
+            .filter(|process| process.state != JobState::Done)
+            .map(|process| process.pid)
+            .chain(self.disowned.iter().copied())
+            .collect()
+    }
+
+    // Job is sent SIGCONT as a whole, via its process group
+    pub fn resume(&mut self, id: usize) -> nix::Result<()> {
+        let Some(job) = self.jobs.get_mut(&id) else {
+            return Ok(());
+        };
+
+        kill(Pid::from_raw(-job.pgid.as_raw()), Signal::SIGCONT)?;
+        for process in &mut job.processes {
+            if process.state == JobState::Stopped {
+                process.state = JobState::Running;
+            }
+        }
+        job.update_state();
+        Ok(())
+    }
+
+    // Blocks till job either stops or is done
+    pub fn wait(&mut self, id: usize) -> nix::Result<()> {
+        loop {
+            let Some(job) = self.jobs.get(&id) else {
+                return Ok(());
+            };
+            if job.state != JobState::Running {
+                return Ok(());
+            }
+
+            let Some(process) = job
+                .processes
+                .iter()
+                .find(|process| process.state == JobState::Running)
+            else {
+                return Ok(());
+            };
+
+            let pid = process.pid;
+            match waitpid(pid, Some(WaitPidFlag::WUNTRACED)) {
+                Ok(status) => {
+                    self.record_status(status);
+                }
+                Err(_) => {
+                    self.record_status(WaitStatus::Exited(pid, 0));
+                }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Job> {
+        self.jobs.remove(&id)
+    }
+
+    // Jobs which are done get reported once, and are then forgotten.
+    // Returns their status lines, along with logs of their output
+    // since these can not be looked up by job anymore.
+    pub fn remove_done(&mut self) -> Vec<String> {
+        let done: Vec<&Job> = self
+            .jobs
+            .values()
+            .filter(|job| job.state == JobState::Done)
+            .collect();
+
+        let mut lines = vec![];
+        for job in &done {
+            let mut line = self.status_line(job);
+            if let Some(log) = &job.log {
+                line.insert_str(line.len() - 1, &format!("  (log: {})", log.display()));
+            }
+            lines.push(line);
+        }
+
+        let ids: Vec<usize> = done.iter().map(|job| job.id).collect();
+        for id in ids {
+            self.jobs.remove(&id);
+        }
+        lines
+    }
+
+    // Job is forgotten, apart from reaping its processes
+    pub fn disown(&mut self, id: usize) {
+        let Some(job) = self.jobs.remove(&id) else {
+            return;
+        };
+
+        self.disowned.extend(
+            job.processes
+                .iter()
+                .filter(|process| process.state != JobState::Done)
+                .map(|process| process.pid),
+        );
+    }
+
+    // Sent on hangup of shell, stopped jobs are continued
+    // too, so that they get to act on it
+    pub fn hang_up(&self) {
+        for job in self.jobs.values() {
+            if !job.hangup || job.state == JobState::Done {
+                continue;
+            }
+
+            let pgid = Pid::from_raw(-job.pgid.as_raw());
+            let _ = kill(pgid, Signal::SIGHUP);
+            if job.state == JobState::Stopped {
+                let _ = kill(pgid, Signal::SIGCONT);
+            }
+        }
+    }
+
+    // Job spec is one of:
+    // - `%N`: job with id N
+    // - `%+`, `%%` or `%`: current job
+    // - `%-`: previous job
+    // - `%string`: most recent job whose command starts with string
+    // - `%?string`: most recent job whose command contains string
+    pub fn resolve(&self, spec: &str) -> Option<usize> {
+        let spec = spec.strip_prefix('%')?;
+        let (current, previous) = self.current_and_previous();
+
+        match spec {
+            "" | "+" | "%" => current,
+            "-" => previous,
+            _ => {
+                if let Ok(id) = spec.parse::<usize>() {
+                    return self.jobs.contains_key(&id).then_some(id);
+                }
+
+                let matches = |job: &&Job| match spec.strip_prefix('?') {
+                    Some(part) => job.command.contains(part),
+                    None => job.command.starts_with(spec),
+                };
+                self.jobs.values().rev().find(matches).map(|job| job.id)
+            }
+        }
+    }
+
+    // Current job, `+` in `jobs` output, is the most recent stopped
+    // job if there is one, else the most recent one. Previous
+    // job, `-`, is picked the same way among the rest.
+    pub fn current_and_previous(&self) -> (Option<usize>, Option<usize>) {
+        let mut ids: Vec<usize> = self
+            .jobs
+            .values()
+            .rev()
+            .filter(|job| job.state == JobState::Stopped)
+            .map(|job| job.id)
+            .collect();
+        ids.extend(
+            self.jobs
+                .values()
+                .rev()
+                .filter(|job| job.state != JobState::Stopped)
+                .map(|job| job.id),
+        );
+
+        (ids.first().copied(), ids.get(1).copied())
+    }
+
+    // Formats job the way `jobs` lists it
+    pub fn status_line(&self, job: &Job) -> String {
+        let (current, previous) = self.current_and_previous();
+        let mark = if current == Some(job.id) {
+            '+'
+        } else if previous == Some(job.id) {
+            '-'
+        } else {
+            ' '
+        };
+        let background = if job.state == JobState::Running {
+            " &"
+        } else {
+            ""
+        };
+
+        format!(
+            "[{}]{}  {:<24}{}{}\n",
+            job.id,
+            mark,
+            job.state.name(),
+            job.command,
+            background
+        )
+    }
+}
+
+// Status a process exited with, 128 + signal number if killed by one
+pub fn exit_code_of(status: WaitStatus) -> Option<i32> {
+    match status {
+        WaitStatus::Exited(_, code) => Some(code),
+        WaitStatus::Signaled(_, signal, _) => Some(128 + signal as i32),
+        _ => None,
+    }
+}
+
+// Logs are named after shell pid too, so that concurrently
+// running shells do not overwrite logs of each other
+pub fn log_path(shell_pid: Pid, id: usize) -> Option<PathBuf> {
+    let file_name = format!("{}.{}.log", shell_pid, id);
+    Some(session::state_dir()?.join("jobs").join(file_name))
+}
+
+// Terminal given to a job brought to foreground, it is taken back
+// along with modes shell had set once job stops or exits, since job
+// could have left terminal in whatever modes it was using
+#[derive(Clone, Debug)]
+pub struct TerminalHandover {
+    modes: Termios,
+}
+
+impl TerminalHandover {
+    // Nothing is handed over if stdin is not a terminal
+    pub fn give(pgid: Pid) -> nix::Result<Option<Self>> {
+        if !isatty(0).unwrap_or(false) {
+            return Ok(None);
+        }
+
+        let modes = tcgetattr(0)?;
+        tcsetpgrp(0, pgid)?;
+        Ok(Some(Self { modes }))
+    }
+
+    // Done by the job itself too right after fork, so that it has
+    // the terminal even if it reads before shell hands it over
+    pub fn claim() -> nix::Result<()> {
+        if !isatty(0).unwrap_or(false) {
+            return Ok(());
+        }
+        set_foreground(getpgrp())
+    }
+
+    pub fn take_back(self) -> nix::Result<()> {
+        set_foreground(getpgrp())?;
+        tcsetattr(0, SetArg::TCSADRAIN, &self.modes)
+    }
+}
+
+// Done by interactive shell before it controls any jobs. Shell started
+// in background waits till it is brought to foreground, stopping along
+// with rest of its group on SIGTTIN, then goes in a group of its own
+// and makes it the foreground one.
+pub fn init_job_control() -> nix::Result<()> {
+    loop {
+        let pgrp = getpgrp();
+        if tcgetpgrp(0)? == pgrp {
+            break;
+        }
+        killpg(pgrp, Signal::SIGTTIN)?;
+    }
+
+    interrupt::ignore_job_control()?;
+
+    // Session leader, e.g. shell of a terminal emulator, is
+    // already leader of its group and cannot change it
+    let pid = getpid();
+    if getpgrp() != pid {
+        setpgid(pid, pid)?;
+    }
+    tcsetpgrp(0, pid)
+}
+
+// Caller is in background at this point, SIGTTOU is ignored
+// so that changing foreground group does not stop it
+fn set_foreground(pgid: Pid) -> nix::Result<()> {
+    let handler = unsafe { signal(Signal::SIGTTOU, SigHandler::SigIgn) }?;
+    let result = tcsetpgrp(0, pgid);
+    unsafe { signal(Signal::SIGTTOU, handler) }?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use nix::{
+        sys::{signal::Signal, wait::WaitStatus},
+        unistd::Pid,
+    };
+
+    use super::{JobState, Jobs};
+
+    #[test]
+    fn test_job_status_lines() {
+        let pid = Pid::from_raw;
+        let mut jobs = Jobs::default();
+        jobs.add(pid(42), &[pid(42)], "sleep 10", None);
+        jobs.add(pid(43), &[pid(43), pid(44)], "yes | head", None);
+        assert_eq!(jobs.next_id(), 3);
+
+        let line = |jobs: &Jobs, id| jobs.status_line(jobs.get(id).unwrap());
+        assert_eq!(line(&jobs, 1), "[1]-  Running                 sleep 10 &\n");
+        assert_eq!(
+            line(&jobs, 2),
+            "[2]+  Running                 yes | head &\n"
+        );
+
+        // Stopped job becomes the current one
+        assert!(jobs.record_status(WaitStatus::Stopped(pid(42), Signal::SIGTSTP)));
+        assert_eq!(line(&jobs, 1), "[1]+  Stopped                 sleep 10\n");
+        assert_eq!(
+            line(&jobs, 2),
+            "[2]-  Running                 yes | head &\n"
+        );
+
+        assert!(jobs.record_status(WaitStatus::Signaled(pid(43), Signal::SIGPIPE, false)));
+        assert_eq!(jobs.get(2).unwrap().state, JobState::Running);
+        assert!(jobs.record_status(WaitStatus::Exited(pid(44), 3)));
+        assert_eq!(line(&jobs, 2), "[2]-  Done                    yes | head\n");
+        assert_eq!(jobs.get(2).unwrap().exit_code(), Some(3));
+        assert_eq!(jobs.get(2).unwrap().processes[0].exit_code, Some(141));
+
+        assert!(!jobs.record_status(WaitStatus::Exited(pid(45), 0)));
+    }
+
+    #[test]
+    fn test_removal_of_done_jobs() {
+        let pid = Pid::from_raw;
+        let mut jobs = Jobs::default();
+        jobs.add(pid(42), &[pid(42)], "sleep 10", None);
+        jobs.add(
+            pid(43),
+            &[pid(43)],
+            "make",
+            Some(PathBuf::from("/tmp/2.log")),
+        );
+        jobs.add(pid(44), &[pid(44)], "sleep 5", None);
+        assert!(jobs.remove_done().is_empty());
+
+        jobs.record_status(WaitStatus::Exited(pid(43), 2));
+        jobs.record_status(WaitStatus::Exited(pid(44), 0));
+        assert_eq!(
+            jobs.remove_done(),
+            [
+                "[2]-  Done                    make  (log: /tmp/2.log)\n",
+                "[3]+  Done                    sleep 5\n"
+            ]
+        );
+        assert_eq!(jobs.iter().map(|job| job.id).collect::<Vec<_>>(), [1]);
+        assert_eq!(jobs.next_id(), 2);
+    }
+
+    #[test]
+    fn test_resolution_of_job_specs() {
+        let pid = Pid::from_raw;
+        let mut jobs = Jobs::default();
+        jobs.add(pid(42), &[pid(42)], "sleep 10", None);
+        jobs.add(pid(43), &[pid(43)], "make all", None);
+        jobs.add(pid(44), &[pid(44)], "sleep 20", None);
+
+        assert_eq!(jobs.resolve("%1"), Some(1));
+        assert_eq!(jobs.resolve("%4"), None);
+        assert_eq!(jobs.resolve("1"), None);
+        for spec in ["%", "%%", "%+"] {
+            assert_eq!(jobs.resolve(spec), Some(3));
+        }
+        assert_eq!(jobs.resolve("%-"), Some(2));
+        assert_eq!(jobs.resolve("%sleep"), Some(3));
+        assert_eq!(jobs.resolve("%?all"), Some(2));
+        assert_eq!(jobs.resolve("%vim"), None);
+
+        jobs.remove(3);
+        assert_eq!(jobs.resolve("%sleep"), Some(1));
+        assert_eq!(jobs.resolve("%-"), Some(1));
+
+        jobs.disown(1);
+        assert_eq!(jobs.resolve("%sleep"), None);
+        assert_eq!(jobs.iter().count(), 1);
+    }
+}
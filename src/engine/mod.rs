@@ -0,0 +1,3437 @@
+pub mod adapter;
+pub mod alias;
+pub mod builtin;
+pub mod capture;
+pub mod condition;
+pub mod config;
+pub mod hash;
+pub mod hint;
+pub mod history;
+pub mod inspect;
+pub mod jobs;
+pub mod options;
+pub mod pipeline;
+pub mod profile;
+pub mod server;
+pub mod session;
+pub mod summary;
+pub mod timer;
+pub mod trap;
+pub mod variables;
+
+use nix::{
+    errno::Errno,
+    fcntl::{fcntl, open, FcntlArg, FdFlag, OFlag},
+    sys::{
+        signal::{kill, signal, SigHandler, Signal},
+        stat::Mode,
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::{
+        chdir, close, dup2, execve, fork, isatty, pipe2, setpgid, ForkResult, Pid, getpid,
+    },
+};
+
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    ffi::CString,
+    fs::File,
+    io::{self, Read, Write},
+    os::unix::prelude::{FromRawFd, OsStrExt, RawFd},
+    path::{Component, Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    command::{
+        arithmetic,
+        expansion::{self, ExpansionContext},
+        glob::GlobOptions,
+        is_unqualified_path,
+        lexer::Lexer,
+        parser::{ExecuteMode, FdSource, OpType, ParseResult, Parser},
+        quote,
+        token::Token,
+        unquote, Assignment, AssignmentValue, Command,
+    },
+    errors::ShellError,
+    frontend::{
+        edit_line, read_line, write_error_to_shell, write_to_stderr, write_to_stdout, Inputrc,
+        Prompt, Spinner,
+    },
+    interrupt,
+};
+
+use alias::Aliases;
+use builtin::{Builtin, Builtins, Io, Origin};
+use capture::{StderrCapture, StderrTee};
+use config::{Config, ConfigValue};
+use hash::CommandHash;
+use hint::FailureStreak;
+use history::History;
+use jobs::{JobState, Jobs, TerminalHandover};
+use options::Options;
+use pipeline::{Pipeline, Stage, Waited};
+use profile::StartupProfile;
+use session::{JobSummary, SessionState};
+use summary::ScriptSummary;
+use timer::PipelineTimer;
+use trap::{Condition, Traps};
+use variables::{SavedVariable, Variables};
+
+// Words handled by parser when they begin a command
+const SHELL_KEYWORDS: [&str; 4] = ["exit", "time", "noglob", "select"];
+
+// Session state is saved at least this often, apart from on exit
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+// Prefix of commands traced with xtrace when PS4 is not set
+const DEFAULT_PS4: &str = "+ ";
+
+// Exit status of commands killed by `cmdtimeout`, same as timeout(1)
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+// Exit status of commands which are not found
+const COMMAND_NOT_FOUND_EXIT_CODE: i32 = 127;
+
+// Fds shell opens for itself, e.g. files of redirections or fds saved
+// while builtins run redirected, are moved at or above this, out of
+// the way of fds scripts use
+const SHELL_FD_MIN: RawFd = 10;
+
+#[derive(Clone, Debug)]
+pub struct Engine {
+    // Exit status of the last command, pipeline or builtin run, `$?`
+    pub last_status: i32,
+    pub variables: Variables,
+    // `$1` onwards, arguments given to script being run
+    pub positional_params: Vec<String>,
+    pub options: Options,
+    // Line editing settings, loaded from inputrc
+    pub inputrc: Inputrc,
+    pub prompt: Prompt,
+    // Set with `--summary`, top level commands of scripts get recorded in it
+    pub summary: Option<ScriptSummary>,
+    // Started as login shell, profile files get sourced before first prompt
+    pub login: bool,
+    // Config as it was last applied, `reload` compares against it
+    config: Config,
+    // Options right after startup files were last loaded, any
+    // difference from them was made during the session
+    loaded_options: Options,
+    // Set when reading commands from terminal
+    interactive: bool,
+    execution_mode: ExecutionMode,
+    // Operations to be done on different `fd`s
+    fds_ops: HashMap<i32, FdOperation>,
+    // Stages of pipeline being run, a single command being one too
+    pipeline: Pipeline,
+    // Number of enclosing loops currently executing
+    loop_depth: usize,
+    // Number of loops still to be broken out of, set by `break`
+    pending_loop_breaks: usize,
+    // Number of enclosing conditions of `if`, `while` and `until`
+    // currently executing, errexit ignores failures in them
+    condition_depth: usize,
+    // Line shown by `:p` of history expansion, editor
+    // starts out with it at next prompt
+    edit_buffer: String,
+    // Status given to `exit`, shell exits with it
+    exit_code: Option<i32>,
+    // Set when a command fails with errexit in interactive
+    // shell, rest of the commands of input are skipped
+    abort_input: bool,
+    // Directories saved by `pushd`, top of the stack is last
+    dirstack: Vec<PathBuf>,
+    // Background jobs started in this session
+    jobs: Jobs,
+    // Commands read from terminal, recorded before they run
+    history: History,
+    // Failures of foreground commands, hinted at with `failhint`
+    failures: FailureStreak,
+    // Builtins registered by application embedding dss
+    builtins: Builtins,
+    // Paths commands were found at in PATH
+    command_hash: CommandHash,
+    // Defined with `alias`, looked up before builtins and PATH
+    aliases: Aliases,
+    // Commands set with `trap`, run when signals are received
+    traps: Traps,
+    // Set by applications embedding dss, nothing gets forked
+    // so only builtins run
+    sandboxed: bool,
+    // Set with `-r`, see `set_restricted`
+    restricted: bool,
+    // Lines of input read before the command being lexed, token
+    // lines are counted from it for `$LINENO`
+    command_start_line: usize,
+    lines_read: usize,
+    // Read ends of `<(command)` pipes, open till command they were
+    // substituted in is done
+    process_substitution_fds: Vec<RawFd>,
+    // Commands of `<(command)` which have not been reaped yet
+    process_substitution_pids: Vec<Pid>,
+    // Last process of the last pipeline started in background, `$!`
+    background_pid: Option<Pid>,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum FdOperation {
+    Set { to: i32 },
+    // Make fd a copy of another fd after all `Set`s
+    // are done, e.g. `|&` makes 2 a copy of 1
+    Duplicate { of: i32 },
+    Close,
+}
+
+// What a command name refers to, see `Engine::find_command`
+#[derive(Clone, Debug)]
+enum CommandKind {
+    Keyword,
+    Alias(String),
+    Builtin,
+    CustomBuiltin,
+    // Hashed ones were found in cache of `hash`
+    File { path: PathBuf, hashed: bool },
+}
+
+#[derive(Copy, Clone, Debug)]
+enum ExecutionMode {
+    Normal,
+    Subshell,
+    Pipeline,
+    Redirect,
+    Background,
+}
+
+impl Engine {
+    // Phases of initialization get timed in profile
+    pub fn new(profile: &mut StartupProfile) -> Self {
+        let variables = profile.measure("environment", Variables::from_environment);
+        let path_var = variables.get_str("PATH").unwrap_or_default();
+        let command_hash = profile.measure("PATH scan", || CommandHash::new(&path_var));
+
+        Self {
+            last_status: 0,
+            variables,
+            positional_params: vec![],
+            options: Options::new(),
+            inputrc: Inputrc::default(),
+            prompt: Prompt::new(),
+            summary: None,
+            login: false,
+            config: Config::default(),
+            loaded_options: Options::new(),
+            interactive: false,
+            execution_mode: ExecutionMode::Normal,
+            fds_ops: HashMap::new(),
+            pipeline: Pipeline::default(),
+            loop_depth: 0,
+            pending_loop_breaks: 0,
+            condition_depth: 0,
+            edit_buffer: String::new(),
+            exit_code: None,
+            abort_input: false,
+            dirstack: vec![],
+            jobs: Jobs::default(),
+            history: History::default(),
+            failures: FailureStreak::default(),
+            builtins: Builtins::with_shell_builtins(),
+            command_hash,
+            aliases: Aliases::default(),
+            traps: Traps::default(),
+            sandboxed: false,
+            restricted: false,
+            command_start_line: 0,
+            lines_read: 0,
+            process_substitution_fds: vec![],
+            process_substitution_pids: vec![],
+            background_pid: None,
+        }
+    }
+
+    // Status the shell should exit with, which is of
+    // the last command if `exit` did not give one
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code.unwrap_or(self.last_status)
+    }
+
+    // Session state of previous session is restored if resume is set
+    pub fn fire_on(&mut self, profile: &mut StartupProfile, resume: bool) -> anyhow::Result<()> {
+        write_to_stdout("Welcome to Dead Simple Shell!\n")?;
+
+        // Commands are read from stdin either way, but only a
+        // terminal makes shell interactive and lets it control jobs
+        self.interactive = isatty(0).unwrap_or(false);
+        if self.interactive {
+            jobs::init_job_control()?;
+        }
+
+        // Startup files are read unrestricted, e.g. to set PATH, same as bash
+        let restricted = self.restricted;
+        self.set_restricted(false);
+
+        // Profile files are only read once, unlike files `reload` reloads
+        if self.login {
+            profile.measure("login profile", || self.source_login_profile())?;
+        } else if self.interactive {
+            profile.measure("ENV file", || self.source_env_file())?;
+        }
+        self.load_startup_files(profile, false)?;
+
+        if resume {
+            self.resume_session()?;
+        }
+        self.set_restricted(restricted);
+
+        // Startup files can set HISTFILE
+        if let Some(path) = self.history_file() {
+            profile.measure("history", || self.history.load(&path));
+        }
+
+        if profile.is_enabled() {
+            write_to_stderr(&profile.report())?;
+        }
+
+        interrupt::install()?;
+
+        let mut last_save = Instant::now();
+        'repl: loop {
+            if last_save.elapsed() >= AUTOSAVE_INTERVAL {
+                self.save_session();
+                last_save = Instant::now();
+            }
+
+            // Background jobs done since last prompt are reported
+            // before it, instead of lingering in the job table
+            self.reap_children();
+            for line in self.jobs.remove_done() {
+                write_to_stderr(&line)?;
+            }
+
+            if let Some(hint) = self.failures.take_hint() {
+                write_to_stderr(&format!("{}\n", hint))?;
+            }
+
+            let mut lexer = Lexer::new();
+            let mut command_str = String::new();
+            while !lexer.complete_processing() {
+                // If we have more than 1 tokens
+                // at this stage, we would have parsed
+                // some last cycle, which means we are
+                // in multiline mode
+                if !lexer.is_empty() {
+                    self.prompt.activate_multiline_prompt();
+                }
+
+                self.prompt.render(self.last_status)?;
+
+                let mut input_str = String::new();
+
+                // Zero bytes read means we hit EOF ( Ctrl-D ), while
+                // Ctrl-C abandons what was typed so far for a fresh prompt
+                let initial = std::mem::take(&mut self.edit_buffer);
+                match edit_line(self.prompt.width(), &initial, &self.inputrc, &mut input_str) {
+                    Ok(0) => break 'repl,
+                    Ok(_) => {}
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                        write_to_stderr("\n")?;
+                        self.prompt.deactivate_multiline_prompt();
+                        self.last_status = 128 + Signal::SIGINT as i32;
+                        continue 'repl;
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+                self.lines_read += 1;
+
+                // Blank lines inside quotes are still a part of them
+                if input_str.trim().is_empty() && lexer.is_empty() {
+                    continue;
+                }
+
+                let Some(input_str) = self.expand_history(input_str)? else {
+                    self.prompt.deactivate_multiline_prompt();
+                    continue 'repl;
+                };
+
+                if lexer.is_empty() {
+                    self.command_start_line = self.lines_read - 1;
+                }
+                // Commands with syntax errors are skipped, rest still run
+                let errors = lexer.scan_recovering(&input_str);
+                let failed = !errors.is_empty();
+                for err in errors {
+                    write_to_stderr(&ShellError::LexError(err).to_string())?;
+                    self.last_status = 1;
+                }
+                if failed && lexer.is_empty() {
+                    self.prompt.deactivate_multiline_prompt();
+                    continue 'repl;
+                }
+                command_str.push_str(&input_str);
+
+                self.prompt.deactivate_multiline_prompt();
+            }
+
+            self.record_history(&command_str);
+
+            interrupt::clear();
+            self.abort_input = false;
+            let break_term_loop = self.parse_and_execute(&lexer.tokens)?;
+            if break_term_loop {
+                break;
+            }
+        }
+
+        // Reading ends on hangup as terminal is gone, with
+        // signal still to be acted upon
+        self.run_pending_traps()?;
+        self.save_session();
+        Ok(())
+    }
+
+    // $HISTFILE, or history in state directory of dss
+    fn history_file(&self) -> Option<PathBuf> {
+        match self.variables.get_str("HISTFILE") {
+            Some(path) if !path.is_empty() => Some(PathBuf::from(path)),
+            _ => Some(session::state_dir()?.join("history")),
+        }
+    }
+
+    // Commands are saved as they are entered, so that
+    // concurrently running shells do not lose any
+    fn record_history(&mut self, command: &str) {
+        self.history.push(command);
+        if let Some(path) = self.history_file() {
+            let _ = History::append_to_file(&path, command);
+        }
+    }
+
+    // History expansion of line read from terminal. Returns None if
+    // line is not to be run, as with `:p` or failed expansions, which
+    // drop the command being read
+    fn expand_history(&mut self, input_str: String) -> anyhow::Result<Option<String>> {
+        match self.history.expand(&input_str) {
+            Ok(None) => Ok(Some(input_str)),
+            // Line is loaded into editor for next prompt, so that it can
+            // be edited before running it, it goes to history as well
+            Ok(Some(expansion)) if expansion.print_only => {
+                write_to_stdout(&expansion.line)?;
+                self.record_history(&expansion.line);
+                self.edit_buffer = expansion.line.trim_end_matches('\n').to_string();
+                Ok(None)
+            }
+            // Expanded line is shown, so that it is clear what runs
+            Ok(Some(expansion)) => {
+                write_to_stderr(&expansion.line)?;
+                Ok(Some(expansion.line))
+            }
+            Err(err) => {
+                write_to_stderr(&format!("dss: {}\n", err))?;
+                Ok(None)
+            }
+        }
+    }
+
+    // Saving is best effort, failing to do so should not
+    // disturb the session, so errors are ignored
+    fn save_session(&mut self) {
+        let Ok(cwd) = std::env::current_dir() else {
+            return;
+        };
+
+        // Only jobs still running are worth remembering
+        self.reap_children();
+        let jobs = self
+            .jobs
+            .iter()
+            .filter(|job| job.state != JobState::Done)
+            .map(|job| JobSummary {
+                pid: job.pgid.as_raw(),
+                command: job.command.clone(),
+            })
+            .collect();
+
+        let state = SessionState {
+            cwd,
+            dirstack: self.dirstack.clone(),
+            jobs,
+        };
+        state.save().ok();
+    }
+
+    fn resume_session(&mut self) -> anyhow::Result<()> {
+        let Some(state) = SessionState::load() else {
+            write_to_stderr("dss: resume: no saved session found\n")?;
+            return Ok(());
+        };
+
+        if let Err(err) = self.change_directory(&state.cwd)? {
+            write_to_stderr(&format!("dss: resume: {}: {}\n", state.cwd.display(), err))?;
+        }
+        self.dirstack = state.dirstack;
+
+        // Jobs can not be adopted by a new shell, only reported
+        if !state.jobs.is_empty() {
+            write_to_stderr("dss: resume: jobs running when previous session ended:\n")?;
+            for job in state.jobs {
+                write_to_stderr(&format!("  [{}] {}\n", job.pid, job.command))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Loads inputrc, config and rc file, in that order, both at
+    // startup and on `reload`. Options changed during session
+    // are kept over changed config values unless forced
+    fn load_startup_files(
+        &mut self,
+        profile: &mut StartupProfile,
+        force: bool,
+    ) -> anyhow::Result<()> {
+        let (inputrc, mut problems) = profile.measure("inputrc", Inputrc::load);
+        self.inputrc = inputrc;
+
+        let (config, config_problems) = profile.measure("config", Config::load);
+        problems.extend(config_problems);
+        problems.extend(self.apply_config(config, force));
+
+        for problem in problems {
+            write_to_stderr(&format!("dss: {}\n", problem))?;
+        }
+
+        profile.measure("rc file", || self.source_rc())?;
+        self.loaded_options = self.options.clone();
+
+        Ok(())
+    }
+
+    // Returns problems found while applying config and conflicts
+    // between options changed in config and in session
+    fn apply_config(&mut self, config: Config, force: bool) -> Vec<String> {
+        let mut problems = vec![];
+
+        self.prompt.set_symbol(config.prompt.as_deref());
+
+        for (key, function) in &config.key_bindings {
+            // Key sequences are written without quotes in config
+            let key = if key.starts_with('\\') {
+                format!("\"{}\"", key)
+            } else {
+                key.clone()
+            };
+
+            if let Err(problem) = self.inputrc.bind(&key, function) {
+                problems.push(format!("config: {}", problem));
+            }
+        }
+
+        for (name, value) in &config.options {
+            // Untouched entries leave options as they are, so
+            // that reload does not undo changes made in session
+            if !force && self.config.option(name) == Some(value) {
+                continue;
+            }
+
+            let current = self.options.value(name);
+            if !force && current != self.loaded_options.value(name) {
+                problems.push(format!(
+                    "reload: {} was changed in this session, keeping {} over {} (use reload -f to apply config)",
+                    name,
+                    current.unwrap_or_default(),
+                    value.to_option_value()
+                ));
+                continue;
+            }
+
+            let result = match value {
+                ConfigValue::Boolean(enable) => self.options.set(name, *enable),
+                value => self
+                    .options
+                    .set(&format!("{}={}", name, value.to_option_value()), true),
+            };
+
+            if let Err(err) = result {
+                let message = err.to_string();
+                let message = message.trim_start_matches("dss: ").trim_end();
+                problems.push(format!("config: {}", message));
+            }
+        }
+
+        self.config = config;
+        problems
+    }
+
+    // Runs commands from $DSS_RC, or from ~/.dssrc if it exists
+    fn source_rc(&mut self) -> anyhow::Result<()> {
+        // File named explicitly is reported if missing, same as with `source`
+        if let Some(path) = self.variables.get_str("DSS_RC").filter(|path| !path.is_empty()) {
+            if let Some(source) = self.builtins.get("source") {
+                source.exec(self, &["source".to_string(), path], &mut Io::default());
+            }
+            return Ok(());
+        }
+
+        let Some(home) = self.variables.get_str("HOME") else {
+            return Ok(());
+        };
+
+        self.source_if_exists(&PathBuf::from(home).join(".dssrc"))
+    }
+
+    // Runs commands from /etc/profile and then ~/.profile, the
+    // ones that exist, same as sh does for login shells
+    fn source_login_profile(&mut self) -> anyhow::Result<()> {
+        self.source_if_exists(Path::new("/etc/profile"))?;
+
+        let Some(home) = self.variables.get_str("HOME") else {
+            return Ok(());
+        };
+        self.source_if_exists(&PathBuf::from(home).join(".profile"))
+    }
+
+    // Runs commands from file $ENV names once expanded, same as sh
+    // does for interactive shells which are not login ones
+    fn source_env_file(&mut self) -> anyhow::Result<()> {
+        let Some(env) = self.variables.get_str("ENV").filter(|env| !env.is_empty()) else {
+            return Ok(());
+        };
+
+        match expansion::expand_assignment_value(&env, self) {
+            Ok(path) => self.source_if_exists(Path::new(&path)),
+            Err(err) => write_to_stderr(&format!("dss: ENV: {}\n", err)),
+        }
+    }
+
+    fn source_if_exists(&mut self, path: &Path) -> anyhow::Result<()> {
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return Ok(());
+        };
+
+        self.source(path, &contents, &[])
+    }
+
+    // Runs commands of file in current shell, arguments given replace
+    // positional parameters till it is done. `exit` in file only stops
+    // running the file.
+    fn source(&mut self, path: &Path, contents: &str, args: &[String]) -> anyhow::Result<()> {
+        let positional_params = (!args.is_empty())
+            .then(|| std::mem::replace(&mut self.positional_params, args.to_vec()));
+        let result = self.execute_script(path, contents);
+        if let Some(positional_params) = positional_params {
+            self.positional_params = positional_params;
+        }
+
+        if let Some(exit_code) = self.exit_code.take() {
+            self.last_status = exit_code;
+        }
+        result
+    }
+
+    // For applications embedding dss, builtins are to be
+    // registered before commands are run
+    pub fn register_builtin(&mut self, name: &str, builtin: impl Builtin + 'static) {
+        self.builtins.register(name, Origin::Application, builtin);
+    }
+
+    // External commands, subshells and command substitutions fail
+    // afterwards, leaving builtins and registered builtins to run
+    pub fn enable_sandbox(&mut self) {
+        self.sandboxed = true;
+    }
+
+    // Restricted mode, for constrained accounts: directory can not be
+    // changed, nor can variables naming what gets run, e.g. PATH.
+    // Commands can not be run by path and output not redirected.
+    pub fn set_restricted(&mut self, restricted: bool) {
+        self.restricted = restricted;
+        self.variables.set_restricted(restricted);
+    }
+
+    // Reports what restricted mode forbids, returning whether it is
+    fn forbidden_in_restricted(&mut self, what: &str) -> anyhow::Result<bool> {
+        if !self.restricted {
+            return Ok(false);
+        }
+
+        write_to_stderr(&ShellError::Restricted(what.to_string()).to_string())?;
+        self.last_status = 1;
+        Ok(true)
+    }
+
+    // Runs commands in string same as a script, for
+    // applications embedding dss
+    pub fn run_str(&mut self, commands: &str) -> anyhow::Result<()> {
+        self.execute_script(Path::new("dss"), commands)
+    }
+
+    // Runs commands of a script read from path, without any prompts.
+    // Lines are fed same as they are read from terminal, so that
+    // every complete command is run before the next one
+    pub fn execute_script(&mut self, path: &Path, contents: &str) -> anyhow::Result<()> {
+        // Commands of scripts run by a command, e.g. by `fc`,
+        // are part of that command in summary
+        let mut summary = self.summary.take();
+        let result = self.execute_script_commands(path, contents, &mut summary);
+        self.summary = summary;
+        result
+    }
+
+    fn execute_script_commands(
+        &mut self,
+        path: &Path,
+        contents: &str,
+        summary: &mut Option<ScriptSummary>,
+    ) -> anyhow::Result<()> {
+        let mut lexer = Lexer::new();
+        let mut command_str = String::new();
+        for (idx, line) in contents.lines().enumerate() {
+            // Blank lines inside a command still count for `$LINENO`
+            if lexer.is_empty() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                self.command_start_line = idx;
+            }
+
+            // Commands with syntax errors are skipped, rest still run
+            for err in lexer.scan_recovering(&format!("{}\n", line)) {
+                write_to_stderr(&format!("dss: {}: line {}: {}\n", path.display(), idx + 1, err))?;
+                self.last_status = 1;
+            }
+            if lexer.is_empty() {
+                continue;
+            }
+            command_str.push_str(&format!("{}\n", line));
+
+            if lexer.complete_processing() {
+                let start = Instant::now();
+                let exited = self.parse_and_execute(&lexer.tokens)?;
+
+                if let Some(summary) = summary {
+                    summary.record(
+                        self.command_start_line + 1,
+                        &command_str,
+                        self.exit_code() == 0,
+                        start.elapsed(),
+                    );
+                }
+
+                // `exit` only stops running the script
+                if exited {
+                    return Ok(());
+                }
+                lexer = Lexer::new();
+                command_str.clear();
+            }
+        }
+
+        if !lexer.is_empty() {
+            write_to_stderr(&format!(
+                "dss: {}: syntax error: unexpected end of file\n",
+                path.display()
+            ))?;
+            self.last_status = 1;
+        }
+
+        Ok(())
+    }
+
+    pub fn parse_and_execute(&mut self, tokens: &[Token]) -> anyhow::Result<bool> {
+        // Aliases defined by line only take effect from next one on
+        let tokens = self.aliases.expand(tokens);
+        let mut parser = Parser::new(&tokens);
+
+        let mut parse_results = vec![];
+        loop {
+            match parser.get_command() {
+                Ok(Some(parse_result)) => parse_results.push(parse_result),
+                Ok(None) => break,
+                Err(err) => {
+                    // Report and skip only the erroneous command, along with
+                    // commands chained to it, rest of the input still runs
+                    write_to_stderr(&err.to_string())?;
+                    self.last_status = 1;
+
+                    while parse_results.last().is_some_and(chains_to_next_command) {
+                        parse_results.pop();
+                    }
+
+                    parser.recover();
+                }
+            }
+        }
+
+        self.execute(parse_results)
+    }
+
+    fn execute(&mut self, parse_results: Vec<ParseResult>) -> anyhow::Result<bool> {
+        let mut timer = None;
+        let mut negate_pipeline = false;
+        let mut in_pipeline = false;
+        // `&&` or `||` pipeline being run comes after, if any, and
+        // whether it is skipped as status before it decides
+        let mut and_or = None;
+        let mut skipped = false;
+
+        // Whether pipeline each command is part of ends with `&`
+        let mut backgrounded = vec![false; parse_results.len()];
+        for (idx, parse_result) in parse_results.iter().enumerate().rev() {
+            backgrounded[idx] = match parse_result.associated_operator {
+                Some(OpType::Pipe | OpType::PipeWithStderr) => {
+                    backgrounded.get(idx + 1).copied().unwrap_or(false)
+                }
+                Some(OpType::Background) => true,
+                _ => false,
+            };
+        }
+
+        for (idx, mut parse_result) in parse_results.into_iter().enumerate() {
+            // Previous command ended the timed pipeline
+            let first_stage = !in_pipeline;
+            if first_stage {
+                report_pipeline_time(&mut timer)?;
+            }
+            in_pipeline = matches!(
+                parse_result.associated_operator,
+                Some(OpType::Pipe | OpType::PipeWithStderr)
+            );
+
+            // Pipelines of an AND-OR list which are skipped leave
+            // status as it is, e.g. `false && a || b` runs `b`
+            if first_stage {
+                skipped = match and_or {
+                    Some(OpType::AndIf) => self.last_status != 0,
+                    Some(OpType::OrIf) => self.last_status == 0,
+                    _ => false,
+                };
+            }
+            if !in_pipeline {
+                and_or = parse_result
+                    .associated_operator
+                    .clone()
+                    .filter(|op| matches!(op, OpType::AndIf | OpType::OrIf));
+            }
+            if skipped {
+                continue;
+            }
+
+            // Stages of a background pipeline make up a job, as do
+            // stages of any pipeline run by interactive shell, so that
+            // foreground ones get the terminal for the time they run
+            if first_stage {
+                self.pipeline.background = backgrounded[idx];
+                self.pipeline.own_group = backgrounded[idx] || self.interactive;
+            }
+
+            // `break` skips rest of the loop body
+            if self.pending_loop_breaks > 0 {
+                break;
+            }
+
+            if self.run_pending_traps()? {
+                return Ok(true);
+            }
+
+            // Ctrl-C cancels rest of the commands, same as a failure
+            if interrupt::is_interrupted() {
+                self.last_status = 1;
+                break;
+            }
+
+            if self.abort_input {
+                break;
+            }
+
+            self.variables.set_line_number(self.command_start_line + parse_result.line + 1);
+
+            if parse_result.timed {
+                timer = Some(PipelineTimer::start()?);
+            }
+            negate_pipeline |= parse_result.negated;
+
+            if parse_result.exit_term {
+                let exit_code = self.parse_exit_status(parse_result.exit_status.as_deref())?;
+                self.exit_code = Some(exit_code);
+                return Ok(true);
+            }
+
+            let execute_mode = std::mem::replace(&mut parse_result.execute_mode, ExecuteMode::Normal);
+            let stages = self.pipeline.stages.len();
+            let set_stdin_to = match execute_mode {
+                ExecuteMode::Normal => {
+                    self.execution_mode = ExecutionMode::Normal;
+
+                    // Assignment only commands, e.g. `n=5+5`
+                    if parse_result.cmds.is_empty() {
+                        self.execute_assignments(&parse_result.assignments)?;
+                        None
+                    } else {
+                        // Currently trying to follow a philosophy of only executing
+                        // one command at a time for separators and other normal stuff
+                        //
+                        // while 2 commands for redirect opertaor, second command contains
+                        // file path, so it is one command in true sense
+                        assert!(parse_result.cmds.len() == 1 || parse_result.cmds.len() == 2);
+
+                        if self.output_redirection_forbidden(&parse_result)?
+                            || self.fd_variable_unassignable(&parse_result)?
+                        {
+                            None
+                        } else if let Some(set_stdin_to) =
+                            self.handle_operations_before_exec(&parse_result)?
+                        {
+                            // Assignments before command only last for it
+                            match self.assign_for_command(&parse_result.assignments)? {
+                                Some(saved) => {
+                                    let cmd = parse_result.cmds[0].clone();
+                                    let result = self.execute_command(cmd);
+                                    self.variables.restore(saved);
+                                    result?;
+                                }
+                                None => self.close_redirection_fds()?,
+                            }
+                            self.finish_process_substitutions()?;
+                            set_stdin_to
+                        } else {
+                            self.close_redirection_fds()?;
+                            self.finish_process_substitutions()?;
+                            None
+                        }
+                    }
+                }
+                ExecuteMode::Subshell(parse_results) => {
+                    self.execution_mode = ExecutionMode::Subshell;
+                    self.fork_process_and_execute(
+                        None,
+                        ExecuteMode::Subshell(parse_results),
+                        false,
+                    )?;
+                    None
+                }
+                ExecuteMode::Arithmetic(expr) => {
+                    self.execution_mode = ExecutionMode::Normal;
+
+                    // Non-zero result means success
+                    self.last_status = match arithmetic::evaluate(&expr, &mut self.variables) {
+                        Ok(value) => exit_status(value != 0),
+                        Err(err) => {
+                            write_to_stderr(&err.to_string())?;
+                            1
+                        }
+                    };
+                    None
+                }
+                ExecuteMode::Select { name, words, body } => {
+                    self.execution_mode = ExecutionMode::Normal;
+
+                    self.loop_depth += 1;
+                    let select_result = self.execute_select(&name, &words, &body);
+                    self.loop_depth -= 1;
+
+                    if select_result? {
+                        return Ok(true);
+                    }
+                    None
+                }
+                ExecuteMode::If { branches, else_body } => {
+                    self.execution_mode = ExecutionMode::Normal;
+
+                    if self.execute_if(&branches, else_body.as_deref())? {
+                        return Ok(true);
+                    }
+                    None
+                }
+                ExecuteMode::Loop { until, condition, body } => {
+                    self.execution_mode = ExecutionMode::Normal;
+
+                    self.loop_depth += 1;
+                    let loop_result = self.execute_loop(until, &condition, &body);
+                    self.loop_depth -= 1;
+
+                    if loop_result? {
+                        return Ok(true);
+                    }
+                    None
+                }
+                ExecuteMode::For { name, words, body } => {
+                    self.execution_mode = ExecutionMode::Normal;
+
+                    self.loop_depth += 1;
+                    let for_result = self.execute_for(&name, words, &body);
+                    self.loop_depth -= 1;
+
+                    if for_result? {
+                        return Ok(true);
+                    }
+                    None
+                }
+                ExecuteMode::Group(parse_results) => {
+                    self.execution_mode = ExecutionMode::Normal;
+
+                    if self.execute(parse_results)? {
+                        return Ok(true);
+                    }
+                    None
+                }
+            };
+
+            // Stage did not record its status if it was not forked
+            if self.pipeline.stages.len() == stages {
+                self.pipeline.stages.push(Stage::Done(self.last_status));
+            }
+
+            if !in_pipeline {
+                self.finish_pipeline()?;
+            }
+
+            // Status of pipeline is of its last command
+            let negated = negate_pipeline && !in_pipeline;
+            if negated {
+                self.last_status = exit_status(self.last_status != 0);
+                negate_pipeline = false;
+            }
+
+            // Script stops on failure with errexit, while interactive
+            // shell only skips rest of the input
+            if !in_pipeline && self.errexit_triggered(&parse_result, negated) {
+                if self.interactive {
+                    self.abort_input = true;
+                    break;
+                }
+                self.exit_code = Some(self.last_status);
+                return Ok(true);
+            }
+
+            self.handle_operations_after_exec(set_stdin_to);
+        }
+
+        report_pipeline_time(&mut timer)?;
+        self.run_pending_traps()
+    }
+
+    // Failing pipeline counts with errexit, unless its status is
+    // tested, either by `!` or by `&&` and `||` after it. Only the
+    // last pipeline of an AND-OR list is left to be checked, pipelines
+    // it skipped never get here
+    fn errexit_triggered(&self, parse_result: &ParseResult, negated: bool) -> bool {
+        self.options.errexit
+            && self.last_status != 0
+            && self.condition_depth == 0
+            && !negated
+            && !matches!(
+                parse_result.associated_operator,
+                Some(OpType::AndIf | OpType::OrIf)
+            )
+    }
+
+    // Traps of signals received since last check are run. Returns
+    // whether one of them exited the shell
+    fn run_pending_traps(&mut self) -> anyhow::Result<bool> {
+        for signal in interrupt::take_pending() {
+            if signal == Signal::SIGCHLD {
+                self.reap_children();
+            }
+
+            let Some(action) = self.traps.get(Condition::Signal(signal)).cloned() else {
+                // Hangup is only caught by interactive shell, jobs
+                // get it too as terminal they ran on is gone
+                if signal == Signal::SIGHUP {
+                    self.jobs.hang_up();
+                    self.exit_code = Some(128 + signal as i32);
+                    return Ok(true);
+                }
+                continue;
+            };
+
+            self.run_trap(&action)?;
+            if self.exit_code.is_some() {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    // Runs EXIT trap, if any, right before shell exits. Status shell
+    // exits with is kept, unless the trap itself runs `exit`
+    pub fn run_exit_trap(&mut self) -> anyhow::Result<()> {
+        let Some(action) = self.traps.get(Condition::Exit).cloned() else {
+            return Ok(());
+        };
+        // It runs only once, even if it is what exits
+        self.traps.reset(Condition::Exit);
+
+        let exit_code = self.exit_code();
+        self.exit_code = None;
+        self.run_trap(&action)?;
+        self.exit_code.get_or_insert(exit_code);
+
+        Ok(())
+    }
+
+    // Status of command trap ran after is kept
+    fn run_trap(&mut self, action: &str) -> anyhow::Result<()> {
+        let last_status = self.last_status;
+
+        let mut lexer = Lexer::new();
+        if let Err(err) = lexer
+            .scan(&format!("{}\n", action))
+            .and_then(|_| self.parse_and_execute(&lexer.tokens))
+        {
+            write_to_stderr(&err.to_string())?;
+        }
+
+        self.last_status = last_status;
+        Ok(())
+    }
+
+    // Status of pipeline is of its last command, unless pipefail is
+    // set, then it is of the last of its commands to fail. Stages of a
+    // background pipeline are not waited for, they become a job.
+    // Statuses of all stages are kept in PIPESTATUS.
+    fn finish_pipeline(&mut self) -> anyhow::Result<()> {
+        let mut pipeline = std::mem::take(&mut self.pipeline);
+
+        if pipeline.background {
+            let pids = pipeline.pids();
+            if let (Some(first), Some(last)) = (pids.first(), pids.last()) {
+                self.background_pid = Some(*last);
+                let pgid = pipeline.pgid.unwrap_or(*first);
+                let command = pipeline.commands.join(" | ");
+                self.jobs.add(pgid, &pids, &command, pipeline.log.clone());
+                for status in pipeline.reaped() {
+                    self.jobs.record_status(status);
+                }
+            }
+            return self.set_pipe_status(&[0]);
+        }
+
+        let pids = pipeline.pids();
+        let statuses = match pipeline.wait()? {
+            Waited::Done(statuses) => statuses,
+            Waited::Stopped(reported) => return self.add_stopped_job(&pipeline, &pids, reported),
+        };
+        let failed = statuses.iter().rev().find(|status| **status != 0);
+        self.last_status = match (failed, statuses.last()) {
+            (Some(status), _) if self.options.pipefail => *status,
+            (_, Some(status)) => *status,
+            _ => self.last_status,
+        };
+
+        self.set_pipe_status(&statuses)
+    }
+
+    // Stopped pipeline becomes a job, which can be resumed with `fg`.
+    // Its status is that of a process killed by the stop signal.
+    fn add_stopped_job(
+        &mut self,
+        pipeline: &Pipeline,
+        pids: &[Pid],
+        reported: Vec<WaitStatus>,
+    ) -> anyhow::Result<()> {
+        let pgid = pipeline.pgid.unwrap_or(pids[0]);
+        let id = self.jobs.add(pgid, pids, &pipeline.commands.join(" | "), None);
+
+        for status in reported {
+            if let WaitStatus::Stopped(_, signal) = status {
+                self.last_status = 128 + signal as i32;
+            }
+            self.jobs.record_status(status);
+        }
+
+        if let Some(job) = self.jobs.get(id) {
+            write_to_stderr(&format!("\n{}", self.jobs.status_line(job)))?;
+        }
+        self.set_pipe_status(&[self.last_status])
+    }
+
+    fn set_pipe_status(&mut self, statuses: &[i32]) -> anyhow::Result<()> {
+        self.variables.assign(&Assignment {
+            name: "PIPESTATUS".to_string(),
+            value: AssignmentValue::Array(statuses.iter().map(i32::to_string).collect()),
+            append: false,
+        })
+    }
+
+    // Prints a numbered menu of words and reads a choice, which is
+    // stored in REPLY, while the chosen word is assigned to name.
+    // Body runs for every choice till EOF or `break`.
+    fn execute_select(
+        &mut self,
+        name: &str,
+        words: &[String],
+        body: &[ParseResult],
+    ) -> anyhow::Result<bool> {
+        if words.is_empty() {
+            return Ok(false);
+        }
+
+        let mut print_menu = true;
+        loop {
+            if print_menu {
+                for (idx, word) in words.iter().enumerate() {
+                    write_to_stderr(&format!("{}) {}\n", idx + 1, word))?;
+                }
+            }
+
+            let prompt = self
+                .variables
+                .get_str("PS3")
+                .unwrap_or_else(|| "#? ".to_string());
+            write_to_stderr(&prompt)?;
+
+            let mut reply = String::new();
+            match read_line(&mut reply) {
+                Ok(0) => {
+                    write_to_stderr("\n")?;
+                    return Ok(false);
+                }
+                Ok(_) => {}
+                // Ctrl-C gives up on the menu
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                    write_to_stderr("\n")?;
+                    self.last_status = 128 + Signal::SIGINT as i32;
+                    return Ok(false);
+                }
+                Err(err) => return Err(err.into()),
+            }
+            let reply = reply.trim_end_matches('\n');
+
+            // Empty reply just shows the menu again
+            print_menu = reply.is_empty();
+            if print_menu {
+                continue;
+            }
+
+            // Invalid choices set name to empty string, same as bash
+            let choice = reply
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| words.get(number.checked_sub(1)?))
+                .cloned()
+                .unwrap_or_default();
+
+            for (var_name, value) in [("REPLY", reply.to_string()), (name, choice)] {
+                self.variables.assign(&Assignment {
+                    name: var_name.to_string(),
+                    value: AssignmentValue::Scalar(value),
+                    append: false,
+                })?;
+            }
+
+            if self.execute(body.to_vec())? {
+                return Ok(true);
+            }
+
+            if self.pending_loop_breaks > 0 {
+                self.pending_loop_breaks -= 1;
+                return Ok(false);
+            }
+
+            if interrupt::is_interrupted() || self.abort_input {
+                return Ok(false);
+            }
+        }
+    }
+
+    // Status is of the body which ran, 0 if none did
+    fn execute_if(
+        &mut self,
+        branches: &[(Vec<ParseResult>, Vec<ParseResult>)],
+        else_body: Option<&[ParseResult]>,
+    ) -> anyhow::Result<bool> {
+        for (condition, body) in branches {
+            if self.execute_condition(condition)? {
+                return Ok(true);
+            }
+
+            if self.last_status == 0 {
+                return self.execute(body.to_vec());
+            }
+        }
+
+        match else_body {
+            Some(body) => self.execute(body.to_vec()),
+            None => {
+                self.last_status = 0;
+                Ok(false)
+            }
+        }
+    }
+
+    // Status is of the last body run, 0 if it never ran
+    fn execute_loop(
+        &mut self,
+        until: bool,
+        condition: &[ParseResult],
+        body: &[ParseResult],
+    ) -> anyhow::Result<bool> {
+        let mut status = 0;
+        loop {
+            if self.execute_condition(condition)? {
+                return Ok(true);
+            }
+
+            if self.loop_ended() || (self.last_status == 0) == until {
+                break;
+            }
+
+            if self.execute(body.to_vec())? {
+                return Ok(true);
+            }
+            status = self.last_status;
+
+            if self.loop_ended() {
+                break;
+            }
+        }
+
+        self.last_status = status;
+        Ok(false)
+    }
+
+    fn execute_for(
+        &mut self,
+        name: &str,
+        words: Vec<Token>,
+        body: &[ParseResult],
+    ) -> anyhow::Result<bool> {
+        let command = Command {
+            tokens: words,
+            ..Command::default()
+        };
+        let words = match command.expand_words(self) {
+            Ok(words) => words,
+            Err(err) => {
+                write_to_stderr(&err.to_string())?;
+                self.last_status = 1;
+                return Ok(false);
+            }
+        };
+
+        self.last_status = 0;
+        for word in words {
+            self.variables.assign(&Assignment {
+                name: name.to_string(),
+                value: AssignmentValue::Scalar(word),
+                append: false,
+            })?;
+
+            if self.execute(body.to_vec())? {
+                return Ok(true);
+            }
+
+            if self.loop_ended() {
+                break;
+            }
+        }
+
+        Ok(false)
+    }
+
+    // Failures in condition are tested, so errexit leaves them be
+    fn execute_condition(&mut self, condition: &[ParseResult]) -> anyhow::Result<bool> {
+        self.condition_depth += 1;
+        let result = self.execute(condition.to_vec());
+        self.condition_depth -= 1;
+        result
+    }
+
+    // Loop being run got broken out of, interrupted, or errexit
+    // skipped rest of the input
+    fn loop_ended(&mut self) -> bool {
+        if self.pending_loop_breaks > 0 {
+            self.pending_loop_breaks -= 1;
+            return true;
+        }
+
+        interrupt::is_interrupted() || self.abort_input
+    }
+
+    // Restricted mode lets output be redirected to no file,
+    // command is not run then
+    fn output_redirection_forbidden(&mut self, parse_result: &ParseResult) -> anyhow::Result<bool> {
+        if !matches!(
+            parse_result.associated_operator,
+            Some(
+                OpType::RedirectOutput(_)
+                    | OpType::RedirectAppendOutput(_)
+                    | OpType::RedirectReadWrite(_)
+            )
+        ) {
+            return Ok(false);
+        }
+
+        let file_path = parse_result.cmds.last().map(|cmd| cmd.path.display().to_string());
+        self.forbidden_in_restricted(&file_path.unwrap_or_default())
+    }
+
+    // Fd picked for `{name}` redirection is stored in name, command
+    // does not run if that can not be done, e.g. name is readonly
+    fn fd_variable_unassignable(&mut self, parse_result: &ParseResult) -> anyhow::Result<bool> {
+        let Some(name) = &parse_result.fd_variable else {
+            return Ok(false);
+        };
+
+        if let Err(err) = self.variables.check_assignable(name) {
+            write_to_stderr(&err.to_string())?;
+            self.last_status = 1;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    // Fd of `{name}` redirection is the lowest one at or above
+    // SHELL_FD_MIN which is not open, it is set up by dup2 same as
+    // any other, so only its number is taken here
+    fn allocate_fd(&mut self, name: &str, file_fd: RawFd) -> anyhow::Result<RawFd> {
+        let fd = fcntl(file_fd, FcntlArg::F_DUPFD(SHELL_FD_MIN))?;
+        close(fd)?;
+
+        self.variables.assign(&Assignment {
+            name: name.to_string(),
+            value: AssignmentValue::Scalar(fd.to_string()),
+            append: false,
+        })?;
+        Ok(fd)
+    }
+
+    // `>&` and `<&`, None as source means "-", so fd just gets closed.
+    // With `{name}` closed fd is the one in variable, e.g. `{fd}>&-`,
+    // while for duplicating it is a newly allocated one
+    fn duplicate_fd(
+        &mut self,
+        source: &FdSource,
+        target: RawFd,
+        fd_variable: &Option<String>,
+    ) -> anyhow::Result<bool> {
+        let Some(source) = self.resolve_fd_source(source)? else {
+            return Ok(false);
+        };
+
+        let target = match (fd_variable, source) {
+            (Some(name), None) => match self.variables.get_str(name).unwrap_or_default().parse() {
+                Ok(fd) => fd,
+                Err(_) => {
+                    write_to_stderr(&format!("dss: {{{}}}: ambiguous redirect\n", name))?;
+                    self.last_status = 1;
+                    return Ok(false);
+                }
+            },
+            (Some(name), Some(source_fd)) => match self.allocate_fd(name, source_fd) {
+                Ok(fd) => fd,
+                Err(_) => {
+                    write_error_to_shell(Errno::EBADF, &source_fd.to_string())?;
+                    self.last_status = 1;
+                    return Ok(false);
+                }
+            },
+            (None, _) => target,
+        };
+
+        match source {
+            Some(source_fd) => self
+                .fds_ops
+                .insert(target, FdOperation::Duplicate { of: source_fd }),
+            None => self.fds_ops.insert(target, FdOperation::Close),
+        };
+        Ok(true)
+    }
+
+    // Some(None) is `-`, None if source did not expand to a
+    // fd, an error is reported then
+    fn resolve_fd_source(&mut self, source: &FdSource) -> anyhow::Result<Option<Option<RawFd>>> {
+        let word = match source {
+            FdSource::Fd(fd) => return Ok(Some(Some(*fd))),
+            FdSource::Close => return Ok(Some(None)),
+            FdSource::Word(word) => word,
+        };
+
+        let words = expansion::expand_word(word, None, self)?;
+        match words.as_slice() {
+            [value] if value == "-" => return Ok(Some(None)),
+            [value] => {
+                if let Ok(fd) = value.parse::<RawFd>() {
+                    return Ok(Some(Some(fd)));
+                }
+            }
+            _ => {}
+        }
+
+        write_to_stderr(&format!("dss: {}: ambiguous redirect\n", word))?;
+        self.last_status = 1;
+        Ok(None)
+    }
+
+    // Returns fd next stage of pipeline reads from, if any. None if file
+    // of redirection could not be opened, command is not run then
+    fn handle_operations_before_exec(
+        &mut self,
+        parse_result: &ParseResult,
+    ) -> anyhow::Result<Option<Option<i32>>> {
+        let mut set_stdin_to: Option<i32> = None;
+        let last_cmd = parse_result
+            .cmds
+            .last()
+            .expect("expected file path to be present");
+        let file_path = &last_cmd.path;
+
+        // Operators which needs addressing before execution starts
+        match parse_result.associated_operator {
+            Some(OpType::RedirectAppendOutput(fd_opt)) | Some(OpType::RedirectOutput(fd_opt)) => {
+                let mut flags = OFlag::O_CREAT;
+                if matches!(
+                    parse_result.associated_operator,
+                    Some(OpType::RedirectOutput(_))
+                ) {
+                    flags.insert(OFlag::O_TRUNC);
+                } else {
+                    flags.insert(OFlag::O_APPEND);
+                }
+                flags.insert(OFlag::O_WRONLY);
+
+                let mode = created_file_mode();
+                let Ok(file_fd) = self.open_file_of_redirection(file_path, flags, mode)? else {
+                    return Ok(None);
+                };
+                let fd_to_be_set = match &parse_result.fd_variable {
+                    Some(name) => self.allocate_fd(name, file_fd)?,
+                    // Default value: stdout
+                    None => fd_opt.map_or(1, |fd| fd),
+                };
+                self.fds_ops
+                    .insert(fd_to_be_set, FdOperation::Set { to: file_fd });
+
+                self.execution_mode = ExecutionMode::Redirect;
+            }
+            Some(OpType::RedirectInput(fd_opt)) | Some(OpType::RedirectReadWrite(fd_opt)) => {
+                let (flags, mode) = if matches!(
+                    parse_result.associated_operator,
+                    Some(OpType::RedirectReadWrite(_))
+                ) {
+                    let mut flags = OFlag::O_CREAT;
+                    flags.insert(OFlag::O_RDWR);
+
+                    (flags, created_file_mode())
+                } else {
+                    (OFlag::O_RDONLY, Mode::empty())
+                };
+
+                let Ok(file_fd) = self.open_file_of_redirection(file_path, flags, mode)? else {
+                    return Ok(None);
+                };
+                let fd_to_be_set = match &parse_result.fd_variable {
+                    Some(name) => self.allocate_fd(name, file_fd)?,
+                    // Default value: stdin
+                    None => fd_opt.map_or(0, |fd| fd),
+                };
+                self.fds_ops
+                    .insert(fd_to_be_set, FdOperation::Set { to: file_fd });
+
+                self.execution_mode = ExecutionMode::Redirect;
+            }
+            Some(OpType::RedirectSquirrelOutput { ref source, target })
+            | Some(OpType::RedirectSquirrelInput { ref source, target }) => {
+                // Default value: stdout for output, stdin for input
+                let default_fd = match parse_result.associated_operator {
+                    Some(OpType::RedirectSquirrelOutput { .. }) => 1,
+                    _ => 0,
+                };
+                let target = target.unwrap_or(default_fd);
+                if !self.duplicate_fd(source, target, &parse_result.fd_variable)? {
+                    return Ok(None);
+                }
+
+                self.execution_mode = ExecutionMode::Redirect;
+            }
+            Some(OpType::Pipe) | Some(OpType::PipeWithStderr) => {
+                // Stages would otherwise keep the other end of their own pipe open
+                let (fd0, fd1) = pipe2(OFlag::O_CLOEXEC)?;
+                set_stdin_to = Some(fd0);
+                self.fds_ops.insert(1, FdOperation::Set { to: fd1 });
+                if matches!(
+                    parse_result.associated_operator,
+                    Some(OpType::PipeWithStderr)
+                ) {
+                    self.fds_ops.insert(2, FdOperation::Duplicate { of: 1 });
+                }
+                self.execution_mode = ExecutionMode::Pipeline;
+            }
+            Some(OpType::Background) => {
+                self.execution_mode = ExecutionMode::Background;
+            }
+            _ => {}
+        }
+
+        Ok(Some(set_stdin_to))
+    }
+
+    // Failing to open is reported the way a failing command would be,
+    // outer error is of reporting it
+    fn open_file_of_redirection(
+        &mut self,
+        path: &Path,
+        flags: OFlag,
+        mode: Mode,
+    ) -> anyhow::Result<nix::Result<RawFd>> {
+        let result = open_redirection(path, flags, mode);
+        if let Err(errno) = result {
+            write_error_to_shell(errno, &path.display().to_string())?;
+            self.last_status = 1;
+        }
+        Ok(result)
+    }
+
+    fn handle_operations_after_exec(&mut self, set_stdin_to: Option<i32>) {
+        self.reset_fds_ops();
+
+        // If execution mode last cycle is pipeline
+        if matches!(self.execution_mode, ExecutionMode::Pipeline) {
+            // Read fd from previous pipe operation
+            // to set curr stdin
+            if let Some(fd) = set_stdin_to {
+                self.fds_ops.insert(0, FdOperation::Set { to: fd });
+            }
+        }
+    }
+
+    fn reset_fds_ops(&mut self) {
+        self.fds_ops = HashMap::new();
+    }
+
+    // Path command is run from, unqualified names are looked up in PATH.
+    // None if there is no such command, which gets reported
+    fn resolve_command(&mut self, name: &str) -> anyhow::Result<Option<PathBuf>> {
+        if name.contains('/') && self.forbidden_in_restricted(name)? {
+            return Ok(None);
+        }
+
+        if !is_unqualified_path(Path::new(name)) {
+            return Ok(Some(PathBuf::from(name)));
+        }
+
+        let path_var = self.variables.get_str("PATH").unwrap_or_default();
+        let cmd_path = self.command_hash.lookup(name, &path_var);
+        if cmd_path.is_none() {
+            write_to_stderr(&ShellError::CommandNotFound(name.to_string()).to_string())?;
+        }
+
+        Ok(cmd_path)
+    }
+
+    // Builtins run in shell itself, so their redirections are applied
+    // to fds of shell, which are saved to be restored once builtin is
+    // done. None if redirections could not be applied, which is
+    // reported and fails the builtin.
+    fn redirect_shell_fds(&mut self, name: &str) -> anyhow::Result<Option<Vec<SavedFd>>> {
+        let saved_fds: Vec<SavedFd> = self
+            .fds_ops
+            .keys()
+            .map(|fd| SavedFd {
+                fd: *fd,
+                copy: fcntl(*fd, FcntlArg::F_DUPFD_CLOEXEC(SHELL_FD_MIN)).ok(),
+            })
+            .collect();
+
+        let mut result = Ok(());
+        for (fd, op) in &self.fds_ops {
+            result = match op {
+                FdOperation::Set { to } => dup2(*to, *fd).map(drop),
+                // Closing an fd which is not open is fine
+                FdOperation::Close => close(*fd).or(Ok(())),
+                FdOperation::Duplicate { .. } => Ok(()),
+            };
+            if result.is_err() {
+                break;
+            }
+        }
+        if result.is_ok() {
+            for (fd, op) in &self.fds_ops {
+                if let FdOperation::Duplicate { of } = op {
+                    result = dup2(*of, *fd).map(drop);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        // Fds opened for redirections are not needed past this,
+        // e.g. next command of pipeline reads EOF once builtin is done
+        self.discard_redirections();
+
+        if let Err(err) = result {
+            restore_shell_fds(saved_fds)?;
+            write_to_stderr(&format!("dss: {}: {}\n", name, err))?;
+            self.last_status = 1;
+            return Ok(None);
+        }
+        Ok(Some(saved_fds))
+    }
+
+    fn discard_redirections(&mut self) {
+        for value in self.fds_ops.values() {
+            if let FdOperation::Set { to } = value {
+                let _ = close(*to);
+            }
+        }
+        self.reset_fds_ops();
+    }
+
+    fn close_redirection_fds(&self) -> anyhow::Result<()> {
+        for value in self.fds_ops.values() {
+            match value {
+                // Read end of a pipe is closed too, otherwise its writer
+                // never gets SIGPIPE once the reader is gone
+                FdOperation::Set { to } => close(*to)?,
+                // Nothing got opened for these, and fds
+                // of shell itself should stay as they are
+                FdOperation::Duplicate { .. } | FdOperation::Close => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn execute_assignments(&mut self, assignments: &[Assignment]) -> anyhow::Result<()> {
+        for assignment in assignments {
+            if let Err(err) = self.expand_assignment(assignment).and_then(|assignment| {
+                self.trace(&traced_assignment(&assignment))?;
+                self.variables.assign(&assignment)
+            }) {
+                write_to_stderr(&err.to_string())?;
+                self.last_status = 1;
+                return Ok(());
+            }
+        }
+
+        self.last_status = 0;
+        Ok(())
+    }
+
+    // Assignments before a command are expanded same as ones on their
+    // own, command does not run if any of them fails
+    fn assign_for_command(
+        &mut self,
+        assignments: &[Assignment],
+    ) -> anyhow::Result<Option<Vec<SavedVariable>>> {
+        let mut saved = vec![];
+        for assignment in assignments {
+            let old_var = self.expand_assignment(assignment).and_then(|assignment| {
+                self.trace(&traced_assignment(&assignment))?;
+                self.variables.assign_temporarily(&assignment)
+            });
+
+            match old_var {
+                Ok(old_var) => saved.push(old_var),
+                Err(err) => {
+                    self.variables.restore(saved);
+                    write_to_stderr(&err.to_string())?;
+                    self.last_status = 1;
+                    return Ok(None);
+                }
+            }
+        }
+
+        Ok(Some(saved))
+    }
+
+    // With xtrace commands are written to stderr once expanded, right
+    // before they run, after PS4 which is expanded too
+    fn trace(&mut self, command: &str) -> anyhow::Result<()> {
+        if !self.options.xtrace {
+            return Ok(());
+        }
+
+        let prefix = match self.variables.get_str("PS4") {
+            Some(ps4) => expansion::expand_assignment_value(&ps4, self).unwrap_or(ps4),
+            None => DEFAULT_PS4.to_string(),
+        };
+        write_to_stderr(&format!("{}{}\n", prefix, command))
+    }
+
+    // Scalar values are expanded as a whole, while
+    // array elements are expanded just like words
+    fn expand_assignment(&mut self, assignment: &Assignment) -> anyhow::Result<Assignment> {
+        let value = match &assignment.value {
+            AssignmentValue::Scalar(value) => {
+                AssignmentValue::Scalar(expansion::expand_assignment_value(value, self)?)
+            }
+            AssignmentValue::Array(values) => {
+                let glob_options = self.glob_options();
+                let mut words = vec![];
+                for value in values {
+                    words.extend(expansion::expand_word(value, Some(&glob_options), self)?);
+                }
+                AssignmentValue::Array(words)
+            }
+        };
+
+        Ok(Assignment {
+            name: assignment.name.clone(),
+            value,
+            append: assignment.append,
+        })
+    }
+
+    fn execute_command(&mut self, command: Command) -> anyhow::Result<()> {
+        // Command does not run at all if expanding its words
+        // fails, e.g. a pattern matching nothing with failglob
+        let words = match command.expand_words(self) {
+            Ok(words) => words,
+            Err(err) => {
+                write_to_stderr(&err.to_string())?;
+                self.last_status = 1;
+                return Ok(());
+            }
+        };
+
+        // Words can expand to nothing, e.g. `$unset`, or there can be
+        // none at all, e.g. `> file`, redirections still get opened
+        if words.is_empty() {
+            self.discard_redirections();
+            self.last_status = 0;
+            return Ok(());
+        }
+
+        self.trace(&quote::join(&words))?;
+        self.execute_words(words)
+    }
+
+    // Runs builtin or external command words are of, words of
+    // `command` builtin skip aliases by coming straight here
+    fn execute_words(&mut self, words: Vec<String>) -> anyhow::Result<()> {
+        let cmd_name = &words[0];
+        let builtin = self.builtins.get(cmd_name);
+        if builtin.is_some() && matches!(self.execution_mode, ExecutionMode::Pipeline) {
+            // Stages before last one of a pipeline run alongside each
+            // other, builtins among them too, e.g. `history | grep ssh`
+            self.fork_process_and_execute(Some(words), ExecuteMode::Normal, true)?;
+        } else if cmd_name == "exec" && words.len() == 1 {
+            // Without a command, redirections of `exec` stay
+            // applied to shell, e.g. `exec 2> log.txt`
+            if let Some(saved_fds) = self.redirect_shell_fds(cmd_name)? {
+                for copy in saved_fds.into_iter().filter_map(|saved_fd| saved_fd.copy) {
+                    close(copy)?;
+                }
+                self.last_status = 0;
+            }
+        } else if let Some(builtin) = builtin {
+            let Some(saved_fds) = self.redirect_shell_fds(cmd_name)? else {
+                return Ok(());
+            };
+
+            self.last_status = builtin.exec(self, &words, &mut Io::default());
+            restore_shell_fds(saved_fds)?;
+        } else if matches!(self.execution_mode, ExecutionMode::Subshell) {
+            match self.resolve_command(cmd_name)? {
+                Some(cmd_path) => execute_external_cmd(&words, &cmd_path, &self.variables)?,
+                None => unsafe { libc::_exit(COMMAND_NOT_FOUND_EXIT_CODE) },
+            }
+        } else {
+            self.fork_process_and_execute(Some(words), ExecuteMode::Normal, false)?;
+        }
+
+        Ok(())
+    }
+
+    // PWD is kept logical, same as other shells, i.e. `cd link/..` goes back
+    // to where it started, not to parent of directory link points to. Going
+    // by physical path is fallen back to if logical one fails, as when some
+    // directory in it got removed. Outer error is of setting variables
+    fn change_directory(&mut self, dir: &Path) -> anyhow::Result<nix::Result<()>> {
+        let old_pwd = self.current_dir()?;
+        let logical = normalize_path(&old_pwd.join(dir));
+
+        let new_pwd = if chdir(&logical).is_ok() {
+            logical
+        } else if let Err(err) = chdir(dir) {
+            return Ok(Err(err));
+        } else {
+            std::env::current_dir()?
+        };
+
+        for (name, dir) in [("OLDPWD", old_pwd), ("PWD", new_pwd)] {
+            self.variables.assign(&Assignment {
+                name: name.to_string(),
+                value: AssignmentValue::Scalar(dir.display().to_string()),
+                append: false,
+            })?;
+        }
+
+        Ok(Ok(()))
+    }
+
+    // Directory shell is in, the way PWD names it
+    fn current_dir(&self) -> io::Result<PathBuf> {
+        match self.variables.get_str("PWD").map(PathBuf::from) {
+            Some(pwd) if pwd.is_absolute() => Ok(pwd),
+            _ => std::env::current_dir(),
+        }
+    }
+
+    // Spinner is drawn on terminal, so it is only shown when neither
+    // stdout nor stderr of job go there, e.g. `cmd > log` of a shell
+    // which ran `exec 2> errors`. Fds of redirections are to be open yet
+    fn progress_threshold(&self) -> Option<Duration> {
+        let to_terminal = |fd| isatty(self.redirected_fd(fd)).unwrap_or(false);
+
+        self.options
+            .progress
+            .filter(|_| self.interactive && !to_terminal(1) && !to_terminal(2))
+    }
+
+    // Fd of shell which fd of command ends up being, -1 if it is closed.
+    // Duplicates are made after `Set`s, so they are of the fd set
+    fn redirected_fd(&self, fd: i32) -> RawFd {
+        let set_fd = |fd| match self.fds_ops.get(&fd) {
+            Some(FdOperation::Set { to }) => *to,
+            Some(FdOperation::Close) => -1,
+            _ => fd,
+        };
+
+        match self.fds_ops.get(&fd) {
+            Some(FdOperation::Duplicate { of }) => set_fd(*of),
+            _ => set_fd(fd),
+        }
+    }
+
+    // Status is taken modulo 256 like any exit status, non numeric
+    // ones are reported and exit with 2, same as bash
+    fn parse_exit_status(&self, status: Option<&str>) -> anyhow::Result<i32> {
+        let Some(status) = status else {
+            return Ok(self.exit_code());
+        };
+
+        let status = unquote(status);
+        match status.trim().parse::<i64>() {
+            Ok(code) => Ok(code.rem_euclid(256) as i32),
+            Err(_) => {
+                write_to_stderr(&format!(
+                    "dss: exit: {}: numeric argument required\n",
+                    status
+                ))?;
+                Ok(2)
+            }
+        }
+    }
+
+    // Lookup done before running a command, without running it
+    fn find_command(&mut self, name: &str) -> Option<CommandKind> {
+        let path_var = self.variables.get_str("PATH").unwrap_or_default();
+
+        if SHELL_KEYWORDS.contains(&name) {
+            Some(CommandKind::Keyword)
+        } else if let Some(value) = self.aliases.get(name) {
+            Some(CommandKind::Alias(value.clone()))
+        } else if let Some(origin) = self.builtins.origin(name) {
+            Some(match origin {
+                Origin::Shell => CommandKind::Builtin,
+                Origin::Application => CommandKind::CustomBuiltin,
+            })
+        } else if !is_unqualified_path(Path::new(name)) {
+            hash::is_executable(Path::new(name)).then(|| CommandKind::File {
+                path: PathBuf::from(name),
+                hashed: false,
+            })
+        } else if let Some(path) = self.command_hash.hashed(name, &path_var) {
+            Some(CommandKind::File { path, hashed: true })
+        } else {
+            hash::search_path(name, &path_var).map(|path| CommandKind::File {
+                path,
+                hashed: false,
+            })
+        }
+    }
+
+    // Capture of stderr of the foreground command about to be
+    // started, if it is needed for `failhint` or `colorstderr`
+    fn stderr_capture(&self, words: &Option<Vec<String>>) -> anyhow::Result<Option<StderrCapture>> {
+        // Colors are only for terminal, not for where stderr is redirected
+        let colorize = self.options.color_stderr && isatty(2).unwrap_or(false);
+
+        let captured = self.interactive
+            && (self.options.fail_hint || colorize)
+            && words.is_some()
+            && matches!(
+                self.execution_mode,
+                ExecutionMode::Normal | ExecutionMode::Redirect
+            )
+            && !self.fds_ops.contains_key(&2);
+
+        if !captured {
+            return Ok(None);
+        }
+
+        Ok(Some(StderrCapture::open(colorize)?))
+    }
+
+    // Log file for the background job about to be started, if
+    // its output is to be captured
+    fn next_job_log(&self) -> anyhow::Result<Option<PathBuf>> {
+        if !matches!(self.execution_mode, ExecutionMode::Background) || !self.options.job_logs {
+            return Ok(None);
+        }
+
+        let Some(path) = jobs::log_path(getpid(), self.jobs.next_id()) else {
+            return Ok(None);
+        };
+
+        if let Some(dir) = path.parent() {
+            if let Err(err) = std::fs::create_dir_all(dir) {
+                write_to_stderr(&format!("dss: joblogs: {}: {}\n", dir.display(), err))?;
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(path))
+    }
+
+    // Builtin is set when words are of one, it runs in the child
+    fn fork_process_and_execute(
+        &mut self,
+        words: Option<Vec<String>>,
+        execute_mode: ExecuteMode,
+        builtin: bool,
+    ) -> anyhow::Result<bool> {
+        if self.sandboxed {
+            // Opened redirections would have been closed after fork
+            self.close_redirection_fds()?;
+            let name = words.as_ref().map_or("subshell", |words| words[0].as_str());
+            write_to_stderr(&ShellError::Sandboxed(name.to_string()).to_string())?;
+            self.last_status = 1;
+            return Ok(false);
+        }
+
+        // Missing commands are reported without forking, starting
+        // one in background still succeeds like it does after fork
+        let cmd_path = match &words {
+            Some(_) if builtin => None,
+            Some(words) => match self.resolve_command(&words[0])? {
+                Some(cmd_path) => Some(cmd_path),
+                None => {
+                    self.close_redirection_fds()?;
+                    if matches!(self.execution_mode, ExecutionMode::Background) {
+                        self.last_status = 0;
+                    } else {
+                        self.last_status = COMMAND_NOT_FOUND_EXIT_CODE;
+                        self.pipeline.stages.push(Stage::Done(COMMAND_NOT_FOUND_EXIT_CODE));
+                    }
+                    return Ok(false);
+                }
+            },
+            None => None,
+        };
+
+        let job_log = self.next_job_log()?;
+        let stderr_capture = self.stderr_capture(&words)?;
+
+        match unsafe { fork() } {
+            Ok(ForkResult::Parent {
+                child: child_pid, ..
+            }) => {
+                let new_group = self.pipeline.join_group(child_pid)?;
+                if let Some(pgid) = new_group.filter(|_| !self.pipeline.background) {
+                    if self.interactive {
+                        self.pipeline.terminal = TerminalHandover::give(pgid)?;
+                    }
+                }
+
+                let command = words.as_deref().map_or("(...)".to_string(), quote::join);
+                self.pipeline.commands.push(command);
+                if matches!(self.execution_mode, ExecutionMode::Background) {
+                    self.pipeline.stages.push(Stage::Running(child_pid));
+                    self.pipeline.log = job_log;
+                    self.last_status = 0;
+                }
+
+                let progress_threshold = self.progress_threshold();
+                self.close_redirection_fds()?;
+
+                let stderr_tee = match &stderr_capture {
+                    Some(capture) => {
+                        close(capture.write_fd)?;
+                        Some(StderrTee::start(capture))
+                    }
+                    None => None,
+                };
+
+                // We do not wait for forked children if the command is
+                // running in pipeline mode
+                //
+                // Note: last command in the pipeline is the only one
+                // we wait for ( that gets handled cause we only set
+                // pipe execution mode when we receive a pipe operator )
+                //
+                // TIP: While debugging piping related issues, comment this if
+                // condition and let it wait on each command execution
+                // Its status is known once pipeline is done
+                if matches!(self.execution_mode, ExecutionMode::Pipeline) {
+                    self.pipeline.stages.push(Stage::Running(child_pid));
+                    self.last_status = 0;
+                }
+
+                if !matches!(self.execution_mode, ExecutionMode::Pipeline)
+                    && !matches!(self.execution_mode, ExecutionMode::Background)
+                {
+                    let wait_status = match (
+                        &words,
+                        self.options.cmd_timeout,
+                        progress_threshold,
+                    ) {
+                        (Some(words), Some(timeout), _) if !self.interactive => {
+                            wait_with_timeout(child_pid, timeout, words)?
+                        }
+                        (_, _, Some(threshold)) => wait_with_progress(child_pid, threshold)?,
+                        _ => waitpid(child_pid, Some(WaitPidFlag::WUNTRACED)).unwrap_or_else(|_| {
+                            panic!("Expected to wait for child with pid: {:?}", child_pid)
+                        }),
+                    };
+
+                    if let (Some(tee), Some(words)) = (stderr_tee, &words) {
+                        let stderr_line = tee.finish();
+                        if self.options.fail_hint {
+                            let successful = matches!(wait_status, WaitStatus::Exited(_, 0));
+                            self.failures.record(&words[0], successful, stderr_line);
+                        }
+                    }
+
+                    // Stopped command becomes a job once pipeline is done
+                    if let WaitStatus::Stopped(pid, signal) = wait_status {
+                        self.pipeline.stages.push(Stage::Stopped(pid, signal));
+                    } else if let Some(status) = jobs::exit_code_of(wait_status) {
+                        self.pipeline.stages.push(Stage::Done(status));
+                        self.last_status = status;
+                    }
+
+                    if let WaitStatus::Exited(_pid, exit_code) = wait_status {
+                        return Ok(exit_code == 0);
+                    }
+                }
+            }
+            Ok(ForkResult::Child) => {
+                interrupt::reset_in_child()?;
+                if let Some(pgid) = self.pipeline.group_for_next_stage() {
+                    setpgid(Pid::from_raw(0), pgid)?;
+                    if self.interactive && !self.pipeline.background {
+                        TerminalHandover::claim()?;
+                    }
+                }
+                // Other stages are not children of this one, and
+                // jobs are only controlled by the shell itself
+                self.pipeline = Pipeline::default();
+                self.interactive = false;
+
+                // Child never goes back to running commands of parent,
+                // not even when running its own command fails
+                let status = match self.execute_in_child(
+                    words,
+                    execute_mode,
+                    builtin,
+                    cmd_path,
+                    job_log,
+                    stderr_capture,
+                ) {
+                    Ok(()) => self.exit_code(),
+                    Err(err) => {
+                        let _ = write_to_stderr(&err.to_string());
+                        1
+                    }
+                };
+                unsafe { libc::_exit(status) };
+            }
+            Err(err) => panic!("Fork failed: {err:?}"),
+        }
+
+        Ok(false)
+    }
+
+    fn execute_in_child(
+        &mut self,
+        words: Option<Vec<String>>,
+        execute_mode: ExecuteMode,
+        builtin: bool,
+        cmd_path: Option<PathBuf>,
+        job_log: Option<PathBuf>,
+        stderr_capture: Option<StderrCapture>,
+    ) -> anyhow::Result<()> {
+        match execute_mode {
+            ExecuteMode::Normal => {
+                let words = words.expect("internal error: should have contained valid command");
+
+                // Redirections of job itself still take precedence
+                if let Some(log) = job_log {
+                    let log_fd = open(
+                        &log,
+                        OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_TRUNC | OFlag::O_CLOEXEC,
+                        Mode::from_bits_truncate(0o644),
+                    )?;
+                    for fd in [1, 2] {
+                        if !self.fds_ops.contains_key(&fd) {
+                            dup2(log_fd, fd)?;
+                        }
+                    }
+                    close(log_fd)?;
+                }
+
+                if let Some(capture) = &stderr_capture {
+                    dup2(capture.write_fd, 2)?;
+                    close(capture.write_fd)?;
+                    close(capture.read_fd)?;
+                }
+
+                for (fd, op) in &self.fds_ops {
+                    match op {
+                        FdOperation::Set { to } => {
+                            dup2(*to, *fd)?;
+                            close(*to)?;
+                        }
+                        FdOperation::Close => {
+                            close(*fd)?;
+                        }
+                        FdOperation::Duplicate { .. } => {}
+                    }
+                }
+
+                for (fd, op) in &self.fds_ops {
+                    if let FdOperation::Duplicate { of } = op {
+                        // e.g. `>&3` after `exec 3>&-`, command is not run
+                        if let Err(errno) = dup2(*of, *fd) {
+                            write_error_to_shell(errno, &of.to_string())?;
+                            self.last_status = 1;
+                            return Ok(());
+                        }
+                    }
+                }
+
+                // Redirections are already in place for builtin
+                if builtin {
+                    self.reset_fds_ops();
+                    self.execution_mode = ExecutionMode::Normal;
+                    return self.execute_words(words);
+                }
+
+                let cmd_path = cmd_path.expect("internal error: command should have been resolved");
+                execute_external_cmd(&words, &cmd_path, &self.variables)?;
+            }
+            // Status of subshell is of its last command, unless it
+            // ran `exit`, which only terminates the subshell
+            ExecuteMode::Subshell(parse_results) => {
+                self.execute(parse_results)?;
+            }
+            ExecuteMode::Arithmetic(_)
+            | ExecuteMode::Select { .. }
+            | ExecuteMode::If { .. }
+            | ExecuteMode::Loop { .. }
+            | ExecuteMode::For { .. }
+            | ExecuteMode::Group(_) => {
+                return Err(ShellError::InternalError(
+                    "compound commands are executed without forking".into(),
+                )
+                .into())
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Engine {
+    // Pipes of `<(command)` are closed once command they were
+    // substituted in has them, commands writing to them get
+    // reaped as they finish, without waiting on them
+    fn finish_process_substitutions(&mut self) -> anyhow::Result<()> {
+        for fd in std::mem::take(&mut self.process_substitution_fds) {
+            close(fd)?;
+        }
+
+        self.reap_children();
+        Ok(())
+    }
+
+    // Children which exited or stopped get reaped here, without
+    // blocking, whatever they were started for, so that none of them
+    // linger as zombies. Once shell watches for children any child of
+    // its process is reaped, otherwise only ones it knows about.
+    fn reap_children(&mut self) {
+        let flags = WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED | WaitPidFlag::WCONTINUED;
+        if interrupt::watches_children() {
+            while let Ok(status) = waitpid(None, Some(flags)) {
+                if status == WaitStatus::StillAlive {
+                    break;
+                }
+                self.record_child_status(status);
+            }
+            return;
+        }
+
+        let pids = self
+            .jobs
+            .pids()
+            .into_iter()
+            .chain(self.pipeline.pids())
+            .chain(self.process_substitution_pids.clone());
+        for pid in pids.collect::<Vec<Pid>>() {
+            match waitpid(pid, Some(flags)) {
+                Ok(WaitStatus::StillAlive) => {}
+                Ok(status) => self.record_child_status(status),
+                // Not a child of shell anymore, nothing to wait for
+                Err(_) => self.record_child_status(WaitStatus::Exited(pid, 0)),
+            }
+        }
+    }
+
+    fn record_child_status(&mut self, status: WaitStatus) {
+        if self.pipeline.record_status(status) || self.jobs.record_status(status) {
+            return;
+        }
+
+        // Rest of them are only reaped, e.g. of `<(command)`
+        if matches!(status, WaitStatus::Exited(..) | WaitStatus::Signaled(..)) {
+            self.process_substitution_pids
+                .retain(|pid| Some(*pid) != status.pid());
+        }
+    }
+
+    // Children do not keep pipes of `<(command)` in parent open
+    fn close_process_substitution_fds(&mut self) -> anyhow::Result<()> {
+        for fd in std::mem::take(&mut self.process_substitution_fds) {
+            close(fd)?;
+        }
+        Ok(())
+    }
+
+    // Runs command of `$(command)` or `<(command)` in forked child,
+    // writing its output to fd
+    fn run_substituted(&mut self, lexer: &Lexer, fd: RawFd) -> ! {
+        let result = (|| -> anyhow::Result<()> {
+            dup2(fd, 1)?;
+            close(fd)?;
+            self.close_process_substitution_fds()?;
+
+            // Redirections and pipeline of the command being
+            // expanded do not apply to the substituted one
+            self.reset_fds_ops();
+            self.pipeline = Pipeline::default();
+            self.interactive = false;
+            interrupt::reset_in_child()?;
+            self.parse_and_execute(&lexer.tokens)?;
+            Ok(())
+        })();
+
+        if let Err(err) = result {
+            write_to_stderr(&err.to_string()).ok();
+            self.last_status = 1;
+        }
+        unsafe { libc::_exit(self.exit_code()) };
+    }
+}
+
+impl ExpansionContext for Engine {
+    fn variables(&mut self) -> &mut Variables {
+        &mut self.variables
+    }
+
+    fn glob_options(&self) -> GlobOptions {
+        GlobOptions {
+            ignore_case: self.inputrc.completion_ignore_case,
+            extended_glob: self.options.extended_glob,
+            max_depth: self.options.glob_max_depth,
+            dot_glob: self.options.dot_glob,
+            null_glob: self.options.null_glob,
+            fail_glob: self.options.fail_glob,
+            max_matches: self.options.glob_max_matches,
+        }
+    }
+
+    fn max_expansion_size(&self) -> Option<usize> {
+        self.options.expand_max_size
+    }
+
+    fn positional_params(&self) -> &[String] {
+        &self.positional_params
+    }
+
+    fn last_status(&self) -> i32 {
+        self.last_status
+    }
+
+    fn background_pid(&self) -> Option<i32> {
+        self.background_pid.map(Pid::as_raw)
+    }
+
+    fn nounset(&self) -> bool {
+        self.options.nounset
+    }
+
+    // Command runs in a forked copy of shell, with its stdout
+    // connected to a pipe which is read till EOF
+    fn command_output(&mut self, command: &str) -> anyhow::Result<String> {
+        if self.sandboxed {
+            return Err(ShellError::Sandboxed(format!("$({})", command)).into());
+        }
+
+        let mut lexer = Lexer::new();
+        lexer.scan(&format!("{}\n", command))?;
+
+        let (read_fd, write_fd) = pipe2(OFlag::O_CLOEXEC)?;
+        match unsafe { fork() }? {
+            ForkResult::Parent { child } => {
+                close(write_fd)?;
+
+                // Reading stops past the limit, command is not let to run on
+                let max_size = self.options.expand_max_size;
+                let mut output = vec![];
+                unsafe { File::from_raw_fd(read_fd) }
+                    .take(max_size.map_or(u64::MAX, |max| max as u64 + 1))
+                    .read_to_end(&mut output)?;
+
+                if let Some(max) = max_size.filter(|max| output.len() > *max) {
+                    kill(child, Signal::SIGKILL)?;
+                    waitpid(child, None)?;
+                    return Err(ShellError::ExpansionError(format!(
+                        "$({}): output is more than {} bytes (expandmaxsize)",
+                        command, max
+                    ))
+                    .into());
+                }
+                waitpid(child, None)?;
+
+                Ok(String::from_utf8_lossy(&output).into_owned())
+            }
+            ForkResult::Child => {
+                close(read_fd)?;
+                self.run_substituted(&lexer, write_fd)
+            }
+        }
+    }
+
+    // Command runs in a forked copy of shell, in background, with its
+    // stdout connected to a pipe which stays open in shell. It is
+    // inherited by the command the path is substituted in.
+    fn process_substitution(&mut self, command: &str) -> anyhow::Result<String> {
+        if self.sandboxed {
+            return Err(ShellError::Sandboxed(format!("<({})", command)).into());
+        }
+
+        let mut lexer = Lexer::new();
+        lexer.scan(&format!("{}\n", command))?;
+
+        let (read_fd, write_fd) = pipe2(OFlag::O_CLOEXEC)?;
+        match unsafe { fork() }? {
+            ForkResult::Parent { child } => {
+                close(write_fd)?;
+                fcntl(read_fd, FcntlArg::F_SETFD(FdFlag::empty()))?;
+                self.process_substitution_fds.push(read_fd);
+                self.process_substitution_pids.push(child);
+
+                Ok(format!("/dev/fd/{}", read_fd))
+            }
+            ForkResult::Child => {
+                close(read_fd)?;
+                self.run_substituted(&lexer, write_fd)
+            }
+        }
+    }
+}
+
+// GOTCHA: This currently executes the command and stops the complete program
+// due to libc::exit at the end
+fn execute_external_cmd(
+    words: &[String],
+    cmd_path: &Path,
+    variables: &Variables,
+) -> anyhow::Result<()> {
+    let cmd_args: Vec<CString> = words
+        .iter()
+        .map(|word| {
+            CString::new(word.as_str()).unwrap_or_else(|_| {
+                panic!(
+                    "expected covnersion of word {} to cstring to be successful",
+                    word
+                )
+            })
+        })
+        .collect();
+    let args: &[CString] = if cmd_args.is_empty() { &[] } else { &cmd_args };
+
+    // Rust ignores SIGPIPE and ignored signals stay so across exec, writers
+    // to a pipe whose reader is gone are to be killed by it instead
+    unsafe { signal(Signal::SIGPIPE, SigHandler::SigDfl) }?;
+
+    // Values with a nul in them can not be passed on
+    let env: Vec<CString> = variables
+        .environment()
+        .iter()
+        .filter_map(|var| CString::new(var.as_str()).ok())
+        .collect();
+
+    // execve() only returns if there's an error, otherwise
+    // child is replaced by the command. For more understanding
+    // read: RETURN VALUES section of execve man page
+    let Err(errno) = execve_(cmd_path, args, &env);
+
+    // Directories can not be run, which is more useful to
+    // know than that permission to do so was denied
+    let errno = match errno {
+        Errno::EACCES if cmd_path.is_dir() => Errno::EISDIR,
+        errno => errno,
+    };
+    write_error_to_shell(errno, &words[0])?;
+
+    unsafe { libc::_exit(exec_failure_exit_code(errno)) };
+}
+
+// Command which is not there exits with 127, one which
+// is there but could not be run with 126, as in POSIX
+fn exec_failure_exit_code(errno: Errno) -> i32 {
+    match errno {
+        Errno::ENOENT | Errno::ENOTDIR => COMMAND_NOT_FOUND_EXIT_CODE,
+        _ => 126,
+    }
+}
+
+fn execve_(path: &Path, args: &[CString], env: &[CString]) -> nix::Result<Infallible> {
+    let path = CString::new(path.as_os_str().as_bytes()).expect("Could not construct CString path");
+
+    // match execve::<CString, CString>(&path, args, &[]) {
+    //     Ok(_) => {}
+    //     Err(_err) => println!("{:?}", _err),
+    // }
+
+    execve::<CString, CString>(&path, args, env)
+}
+
+// Only commands redirected get the file, as fd dup2 sets for them, the
+// one opened here is not inherited. It is kept off fds redirections
+// target, e.g. `exec 3< file` would otherwise get 3 to close as well
+fn open_redirection(path: &Path, flags: OFlag, mode: Mode) -> nix::Result<RawFd> {
+    let fd = open(path, flags | OFlag::O_CLOEXEC, mode)?;
+    if fd >= SHELL_FD_MIN {
+        return Ok(fd);
+    }
+
+    let moved_fd = fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(SHELL_FD_MIN));
+    let _ = close(fd);
+    moved_fd
+}
+
+// Files created by redirections are readable and writable by all,
+// less what umask takes away, same as other shells
+fn created_file_mode() -> Mode {
+    Mode::from_bits_truncate(0o666)
+}
+
+// `.` and `..` are resolved by dropping path components, without
+// following any symlinks, e.g. `/a/link/../b` becomes `/a/b`
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+fn chains_to_next_command(parse_result: &ParseResult) -> bool {
+    matches!(
+        parse_result.associated_operator,
+        Some(OpType::AndIf | OpType::OrIf | OpType::Pipe | OpType::PipeWithStderr)
+    )
+}
+
+fn report_pipeline_time(timer: &mut Option<PipelineTimer>) -> anyhow::Result<()> {
+    if let Some(timer) = timer.take() {
+        write_to_stderr(&timer.report()?)?;
+    }
+
+    Ok(())
+}
+
+// Polls child till it exits, killing it once timeout is over,
+// in which case it is reported as exited with TIMEOUT_EXIT_CODE
+fn wait_with_timeout(
+    child_pid: Pid,
+    timeout: Duration,
+    words: &[String],
+) -> anyhow::Result<WaitStatus> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        match waitpid(child_pid, Some(WaitPidFlag::WNOHANG))? {
+            WaitStatus::StillAlive if Instant::now() >= deadline => {
+                kill(child_pid, Signal::SIGKILL)?;
+                waitpid(child_pid, None)?;
+
+                write_to_stderr(&format!(
+                    "dss: {}: timed out after {}s\n",
+                    quote::join(words),
+                    timeout.as_secs()
+                ))?;
+                return Ok(WaitStatus::Exited(child_pid, TIMEOUT_EXIT_CODE));
+            }
+            WaitStatus::StillAlive => thread::sleep(Duration::from_millis(10)),
+            wait_status => return Ok(wait_status),
+        }
+    }
+}
+
+// Waits for child till it exits or stops, showing a spinner once
+// it has been running for longer than threshold. Shell sleeps till
+// SIGCHLD arrives or spinner is due for its next frame
+fn wait_with_progress(child_pid: Pid, threshold: Duration) -> anyhow::Result<WaitStatus> {
+    let start = Instant::now();
+    let mut spinner = Spinner::new();
+
+    let wait_status = interrupt::with_child_signal_blocked(|| loop {
+        match waitpid(child_pid, Some(WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED))? {
+            WaitStatus::StillAlive => {
+                let elapsed = start.elapsed();
+                let timeout = if elapsed >= threshold {
+                    spinner.tick(elapsed)?;
+                    Spinner::next_frame_in(elapsed)
+                } else {
+                    threshold - elapsed
+                };
+                interrupt::wait_for_child_signal(timeout)?;
+            }
+            wait_status => return anyhow::Ok(wait_status),
+        }
+    })??;
+
+    spinner.clear()?;
+    Ok(wait_status)
+}
+
+// Assignment the way xtrace shows it, values quoted as needed
+fn traced_assignment(assignment: &Assignment) -> String {
+    let operator = if assignment.append { "+=" } else { "=" };
+    let value = match &assignment.value {
+        AssignmentValue::Scalar(value) => quote::quote(value),
+        AssignmentValue::Array(values) => format!("({})", quote::join(values)),
+    };
+    format!("{}{}{}", assignment.name, operator, value)
+}
+
+// Status of a command which only succeeds or fails
+fn exit_status(successful: bool) -> i32 {
+    if successful {
+        0
+    } else {
+        1
+    }
+}
+
+// Fd of shell replaced by redirection of a builtin, None
+// if it was not open before
+struct SavedFd {
+    fd: RawFd,
+    copy: Option<RawFd>,
+}
+
+// Output builtin wrote is flushed before fds it went to are put back
+fn restore_shell_fds(saved_fds: Vec<SavedFd>) -> anyhow::Result<()> {
+    io::stdout().flush()?;
+    io::stderr().flush()?;
+
+    for SavedFd { fd, copy } in saved_fds {
+        match copy {
+            Some(copy) => {
+                dup2(copy, fd)?;
+                close(copy)?;
+            }
+            None => {
+                let _ = close(fd);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        time::{Duration, Instant},
+    };
+
+    use nix::{
+        sys::{
+            signal::{kill, Signal},
+            wait::waitpid,
+        },
+        unistd::getpgid,
+    };
+
+    use crate::command::lexer::Lexer;
+
+    use super::{config::Config, jobs::JobState, profile::StartupProfile, variables::Value, Engine};
+
+    // Trying to use `true` and `false` in tests here
+    // cause they are readily available on UNIX systems
+    // or are easy to replicate behaviour of too
+
+    fn get_tokens(input_str: &str) -> anyhow::Result<Lexer> {
+        let mut lexer = Lexer::new();
+        lexer.scan(input_str)?;
+        Ok(lexer)
+    }
+
+    fn check(input_str: &str) -> Engine {
+        let mut engine = Engine::new(&mut StartupProfile::default());
+
+        let ip_str = input_str.to_string() + "\n";
+        let lexer = get_tokens(&ip_str).expect("lexer failed, check lexer tests");
+
+        engine
+            .parse_and_execute(&lexer.tokens)
+            .expect("expected successful execution");
+
+        engine
+    }
+
+    #[test]
+    fn test_simple_cmd_execution() {
+        let engine = check("ls");
+        assert_eq!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_simple_cmd_with_args_execution() {
+        let engine = check("ls -la");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("ls -la src/");
+        assert_eq!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_with_semicolon_separator() {
+        let engine = check("ls -la ; true");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("false ; true");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("true ; false");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_with_logical_or_separator() {
+        let engine = check("true || true");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("false || false");
+        assert_ne!(engine.last_status, 0);
+
+        let engine = check("true || false");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("false || true");
+        assert_eq!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_with_logical_and_separator() {
+        let engine = check("true && true");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("true && true");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("true && false");
+        assert_ne!(engine.last_status, 0);
+
+        let engine = check("false && true");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_with_negate_exit_status() {
+        let engine = check("true && ! false");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("! false || ! true");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("! true");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_subshell_cmds() {
+        let engine = check("(true)");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("(false)");
+        assert_ne!(engine.last_status, 0);
+
+        let engine = check("(sh -c 'exit 3')");
+        assert_eq!(engine.last_status, 3);
+
+        let dir = std::env::temp_dir().join(format!("dss_subshell_test_{}", std::process::id()));
+        let dir = dir.display();
+        let cwd = std::env::current_dir().unwrap();
+        let engine = check(&format!("(mkdir {dir} && cd {dir} && pwd) && pwd"));
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(std::env::current_dir().unwrap(), cwd);
+
+        // cleanup
+        let engine = check(&format!("rm -r {dir}"));
+        assert_eq!(engine.last_status, 0);
+
+        // Only the subshell exits, with status given to `exit`
+        let engine = check(&format!("(mkdir {dir} && cd {dir} && exit) && pwd && n=1"));
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(engine.exit_code, None);
+        assert_eq!(engine.variables.get_str("n"), Some("1".into()));
+
+        let engine = check(&format!("(cd {dir} && exit 4) ; n=$?"));
+        assert_eq!(engine.variables.get_str("n"), Some("4".into()));
+
+        // cleanup
+        let engine = check(&format!("rm -r {dir}"));
+        assert_eq!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_piped_cmds() {
+        let engine = check(" ls -la | grep c | sort | uniq");
+        assert_eq!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_pipe_stderr_cmds() {
+        // grep only succeeds if stderr of ls reached it
+        let engine = check("ls doesnotexist |& grep -q doesnotexist");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("ls doesnotexist | grep -q doesnotexist");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_redirect_output_ops() {
+        let engine = check("ls > files2");
+        assert_eq!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_redirect_input_ops() {
+        let engine = check("ls > files2");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("rm files2");
+        assert_eq!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_redirect_append_ops() {
+        let engine = check("echo foo >> files2");
+        assert_eq!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_redirect_read_write_ops() {
+        let engine = check("echo foo <> files2");
+        assert_eq!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_redirect_squirrel_output() {
+        let engine = check("ls /tmp/ doesnotexist 2&>1");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_redirect_squirrel_input() {
+        let engine = check("echo foo <&2");
+        assert_eq!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_bg_processes() {
+        let engine = check("ping google.com &");
+        assert_eq!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_integer_assignments() {
+        let engine = check("declare -i n ; n=5+5");
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_str("n"), Some("10".into()));
+
+        let engine = check("declare -i n=2*3 && n+=4");
+        assert_eq!(engine.variables.get_str("n"), Some("10".into()));
+
+        // Without integer attribute it is a plain string
+        let engine = check("n=5+5");
+        assert_eq!(engine.variables.get_str("n"), Some("5+5".into()));
+
+        let engine = check("declare -i n ; n=1/0");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_exported_and_readonly_declarations() {
+        let engine = check("declare -x shared=1 ; declare -r fixed=2 && fixed=3");
+        assert_ne!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_str("fixed"), Some("2".into()));
+
+        let environment = engine.variables.environment();
+        assert!(environment.contains(&"shared=1".to_string()));
+        assert!(!environment.contains(&"fixed=2".to_string()));
+    }
+
+    #[test]
+    fn test_cmd_execution_of_array_declarations() {
+        let engine = check("x='b c' ; declare -a p=(a \"$x\" 'd e')");
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(
+            engine.variables.get("p").map(|var| var.value.clone()),
+            Some(Value::Array(vec!["a".into(), "b c".into(), "d e".into()]))
+        );
+
+        let engine = check("declare -r p=(a) && p=(b)");
+        assert_ne!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_array("p"), Some(vec!["a".into()]));
+    }
+
+    #[test]
+    fn test_cmd_execution_with_prefix_assignments() {
+        let engine = check("v=old ; v=new w=$v sh -c 'test $v$w = newnew' ; s=$?");
+        assert_eq!(engine.variables.get_str("s"), Some("0".into()));
+        assert_eq!(engine.variables.get_str("v"), Some("old".into()));
+        assert_eq!(engine.variables.get_str("w"), None);
+
+        // Command does not run if assignment fails
+        let engine = check("readonly v=1 ; v=2 touch nonexistent_prefix_file");
+        assert_ne!(engine.last_status, 0);
+        assert!(!std::path::Path::new("nonexistent_prefix_file").exists());
+    }
+
+    #[test]
+    fn test_cmd_execution_of_export_builtin() {
+        let engine = check("greeting=hi ; export greeting name=dss ; export -n name");
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_str("name"), Some("dss".into()));
+
+        let environment = engine.variables.environment();
+        assert!(environment.contains(&"greeting=hi".to_string()));
+        assert!(!environment.contains(&"name=dss".to_string()));
+
+        let engine = check("declare -r fixed=1 ; export fixed=2 1x=3");
+        assert_ne!(engine.last_status, 0);
+        assert!(!engine.variables.environment().contains(&"fixed=2".to_string()));
+    }
+
+    #[test]
+    fn test_cmd_execution_of_append_assignments() {
+        let engine = check("s=foo ; s+=bar");
+        assert_eq!(engine.variables.get_str("s"), Some("foobar".into()));
+
+        let engine = check("arr=(a b) ; arr+=(c d)");
+        assert_eq!(
+            engine.variables.get("arr").map(|var| var.value.clone()),
+            Some(Value::Array(vec![
+                "a".into(),
+                "b".into(),
+                "c".into(),
+                "d".into()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_cmd_execution_of_let_builtin() {
+        let engine = check("let \"i = 5\" && let \"i = i + 1\"");
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_str("i"), Some("6".into()));
+
+        let engine = check("let i=3 'j += i * 2'");
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_str("j"), Some("6".into()));
+
+        // Last expression evaluating to 0 is a failure
+        let engine = check("let 1 0");
+        assert_ne!(engine.last_status, 0);
+
+        let engine = check("let 1/0");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_continues_after_parse_errors() {
+        let engine = check("a=1 ) ; b=2");
+        assert_eq!(engine.variables.get_str("a"), None);
+        assert_eq!(engine.variables.get_str("b"), Some("2".into()));
+
+        // Commands chained to the erroneous one are skipped too
+        let engine = check("c=3 && d=4 ) ; e=5");
+        assert_eq!(engine.variables.get_str("c"), None);
+        assert_eq!(engine.variables.get_str("e"), Some("5".into()));
+    }
+
+    #[test]
+    fn test_cmd_execution_of_select_without_words() {
+        // Nothing to choose from, so body never runs
+        let engine = check("select f in ; do n=1 ; done ; m=2");
+        assert_eq!(engine.variables.get_str("n"), None);
+        assert_eq!(engine.variables.get_str("m"), Some("2".into()));
+    }
+
+    #[test]
+    fn test_cmd_execution_of_break_outside_loop() {
+        let engine = check("break ; n=1");
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_str("n"), Some("1".into()));
+    }
+
+    #[test]
+    fn test_cmd_execution_with_cmd_timeout() {
+        let start = std::time::Instant::now();
+        let engine = check("set -o cmdtimeout=1 ; sleep 5");
+        assert_ne!(engine.last_status, 0);
+        assert!(start.elapsed() < std::time::Duration::from_secs(4));
+
+        let engine = check("set -o cmdtimeout=5 ; ls");
+        assert_eq!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_arithmetic_cmd() {
+        let engine = check("i=1 ; (( i += (2+3)*4 )) && ((j = i / 3))");
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_str("i"), Some("21".into()));
+        assert_eq!(engine.variables.get_str("j"), Some("7".into()));
+
+        let engine = check("(( 0 ))");
+        assert_ne!(engine.last_status, 0);
+
+        let engine = check("(( 1/0 ))");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_timed_pipeline() {
+        let engine = check("time ls | wc -l");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("time ls /no/such/dir");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_negated_pipeline() {
+        // Negation applies to status of the last command only
+        let engine = check("! ls | grep -q doesnotexist");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("! ls | grep -q -v doesnotexist");
+        assert_ne!(engine.last_status, 0);
+
+        let engine = check("! (( 0 )) && ! n=1");
+        assert_ne!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_str("n"), Some("1".into()));
+    }
+
+    #[test]
+    fn test_cmd_execution_with_unmatched_globs() {
+        let engine = check("ls /no/such/*.dir");
+        assert_ne!(engine.last_status, 0);
+
+        // Pattern vanishes, leaving plain `ls`
+        let engine = check("set -o nullglob ; ls /no/such/*.dir");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("set -o failglob ; declare n=1 /no/such/*.dir");
+        assert_ne!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_str("n"), None);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_set_builtin() {
+        let engine = check("set -eu -o pipefail +e -- a 'b c' ; n=$# ; second=$2");
+        assert!(engine.options.nounset && engine.options.pipefail && !engine.options.errexit);
+        assert_eq!(engine.variables.get_str("n"), Some("2".into()));
+        assert_eq!(engine.variables.get_str("second"), Some("b c".into()));
+
+        let engine = check("set x y ; set -- ; n=$#");
+        assert_eq!(engine.variables.get_str("n"), Some("0".into()));
+
+        let engine = check("set -q");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_pipelines_with_pipefail() {
+        let engine = check("ls /no/such/dir | cat");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("set -o pipefail ; ls /no/such/dir | cat");
+        assert_ne!(engine.last_status, 0);
+
+        let engine = check("set -o pipefail ; ! ls /no/such/dir | cat");
+        assert_eq!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_with_errexit() {
+        let engine = check("set -e ; sh -c 'exit 3' ; n=1");
+        assert_eq!(engine.exit_code, Some(3));
+        assert_eq!(engine.variables.get_str("n"), None);
+
+        // Failures whose status is tested do not stop the shell
+        let engine = check("set -e ; ! true ; false || true ; false | true ; n=1");
+        assert_eq!(engine.exit_code, None);
+        assert_eq!(engine.variables.get_str("n"), Some("1".into()));
+
+        // Pipeline `&&` skips leaves status of the tested one behind
+        let engine = check("set -e ; false && true ; n=1");
+        assert_eq!(engine.exit_code, None);
+        assert_eq!(engine.variables.get_str("n"), Some("1".into()));
+
+        // Last pipeline of the list is not tested
+        let engine = check("set -e ; false || false ; n=1");
+        assert_eq!(engine.exit_code, Some(1));
+        assert_eq!(engine.variables.get_str("n"), None);
+
+        let engine = check("set -e -o pipefail ; false | true ; n=1");
+        assert_eq!(engine.exit_code, Some(1));
+        assert_eq!(engine.variables.get_str("n"), None);
+    }
+
+    #[test]
+    fn test_pipe_status_of_pipelines() {
+        let pipe_status = |engine: &Engine| engine.variables.get_array("PIPESTATUS");
+
+        let engine = check("false | sh -c 'exit 3' | true");
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(pipe_status(&engine), Some(vec!["1".into(), "3".into(), "0".into()]));
+
+        let engine = check("ls /no/such/dir");
+        assert_eq!(pipe_status(&engine), Some(vec!["2".into()]));
+
+        let engine = check("n=1 ; sleep 0 &");
+        assert_eq!(pipe_status(&engine), Some(vec!["0".into()]));
+    }
+
+    #[test]
+    fn test_exit_status_of_commands_failing_to_run() {
+        let engine = check("doesnotexist | / | ./doesnotexist");
+        assert_ne!(engine.last_status, 0);
+        assert_eq!(
+            engine.variables.get_array("PIPESTATUS"),
+            Some(vec!["127".into(), "126".into(), "127".into()])
+        );
+    }
+
+    #[test]
+    fn test_cmd_execution_of_background_pipelines() {
+        // Shell does not wait for any stage, all of them make up the job
+        let start = Instant::now();
+        let mut engine = check("sleep 2 | cat &");
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(engine.last_status, 0);
+
+        let job = engine.jobs.get(1).expect("pipeline should have been a job");
+        assert_eq!(job.command, "sleep 2 | cat");
+        assert_eq!(job.processes.len(), 2);
+        assert_eq!(job.pgid, job.processes[0].pid);
+        for process in &job.processes {
+            assert_eq!(getpgid(Some(process.pid)), Ok(job.pgid));
+        }
+
+        engine.jobs.wait(1).unwrap();
+        assert_eq!(engine.jobs.get(1).map(|job| job.state), Some(JobState::Done));
+    }
+
+    #[test]
+    fn test_cmd_execution_of_stopped_cmds() {
+        // Shell goes on once command stops, it is left as a job
+        let mut engine = check("sh -c 'kill -STOP $$' ; n=$?");
+        assert_eq!(engine.variables.get_str("n"), Some("147".into()));
+
+        let job = engine.jobs.get(1).expect("stopped command should have been a job");
+        assert_eq!(job.state, JobState::Stopped);
+        assert_eq!(job.command, "sh -c 'kill -STOP $$'");
+
+        let pid = job.processes[0].pid;
+        kill(pid, Signal::SIGKILL).unwrap();
+        let status = waitpid(pid, None).unwrap();
+        assert!(engine.jobs.record_status(status));
+        assert_eq!(engine.jobs.get(1).map(|job| job.state), Some(JobState::Done));
+    }
+
+    #[test]
+    fn test_cmd_execution_with_expansion_limits() {
+        let engine = check("set -o expandmaxsize=10 ; out=$(yes) || n=1");
+        assert_eq!(engine.variables.get_str("out"), None);
+        assert_eq!(engine.variables.get_str("n"), Some("1".into()));
+
+        let engine = check("set -o globmaxmatches=1 ; ls /*");
+        assert_ne!(engine.last_status, 0);
+
+        let engine = check("set +o globmaxmatches ; declare -a paths=(/*)");
+        assert_eq!(engine.last_status, 0);
+        assert!(engine.variables.get_array("paths").is_some_and(|paths| paths.len() > 1));
+    }
+
+    #[test]
+    fn test_cmd_execution_of_type_builtin() {
+        let engine = check("alias ll='ls -la' ; type ll cd exit ls /bin/sh");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("type cd no_such_command");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_history_builtin() {
+        let engine = check("history && history 2");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("history -2");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_true_and_false_builtins() {
+        let engine = check("true && : ignored args && ! false");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("true && false");
+        assert_ne!(engine.last_status, 0);
+
+        let path = std::env::temp_dir().join(format!("dss_colon_test_{}", std::process::id()));
+        std::fs::write(&path, "contents").unwrap();
+        check(&format!(": > {}", path.display()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_cmd_execution_of_test_builtins() {
+        let engine = check("test -d / && [ ! -f / ] && [ 2 -lt 10 -a abc = abc ]");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("[ -f / ]");
+        assert_eq!(engine.last_status, 1);
+
+        // Misuse fails with 2, unlike a false expression
+        let engine = check("[ -d /");
+        assert_eq!(engine.last_status, 2);
+
+        let engine = check("[ 1 -eq ]");
+        assert_eq!(engine.last_status, 2);
+
+        let engine = check("test 1 -eq one");
+        assert_eq!(engine.last_status, 2);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_pwd_builtin() {
+        let engine = check("pwd && pwd -P -L");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("pwd -x");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_command_builtin() {
+        let mut engine = check("alias ls=false");
+        engine.run_str("command ls > /dev/null\n").unwrap();
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("command -v cd ls > /dev/null && command -V exit > /dev/null");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("command -v no_such_command");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_alias_builtins() {
+        let mut engine = check("alias fail=false");
+        engine.run_str("fail\n").unwrap();
+        assert_ne!(engine.last_status, 0);
+
+        // Value can be any command line, its commands are expanded too
+        let mut engine = check("alias fail=false ; alias both='true | fail'");
+        engine.run_str("both\n").unwrap();
+        assert_ne!(engine.last_status, 0);
+        engine.run_str("alias ok='true && n=1'\nok || n=2\n").unwrap();
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_str("n"), Some("1".into()));
+
+        let engine = check("alias fail=false ; unalias fail ; alias fail");
+        assert_ne!(engine.last_status, 0);
+
+        let engine = check("alias yes='echo yes' ; alias yes");
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(engine.aliases.get("yes"), Some(&"echo yes".to_string()));
+    }
+
+    #[test]
+    fn test_cmd_execution_of_inspect_builtin() {
+        let engine = check("inspect ; inspect options fds");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("inspect traces");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_hash_builtin() {
+        let engine = check("ls > /dev/null ; ls > /dev/null ; hash cat");
+        assert_eq!(engine.last_status, 0);
+        let list = engine.command_hash.list();
+        assert!(list.contains("   2\t") && list.contains("   0\t"), "{}", list);
+
+        let engine = check("hash -r ; hash no_such_command");
+        assert_ne!(engine.last_status, 0);
+
+        // Commands are looked up in PATH as it is now
+        let engine = check("ls > /dev/null ; PATH=/no/such/dir ; ls");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_exit() {
+        let engine = check("exit 3 ; n=1");
+        assert_eq!(engine.exit_code(), 3);
+        assert_eq!(engine.variables.get_str("n"), None);
+
+        let engine = check("sh -c 'exit 4' ; exit");
+        assert_eq!(engine.exit_code(), 4);
+
+        // Shell exits with status of its last command
+        let engine = check("true | sh -c 'kill -9 $$'");
+        assert_eq!(engine.exit_code(), 137);
+
+        let engine = check("exit 257");
+        assert_eq!(engine.exit_code(), 1);
+
+        let engine = check("exit abc");
+        assert_eq!(engine.exit_code(), 2);
+
+        // Only the subshell exits
+        let engine = check("(exit 3) || n=1");
+        assert_eq!(engine.exit_code, None);
+        assert_eq!(engine.variables.get_str("n"), Some("1".into()));
+    }
+
+    #[test]
+    fn test_cmd_execution_of_source() {
+        let path = std::env::temp_dir().join(format!("dss_source_test_{}", std::process::id()));
+        std::fs::write(&path, "n=$1\nexit 3\nm=1\n").unwrap();
+
+        let engine = check(&format!("set -- a; source {} b; s=$? p=$1", path.display()));
+        assert_eq!(engine.variables.get_str("n"), Some("b".into()));
+        assert_eq!(engine.variables.get_str("m"), None);
+        assert_eq!(engine.variables.get_str("s"), Some("3".into()));
+        assert_eq!(engine.variables.get_str("p"), Some("a".into()));
+        assert_eq!(engine.exit_code, None);
+
+        let engine = check(&format!(". {}", path.with_extension("missing").display()));
+        assert_eq!(engine.last_status, 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_cmd_execution_with_noglob_prefix() {
+        let engine = check("set -o failglob ; noglob declare n=/no/such/*.dir");
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_str("n"), Some("/no/such/*.dir".into()));
+    }
+
+    #[test]
+    fn test_cmd_execution_with_expansions() {
+        let engine = check("n=$((2 * 3)) ; m=\"$n $(echo a   b)\" ; arr=($(echo x y) $n)");
+        assert_eq!(engine.variables.get_str("n"), Some("6".into()));
+        assert_eq!(engine.variables.get_str("m"), Some("6 a b".into()));
+        assert_eq!(
+            engine.variables.get("arr").map(|var| var.value.clone()),
+            Some(Value::Array(vec!["x".into(), "y".into(), "6".into()]))
+        );
+
+        // Unset variable leaves no words to run
+        let engine = check("$unset");
+        assert_eq!(engine.last_status, 0);
+
+        let engine = check("d=/no/such/dir ; ls $d");
+        assert_ne!(engine.last_status, 0);
+    }
+
+    #[test]
+    fn test_cmd_execution_of_fc_builtin() {
+        let mut engine = check("true");
+        engine.history.push("n=1\n");
+        engine.history.push("m=\"$n$n\"\n");
+
+        let lexer = get_tokens("fc -s n=k n ; n=2 ; fc -s\n").unwrap();
+        engine.parse_and_execute(&lexer.tokens).unwrap();
+        assert_eq!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_str("k"), Some("1".into()));
+        assert_eq!(engine.variables.get_str("m"), Some("22".into()));
+
+        let lexer = get_tokens("fc -e 'sed -i s/1/5/' 1 ; FCEDIT=false ; fc 1\n").unwrap();
+        engine.parse_and_execute(&lexer.tokens).unwrap();
+        assert_ne!(engine.last_status, 0);
+        assert_eq!(engine.variables.get_str("n"), Some("5".into()));
+
+        for input_str in ["fc -s ls", "fc -s =x", "fc -l 1 2 3", "fc 1 2 3", "fc -e", "fc -x"] {
+            let lexer = get_tokens(&format!("{}\n", input_str)).unwrap();
+            engine.parse_and_execute(&lexer.tokens).unwrap();
+            assert_ne!(engine.last_status, 0, "{}", input_str);
+        }
+    }
+
+    #[test]
+    fn test_lineno_follows_script_lines() {
+        let mut engine = check("true");
+        let script = "a=$LINENO\n\nb=$LINENO; (\n\n c=$LINENO\n)\nd=$LINENO\n";
+        engine.execute_script(Path::new("test"), script).unwrap();
+
+        assert_eq!(engine.variables.get_str("a"), Some("1".into()));
+        assert_eq!(engine.variables.get_str("b"), Some("3".into()));
+        assert_eq!(engine.variables.get_str("d"), Some("7".into()));
+    }
+
+    #[test]
+    fn test_reload_keeps_options_changed_in_session() {
+        let mut engine = check("true");
+        let (config, _) = Config::parse("[options]\ncmdtimeout = 5\nnullglob = true\n");
+        assert!(engine.apply_config(config, false).is_empty());
+        engine.loaded_options = engine.options.clone();
+
+        let lexer = get_tokens("set -o cmdtimeout=7 ; set +o nullglob\n").unwrap();
+        engine.parse_and_execute(&lexer.tokens).unwrap();
+
+        // Only changed config entries can conflict with session
+        let (config, _) =
+            Config::parse("prompt = \"% \"\n[options]\ncmdtimeout = 10\nnullglob = true\n");
+        let problems = engine.apply_config(config.clone(), false);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("reload: cmdtimeout was changed in this session"));
+        assert_eq!(engine.options.value("cmdtimeout"), Some("7".into()));
+        assert!(!engine.options.null_glob);
+
+        assert!(engine.apply_config(config, true).is_empty());
+        assert_eq!(engine.options.value("cmdtimeout"), Some("10".into()));
+        assert!(engine.options.null_glob);
+    }
+}
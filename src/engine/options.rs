@@ -0,0 +1,314 @@
+use std::time::Duration;
+
+use crate::errors::ShellError;
+
+// Sorted, same as they are listed
+const OPTION_NAMES: [&str; 16] = [
+    "cmdtimeout",
+    "colorstderr",
+    "dotglob",
+    "errexit",
+    "expandmaxsize",
+    "extendedglob",
+    "failglob",
+    "failhint",
+    "globmaxdepth",
+    "globmaxmatches",
+    "joblogs",
+    "nounset",
+    "nullglob",
+    "pipefail",
+    "progress",
+    "xtrace",
+];
+
+// Limits guarding shell against runaway expansions, e.g. `**` over
+// `/` or `$(cat /dev/zero)`, unless changed in config or with `set`
+const DEFAULT_GLOB_MAX_MATCHES: usize = 100_000;
+const DEFAULT_EXPAND_MAX_SIZE: usize = 16 * 1024 * 1024;
+
+// Single letter flags of `set`, e.g. `set -e`, with options they toggle
+const FLAGS: [(char, &str); 3] = [('e', "errexit"), ('u', "nounset"), ('x', "xtrace")];
+
+// Options toggled with `set -o name` and `set +o name`
+#[derive(Clone, Debug)]
+pub struct Options {
+    // Set with `cmdtimeout=N`, external commands running for more
+    // than N seconds in non-interactive mode get killed
+    pub cmd_timeout: Option<Duration>,
+    // Enables glob qualifiers, e.g. `*(/)`
+    pub extended_glob: bool,
+    // Set with `globmaxdepth=N`, limits how deep `**` descends
+    pub glob_max_depth: Option<usize>,
+    // Set with `globmaxmatches=N`, patterns matching more paths fail
+    pub glob_max_matches: Option<usize>,
+    // Set with `expandmaxsize=N`, words and command substitutions
+    // expanding to more than N bytes fail
+    pub expand_max_size: Option<usize>,
+    // Lets patterns match hidden files
+    pub dot_glob: bool,
+    // Removes patterns matching nothing instead of keeping them
+    pub null_glob: bool,
+    // Fails commands having a pattern that matches nothing
+    pub fail_glob: bool,
+    // Captures output of background jobs to files, shown by `jobs -o`
+    pub job_logs: bool,
+    // Shows a hint when an interactive foreground command keeps
+    // failing, with the last line it wrote to stderr. Commands then
+    // write to a pseudo terminal, their output is copied on from it
+    pub fail_hint: bool,
+    // Shows stderr of interactive foreground commands in
+    // red, it is captured same as for `failhint`
+    pub color_stderr: bool,
+    // Set with `progress=N`, a spinner is shown for interactive
+    // foreground jobs running for more than N seconds
+    pub progress: Option<Duration>,
+    // Pipeline fails if any of its commands does, not only the last one
+    pub pipefail: bool,
+    // `set -e`
+    pub errexit: bool,
+    // `set -u`
+    pub nounset: bool,
+    // `set -x`
+    pub xtrace: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            cmd_timeout: None,
+            extended_glob: false,
+            glob_max_depth: None,
+            glob_max_matches: Some(DEFAULT_GLOB_MAX_MATCHES),
+            expand_max_size: Some(DEFAULT_EXPAND_MAX_SIZE),
+            dot_glob: false,
+            null_glob: false,
+            fail_glob: false,
+            job_logs: false,
+            fail_hint: false,
+            color_stderr: false,
+            progress: None,
+            pipefail: false,
+            errexit: false,
+            nounset: false,
+            xtrace: false,
+        }
+    }
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Option can carry a value, e.g. `cmdtimeout=10`
+    pub fn set(&mut self, option: &str, enable: bool) -> anyhow::Result<()> {
+        let (name, value) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (option, None),
+        };
+
+        match (name, value, enable) {
+            ("cmdtimeout", Some(value), true) => {
+                let secs = parse_secs(value)?;
+
+                // Zero disables the timeout, same as `timeout 0`
+                self.cmd_timeout = (secs > 0).then(|| Duration::from_secs(secs));
+            }
+            ("cmdtimeout", None, true) => {
+                return Err(ShellError::BuiltinError(
+                    "set",
+                    "cmdtimeout: expected cmdtimeout=N".into(),
+                )
+                .into())
+            }
+            ("cmdtimeout", None, false) => self.cmd_timeout = None,
+            ("extendedglob", None, enable) => self.extended_glob = enable,
+            ("dotglob", None, enable) => self.dot_glob = enable,
+            ("nullglob", None, enable) => self.null_glob = enable,
+            ("failglob", None, enable) => self.fail_glob = enable,
+            ("joblogs", None, enable) => self.job_logs = enable,
+            ("failhint", None, enable) => self.fail_hint = enable,
+            ("colorstderr", None, enable) => self.color_stderr = enable,
+            ("pipefail", None, enable) => self.pipefail = enable,
+            ("errexit", None, enable) => self.errexit = enable,
+            ("nounset", None, enable) => self.nounset = enable,
+            ("xtrace", None, enable) => self.xtrace = enable,
+            ("globmaxdepth", Some(value), true) => {
+                let depth = value.parse::<usize>().map_err(|_| {
+                    ShellError::BuiltinError("set", format!("{}: invalid depth", value))
+                })?;
+                self.glob_max_depth = Some(depth);
+            }
+            ("globmaxdepth", None, false) => self.glob_max_depth = None,
+            ("globmaxmatches", Some(value), true) => {
+                self.glob_max_matches = Some(parse_limit(value)?);
+            }
+            ("globmaxmatches", None, false) => self.glob_max_matches = None,
+            ("expandmaxsize", Some(value), true) => {
+                self.expand_max_size = Some(parse_limit(value)?);
+            }
+            ("expandmaxsize", None, false) => self.expand_max_size = None,
+            ("progress", Some(value), true) => {
+                self.progress = Some(Duration::from_secs(parse_secs(value)?));
+            }
+            ("progress", None, false) => self.progress = None,
+            _ => {
+                return Err(ShellError::BuiltinError(
+                    "set",
+                    format!("{}: invalid option name", option),
+                )
+                .into())
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn set_flag(&mut self, flag: char, enable: bool) -> anyhow::Result<()> {
+        let Some((_, option)) = FLAGS.iter().find(|(name, _)| *name == flag) else {
+            let sign = if enable { '-' } else { '+' };
+            let message = format!("{}{}: invalid option", sign, flag);
+            return Err(ShellError::BuiltinError("set", message).into());
+        };
+
+        self.set(option, enable)
+    }
+
+    // Current value of option formatted the way `set -o` lists it
+    pub fn value(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "cmdtimeout" => self
+                .cmd_timeout
+                .map_or("off".to_string(), |timeout| timeout.as_secs().to_string()),
+            "colorstderr" => on_off(self.color_stderr).to_string(),
+            "dotglob" => on_off(self.dot_glob).to_string(),
+            "errexit" => on_off(self.errexit).to_string(),
+            "expandmaxsize" => self
+                .expand_max_size
+                .map_or("off".to_string(), |max| max.to_string()),
+            "extendedglob" => on_off(self.extended_glob).to_string(),
+            "failglob" => on_off(self.fail_glob).to_string(),
+            "failhint" => on_off(self.fail_hint).to_string(),
+            "globmaxdepth" => self
+                .glob_max_depth
+                .map_or("off".to_string(), |depth| depth.to_string()),
+            "globmaxmatches" => self
+                .glob_max_matches
+                .map_or("off".to_string(), |max| max.to_string()),
+            "joblogs" => on_off(self.job_logs).to_string(),
+            "nounset" => on_off(self.nounset).to_string(),
+            "nullglob" => on_off(self.null_glob).to_string(),
+            "pipefail" => on_off(self.pipefail).to_string(),
+            "progress" => self
+                .progress
+                .map_or("off".to_string(), |threshold| threshold.as_secs().to_string()),
+            "xtrace" => on_off(self.xtrace).to_string(),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+
+    // Formats options in the way `set -o` lists them
+    pub fn list(&self) -> String {
+        OPTION_NAMES
+            .iter()
+            .map(|name| format!("{}\t{}\n", name, self.value(name).unwrap_or_default()))
+            .collect()
+    }
+}
+
+fn parse_secs(value: &str) -> anyhow::Result<u64> {
+    value
+        .parse::<u64>()
+        .map_err(|_| ShellError::BuiltinError("set", format!("{}: invalid timeout", value)).into())
+}
+
+fn parse_limit(value: &str) -> anyhow::Result<usize> {
+    value
+        .parse::<usize>()
+        .map_err(|_| ShellError::BuiltinError("set", format!("{}: invalid limit", value)).into())
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Options;
+
+    #[test]
+    fn test_setting_cmd_timeout() {
+        let mut options = Options::new();
+
+        options.set("cmdtimeout=5", true).unwrap();
+        assert_eq!(options.cmd_timeout, Some(Duration::from_secs(5)));
+        assert!(options.list().contains("cmdtimeout\t5\n"));
+
+        options.set("cmdtimeout", false).unwrap();
+        assert_eq!(options.cmd_timeout, None);
+
+        assert!(options.set("cmdtimeout=abc", true).is_err());
+        assert!(options.set("cmdtimeout", true).is_err());
+
+        options.set("progress=2", true).unwrap();
+        assert_eq!(options.progress, Some(Duration::from_secs(2)));
+        assert!(options.list().contains("progress\t2\n"));
+        options.set("progress", false).unwrap();
+        assert_eq!(options.progress, None);
+
+        options.set("globmaxdepth=3", true).unwrap();
+        assert_eq!(options.glob_max_depth, Some(3));
+        options.set("globmaxdepth", false).unwrap();
+        assert_eq!(options.glob_max_depth, None);
+
+        options.set("globmaxmatches=10", true).unwrap();
+        assert_eq!(options.glob_max_matches, Some(10));
+        options.set("expandmaxsize", false).unwrap();
+        assert_eq!(options.expand_max_size, None);
+        assert!(options.list().contains("expandmaxsize\toff\n"));
+        assert!(options.set("globmaxmatches=-1", true).is_err());
+        assert!(options.set("nosuchoption", true).is_err());
+    }
+
+    #[test]
+    fn test_setting_boolean_options() {
+        let mut options = Options::new();
+        assert_eq!(
+            options.list(),
+            "cmdtimeout\toff\ncolorstderr\toff\ndotglob\toff\nerrexit\toff\n\
+             expandmaxsize\t16777216\nextendedglob\toff\nfailglob\toff\nfailhint\toff\n\
+             globmaxdepth\toff\nglobmaxmatches\t100000\njoblogs\toff\nnounset\toff\n\
+             nullglob\toff\npipefail\toff\nprogress\toff\nxtrace\toff\n"
+        );
+
+        options.set("extendedglob", true).unwrap();
+        assert!(options.extended_glob);
+        assert!(options.list().contains("extendedglob\ton\n"));
+
+        options.set("extendedglob", false).unwrap();
+        assert!(!options.extended_glob);
+
+        assert!(options.set("extendedglob=1", true).is_err());
+
+        options.set("nullglob", true).unwrap();
+        options.set("failglob", true).unwrap();
+        options.set("dotglob", true).unwrap();
+        assert!(options.null_glob && options.fail_glob && options.dot_glob);
+        assert!(options.list().contains("nullglob\ton\n"));
+
+        options.set_flag('e', true).unwrap();
+        options.set_flag('x', true).unwrap();
+        options.set_flag('x', false).unwrap();
+        assert!(options.errexit && !options.xtrace);
+        assert!(options.set_flag('q', true).is_err());
+    }
+}
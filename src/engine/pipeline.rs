@@ -0,0 +1,225 @@
+use std::path::PathBuf;
+
+use nix::{
+    errno::Errno,
+    sys::{
+        signal::Signal,
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::{setpgid, Pid},
+};
+
+use super::jobs::{self, TerminalHandover};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    // Forked, not waited for yet
+    Running(Pid),
+    // Exit status of stage
+    Done(i32),
+    // Stopped while it was waited for, e.g. by Ctrl-Z
+    Stopped(Pid, Signal),
+    // Exited or stopped before pipeline got waited for, e.g. once
+    // SIGCHLD arrived while rest of stages were being started
+    Reaped(WaitStatus),
+}
+
+// How waiting for stages of pipeline ended
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Waited {
+    // Exit statuses of all stages
+    Done(Vec<i32>),
+    // Some of the stages got stopped, which makes pipeline a job.
+    // Holds statuses stages reported, for job to start off with.
+    Stopped(Vec<WaitStatus>),
+}
+
+// Stages of the pipeline being run. They are started one at a time,
+// each reading from pipe of the previous one, and are waited for
+// once the last one is done.
+#[derive(Clone, Debug, Default)]
+pub struct Pipeline {
+    // Stages started so far, in order. Builtins are done by the
+    // time next stage starts, as is last stage run in foreground
+    pub stages: Vec<Stage>,
+    // Commands of stages, making up command of job
+    pub commands: Vec<String>,
+    // Pipeline ending with `&`, its stages become a job
+    pub background: bool,
+    // Where output of last stage of job goes with `joblogs` set
+    pub log: Option<PathBuf>,
+    // Whether stages go in a process group of their own, which is
+    // the case when they make up a job shell controls
+    pub own_group: bool,
+    // Group of the first stage, rest of stages join it
+    pub pgid: Option<Pid>,
+    // Foreground pipeline in its own group gets the terminal
+    pub terminal: Option<TerminalHandover>,
+}
+
+impl Pipeline {
+    // Group a stage about to be forked goes in, zero meaning a new
+    // one named after the stage itself
+    pub fn group_for_next_stage(&self) -> Option<Pid> {
+        self.own_group
+            .then(|| self.pgid.unwrap_or(Pid::from_raw(0)))
+    }
+
+    // Both parent and child set group of the stage, so that it is in
+    // place before either goes on. If child did it and already ran
+    // exec, EACCES is returned which is fine.
+    pub fn join_group(&mut self, pid: Pid) -> nix::Result<Option<Pid>> {
+        let Some(pgid) = self.group_for_next_stage() else {
+            return Ok(None);
+        };
+
+        let pgid = if pgid.as_raw() == 0 { pid } else { pgid };
+        match setpgid(pid, pgid) {
+            Ok(()) | Err(Errno::EACCES) => {}
+            Err(err) => return Err(err),
+        }
+
+        let new_group = self.pgid.is_none();
+        self.pgid = Some(pgid);
+        Ok(new_group.then_some(pgid))
+    }
+
+    pub fn pids(&self) -> Vec<Pid> {
+        self.stages
+            .iter()
+            .filter_map(|stage| match stage {
+                Stage::Running(pid) | Stage::Stopped(pid, _) => Some(*pid),
+                Stage::Reaped(status) => status.pid(),
+                Stage::Done(_) => None,
+            })
+            .collect()
+    }
+
+    // Returns whether the process reaped was one of stages
+    pub fn record_status(&mut self, status: WaitStatus) -> bool {
+        let Some(stage) = self
+            .stages
+            .iter_mut()
+            .find(|stage| matches!(stage, Stage::Running(pid) if Some(*pid) == status.pid()))
+        else {
+            return false;
+        };
+
+        if matches!(status, WaitStatus::Continued(_)) {
+            return true;
+        }
+        *stage = Stage::Reaped(status);
+        true
+    }
+
+    // Statuses of stages already reaped, for job stages make up
+    pub fn reaped(&self) -> Vec<WaitStatus> {
+        self.stages
+            .iter()
+            .filter_map(|stage| match stage {
+                Stage::Reaped(status) => Some(*status),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Waits for stages which were not waited for yet, till they
+    // exit or stop
+    pub fn wait(&mut self) -> nix::Result<Waited> {
+        let mut statuses = vec![];
+        let mut reported = vec![];
+        for stage in std::mem::take(&mut self.stages) {
+            let status = match stage {
+                Stage::Running(pid) => waitpid(pid, Some(WaitPidFlag::WUNTRACED))?,
+                Stage::Stopped(pid, signal) => WaitStatus::Stopped(pid, signal),
+                Stage::Reaped(status) => status,
+                Stage::Done(status) => {
+                    statuses.push(status);
+                    continue;
+                }
+            };
+            reported.push(status);
+            statuses.extend(jobs::exit_code_of(status));
+        }
+
+        if let Some(terminal) = self.terminal.take() {
+            terminal.take_back()?;
+        }
+
+        if reported
+            .iter()
+            .any(|status| matches!(status, WaitStatus::Stopped(..)))
+        {
+            return Ok(Waited::Stopped(reported));
+        }
+        Ok(Waited::Done(statuses))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::{
+        sys::{signal::Signal, wait::WaitStatus},
+        unistd::Pid,
+    };
+
+    use super::{Pipeline, Stage, Waited};
+
+    #[test]
+    fn test_pipeline_groups() {
+        let mut pipeline = Pipeline::default();
+        assert_eq!(pipeline.group_for_next_stage(), None);
+
+        pipeline.own_group = true;
+        assert_eq!(pipeline.group_for_next_stage(), Some(Pid::from_raw(0)));
+
+        pipeline.pgid = Some(Pid::from_raw(42));
+        assert_eq!(pipeline.group_for_next_stage(), Some(Pid::from_raw(42)));
+    }
+
+    #[test]
+    fn test_pipeline_statuses() {
+        let mut pipeline = Pipeline {
+            stages: vec![
+                Stage::Done(1),
+                Stage::Running(Pid::from_raw(42)),
+                Stage::Done(0),
+            ],
+            ..Pipeline::default()
+        };
+        assert_eq!(pipeline.pids(), [Pid::from_raw(42)]);
+
+        pipeline.stages.remove(1);
+        assert_eq!(pipeline.wait(), Ok(Waited::Done(vec![1, 0])));
+        assert!(pipeline.stages.is_empty());
+
+        let pid = Pid::from_raw(42);
+        pipeline.stages = vec![Stage::Done(0), Stage::Stopped(pid, Signal::SIGTSTP)];
+        assert_eq!(pipeline.pids(), [pid]);
+        assert_eq!(
+            pipeline.wait(),
+            Ok(Waited::Stopped(vec![WaitStatus::Stopped(
+                pid,
+                Signal::SIGTSTP
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_recording_of_reaped_stages() {
+        let (first, second) = (Pid::from_raw(42), Pid::from_raw(43));
+        let mut pipeline = Pipeline {
+            stages: vec![Stage::Running(first), Stage::Running(second)],
+            ..Pipeline::default()
+        };
+
+        assert!(!pipeline.record_status(WaitStatus::Exited(Pid::from_raw(44), 0)));
+        assert!(pipeline.record_status(WaitStatus::Continued(second)));
+        assert!(pipeline.record_status(WaitStatus::Exited(first, 3)));
+        assert_eq!(pipeline.pids(), [first, second]);
+        assert_eq!(pipeline.reaped(), [WaitStatus::Exited(first, 3)]);
+
+        pipeline.stages.pop();
+        assert_eq!(pipeline.wait(), Ok(Waited::Done(vec![3])));
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::{Duration, Instant};
+
+// Time taken by each phase of shell startup, printed
+// when dss is started with `--profile`
+#[derive(Debug, Default)]
+pub struct StartupProfile {
+    enabled: bool,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl StartupProfile {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            phases: vec![],
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn measure<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(phase, start.elapsed());
+        result
+    }
+
+    fn record(&mut self, phase: &'static str, duration: Duration) {
+        self.phases.push((phase, duration));
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = String::from("startup profile:\n");
+        for (phase, duration) in &self.phases {
+            report.push_str(&format_phase(phase, *duration));
+        }
+
+        let total = self.phases.iter().map(|(_, duration)| *duration).sum();
+        report.push_str(&format_phase("total", total));
+        report
+    }
+}
+
+fn format_phase(phase: &str, duration: Duration) -> String {
+    format!("  {:<12}{:>10.3}ms\n", phase, duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::StartupProfile;
+
+    #[test]
+    fn test_profile_report() {
+        let mut profile = StartupProfile::new(true);
+        profile.record("PATH scan", Duration::from_micros(1500));
+        profile.record("inputrc", Duration::from_micros(250));
+
+        assert_eq!(
+            profile.report(),
+            "startup profile:\n  PATH scan        1.500ms\n  inputrc          0.250ms\n  total            1.750ms\n"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+// Long lived shell for tooling, started with `dss --server <socket>`.
+// Every line a client sends over the Unix socket is run as a command
+// of the same shell, so variables, options and cwd carry over from one
+// command, and client, to the next. Clients are served one at a time.
+// Reply to every command is its output and exit status, framed as:
+//
+//   stdout <len>\n<len bytes>
+//   stderr <len>\n<len bytes>
+//   status <code>\n
+//
+// Commands see stdin at EOF. `exit` replies and then stops the server.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    os::unix::{
+        net::{UnixListener, UnixStream},
+        prelude::{FromRawFd, RawFd},
+    },
+    path::Path,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use nix::{
+    fcntl::{fcntl, open, FcntlArg, OFlag},
+    sys::stat::Mode,
+    unistd::{close, dup2, pipe2},
+};
+
+use crate::frontend::write_to_stderr;
+
+use super::{capture::DRAIN_TIMEOUT, Engine};
+
+struct Reply {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    status: i32,
+}
+
+impl Reply {
+    fn encode(&self) -> Vec<u8> {
+        let mut reply = format!("stdout {}\n", self.stdout.len()).into_bytes();
+        reply.extend_from_slice(&self.stdout);
+        reply.extend_from_slice(format!("stderr {}\n", self.stderr.len()).as_bytes());
+        reply.extend_from_slice(&self.stderr);
+        reply.extend_from_slice(format!("status {}\n", self.status).as_bytes());
+        reply
+    }
+}
+
+// Failing to bind exits with 2, same as invalid options to dss
+pub fn run(engine: &mut Engine, path: &Path) -> anyhow::Result<()> {
+    let listener = match bind(path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            write_to_stderr(&format!("dss: --server: {}: {}\n", path.display(), err))?;
+            std::process::exit(2);
+        }
+    };
+
+    let null = open("/dev/null", OFlag::O_RDONLY | OFlag::O_CLOEXEC, Mode::empty())?;
+    dup2(null, 0)?;
+    close(null)?;
+
+    for stream in listener.incoming() {
+        // Client going away midway only ends its own connection
+        match serve(engine, stream?) {
+            Ok(true) => break,
+            Ok(false) => {}
+            Err(err) => write_to_stderr(&format!("dss: --server: {}\n", err))?,
+        }
+    }
+
+    fs::remove_file(path).ok();
+    Ok(())
+}
+
+// Socket left behind by a server which is gone gets replaced
+fn bind(path: &Path) -> io::Result<UnixListener> {
+    match UnixListener::bind(path) {
+        Err(err) if err.kind() == ErrorKind::AddrInUse => match UnixStream::connect(path) {
+            Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+                fs::remove_file(path)?;
+                UnixListener::bind(path)
+            }
+            _ => Err(err),
+        },
+        result => result,
+    }
+}
+
+// Returns whether the server is to stop
+fn serve(engine: &mut Engine, stream: UnixStream) -> anyhow::Result<bool> {
+    let mut writer = stream.try_clone()?;
+
+    for line in BufReader::new(stream).lines() {
+        let reply = run_command(engine, &line?)?;
+        writer.write_all(&reply.encode())?;
+
+        if engine.exit_code.is_some() {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+fn run_command(engine: &mut Engine, command: &str) -> anyhow::Result<Reply> {
+    let stdout = OutputCapture::start(1)?;
+    let stderr = OutputCapture::start(2)?;
+
+    let result = engine.execute_script(Path::new("server"), command);
+
+    let mut reply = Reply {
+        stdout: stdout.finish()?,
+        stderr: stderr.finish()?,
+        status: 0,
+    };
+
+    if let Err(err) = result {
+        reply.stderr.extend_from_slice(err.to_string().as_bytes());
+        engine.last_status = 1;
+    }
+    reply.status = engine.exit_code();
+
+    Ok(reply)
+}
+
+// Points fd of shell to a pipe for the time a command runs,
+// collecting everything written to it
+struct OutputCapture {
+    fd: RawFd,
+    saved_fd: RawFd,
+    output: Arc<Mutex<Vec<u8>>>,
+    done: mpsc::Receiver<()>,
+}
+
+impl OutputCapture {
+    fn start(fd: RawFd) -> anyhow::Result<Self> {
+        io::stdout().flush()?;
+        io::stderr().flush()?;
+
+        let saved_fd = fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(fd))?;
+        let (read_fd, write_fd) = pipe2(OFlag::O_CLOEXEC)?;
+        dup2(write_fd, fd)?;
+        close(write_fd)?;
+
+        let output = Arc::new(Mutex::new(vec![]));
+        let (done_sender, done) = mpsc::channel();
+
+        let reader_output = Arc::clone(&output);
+        thread::spawn(move || {
+            let mut reader = unsafe { File::from_raw_fd(read_fd) };
+            let mut buf = [0; 4096];
+
+            while let Ok(len) = reader.read(&mut buf) {
+                if len == 0 {
+                    break;
+                }
+
+                if let Ok(mut output) = reader_output.lock() {
+                    output.extend_from_slice(&buf[..len]);
+                }
+            }
+
+            let _ = done_sender.send(());
+        });
+
+        Ok(Self {
+            fd,
+            saved_fd,
+            output,
+            done,
+        })
+    }
+
+    // Background jobs can keep the pipe open, their output
+    // is only collected till the command is done
+    fn finish(self) -> anyhow::Result<Vec<u8>> {
+        io::stdout().flush()?;
+        io::stderr().flush()?;
+
+        dup2(self.saved_fd, self.fd)?;
+        close(self.saved_fd)?;
+
+        let _ = self.done.recv_timeout(DRAIN_TIMEOUT);
+        let output = match self.output.lock() {
+            Ok(mut output) => std::mem::take(&mut *output),
+            Err(_) => vec![],
+        };
+
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Reply;
+
+    #[test]
+    fn test_reply_framing() {
+        let reply = Reply {
+            stdout: b"one\ntwo\n".to_vec(),
+            stderr: vec![],
+            status: 1,
+        };
+
+        assert_eq!(
+            String::from_utf8(reply.encode()).unwrap(),
+            "stdout 8\none\ntwo\nstderr 0\nstatus 1\n"
+        );
+    }
+}
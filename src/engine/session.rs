@@ -0,0 +1,144 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+// State of a session which `dss --resume` restores, saved to
+// $XDG_STATE_HOME/dss/session or ~/.local/state/dss/session
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SessionState {
+    pub cwd: PathBuf,
+    // Top of the stack is last, same as `Engine::dirstack`
+    pub dirstack: Vec<PathBuf>,
+    pub jobs: Vec<JobSummary>,
+}
+
+// Background job which was still running when state got saved
+#[derive(Clone, Debug, PartialEq)]
+pub struct JobSummary {
+    pub pid: i32,
+    pub command: String,
+}
+
+impl SessionState {
+    pub fn load() -> Option<Self> {
+        let contents = fs::read_to_string(state_file()?).ok()?;
+        Some(Self::parse(&contents))
+    }
+
+    // State is written to a temporary file first and then renamed,
+    // so that a crash midway never leaves a partially written file
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = state_file() else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, self.serialize())?;
+        fs::rename(&tmp_path, &path)?;
+
+        Ok(())
+    }
+
+    // One entry per line, e.g. `cwd /home/dss` or `job 42 sleep 10`.
+    // Entries having a newline can not be saved and are skipped
+    pub fn serialize(&self) -> String {
+        let mut lines = vec![format!("cwd {}", self.cwd.display())];
+        for dir in &self.dirstack {
+            lines.push(format!("dir {}", dir.display()));
+        }
+        for job in &self.jobs {
+            lines.push(format!("job {} {}", job.pid, job.command));
+        }
+
+        lines
+            .into_iter()
+            .filter(|line| !line.contains('\n'))
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    // Unknown or malformed lines are ignored, state is best effort
+    pub fn parse(contents: &str) -> Self {
+        let mut state = Self::default();
+
+        for line in contents.lines() {
+            let Some((kind, value)) = line.split_once(' ') else {
+                continue;
+            };
+
+            match kind {
+                "cwd" => state.cwd = PathBuf::from(value),
+                "dir" => state.dirstack.push(PathBuf::from(value)),
+                "job" => {
+                    let Some((pid, command)) = value.split_once(' ') else {
+                        continue;
+                    };
+                    if let Ok(pid) = pid.parse::<i32>() {
+                        state.jobs.push(JobSummary {
+                            pid,
+                            command: command.to_string(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        state
+    }
+}
+
+// Directory for files dss keeps across sessions
+pub fn state_dir() -> Option<PathBuf> {
+    let state_home = match (env::var_os("XDG_STATE_HOME"), env::var_os("HOME")) {
+        (Some(state_home), _) => PathBuf::from(state_home),
+        (None, Some(home)) => Path::new(&home).join(".local/state"),
+        (None, None) => return None,
+    };
+
+    Some(state_home.join("dss"))
+}
+
+fn state_file() -> Option<PathBuf> {
+    Some(state_dir()?.join("session"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{JobSummary, SessionState};
+
+    #[test]
+    fn test_session_state_round_trip() {
+        let state = SessionState {
+            cwd: PathBuf::from("/home/dss/src code"),
+            dirstack: vec![PathBuf::from("/tmp"), PathBuf::from("/a\nb")],
+            jobs: vec![JobSummary {
+                pid: 42,
+                command: "sleep 10".into(),
+            }],
+        };
+
+        let serialized = state.serialize();
+        assert_eq!(
+            serialized,
+            "cwd /home/dss/src code\ndir /tmp\njob 42 sleep 10\n"
+        );
+
+        let parsed = SessionState::parse(&serialized);
+        assert_eq!(parsed.cwd, state.cwd);
+        assert_eq!(parsed.dirstack, vec![PathBuf::from("/tmp")]);
+        assert_eq!(parsed.jobs, state.jobs);
+
+        assert_eq!(
+            SessionState::parse("garbage\njob x y\n"),
+            SessionState::default()
+        );
+    }
+}
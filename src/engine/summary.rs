@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use crate::frontend::{display_width, truncate_to_width};
+
+// Commands longer than this are cut in the report
+const COMMAND_WIDTH: usize = 50;
+
+// Status and duration of every top level command of a script,
+// printed after it when dss is started with `--summary`
+#[derive(Clone, Debug, Default)]
+pub struct ScriptSummary {
+    commands: Vec<CommandRecord>,
+}
+
+#[derive(Clone, Debug)]
+struct CommandRecord {
+    line: usize,
+    command: String,
+    successful: bool,
+    duration: Duration,
+}
+
+impl ScriptSummary {
+    pub fn record(&mut self, line: usize, command: &str, successful: bool, duration: Duration) {
+        self.commands.push(CommandRecord {
+            line,
+            command: command.to_string(),
+            successful,
+            duration,
+        });
+    }
+
+    fn failed(&self) -> usize {
+        self.commands
+            .iter()
+            .filter(|record| !record.successful)
+            .count()
+    }
+
+    // Script which ends successfully still fails if any of its commands did
+    pub fn exit_code(&self, exit_code: i32) -> i32 {
+        if exit_code == 0 && self.failed() > 0 {
+            return 1;
+        }
+
+        exit_code
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = String::from("summary:\n  line  status  time      command\n");
+        for record in &self.commands {
+            report.push_str(&format!(
+                "  {:>4}  {:<6}  {:>7.3}s  {}\n",
+                record.line,
+                if record.successful { "ok" } else { "failed" },
+                record.duration.as_secs_f64(),
+                summarize(&record.command)
+            ));
+        }
+
+        report.push_str(&format!(
+            "  {} commands, {} failed\n",
+            self.commands.len(),
+            self.failed()
+        ));
+        report
+    }
+}
+
+// Only first line of multiline commands is shown
+fn summarize(command: &str) -> String {
+    let mut lines = command.trim().lines();
+    let first_line = lines.next().unwrap_or_default();
+    let multiline = lines.next().is_some();
+
+    if display_width(first_line) > COMMAND_WIDTH {
+        return truncate_to_width(first_line, COMMAND_WIDTH);
+    }
+
+    if multiline {
+        return format!("{} ...", first_line);
+    }
+
+    first_line.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::ScriptSummary;
+
+    #[test]
+    fn test_summary_report() {
+        let mut summary = ScriptSummary::default();
+        summary.record(1, "echo hi\n", true, Duration::from_millis(2));
+        summary.record(3, "(\n  false\n)\n", false, Duration::from_millis(1500));
+        summary.record(6, &format!("echo {}", "x".repeat(60)), true, Duration::ZERO);
+
+        assert_eq!(
+            summary.report(),
+            "summary:\n  line  status  time      command\n\
+             \x20    1  ok        0.002s  echo hi\n\
+             \x20    3  failed    1.500s  ( ...\n\
+             \x20    6  ok        0.000s  echo xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx...\n\
+             \x20 3 commands, 1 failed\n"
+        );
+
+        assert_eq!(summary.exit_code(0), 1);
+        assert_eq!(summary.exit_code(4), 4);
+        assert_eq!(ScriptSummary::default().exit_code(0), 0);
+    }
+}
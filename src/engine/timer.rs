@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+use nix::sys::{
+    resource::{getrusage, Usage, UsageWho},
+    time::{TimeVal, TimeValLike},
+};
+
+// Measures a pipeline prefixed with `time`, user and
+// sys times are of children reaped while it ran
+pub struct PipelineTimer {
+    start: Instant,
+    children_usage: Usage,
+}
+
+impl PipelineTimer {
+    pub fn start() -> anyhow::Result<Self> {
+        Ok(Self {
+            start: Instant::now(),
+            children_usage: getrusage(UsageWho::RUSAGE_CHILDREN)?,
+        })
+    }
+
+    // Summary in the same format as bash
+    pub fn report(&self) -> anyhow::Result<String> {
+        let real = self.start.elapsed();
+        let children_usage = getrusage(UsageWho::RUSAGE_CHILDREN)?;
+        let user = children_usage.user_time() - self.children_usage.user_time();
+        let sys = children_usage.system_time() - self.children_usage.system_time();
+
+        Ok(format!(
+            "\nreal\t{}\nuser\t{}\nsys\t{}\n",
+            format_duration(real),
+            format_duration(to_duration(user)),
+            format_duration(to_duration(sys))
+        ))
+    }
+}
+
+fn to_duration(time_val: TimeVal) -> Duration {
+    Duration::from_micros(time_val.num_microseconds().max(0) as u64)
+}
+
+// e.g. 1m2.345s
+fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    format!(
+        "{}m{}.{:03}s",
+        millis / 60_000,
+        millis % 60_000 / 1000,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::format_duration;
+
+    #[test]
+    fn test_formatting_of_durations() {
+        assert_eq!(format_duration(Duration::from_millis(0)), "0m0.000s");
+        assert_eq!(format_duration(Duration::from_millis(62_345)), "1m2.345s");
+    }
+}
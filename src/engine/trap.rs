@@ -0,0 +1,108 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use nix::sys::signal::Signal;
+
+use crate::command::quote::quote;
+
+// What a trap is set for, EXIT being shell exiting
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Condition {
+    Exit,
+    Signal(Signal),
+}
+
+impl Condition {
+    // Signals can be named with or without SIG prefix, or by number
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.to_uppercase();
+        if name == "EXIT" || name == "0" {
+            return Some(Self::Exit);
+        }
+
+        if let Ok(number) = name.parse::<i32>() {
+            return Signal::try_from(number).ok().map(Self::Signal);
+        }
+
+        let name = if name.starts_with("SIG") {
+            name
+        } else {
+            format!("SIG{}", name)
+        };
+        Signal::from_str(&name).ok().map(Self::Signal)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Exit => "EXIT",
+            Self::Signal(signal) => signal.as_str(),
+        }
+    }
+}
+
+// Commands set with `trap`, an empty one ignores the signal
+#[derive(Clone, Debug, Default)]
+pub struct Traps {
+    actions: BTreeMap<Condition, String>,
+}
+
+impl Traps {
+    pub fn set(&mut self, condition: Condition, action: &str) {
+        self.actions.insert(condition, action.to_string());
+    }
+
+    pub fn reset(&mut self, condition: Condition) {
+        self.actions.remove(&condition);
+    }
+
+    pub fn get(&self, condition: Condition) -> Option<&String> {
+        self.actions.get(&condition)
+    }
+
+    // Formats trap the way `trap -p` prints it, so that
+    // output can be fed back to the shell
+    pub fn definition(&self, condition: Condition) -> Option<String> {
+        let action = self.actions.get(&condition)?;
+        Some(format!("trap -- {} {}", quote(action), condition.name()))
+    }
+
+    pub fn conditions(&self) -> Vec<Condition> {
+        self.actions.keys().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::sys::signal::Signal;
+
+    use super::{Condition, Traps};
+
+    #[test]
+    fn test_traps() {
+        assert_eq!(Condition::parse("EXIT"), Some(Condition::Exit));
+        assert_eq!(Condition::parse("0"), Some(Condition::Exit));
+        for name in ["INT", "int", "SIGINT", "2"] {
+            assert_eq!(Condition::parse(name), Some(Condition::Signal(Signal::SIGINT)));
+        }
+        assert_eq!(Condition::parse("NOSUCH"), None);
+        assert_eq!(Condition::parse("100"), None);
+
+        let mut traps = Traps::default();
+        traps.set(Condition::Signal(Signal::SIGTERM), "");
+        traps.set(Condition::Exit, "echo 'bye now'");
+        assert_eq!(
+            traps.conditions(),
+            [Condition::Exit, Condition::Signal(Signal::SIGTERM)]
+        );
+        assert_eq!(
+            traps.definition(Condition::Exit),
+            Some("trap -- \"echo 'bye now'\" EXIT".into())
+        );
+        assert_eq!(
+            traps.definition(Condition::Signal(Signal::SIGTERM)),
+            Some("trap -- '' SIGTERM".into())
+        );
+
+        traps.reset(Condition::Exit);
+        assert_eq!(traps.get(Condition::Exit), None);
+    }
+}
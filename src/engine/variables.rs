@@ -0,0 +1,656 @@
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    fs,
+    os::unix::fs::MetadataExt,
+    path::Path,
+    process,
+    time::Instant,
+};
+
+use nix::{
+    sys::utsname::uname,
+    unistd::{getuid, User},
+};
+
+use crate::{
+    command::{arithmetic, quote::quote, Assignment, AssignmentValue},
+    errors::ShellError,
+};
+
+#[derive(Clone, Debug)]
+pub struct Variables {
+    vars: HashMap<String, Variable>,
+    // State behind dynamic variables, see `Dynamic`
+    random_state: Cell<u32>,
+    seconds_base: u64,
+    seconds_start: Instant,
+    line_number: usize,
+    // Set in restricted mode, variables naming what gets run can not
+    // be assigned then
+    restricted: bool,
+}
+
+// Variables restricted mode keeps as they are
+const RESTRICTED_NAMES: [&str; 4] = ["PATH", "SHELL", "ENV", "DSS_RC"];
+
+impl Default for Variables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Variables whose value is computed every time they are read
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Dynamic {
+    Random,
+    Seconds,
+    LineNo,
+}
+
+impl Dynamic {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "RANDOM" => Some(Self::Random),
+            "SECONDS" => Some(Self::Seconds),
+            "LINENO" => Some(Self::LineNo),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Variable {
+    pub value: Value,
+    // Set with `declare -i`, every assignment to
+    // such variable is evaluated arithmetically
+    pub integer: bool,
+    // Passed on in environment of commands run by shell
+    pub exported: bool,
+    // Set with `declare -r` or `readonly`, assigning to
+    // such variable fails, as does unsetting it
+    pub readonly: bool,
+}
+
+// Variable a temporary assignment replaced, None if it was unset
+pub type SavedVariable = (String, Option<Variable>);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Scalar(String),
+    Array(Vec<String>),
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.subsec_nanos())
+            .unwrap_or_default();
+
+        Self {
+            vars: HashMap::new(),
+            random_state: Cell::new(nanos ^ process::id()),
+            seconds_base: 0,
+            seconds_start: Instant::now(),
+            line_number: 0,
+            restricted: false,
+        }
+    }
+
+    // Variables of environment shell was started with, all exported
+    pub fn from_environment() -> Self {
+        let mut variables = Self::new();
+        for (name, value) in std::env::vars_os() {
+            let (Some(name), Some(value)) = (name.to_str(), value.to_str()) else {
+                continue;
+            };
+
+            variables.vars.insert(
+                name.to_string(),
+                Variable {
+                    value: Value::Scalar(value.to_string()),
+                    integer: false,
+                    exported: true,
+                    readonly: false,
+                },
+            );
+        }
+
+        // PWD from parent is kept only if it still names current
+        // directory, it can differ from it by symlinks taken to get there
+        if let Ok(cwd) = std::env::current_dir() {
+            let pwd = variables
+                .get_str("PWD")
+                .filter(|pwd| Path::new(pwd).is_absolute() && is_same_file(Path::new(pwd), &cwd))
+                .unwrap_or_else(|| cwd.display().to_string());
+
+            variables.insert_exported("PWD", pwd);
+        }
+
+        variables.set_login_defaults();
+        variables
+    }
+
+    // Minimal containers can start shell without these, they are
+    // derived the way login would have set them
+    fn set_login_defaults(&mut self) {
+        if let Ok(Some(user)) = User::from_uid(getuid()) {
+            for name in ["USER", "LOGNAME"] {
+                if self.get(name).is_none() {
+                    self.insert_exported(name, user.name.clone());
+                }
+            }
+
+            if self.get("HOME").is_none() {
+                self.insert_exported("HOME", user.dir.to_string_lossy().into_owned());
+            }
+        }
+
+        if self.get("HOSTNAME").is_none() {
+            if let Ok(uts) = uname() {
+                self.insert_exported("HOSTNAME", uts.nodename().to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    fn insert_exported(&mut self, name: &str, value: String) {
+        self.vars.insert(
+            name.to_string(),
+            Variable {
+                value: Value::Scalar(value),
+                integer: false,
+                exported: true,
+                readonly: false,
+            },
+        );
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Variable> {
+        self.vars.get(name)
+    }
+
+    // Referencing an array without a subscript is
+    // same as referencing its first element
+    pub fn get_str(&self, name: &str) -> Option<String> {
+        if let Some(dynamic) = Dynamic::from_name(name) {
+            return self.dynamic_value(dynamic);
+        }
+
+        self.get(name).map(|var| match &var.value {
+            Value::Scalar(value) => value.clone(),
+            Value::Array(values) => values.first().cloned().unwrap_or_default(),
+        })
+    }
+
+    // All elements of an array, a scalar being its only element
+    pub fn get_array(&self, name: &str) -> Option<Vec<String>> {
+        if let Some(dynamic) = Dynamic::from_name(name) {
+            return self.dynamic_value(dynamic).map(|value| vec![value]);
+        }
+
+        self.get(name).map(|var| match &var.value {
+            Value::Scalar(value) => vec![value.clone()],
+            Value::Array(values) => values.clone(),
+        })
+    }
+
+    // Exported variables as `name=value`, for environment of commands.
+    // Arrays are not exported, same as bash
+    pub fn environment(&self) -> Vec<String> {
+        self.names()
+            .into_iter()
+            .filter(|name| {
+                self.vars
+                    .get(*name)
+                    .is_some_and(|var| var.exported && matches!(var.value, Value::Scalar(_)))
+            })
+            .filter_map(|name| Some(format!("{}={}", name, self.get_str(name)?)))
+            .collect()
+    }
+
+    // Line of input the command being run is on, for `$LINENO`
+    pub fn set_line_number(&mut self, line_number: usize) {
+        self.line_number = line_number;
+    }
+
+    pub fn names(&self) -> Vec<&String> {
+        let mut names: Vec<&String> = self.vars.keys().collect();
+        names.sort();
+        names
+    }
+
+    pub fn set_integer_attribute(&mut self, name: &str, integer: bool) {
+        self.entry(name).integer = integer;
+    }
+
+    // Marking an unset variable creates it, same as other attributes
+    pub fn set_exported_attribute(&mut self, name: &str, exported: bool) {
+        self.entry(name).exported = exported;
+    }
+
+    // There is no way back, same as bash
+    pub fn set_restricted(&mut self, restricted: bool) {
+        self.restricted = restricted;
+    }
+
+    pub fn set_readonly_attribute(&mut self, name: &str) {
+        self.entry(name).readonly = true;
+    }
+
+    pub fn set_array_attribute(&mut self, name: &str) {
+        let var = self.vars.entry(name.to_string()).or_insert_with(|| Variable {
+            value: Value::Array(vec![]),
+            integer: false,
+            exported: false,
+            readonly: false,
+        });
+
+        if let Value::Scalar(value) = &var.value {
+            var.value = if value.is_empty() {
+                Value::Array(vec![])
+            } else {
+                Value::Array(vec![value.clone()])
+            };
+        }
+    }
+
+    // Readonly variables, and some more in restricted mode, can not be assigned
+    pub fn check_assignable(&self, name: &str) -> anyhow::Result<()> {
+        if self.vars.get(name).is_some_and(|var| var.readonly) {
+            return Err(ShellError::ReadonlyVariable(name.to_string()).into());
+        }
+        if self.restricted && RESTRICTED_NAMES.contains(&name) {
+            return Err(ShellError::Restricted(name.to_string()).into());
+        }
+
+        Ok(())
+    }
+
+    // Neither readonly variables nor ones restricted mode keeps can be unset
+    pub fn unset(&mut self, name: &str) -> anyhow::Result<()> {
+        if self.vars.get(name).is_some_and(|var| var.readonly) {
+            return Err(ShellError::ReadonlyUnset(name.to_string()).into());
+        }
+        self.check_assignable(name)?;
+
+        self.vars.remove(name);
+        Ok(())
+    }
+
+    pub fn assign(&mut self, assignment: &Assignment) -> anyhow::Result<()> {
+        if let Some(dynamic) = Dynamic::from_name(&assignment.name) {
+            return self.assign_dynamic(dynamic, assignment);
+        }
+
+        // Integer evaluation can itself assign, so work on a copy
+        let old_var = self.vars.get(&assignment.name).cloned();
+        let integer = old_var.as_ref().is_some_and(|var| var.integer);
+        let exported = old_var.as_ref().is_some_and(|var| var.exported);
+        self.check_assignable(&assignment.name)?;
+
+        let value = match &assignment.value {
+            AssignmentValue::Scalar(value) => {
+                let value = self.resolve(value, integer)?;
+                match (assignment.append, old_var) {
+                    // `arr+=value` appends to the first element, same as bash
+                    (true, Some(var)) => match var.value {
+                        Value::Scalar(old) => Value::Scalar(self.append(&old, &value, integer)?),
+                        Value::Array(mut values) => {
+                            match values.first_mut() {
+                                Some(first) => *first = self.append(first, &value, integer)?,
+                                None => values.push(value),
+                            }
+                            Value::Array(values)
+                        }
+                    },
+                    _ => Value::Scalar(value),
+                }
+            }
+            AssignmentValue::Array(values) => {
+                let mut values = values
+                    .iter()
+                    .map(|value| self.resolve(value, integer))
+                    .collect::<anyhow::Result<Vec<String>>>()?;
+
+                match (assignment.append, old_var) {
+                    (true, Some(var)) => {
+                        let mut old_values = match var.value {
+                            Value::Scalar(old) if old.is_empty() => vec![],
+                            Value::Scalar(old) => vec![old],
+                            Value::Array(old_values) => old_values,
+                        };
+                        old_values.append(&mut values);
+                        Value::Array(old_values)
+                    }
+                    _ => Value::Array(values),
+                }
+            }
+        };
+
+        self.vars.insert(
+            assignment.name.clone(),
+            Variable {
+                value,
+                integer,
+                exported,
+                readonly: false,
+            },
+        );
+
+        Ok(())
+    }
+
+    // Assigns and exports variable for the time a command runs, e.g.
+    // `LANG=C sort`. Returns what it replaced, to be put back by
+    // `restore` once command is done
+    pub fn assign_temporarily(
+        &mut self,
+        assignment: &Assignment,
+    ) -> anyhow::Result<SavedVariable> {
+        let old_var = self.vars.get(&assignment.name).cloned();
+        self.assign(assignment)?;
+        self.set_exported_attribute(&assignment.name, true);
+
+        Ok((assignment.name.clone(), old_var))
+    }
+
+    pub fn restore(&mut self, saved: Vec<SavedVariable>) {
+        // Latest first, so that a name assigned twice gets its first value
+        for (name, var) in saved.into_iter().rev() {
+            match var {
+                Some(var) => self.vars.insert(name, var),
+                None => self.vars.remove(&name),
+            };
+        }
+    }
+
+    // Formats variable in the way `declare -p` prints it, so
+    // that output can be fed back to the shell
+    pub fn declaration(&self, name: &str) -> Option<String> {
+        let var = self.get(name)?;
+
+        let mut flags = String::new();
+        if matches!(var.value, Value::Array(_)) {
+            flags.push('a');
+        }
+        if var.integer {
+            flags.push('i');
+        }
+        if var.readonly {
+            flags.push('r');
+        }
+        if var.exported {
+            flags.push('x');
+        }
+        if flags.is_empty() {
+            flags.push('-');
+        }
+
+        let value = match &var.value {
+            Value::Scalar(value) => quote(value),
+            Value::Array(values) => {
+                let elements: Vec<String> = values
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, value)| format!("[{}]={}", idx, quote(value)))
+                    .collect();
+                format!("({})", elements.join(" "))
+            }
+        };
+
+        Some(format!("declare -{} {}={}", flags, name, value))
+    }
+
+    fn entry(&mut self, name: &str) -> &mut Variable {
+        self.vars.entry(name.to_string()).or_insert_with(|| Variable {
+            value: Value::Scalar(String::new()),
+            integer: false,
+            exported: false,
+            readonly: false,
+        })
+    }
+
+    fn dynamic_value(&self, dynamic: Dynamic) -> Option<String> {
+        let value = match dynamic {
+            Dynamic::Random => self.next_random().to_string(),
+            Dynamic::Seconds => {
+                (self.seconds_base + self.seconds_start.elapsed().as_secs()).to_string()
+            }
+            Dynamic::LineNo => self.line_number.to_string(),
+        };
+
+        Some(value)
+    }
+
+    // Assigning to RANDOM seeds it and to SECONDS makes it count on from
+    // the value, LINENO keeps following the shell
+    fn assign_dynamic(&mut self, dynamic: Dynamic, assignment: &Assignment) -> anyhow::Result<()> {
+        let value = match &assignment.value {
+            AssignmentValue::Scalar(value) => value.clone(),
+            AssignmentValue::Array(values) => values.first().cloned().unwrap_or_default(),
+        };
+        let number = arithmetic::evaluate(&value, self).unwrap_or_default();
+
+        match dynamic {
+            Dynamic::Random => self.random_state.set(number as u32),
+            Dynamic::Seconds => {
+                self.seconds_base = number.max(0) as u64;
+                self.seconds_start = Instant::now();
+            }
+            Dynamic::LineNo => {}
+        }
+
+        Ok(())
+    }
+
+    // Linear congruential generator, numbers are in 0..=32767 like bash
+    fn next_random(&self) -> u32 {
+        let state = self
+            .random_state
+            .get()
+            .wrapping_mul(1103515245)
+            .wrapping_add(12345);
+        self.random_state.set(state);
+        (state >> 16) & 0x7fff
+    }
+
+    fn resolve(&mut self, value: &str, integer: bool) -> anyhow::Result<String> {
+        if integer {
+            return Ok(arithmetic::evaluate(value, self)?.to_string());
+        }
+
+        Ok(value.to_string())
+    }
+
+    // `+=` on integer variables adds instead of concatenating
+    fn append(&mut self, old: &str, value: &str, integer: bool) -> anyhow::Result<String> {
+        if integer {
+            let sum = format!("{}+{}", old, value);
+            return Ok(arithmetic::evaluate(&sum, self)?.to_string());
+        }
+
+        Ok(format!("{}{}", old, value))
+    }
+}
+
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use nix::{
+        sys::utsname::uname,
+        unistd::{getuid, User},
+    };
+
+    use crate::command::{Assignment, AssignmentValue};
+
+    use super::{is_same_file, Value, Variables};
+
+    fn assign(variables: &mut Variables, word: &str) {
+        let assignment = Assignment::parse(word).expect("expected a valid assignment word");
+        variables
+            .assign(&assignment)
+            .expect("assignment should have succeeded");
+    }
+
+    #[test]
+    fn test_integer_attribute_evaluates_assignments() {
+        let mut variables = Variables::new();
+        variables.set_integer_attribute("n", true);
+
+        assign(&mut variables, "n=5+5");
+        assert_eq!(variables.get_str("n"), Some("10".into()));
+
+        assign(&mut variables, "n+=2*3");
+        assert_eq!(variables.get_str("n"), Some("16".into()));
+    }
+
+    #[test]
+    fn test_append_to_strings() {
+        let mut variables = Variables::new();
+
+        assign(&mut variables, "s=foo");
+        assign(&mut variables, "s+=bar");
+        assert_eq!(variables.get_str("s"), Some("foobar".into()));
+
+        // Appending to unset variable is same as assigning
+        assign(&mut variables, "t+=baz");
+        assert_eq!(variables.get_str("t"), Some("baz".into()));
+    }
+
+    #[test]
+    fn test_append_to_arrays() {
+        let mut variables = Variables::new();
+
+        let mut assignment = Assignment {
+            name: "arr".into(),
+            value: AssignmentValue::Array(vec!["a".into(), "b".into()]),
+            append: false,
+        };
+        variables.assign(&assignment).unwrap();
+
+        assignment.value = AssignmentValue::Array(vec!["c".into()]);
+        assignment.append = true;
+        variables.assign(&assignment).unwrap();
+
+        assert_eq!(
+            variables.get("arr").unwrap().value,
+            Value::Array(vec!["a".into(), "b".into(), "c".into()])
+        );
+
+        // Scalar append goes to the first element
+        assign(&mut variables, "arr+=x");
+        assert_eq!(variables.get_str("arr"), Some("ax".into()));
+    }
+
+    #[test]
+    fn test_dynamic_variables() {
+        let mut variables = Variables::new();
+
+        assign(&mut variables, "RANDOM=42");
+        let first = variables.get_str("RANDOM").unwrap();
+        let second = variables.get_str("RANDOM").unwrap();
+        assert_ne!(first, second);
+        assert!(second.parse::<u32>().unwrap() <= 32767);
+
+        // Same seed gives same sequence
+        assign(&mut variables, "RANDOM=42");
+        assert_eq!(variables.get_str("RANDOM"), Some(first));
+
+        assign(&mut variables, "SECONDS=100");
+        assert_eq!(variables.get_str("SECONDS"), Some("100".into()));
+
+        variables.set_line_number(7);
+        assign(&mut variables, "LINENO=1");
+        assert_eq!(variables.get_str("LINENO"), Some("7".into()));
+
+    }
+
+    #[test]
+    fn test_environment_of_commands() {
+        let mut variables = Variables::from_environment();
+        let path = std::env::var("PATH").unwrap();
+        assert!(variables.environment().contains(&format!("PATH={}", path)));
+
+        // Assigning keeps variable exported, new variables are not
+        assign(&mut variables, "PATH=/no/bin");
+        assign(&mut variables, "shell_only=1");
+        let environment = variables.environment();
+        assert!(environment.contains(&"PATH=/no/bin".to_string()));
+        assert!(!environment.iter().any(|var| var.starts_with("shell_only=")));
+
+        // Inherited PWD can name it through symlinks
+        let cwd = std::env::current_dir().unwrap();
+        assert!(environment.iter().any(|var| var
+            .strip_prefix("PWD=")
+            .is_some_and(|pwd| is_same_file(Path::new(pwd), &cwd))));
+    }
+
+    #[test]
+    fn test_login_defaults() {
+        let mut variables = Variables::new();
+        assign(&mut variables, "HOME=/home/dss");
+        variables.set_login_defaults();
+
+        let user = User::from_uid(getuid()).unwrap().unwrap();
+        let hostname = uname().unwrap().nodename().to_string_lossy().into_owned();
+        let environment = variables.environment();
+        assert!(environment.contains(&format!("USER={}", user.name)));
+        assert!(environment.contains(&format!("LOGNAME={}", user.name)));
+        assert!(environment.contains(&format!("HOSTNAME={}", hostname)));
+
+        // Values which are set are left alone
+        assert_eq!(variables.get_str("HOME"), Some("/home/dss".into()));
+        assert!(!environment.iter().any(|var| var.starts_with("HOME=")));
+    }
+
+    #[test]
+    fn test_exported_and_readonly_attributes() {
+        let mut variables = Variables::new();
+
+        assign(&mut variables, "name=dss");
+        variables.set_exported_attribute("name", true);
+        assert_eq!(variables.environment(), vec!["name=dss".to_string()]);
+        variables.set_exported_attribute("name", false);
+        assert!(variables.environment().is_empty());
+
+        variables.set_readonly_attribute("name");
+        let assignment = Assignment::parse("name=other").unwrap();
+        assert!(variables.assign(&assignment).is_err());
+        assert!(variables.unset("name").is_err());
+        assert_eq!(variables.get_str("name"), Some("dss".into()));
+
+        assign(&mut variables, "other=dss");
+        assert!(variables.unset("other").is_ok());
+        assert!(variables.unset("other").is_ok());
+        assert_eq!(variables.get_str("other"), None);
+        assert_eq!(
+            variables.declaration("name"),
+            Some("declare -r name=dss".into())
+        );
+    }
+
+    #[test]
+    fn test_restricted_variables() {
+        let mut variables = Variables::new();
+        assign(&mut variables, "PATH=/bin");
+
+        variables.set_restricted(true);
+        let assignment = Assignment::parse("PATH=/tmp").unwrap();
+        assert!(variables.assign(&assignment).is_err());
+        assert_eq!(variables.get_str("PATH"), Some("/bin".into()));
+        assign(&mut variables, "name=dss");
+
+        variables.set_restricted(false);
+        assign(&mut variables, "PATH=/tmp");
+        assert_eq!(variables.get_str("PATH"), Some("/tmp".into()));
+    }
+}
@@ -6,18 +6,47 @@ pub enum ShellError {
     CommandNotFound(String),
     #[error("dss: parse error: could not parse: {0}\n")]
     ParseError(String),
-    #[error("dss: scan error: {0}\n")]
+    #[error("dss: {0}\n")]
     LexError(LexError),
+    // Token the error was found at, `newline` if input ended early
+    #[error("dss: syntax error near unexpected token `{0}'\n")]
+    SyntaxError(String),
+    // Builtin named was given arguments it does not take
+    #[error("dss: {0}: {1}\n")]
+    BuiltinError(&'static str, String),
+    #[error("dss: {0}\n")]
+    ExpansionError(String),
+    #[error("dss: {0}: readonly variable\n")]
+    ReadonlyVariable(String),
+    #[error("dss: unset: {0}: cannot unset: readonly variable\n")]
+    ReadonlyUnset(String),
+    #[error("dss: {0}: only builtins can run in sandbox\n")]
+    Sandboxed(String),
+    #[error("dss: {0}: restricted\n")]
+    Restricted(String),
+    #[error("dss: interrupted\n")]
+    Interrupted,
+    #[error("dss: no match: {0}\n")]
+    NoMatch(String),
+    #[error("dss: arithmetic error: {0}\n")]
+    ArithmeticError(String),
     #[error("dss: internal error [BUG]: {0}\n")]
     InternalError(String),
 }
 
 #[derive(Error, Debug)]
 pub enum LexError {
-    #[error("dss: syntax error: {message} on line: {line} for range: {range:?}\n")]
+    #[error("{message}")]
     SyntaxError {
         message: String,
         line: usize,
         range: (usize, usize),
     },
+    // Quote or substitution is still open at end of input
+    #[error("unexpected EOF while looking for matching `{expected}'")]
+    UnexpectedEof {
+        expected: char,
+        line: usize,
+        range: (usize, usize),
+    },
 }
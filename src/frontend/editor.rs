@@ -0,0 +1,672 @@
+// Line editing on a terminal, which is put in raw mode while a line is
+// read, so that keys arrive as they are typed and shell echoes them
+// itself. Supported keys are the ones moving the cursor (Left/Right,
+// Home/End, Ctrl-B/F/A/E) and the ones deleting around it (Backspace,
+// Delete, Ctrl-D, Ctrl-K, Ctrl-U), anything printable gets inserted.
+// Key bindings from inputrc take precedence over these. In vi mode ESC
+// switches to a few commands instead (h/l, 0/$, x/X, D, i/a).
+
+use std::io::{self, Write};
+
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+    sys::termios::{
+        tcgetattr, tcsetattr, InputFlags, LocalFlags, SetArg, SpecialCharacterIndices, Termios,
+    },
+    unistd::{isatty, read},
+};
+use unicode_width::UnicodeWidthChar;
+
+use super::{read_line, EditingMode, Inputrc, KeyBinding};
+use crate::interrupt;
+
+// Used when terminal does not tell its size
+const DEFAULT_COLUMNS: usize = 80;
+
+// How long vi mode waits for rest of a sequence after ESC
+const ESCAPE_TIMEOUT_MS: i32 = 50;
+
+// Reads line into buf the same way `read_line` does, letting it be
+// edited if stdin and stdout are a terminal. Prompt is expected to be
+// rendered already, taking up prompt_width columns. Editing starts out
+// with initial text in line, it is dropped if there is no terminal
+pub fn edit_line(
+    prompt_width: usize,
+    initial: &str,
+    inputrc: &Inputrc,
+    buf: &mut String,
+) -> io::Result<usize> {
+    if !isatty(0).unwrap_or(false) || !isatty(1).unwrap_or(false) {
+        return read_line(buf);
+    }
+
+    // Only Ctrl-C arriving while reading abandons the line
+    interrupt::clear();
+
+    let raw_mode = RawMode::enable()?;
+    let mut editor = LineEditor::new(prompt_width, initial, inputrc);
+    let result = interrupt::without_restart(|| editor.run())?;
+    drop(raw_mode);
+
+    if let Some(line) = result? {
+        buf.push_str(&line);
+        buf.push('\n');
+        return Ok(line.len() + 1);
+    }
+    Ok(0)
+}
+
+// Modes of terminal are restored when dropped
+struct RawMode {
+    modes: Termios,
+}
+
+impl RawMode {
+    fn enable() -> nix::Result<Self> {
+        let modes = tcgetattr(0)?;
+
+        let mut raw = modes.clone();
+        raw.input_flags
+            .remove(InputFlags::ICRNL | InputFlags::IXON | InputFlags::BRKINT);
+        raw.local_flags
+            .remove(LocalFlags::ECHO | LocalFlags::ICANON | LocalFlags::ISIG | LocalFlags::IEXTEN);
+        raw.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+        raw.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+
+        // Draining keeps what was typed ahead of the prompt
+        tcsetattr(0, SetArg::TCSADRAIN, &raw)?;
+        Ok(Self { modes })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = tcsetattr(0, SetArg::TCSADRAIN, &self.modes);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    KillToEnd,
+    KillToStart,
+    // Ctrl-C
+    Interrupt,
+    // Ctrl-D
+    EndOfFile,
+    // Vi mode only, ESC starts commands and `i` or `a` inserting again
+    CommandMode,
+    InsertMode,
+    AppendMode,
+    // Sequences which are not supported are dropped as a whole
+    Unknown,
+}
+
+// Keys are looked up in bindings from inputrc first, default ones of
+// editing mode are for sequences left unbound. In vi mode bindings
+// only apply while inserting
+struct Keymap {
+    bindings: Vec<KeyBinding>,
+    editing_mode: EditingMode,
+    // Vi mode only, whether keys are commands instead of being inserted
+    vi_command: bool,
+}
+
+impl Keymap {
+    fn new(inputrc: &Inputrc) -> Self {
+        Self {
+            bindings: inputrc.key_bindings.clone(),
+            editing_mode: inputrc.editing_mode,
+            vi_command: false,
+        }
+    }
+
+    // None while key sequence is only the start of a bound one
+    fn lookup(&self, key_seq: &[u8]) -> Option<Key> {
+        if self.vi_command {
+            return Some(vi_command_key(key_seq));
+        }
+
+        if let Some(binding) = self
+            .bindings
+            .iter()
+            .find(|binding| binding.key_seq == key_seq)
+        {
+            return Some(function_key(&binding.function));
+        }
+        if self
+            .bindings
+            .iter()
+            .any(|binding| binding.key_seq.starts_with(key_seq))
+        {
+            return None;
+        }
+
+        match (self.editing_mode, key_seq) {
+            (EditingMode::Vi, [0x1b]) => Some(Key::CommandMode),
+            _ => Some(default_key(key_seq)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct LineBuffer {
+    chars: Vec<char>,
+    // Index into chars, cursor is before the char at it
+    cursor: usize,
+}
+
+impl LineBuffer {
+    fn insert(&mut self, ch: char) {
+        self.chars.insert(self.cursor, ch);
+        self.cursor += 1;
+    }
+
+    // Edits return whether line or cursor changed
+    fn backspace(&mut self) -> bool {
+        if self.cursor == 0 {
+            return false;
+        }
+        self.cursor -= 1;
+        self.chars.remove(self.cursor);
+        true
+    }
+
+    fn delete(&mut self) -> bool {
+        if self.cursor == self.chars.len() {
+            return false;
+        }
+        self.chars.remove(self.cursor);
+        true
+    }
+
+    fn move_left(&mut self) -> bool {
+        self.move_to(self.cursor.saturating_sub(1))
+    }
+
+    fn move_right(&mut self) -> bool {
+        self.move_to((self.cursor + 1).min(self.chars.len()))
+    }
+
+    fn move_to(&mut self, cursor: usize) -> bool {
+        let moved = cursor != self.cursor;
+        self.cursor = cursor;
+        moved
+    }
+
+    fn kill_to_end(&mut self) -> bool {
+        let killed = self.cursor != self.chars.len();
+        self.chars.truncate(self.cursor);
+        killed
+    }
+
+    fn kill_to_start(&mut self) -> bool {
+        let killed = self.cursor != 0;
+        self.chars.drain(..self.cursor);
+        self.cursor = 0;
+        killed
+    }
+
+    // Columns chars take up, up to the cursor or all of them
+    fn width(&self, upto_cursor: bool) -> usize {
+        let end = if upto_cursor {
+            self.cursor
+        } else {
+            self.chars.len()
+        };
+        self.chars[..end]
+            .iter()
+            .map(|ch| ch.width().unwrap_or(0))
+            .sum()
+    }
+
+    fn line(&self) -> String {
+        self.chars.iter().collect()
+    }
+}
+
+struct LineEditor {
+    buffer: LineBuffer,
+    keymap: Keymap,
+    prompt_width: usize,
+    // Row cursor is on, counting from the first one of prompt
+    cursor_row: usize,
+}
+
+impl LineEditor {
+    fn new(prompt_width: usize, initial: &str, inputrc: &Inputrc) -> Self {
+        let chars: Vec<char> = initial.chars().collect();
+        Self {
+            buffer: LineBuffer {
+                cursor: chars.len(),
+                chars,
+            },
+            keymap: Keymap::new(inputrc),
+            prompt_width,
+            cursor_row: prompt_width / terminal_columns(),
+        }
+    }
+
+    // Returns the line once it is accepted, or None on EOF
+    fn run(&mut self) -> io::Result<Option<String>> {
+        if !self.buffer.chars.is_empty() {
+            self.redraw()?;
+        }
+
+        loop {
+            let changed = match self.read_key()? {
+                Key::Char(ch) => {
+                    self.buffer.insert(ch);
+                    true
+                }
+                Key::Enter => {
+                    // Line is left shown in full, output of command follows it
+                    self.buffer.cursor = self.buffer.chars.len();
+                    self.redraw()?;
+                    write_all("\r\n")?;
+                    return Ok(Some(self.buffer.line()));
+                }
+                Key::Backspace => self.buffer.backspace(),
+                Key::Delete => self.buffer.delete(),
+                Key::Left => self.buffer.move_left(),
+                Key::Right => self.buffer.move_right(),
+                Key::Home => self.buffer.move_to(0),
+                Key::End => self.buffer.move_to(self.buffer.chars.len()),
+                Key::KillToEnd => self.buffer.kill_to_end(),
+                Key::KillToStart => self.buffer.kill_to_start(),
+                Key::Interrupt => {
+                    // Abandoned line stays shown too
+                    self.buffer.cursor = self.buffer.chars.len();
+                    self.redraw()?;
+                    write_all("^C")?;
+                    return Err(io::Error::from(io::ErrorKind::Interrupted));
+                }
+                // Only ends input on an empty line, otherwise it deletes
+                Key::EndOfFile if self.buffer.chars.is_empty() => return Ok(None),
+                Key::EndOfFile => self.buffer.delete(),
+                // Cursor steps back onto the last char inserted, same as vi
+                Key::CommandMode => {
+                    self.keymap.vi_command = true;
+                    self.buffer.move_left()
+                }
+                Key::InsertMode => {
+                    self.keymap.vi_command = false;
+                    false
+                }
+                Key::AppendMode => {
+                    self.keymap.vi_command = false;
+                    self.buffer.move_right()
+                }
+                Key::Unknown => false,
+            };
+
+            if changed {
+                self.redraw()?;
+            }
+        }
+    }
+
+    // Keys are read for as long as they make up the start of a bound sequence
+    fn read_key(&self) -> io::Result<Key> {
+        let lone_escape = self.keymap.editing_mode == EditingMode::Vi;
+        let mut key_seq = vec![];
+
+        loop {
+            match read_key_seq(lone_escape)? {
+                Some(bytes) => key_seq.extend(bytes),
+                // Terminal gone, same as EOF
+                None => return Ok(Key::EndOfFile),
+            }
+
+            if let Some(key) = self.keymap.lookup(&key_seq) {
+                return Ok(key);
+            }
+        }
+    }
+
+    fn redraw(&mut self) -> io::Result<()> {
+        let (output, cursor_row) = render(
+            &self.buffer,
+            self.prompt_width,
+            self.cursor_row,
+            terminal_columns(),
+        );
+        self.cursor_row = cursor_row;
+        write_all(&output)
+    }
+}
+
+// Escape sequences redrawing line after prompt, with cursor starting on
+// cursor_row. Returns them along with the row cursor ends up on. Line
+// wraps at given columns, rows are counted from the first one of prompt
+fn render(
+    buffer: &LineBuffer,
+    prompt_width: usize,
+    cursor_row: usize,
+    columns: usize,
+) -> (String, usize) {
+    let mut output = String::new();
+
+    // Back to where line starts, clearing everything after it
+    move_cursor(&mut output, cursor_row, prompt_width, columns);
+    output.push_str("\x1b[J");
+
+    output.extend(&buffer.chars);
+
+    // Terminal does not wrap until something is written past the last
+    // column, cursor is moved to next row right away instead
+    let end = prompt_width + buffer.width(false);
+    if end > prompt_width && end.is_multiple_of(columns) {
+        output.push_str("\r\n");
+    }
+
+    let position = prompt_width + buffer.width(true);
+    move_cursor(&mut output, end / columns, position, columns);
+
+    (output, position / columns)
+}
+
+// Cursor is only ever moved up, from cursor_row to row of position
+fn move_cursor(output: &mut String, cursor_row: usize, position: usize, columns: usize) {
+    let (row, column) = (position / columns, position % columns);
+    if cursor_row > row {
+        output.push_str(&format!("\x1b[{}A", cursor_row - row));
+    }
+    output.push('\r');
+    if column > 0 {
+        output.push_str(&format!("\x1b[{}C", column));
+    }
+}
+
+// Bytes terminal sends for a key, None on EOF. Sequences are either
+// `ESC [ params final`, `ESC O final` or ESC followed by a key, the way
+// meta keys are sent. With lone_escape, ESC not followed right away by
+// anything is a key of its own
+fn read_key_seq(lone_escape: bool) -> io::Result<Option<Vec<u8>>> {
+    let Some(first) = read_byte()? else {
+        return Ok(None);
+    };
+    let mut key_seq = vec![first];
+
+    match first {
+        0x1b if lone_escape && !input_pending()? => {}
+        0x1b => match read_byte()? {
+            Some(b'[') => {
+                key_seq.push(b'[');
+                while let Some(byte) = read_byte()? {
+                    key_seq.push(byte);
+                    if (0x40..=0x7e).contains(&byte) {
+                        break;
+                    }
+                }
+            }
+            Some(b'O') => {
+                key_seq.push(b'O');
+                key_seq.extend(read_byte()?);
+            }
+            Some(byte) => key_seq.push(byte),
+            None => {}
+        },
+        // Rest of the bytes of a UTF-8 encoded char
+        0xc0..=0xf7 => {
+            let len = match first {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                _ => 4,
+            };
+            while key_seq.len() < len {
+                match read_byte()? {
+                    Some(byte) => key_seq.push(byte),
+                    None => break,
+                }
+            }
+        }
+        _ => {}
+    }
+
+    Ok(Some(key_seq))
+}
+
+// Keys of emacs mode, which are also the ones vi mode inserts with
+fn default_key(key_seq: &[u8]) -> Key {
+    match key_seq {
+        [b'\r' | b'\n'] => Key::Enter,
+        [0x7f | 0x08] => Key::Backspace,
+        [0x01] => Key::Home,
+        [0x02] => Key::Left,
+        [0x03] => Key::Interrupt,
+        [0x04] => Key::EndOfFile,
+        [0x05] => Key::End,
+        [0x06] => Key::Right,
+        [0x0b] => Key::KillToEnd,
+        [0x15] => Key::KillToStart,
+        [0x1b, b'[', rest @ ..] => match rest.split_last() {
+            Some((final_byte, params)) => csi_key(params, *final_byte),
+            None => Key::Unknown,
+        },
+        [0x1b, b'O', b'H'] => Key::Home,
+        [0x1b, b'O', b'F'] => Key::End,
+        [byte] if *byte < 0x20 => Key::Unknown,
+        _ => {
+            let text = std::str::from_utf8(key_seq).unwrap_or_default();
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) => Key::Char(ch),
+                _ => Key::Unknown,
+            }
+        }
+    }
+}
+
+// Commands of vi mode, apart from these only keys which are
+// not inserted, e.g. Enter or arrows, do the same as in emacs mode
+fn vi_command_key(key_seq: &[u8]) -> Key {
+    match key_seq {
+        b"h" => Key::Left,
+        b"l" | b" " => Key::Right,
+        b"0" | b"^" => Key::Home,
+        b"$" => Key::End,
+        b"x" => Key::Delete,
+        b"X" => Key::Backspace,
+        b"D" => Key::KillToEnd,
+        b"i" => Key::InsertMode,
+        b"a" => Key::AppendMode,
+        [0x7f | 0x08] => Key::Left,
+        _ => match default_key(key_seq) {
+            Key::Char(_) => Key::Unknown,
+            key => key,
+        },
+    }
+}
+
+// Functions of inputrc which editor supports, others are ignored
+fn function_key(function: &str) -> Key {
+    match function {
+        "accept-line" => Key::Enter,
+        "backward-char" => Key::Left,
+        "forward-char" => Key::Right,
+        "backward-delete-char" => Key::Backspace,
+        "delete-char" => Key::Delete,
+        "beginning-of-line" => Key::Home,
+        "end-of-line" => Key::End,
+        "kill-line" => Key::KillToEnd,
+        "unix-line-discard" => Key::KillToStart,
+        _ => Key::Unknown,
+    }
+}
+
+fn csi_key(params: &[u8], final_byte: u8) -> Key {
+    match (params, final_byte) {
+        (_, b'C') => Key::Right,
+        (_, b'D') => Key::Left,
+        (_, b'H') => Key::Home,
+        (_, b'F') => Key::End,
+        (b"1" | b"7", b'~') => Key::Home,
+        (b"4" | b"8", b'~') => Key::End,
+        (b"3", b'~') => Key::Delete,
+        _ => Key::Unknown,
+    }
+}
+
+// None means EOF
+fn read_byte() -> io::Result<Option<u8>> {
+    let mut byte = [0; 1];
+    loop {
+        match read(0, &mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(Errno::EINTR) if interrupt::is_interrupted() => {
+                return Err(io::Error::from(io::ErrorKind::Interrupted));
+            }
+            Err(Errno::EINTR) => continue,
+            Err(err) => return Err(io::Error::from(err)),
+        }
+    }
+}
+
+// Whether more input arrives within ESCAPE_TIMEOUT_MS
+fn input_pending() -> io::Result<bool> {
+    let mut fds = [PollFd::new(0, PollFlags::POLLIN)];
+    match poll(&mut fds, ESCAPE_TIMEOUT_MS) {
+        Ok(ready) => Ok(ready > 0),
+        Err(Errno::EINTR) if interrupt::is_interrupted() => {
+            Err(io::Error::from(io::ErrorKind::Interrupted))
+        }
+        Err(Errno::EINTR) => Ok(false),
+        Err(err) => Err(io::Error::from(err)),
+    }
+}
+
+fn terminal_columns() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let res = unsafe { libc::ioctl(1, libc::TIOCGWINSZ, &mut size) };
+    if res == 0 && size.ws_col > 0 {
+        size.ws_col as usize
+    } else {
+        DEFAULT_COLUMNS
+    }
+}
+
+fn write_all(output: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(output.as_bytes())?;
+    stdout.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csi_key, render, Key, Keymap, LineBuffer};
+    use crate::frontend::Inputrc;
+
+    fn buffer(line: &str, cursor: usize) -> LineBuffer {
+        LineBuffer {
+            chars: line.chars().collect(),
+            cursor,
+        }
+    }
+
+    #[test]
+    fn test_editing_of_line_buffer() {
+        let mut line = LineBuffer::default();
+        for ch in "ech hi".chars() {
+            line.insert(ch);
+        }
+        assert!(line.move_to(3));
+        line.insert('o');
+        assert_eq!(line.line(), "echo hi");
+
+        assert!(line.move_to(0));
+        assert!(!line.move_left());
+        assert!(!line.backspace());
+        assert!(line.delete());
+        assert_eq!(line.line(), "cho hi");
+
+        assert!(line.move_to(line.chars.len()));
+        assert!(!line.move_right());
+        assert!(!line.delete());
+        assert!(line.backspace());
+        assert_eq!(line.line(), "cho h");
+
+        assert!(line.move_to(3));
+        assert!(line.kill_to_end());
+        assert_eq!(line.line(), "cho");
+        assert!(line.move_left());
+        assert!(line.kill_to_start());
+        assert_eq!((line.line(), line.cursor), ("o".to_string(), 0));
+    }
+
+    #[test]
+    fn test_width_of_line_buffer() {
+        let line = buffer("echo 日本", 6);
+        assert_eq!(line.width(true), 7);
+        assert_eq!(line.width(false), 9);
+    }
+
+    #[test]
+    fn test_rendering_of_line() {
+        // Cursor is put back after `echo`
+        let (output, row) = render(&buffer("echo hi", 4), 2, 0, 80);
+        assert_eq!(output, "\r\x1b[2C\x1b[Jecho hi\r\x1b[6C");
+        assert_eq!(row, 0);
+
+        // Line wraps at 10 columns, cursor goes back to first row
+        let (output, row) = render(&buffer("echo hello world", 2), 2, 0, 10);
+        assert_eq!(output, "\r\x1b[2C\x1b[Jecho hello world\x1b[1A\r\x1b[4C");
+        assert_eq!(row, 0);
+
+        // Filling the last column moves cursor to next row
+        let (output, row) = render(&buffer("echo one", 8), 2, 1, 10);
+        assert_eq!(output, "\x1b[1A\r\x1b[2C\x1b[Jecho one\r\n\r");
+        assert_eq!(row, 1);
+    }
+
+    #[test]
+    fn test_keys_of_escape_sequences() {
+        assert_eq!(csi_key(b"", b'D'), Key::Left);
+        assert_eq!(csi_key(b"1;5", b'C'), Key::Right);
+        assert_eq!(csi_key(b"1", b'~'), Key::Home);
+        assert_eq!(csi_key(b"4", b'~'), Key::End);
+        assert_eq!(csi_key(b"3", b'~'), Key::Delete);
+        assert_eq!(csi_key(b"2", b'~'), Key::Unknown);
+    }
+
+    #[test]
+    fn test_keys_of_bindings() {
+        let keymap = Keymap::new(&Inputrc::default());
+        assert_eq!(keymap.lookup(&[0x02]), Some(Key::Left));
+        assert_eq!(keymap.lookup(b"\x1b[D"), Some(Key::Left));
+        assert_eq!(keymap.lookup("é".as_bytes()), Some(Key::Char('é')));
+        assert_eq!(keymap.lookup(b"\x1bf"), Some(Key::Unknown));
+
+        let (inputrc, _) = Inputrc::parse("\"\\C-b\": end-of-line\n\"\\C-x\\C-k\": kill-line\n");
+        let keymap = Keymap::new(&inputrc);
+        assert_eq!(keymap.lookup(&[0x02]), Some(Key::End));
+        assert_eq!(keymap.lookup(&[0x06]), Some(Key::Right));
+        assert_eq!(keymap.lookup(&[0x18]), None);
+        assert_eq!(keymap.lookup(&[0x18, 0x0b]), Some(Key::KillToEnd));
+    }
+
+    #[test]
+    fn test_keys_of_vi_mode() {
+        let (inputrc, _) = Inputrc::parse("set editing-mode vi\n\"\\C-b\": end-of-line\n");
+        let mut keymap = Keymap::new(&inputrc);
+        assert_eq!(keymap.lookup(b"h"), Some(Key::Char('h')));
+        assert_eq!(keymap.lookup(&[0x02]), Some(Key::End));
+        assert_eq!(keymap.lookup(&[0x1b]), Some(Key::CommandMode));
+
+        keymap.vi_command = true;
+        assert_eq!(keymap.lookup(b"h"), Some(Key::Left));
+        assert_eq!(keymap.lookup(b"a"), Some(Key::AppendMode));
+        assert_eq!(keymap.lookup(b"q"), Some(Key::Unknown));
+        assert_eq!(keymap.lookup(&[0x02]), Some(Key::Left));
+        assert_eq!(keymap.lookup(b"\r"), Some(Key::Enter));
+    }
+}
@@ -0,0 +1,419 @@
+// Readline Init File Spec:
+// - URL: https://tiswww.cwru.edu/php/chet/readline/readline.html#Readline-Init-File
+//
+// Only a subset is supported: `editing-mode`, `completion-ignore-case`,
+// key bindings to known functions and `$if`/`$else`/`$endif` blocks.
+// Unknown variables are ignored, same as readline.
+
+use std::{env, fmt::Display, fs, path::PathBuf};
+
+// Functions which can be bound to a key sequence
+const BINDABLE_FUNCTIONS: [&str; 22] = [
+    "accept-line",
+    "backward-char",
+    "backward-delete-char",
+    "backward-kill-line",
+    "backward-kill-word",
+    "backward-word",
+    "beginning-of-line",
+    "clear-screen",
+    "complete",
+    "delete-char",
+    "end-of-line",
+    "forward-char",
+    "forward-word",
+    "kill-line",
+    "kill-word",
+    "next-history",
+    "previous-history",
+    "reverse-search-history",
+    "transpose-chars",
+    "unix-line-discard",
+    "unix-word-rubout",
+    "yank",
+];
+
+#[derive(Clone, Debug, Default)]
+pub struct Inputrc {
+    pub editing_mode: EditingMode,
+    pub completion_ignore_case: bool,
+    // Later bindings for same key sequence win
+    pub key_bindings: Vec<KeyBinding>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EditingMode {
+    #[default]
+    Emacs,
+    Vi,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBinding {
+    // Raw bytes sent by terminal for the key sequence
+    pub key_seq: Vec<u8>,
+    pub function: String,
+}
+
+impl Inputrc {
+    // Reads $INPUTRC or ~/.inputrc, problems found in
+    // the file are returned along with parsed config
+    pub fn load() -> (Self, Vec<String>) {
+        let path = match (env::var_os("INPUTRC"), env::var_os("HOME")) {
+            (Some(path), _) => PathBuf::from(path),
+            (None, Some(home)) => PathBuf::from(home).join(".inputrc"),
+            (None, None) => return (Self::default(), vec![]),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let (inputrc, problems) = Self::parse(&contents);
+                let problems = problems
+                    .into_iter()
+                    .map(|problem| format!("{}: {}", path.display(), problem))
+                    .collect();
+                (inputrc, problems)
+            }
+            Err(_) => (Self::default(), vec![]),
+        }
+    }
+
+    pub fn parse(contents: &str) -> (Self, Vec<String>) {
+        let mut inputrc = Self::default();
+        let mut problems = vec![];
+        // One entry per `$if`, whether its current branch is taken
+        let mut conditions: Vec<bool> = vec![];
+
+        for (line_no, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let result = if let Some(directive) = line.strip_prefix('$') {
+                inputrc.handle_directive(directive, &mut conditions)
+            } else if conditions.iter().all(|taken| *taken) {
+                inputrc.handle_line(line)
+            } else {
+                Ok(())
+            };
+
+            if let Err(problem) = result {
+                problems.push(format!("line {}: {}", line_no + 1, problem));
+            }
+        }
+
+        if !conditions.is_empty() {
+            problems.push("missing $endif".to_string());
+        }
+
+        (inputrc, problems)
+    }
+
+    fn handle_directive(&self, directive: &str, conditions: &mut Vec<bool>) -> Result<(), String> {
+        let (name, arg) = directive
+            .split_once(char::is_whitespace)
+            .map_or((directive, ""), |(name, arg)| (name, arg.trim()));
+
+        match name {
+            "if" => {
+                // Only our own application name and mode tests can be true
+                let taken = match arg.strip_prefix("mode=") {
+                    Some("emacs") => self.editing_mode == EditingMode::Emacs,
+                    Some("vi") => self.editing_mode == EditingMode::Vi,
+                    Some(_) => false,
+                    None => arg == "dss",
+                };
+                conditions.push(taken);
+            }
+            "else" => match conditions.last_mut() {
+                Some(taken) => *taken = !*taken,
+                None => return Err("$else without $if".to_string()),
+            },
+            "endif" => {
+                if conditions.pop().is_none() {
+                    return Err("$endif without $if".to_string());
+                }
+            }
+            _ => return Err(format!("unsupported directive ${}", name)),
+        }
+
+        Ok(())
+    }
+
+    fn handle_line(&mut self, line: &str) -> Result<(), String> {
+        if let Some(setting) = line.strip_prefix("set ") {
+            let mut parts = setting.split_whitespace();
+            let (Some(name), Some(value)) = (parts.next(), parts.next()) else {
+                return Err(format!("expected value for: {}", line));
+            };
+
+            match name.to_lowercase().as_str() {
+                "editing-mode" => {
+                    self.editing_mode = match value {
+                        "emacs" => EditingMode::Emacs,
+                        "vi" => EditingMode::Vi,
+                        _ => return Err(format!("invalid editing-mode: {}", value)),
+                    }
+                }
+                "completion-ignore-case" => {
+                    self.completion_ignore_case = parse_bool(value)?;
+                }
+                // Readline ignores unknown variables too
+                _ => {}
+            }
+
+            return Ok(());
+        }
+
+        let (key, function) = parse_key_binding(line)?;
+        self.bind(key, function)
+    }
+
+    // Key is either a quoted key sequence or a key name,
+    // in the same way they are written in inputrc
+    pub fn bind(&mut self, key: &str, function: &str) -> Result<(), String> {
+        if !BINDABLE_FUNCTIONS.contains(&function) {
+            return Err(format!("unsupported function: {}", function));
+        }
+
+        let key_seq = match key.strip_prefix('"') {
+            Some(quoted) => {
+                let Some(quoted) = quoted.strip_suffix('"') else {
+                    return Err(format!("unterminated key sequence: {}", key));
+                };
+                parse_key_seq(quoted)?
+            }
+            None => parse_key_name(key)?,
+        };
+
+        self.key_bindings.retain(|binding| binding.key_seq != key_seq);
+        self.key_bindings.push(KeyBinding {
+            key_seq,
+            function: function.to_string(),
+        });
+
+        Ok(())
+    }
+}
+
+impl Display for EditingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let variant_str = match self {
+            EditingMode::Emacs => "emacs",
+            EditingMode::Vi => "vi",
+        };
+
+        write!(f, "{}", variant_str)
+    }
+}
+
+// Formats binding the way `bind -p` prints it, e.g. "\C-a": beginning-of-line
+impl Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut key_seq = String::new();
+        for byte in &self.key_seq {
+            match byte {
+                0x1b => key_seq.push_str("\\e"),
+                0x7f => key_seq.push_str("\\C-?"),
+                b'"' | b'\\' => {
+                    key_seq.push('\\');
+                    key_seq.push(*byte as char);
+                }
+                0..=0x1f => {
+                    key_seq.push_str("\\C-");
+                    key_seq.push((byte + 0x60) as char);
+                }
+                _ => key_seq.push(*byte as char),
+            }
+        }
+
+        write!(f, "\"{}\": {}", key_seq, self.function)
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "on" | "1" => Ok(true),
+        "off" | "0" => Ok(false),
+        _ => Err(format!("invalid boolean value: {}", value)),
+    }
+}
+
+// Splits `key: function`, key can be quoted and contain `:`
+fn parse_key_binding(line: &str) -> Result<(&str, &str), String> {
+    let separator = if line.starts_with('"') {
+        let mut escaped = false;
+        let closing_quote = line.char_indices().skip(1).find(|(_, ch)| {
+            let is_closing = *ch == '"' && !escaped;
+            escaped = *ch == '\\' && !escaped;
+            is_closing
+        });
+
+        closing_quote.and_then(|(idx, _)| line[idx..].find(':').map(|offset| idx + offset))
+    } else {
+        line.find(':')
+    };
+
+    let Some(separator) = separator else {
+        return Err(format!("expected key binding: {}", line));
+    };
+
+    Ok((line[..separator].trim(), line[separator + 1..].trim()))
+}
+
+// Quoted key sequences, e.g. "\C-x\C-r" or "\ef"
+fn parse_key_seq(key_seq: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    let mut chars = key_seq.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            bytes.extend(ch.to_string().as_bytes());
+            continue;
+        }
+
+        match chars.next() {
+            Some('C') | Some('M') if !chars.as_str().starts_with('-') => {
+                return Err(format!("invalid key sequence: {}", key_seq))
+            }
+            Some('C') => {
+                chars.next();
+                let Some(ch) = chars.next() else {
+                    return Err(format!("invalid key sequence: {}", key_seq));
+                };
+                bytes.push(control(ch)?);
+            }
+            Some('M') => {
+                chars.next();
+                let Some(ch) = chars.next() else {
+                    return Err(format!("invalid key sequence: {}", key_seq));
+                };
+                bytes.push(0x1b);
+                bytes.extend(ch.to_string().as_bytes());
+            }
+            Some('e') => bytes.push(0x1b),
+            Some('t') => bytes.push(b'\t'),
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('a') => bytes.push(0x07),
+            Some(ch @ ('"' | '\'' | '\\')) => bytes.push(ch as u8),
+            _ => return Err(format!("invalid key sequence: {}", key_seq)),
+        }
+    }
+
+    Ok(bytes)
+}
+
+// Unquoted key names, e.g. Control-a or Meta-f
+fn parse_key_name(key_name: &str) -> Result<Vec<u8>, String> {
+    let key_name_lower = key_name.to_lowercase();
+
+    let key = match key_name_lower.as_str() {
+        "rubout" | "del" => return Ok(vec![0x7f]),
+        "escape" | "esc" => return Ok(vec![0x1b]),
+        "return" | "ret" | "newline" | "lfd" => return Ok(vec![b'\r']),
+        "tab" => return Ok(vec![b'\t']),
+        "space" | "spc" => return Ok(vec![b' ']),
+        _ => key_name,
+    };
+
+    let (prefix, ch) = match key.rsplit_once('-') {
+        Some((prefix, ch)) => (prefix.to_lowercase(), ch),
+        None => (String::new(), key),
+    };
+
+    let mut ch_iter = ch.chars();
+    let (Some(ch), None) = (ch_iter.next(), ch_iter.next()) else {
+        return Err(format!("invalid key name: {}", key_name));
+    };
+
+    match prefix.as_str() {
+        "" => Ok(ch.to_string().into_bytes()),
+        "control" | "c" => Ok(vec![control(ch)?]),
+        "meta" | "m" => {
+            let mut bytes = vec![0x1b];
+            bytes.extend(ch.to_string().as_bytes());
+            Ok(bytes)
+        }
+        _ => Err(format!("invalid key name: {}", key_name)),
+    }
+}
+
+fn control(ch: char) -> Result<u8, String> {
+    match ch {
+        '?' => Ok(0x7f),
+        ch if ch.is_ascii_alphabetic() || "@[\\]^_".contains(ch) => {
+            Ok(ch.to_ascii_lowercase() as u8 & 0x1f)
+        }
+        _ => Err(format!("invalid control key: {}", ch)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EditingMode, Inputrc, KeyBinding};
+
+    #[test]
+    fn test_parsing_of_settings() {
+        let (inputrc, problems) = Inputrc::parse(
+            "# comment\nset editing-mode vi\nset completion-ignore-case On\nset bell-style none\n",
+        );
+
+        assert!(problems.is_empty());
+        assert_eq!(inputrc.editing_mode, EditingMode::Vi);
+        assert!(inputrc.completion_ignore_case);
+    }
+
+    #[test]
+    fn test_parsing_of_key_bindings() {
+        let (inputrc, problems) = Inputrc::parse(
+            "\"\\C-x\\C-r\": reverse-search-history\nMeta-f: forward-word\nControl-a: end-of-line\n\"\\C-a\": beginning-of-line\n",
+        );
+
+        assert!(problems.is_empty());
+        assert_eq!(
+            inputrc.key_bindings,
+            vec![
+                KeyBinding {
+                    key_seq: vec![0x18, 0x12],
+                    function: "reverse-search-history".into()
+                },
+                KeyBinding {
+                    key_seq: vec![0x1b, b'f'],
+                    function: "forward-word".into()
+                },
+                KeyBinding {
+                    key_seq: vec![0x01],
+                    function: "beginning-of-line".into()
+                },
+            ]
+        );
+        assert_eq!(
+            inputrc.key_bindings[0].to_string(),
+            "\"\\C-x\\C-r\": reverse-search-history"
+        );
+    }
+
+    #[test]
+    fn test_parsing_of_conditionals() {
+        let (inputrc, problems) = Inputrc::parse(
+            "$if Bash\nset editing-mode vi\n$else\nset completion-ignore-case on\n$endif\n$if mode=emacs\nC-k: kill-line\n$endif\n",
+        );
+
+        assert!(problems.is_empty());
+        assert_eq!(inputrc.editing_mode, EditingMode::Emacs);
+        assert!(inputrc.completion_ignore_case);
+        assert_eq!(inputrc.key_bindings.len(), 1);
+    }
+
+    #[test]
+    fn test_parsing_reports_problems() {
+        let (_, problems) = Inputrc::parse(
+            "set editing-mode nano\nC-a: launch-rockets\n\"\\C-a: kill-line\n$endif\n$include /etc/inputrc\n",
+        );
+
+        assert_eq!(problems.len(), 5);
+        assert!(problems[0].starts_with("line 1: "));
+    }
+}
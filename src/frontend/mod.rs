@@ -1,5 +1,15 @@
+mod editor;
+mod inputrc;
 mod writer;
 mod prompt;
+mod reader;
+mod spinner;
+mod width;
 
+pub use editor::*;
+pub use inputrc::*;
 pub use writer::*;
 pub use prompt::*;
+pub use reader::*;
+pub use spinner::*;
+pub use width::*;
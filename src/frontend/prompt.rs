@@ -1,25 +1,46 @@
-use super::{Color, write_to_shell_colored};
+use super::{Color, display_width, write_to_shell_colored};
 
-#[derive(Debug)]
+// Used when config does not set a prompt
+const DEFAULT_SYMBOL: &str = "$ ";
+
+#[derive(Clone, Debug)]
 pub struct Prompt {
     letter: String,
+    // Shown when a new command is expected
+    symbol: String,
     multiline_mode: bool,
     // color: Color,
 }
 
+impl Default for Prompt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Prompt {
 
     pub fn new() -> Self {
         Self {
-            letter: "$ ".into(),
+            letter: DEFAULT_SYMBOL.into(),
+            symbol: DEFAULT_SYMBOL.into(),
             multiline_mode: false,
         }
     }
 
-    pub fn render(&self, execution_successful: bool) -> anyhow::Result<()> {
+    // None goes back to the default prompt
+    pub fn set_symbol(&mut self, symbol: Option<&str>) {
+        self.symbol = symbol.unwrap_or(DEFAULT_SYMBOL).to_string();
+        if !self.multiline_mode {
+            self.letter = self.symbol.clone();
+        }
+    }
+
+    // Colored after exit status of the last command
+    pub fn render(&self, last_status: i32) -> anyhow::Result<()> {
         let color = if self.multiline_mode {
             Color::White
-        } else if execution_successful {
+        } else if last_status == 0 {
             Color::Green
         } else {
             Color::Red
@@ -29,13 +50,18 @@ impl Prompt {
         Ok(())
     }
 
+    // Columns rendered prompt takes up, input being edited starts after them
+    pub fn width(&self) -> usize {
+        display_width(&self.letter)
+    }
+
     pub fn activate_multiline_prompt(&mut self) {
         self.letter = "> ".into();
         self.multiline_mode = true;
     }
 
     pub fn deactivate_multiline_prompt(&mut self) {
-        self.letter = "$ ".into();
+        self.letter = self.symbol.clone();
         self.multiline_mode = false;
     }
 }
@@ -0,0 +1,38 @@
+// Lines are read from stdin a byte at a time, so that nothing past
+// the line is taken away from commands run with it as their stdin
+
+use std::io;
+
+use nix::{errno::Errno, unistd::read};
+
+use crate::interrupt;
+
+// Reads line into buf, returning number of bytes read, zero meaning
+// EOF. Ctrl-C abandons the line, which is reported as Interrupted.
+pub fn read_line(buf: &mut String) -> io::Result<usize> {
+    // Only Ctrl-C arriving while reading abandons the line
+    interrupt::clear();
+
+    let mut line = vec![];
+    let mut byte = [0; 1];
+    let result = interrupt::without_restart(|| loop {
+        match read(0, &mut byte) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {
+                line.push(byte[0]);
+                if byte[0] == b'\n' {
+                    return Ok(());
+                }
+            }
+            Err(Errno::EINTR) if interrupt::is_interrupted() => {
+                return Err(io::Error::from(io::ErrorKind::Interrupted));
+            }
+            Err(Errno::EINTR) => continue,
+            Err(err) => return Err(io::Error::from(err)),
+        }
+    })?;
+    result?;
+
+    buf.push_str(&String::from_utf8_lossy(&line));
+    Ok(line.len())
+}
@@ -0,0 +1,85 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    time::Duration,
+};
+
+const FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+const FRAME_NANOS: u128 = 100_000_000;
+
+// Status line drawn on terminal while a foreground job is running
+#[derive(Debug, Default)]
+pub struct Spinner {
+    // Frame drawn last, frames advance every 100ms
+    frame: Option<u128>,
+    // Nothing gets drawn without a controlling terminal
+    tty: Option<File>,
+}
+
+impl Spinner {
+    pub fn new() -> Self {
+        Self {
+            frame: None,
+            tty: OpenOptions::new().write(true).open("/dev/tty").ok(),
+        }
+    }
+
+    pub fn tick(&mut self, elapsed: Duration) -> anyhow::Result<()> {
+        let frame = elapsed.as_nanos() / FRAME_NANOS;
+        if self.frame == Some(frame) {
+            return Ok(());
+        }
+
+        self.frame = Some(frame);
+        self.draw(&format!("\r{}", status_line(frame, elapsed)))
+    }
+
+    // Time till frame after the one drawn at elapsed
+    pub fn next_frame_in(elapsed: Duration) -> Duration {
+        let into_frame = Duration::from_nanos((elapsed.as_nanos() % FRAME_NANOS) as u64);
+        Duration::from_nanos(FRAME_NANOS as u64) - into_frame
+    }
+
+    // Erases status line, so that it does not mix with what comes next
+    pub fn clear(&mut self) -> anyhow::Result<()> {
+        if self.frame.take().is_some() {
+            self.draw("\r\x1b[K")?;
+        }
+
+        Ok(())
+    }
+
+    fn draw(&mut self, output: &str) -> anyhow::Result<()> {
+        if let Some(tty) = &mut self.tty {
+            tty.write_all(output.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn status_line(frame: u128, elapsed: Duration) -> String {
+    let frame_char = FRAMES[(frame % FRAMES.len() as u128) as usize];
+    format!("{} {}s", frame_char, elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{status_line, Spinner};
+
+    #[test]
+    fn test_status_line() {
+        assert_eq!(status_line(0, Duration::from_millis(50)), "| 0s");
+        assert_eq!(status_line(33, Duration::from_millis(3300)), "/ 3s");
+        assert_eq!(status_line(35, Duration::from_millis(3500)), "\\ 3s");
+    }
+
+    #[test]
+    fn test_next_frame_in() {
+        let next_frame_in = Spinner::next_frame_in;
+        assert_eq!(next_frame_in(Duration::ZERO), Duration::from_millis(100));
+        assert_eq!(next_frame_in(Duration::from_millis(2030)), Duration::from_millis(70));
+    }
+}
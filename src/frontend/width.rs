@@ -0,0 +1,76 @@
+use unicode_width::UnicodeWidthChar;
+
+// Columns text takes up on terminal. Double width characters, like
+// CJK ones and most emoji, take two, combining characters none, and
+// escape sequences, like the ones coloring a prompt, none either
+pub fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch == '\x1b' {
+            skip_escape_sequence(&mut chars);
+            continue;
+        }
+
+        width += ch.width().unwrap_or(0);
+    }
+
+    width
+}
+
+// Cuts text so that it fits in given columns, marking the cut with `...`.
+// A double width character is never split in half
+pub fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if display_width(text) <= max_width {
+        return text.to_string();
+    }
+
+    let mut cut = String::new();
+    let mut width = 0;
+    for ch in text.chars() {
+        let ch_width = ch.width().unwrap_or(0);
+        if width + ch_width > max_width.saturating_sub(3) {
+            break;
+        }
+
+        width += ch_width;
+        cut.push(ch);
+    }
+
+    format!("{}...", cut.trim_end())
+}
+
+// Only CSI sequences, `ESC [ params final`, are expected
+fn skip_escape_sequence(chars: &mut std::str::Chars) {
+    if chars.clone().next() != Some('[') {
+        return;
+    }
+    chars.next();
+
+    for ch in chars.by_ref() {
+        if ('\x40'..='\x7e').contains(&ch) {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{display_width, truncate_to_width};
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("$ "), 2);
+        assert_eq!(display_width("日本語> "), 8);
+        assert_eq!(display_width("🦀 "), 3);
+        // `e` followed by combining acute accent
+        assert_eq!(display_width("cafe\u{301}"), 4);
+        assert_eq!(display_width("\x1b[32m$\x1b[0m "), 2);
+
+        assert_eq!(truncate_to_width("echo hi", 10), "echo hi");
+        assert_eq!(truncate_to_width("echo 日本語です", 10), "echo 日...");
+        assert_eq!(truncate_to_width("echo 日本語です", 11), "echo 日...");
+        assert_eq!(truncate_to_width("echo 日本語です", 12), "echo 日本...");
+    }
+}
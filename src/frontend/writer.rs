@@ -48,16 +48,6 @@ pub fn write_to_shell_colored(output: &str, color: Color) -> anyhow::Result<()>
     Ok(())
 }
 
-pub fn write_error_to_shell(
-    errno: Errno,
-    cmd_str: &str,
-    is_unqualified_path: bool,
-) -> anyhow::Result<()> {
-    if is_unqualified_path {
-        write_to_stderr(&format!("dss: command not found: {}\n", cmd_str))?;
-    } else {
-        write_to_stderr(&format!("dss: {}: {}\n", errno.desc(), cmd_str))?;
-    }
-
-    Ok(())
+pub fn write_error_to_shell(errno: Errno, cmd_str: &str) -> anyhow::Result<()> {
+    write_to_stderr(&format!("dss: {}: {}\n", cmd_str, errno.desc()))
 }
@@ -0,0 +1,196 @@
+// Signals caught by the shell itself. Handler only marks signal as
+// pending, it is acted upon once shell gets to a safe point, either
+// by running its trap or, for Ctrl-C in an interactive shell, by
+// interrupting work in progress, e.g. running a builtin, a loop or
+// expanding a glob over a huge tree. Long running work checks for it
+// and gives up cleanly, instead of the signal killing the whole shell.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use nix::{
+    errno::Errno,
+    sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, SigmaskHow, Signal},
+};
+
+// Indexed by signal number
+static PENDING: [AtomicBool; 65] = [const { AtomicBool::new(false) }; 65];
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+// Set once installed, SIGINT then interrupts even when not trapped
+static INTERRUPTIBLE: AtomicBool = AtomicBool::new(false);
+// Set once shell controls jobs, it then ignores job control signals
+static JOB_CONTROL: AtomicBool = AtomicBool::new(false);
+// Set once shell reaps any child of its own, see `watch_children`
+static WATCHING_CHILDREN: AtomicBool = AtomicBool::new(false);
+// Signals traps ignore, indexed by signal number
+static IGNORED: [AtomicBool; 65] = [const { AtomicBool::new(false) }; 65];
+
+extern "C" fn handle_signal(signum: libc::c_int) {
+    if let Some(pending) = PENDING.get(signum as usize) {
+        pending.store(true, Ordering::Relaxed);
+    }
+
+    if signum == libc::SIGINT && INTERRUPTIBLE.load(Ordering::Relaxed) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+    }
+}
+
+// Interrupted system calls get restarted, so that waiting
+// on a child is not cut short by a trapped signal
+fn set_handler(signal: Signal, handler: SigHandler) -> nix::Result<()> {
+    let action = SigAction::new(handler, SaFlags::SA_RESTART, SigSet::empty());
+    unsafe { sigaction(signal, &action) }?;
+    Ok(())
+}
+
+// Until installed SIGINT and SIGHUP keep their default actions,
+// which is what scripts want, only interactive shell catches them.
+// It ignores SIGQUIT, so that Ctrl-\ does not kill it either.
+pub fn install() -> anyhow::Result<()> {
+    INTERRUPTIBLE.store(true, Ordering::Relaxed);
+    set_handler(Signal::SIGINT, SigHandler::Handler(handle_signal))?;
+    set_handler(Signal::SIGHUP, SigHandler::Handler(handle_signal))?;
+    set_handler(Signal::SIGQUIT, SigHandler::SigIgn)?;
+    Ok(())
+}
+
+// dss catches SIGCHLD, reaping children as they exit. Applications
+// embedding it do not, as their own children would get reaped too.
+pub fn watch_children() -> nix::Result<()> {
+    WATCHING_CHILDREN.store(true, Ordering::Relaxed);
+    set_handler(Signal::SIGCHLD, SigHandler::Handler(handle_signal))
+}
+
+pub fn watches_children() -> bool {
+    WATCHING_CHILDREN.load(Ordering::Relaxed)
+}
+
+// Stopping the shell itself, be it by Ctrl-Z or by it touching the
+// terminal while in background, would leave nothing to resume it
+const JOB_CONTROL_SIGNALS: [Signal; 3] = [Signal::SIGTSTP, Signal::SIGTTIN, Signal::SIGTTOU];
+
+pub fn ignore_job_control() -> nix::Result<()> {
+    JOB_CONTROL.store(true, Ordering::Relaxed);
+    for signal in JOB_CONTROL_SIGNALS {
+        set_handler(signal, SigHandler::SigIgn)?;
+    }
+    Ok(())
+}
+
+// Forked children run commands with default actions of signals
+// interactive shell handles itself, unless a trap ignores them
+pub fn reset_in_child() -> nix::Result<()> {
+    INTERRUPTIBLE.store(false, Ordering::Relaxed);
+    JOB_CONTROL.store(false, Ordering::Relaxed);
+    for signal in [Signal::SIGINT, Signal::SIGQUIT]
+        .into_iter()
+        .chain(JOB_CONTROL_SIGNALS)
+    {
+        if !IGNORED[signal as usize].load(Ordering::Relaxed) {
+            set_handler(signal, SigHandler::SigDfl)?;
+        }
+    }
+    Ok(())
+}
+
+// Runs f with SIGINT cutting its system calls short with EINTR,
+// instead of them getting restarted, so that Ctrl-C can abandon
+// the line being read
+pub fn without_restart<T>(f: impl FnOnce() -> T) -> nix::Result<T> {
+    if !INTERRUPTIBLE.load(Ordering::Relaxed)
+        || IGNORED[Signal::SIGINT as usize].load(Ordering::Relaxed)
+    {
+        return Ok(f());
+    }
+
+    let action = SigAction::new(
+        SigHandler::Handler(handle_signal),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    let old_action = unsafe { sigaction(Signal::SIGINT, &action) }?;
+    let result = f();
+    unsafe { sigaction(Signal::SIGINT, &old_action) }?;
+    Ok(result)
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+// Done before a new command starts, interrupts only cancel the work
+// in progress when they arrived
+pub fn clear() {
+    INTERRUPTED.store(false, Ordering::Relaxed);
+}
+
+// Signal is marked pending instead of taking its action
+pub fn catch(signal: Signal) -> nix::Result<()> {
+    IGNORED[signal as usize].store(false, Ordering::Relaxed);
+    set_handler(signal, SigHandler::Handler(handle_signal))
+}
+
+// Commands started by shell keep ignoring it too
+pub fn ignore(signal: Signal) -> nix::Result<()> {
+    IGNORED[signal as usize].store(true, Ordering::Relaxed);
+    set_handler(signal, SigHandler::SigIgn)
+}
+
+// Back to what the shell does with signal when it is not trapped
+pub fn restore(signal: Signal) -> nix::Result<()> {
+    IGNORED[signal as usize].store(false, Ordering::Relaxed);
+    if matches!(signal, Signal::SIGINT | Signal::SIGHUP) && INTERRUPTIBLE.load(Ordering::Relaxed)
+        || signal == Signal::SIGCHLD && WATCHING_CHILDREN.load(Ordering::Relaxed)
+    {
+        return catch(signal);
+    }
+    if signal == Signal::SIGQUIT && INTERRUPTIBLE.load(Ordering::Relaxed)
+        || JOB_CONTROL_SIGNALS.contains(&signal) && JOB_CONTROL.load(Ordering::Relaxed)
+    {
+        return set_handler(signal, SigHandler::SigIgn);
+    }
+    set_handler(signal, SigHandler::SigDfl)
+}
+
+// Runs f with SIGCHLD blocked, so that one arriving between checking
+// on a child and waiting for signal is not missed by the wait
+pub fn with_child_signal_blocked<T>(f: impl FnOnce() -> T) -> nix::Result<T> {
+    let mut mask = SigSet::empty();
+    mask.add(Signal::SIGCHLD);
+    let old_mask = mask.thread_swap_mask(SigmaskHow::SIG_BLOCK)?;
+    let result = f();
+    old_mask.thread_set_mask()?;
+    Ok(result)
+}
+
+// Sleeps till SIGCHLD arrives or timeout passes, SIGCHLD is expected
+// to be blocked. Signal taken here is still marked pending when shell
+// watches children, so that it gets reaped and trapped as usual
+pub fn wait_for_child_signal(timeout: Duration) -> nix::Result<()> {
+    let mut mask = SigSet::empty();
+    mask.add(Signal::SIGCHLD);
+    let timeout = libc::timespec {
+        tv_sec: timeout.as_secs() as libc::time_t,
+        tv_nsec: timeout.subsec_nanos() as libc::c_long,
+    };
+
+    let signum = unsafe { libc::sigtimedwait(mask.as_ref(), std::ptr::null_mut(), &timeout) };
+    if signum == libc::SIGCHLD && WATCHING_CHILDREN.load(Ordering::Relaxed) {
+        PENDING[libc::SIGCHLD as usize].store(true, Ordering::Relaxed);
+    }
+
+    match Errno::result(signum) {
+        Ok(_) | Err(Errno::EAGAIN | Errno::EINTR) => Ok(()),
+        Err(errno) => Err(errno),
+    }
+}
+
+// Signals received since last call, in order of their numbers
+pub fn take_pending() -> Vec<Signal> {
+    Signal::iterator()
+        .filter(|signal| PENDING[*signal as usize].swap(false, Ordering::Relaxed))
+        .collect()
+}
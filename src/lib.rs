@@ -0,0 +1,7 @@
+// dss can be embedded in other applications too, running commands
+// with `Engine`, which builtins of application can be registered with
+pub mod command;
+pub mod engine;
+pub mod errors;
+pub mod frontend;
+pub mod interrupt;
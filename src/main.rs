@@ -1,18 +1,102 @@
-mod command;
-mod engine;
-mod errors;
-mod frontend;
+use std::path::{Path, PathBuf};
 
-use engine::Engine;
+use dss::{
+    engine::{adapter, profile::StartupProfile, server, summary::ScriptSummary, Engine},
+    frontend::write_to_stderr,
+    interrupt,
+};
 
 // FIXME: Handle error properly everywhere using ShellError
 // FIXME: Remove all unnecessary clones
 // FIXME: Refine APIs exposed by Engine and Command
 
 fn main() -> anyhow::Result<()> {
-    let mut engine = Engine::new();
+    let mut profile_startup = false;
+    let mut resume = false;
+    let mut summary = false;
+    let mut test_adapter = false;
+    let mut server_socket = None;
+    let mut script = None;
+    let mut command_string = None;
+    let mut restricted = false;
+    let mut args = std::env::args();
+    // Login programs start login shell with `-` before its name
+    let mut login = args.next().is_some_and(|name| name.starts_with('-'));
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--profile" => profile_startup = true,
+            "--resume" => resume = true,
+            "--summary" => summary = true,
+            "--posix-test-adapter" => test_adapter = true,
+            "-l" | "--login" => login = true,
+            "-r" => restricted = true,
+            "--server" => match args.next() {
+                Some(socket) => server_socket = Some(PathBuf::from(socket)),
+                None => {
+                    write_to_stderr("dss: --server: socket path expected\n")?;
+                    std::process::exit(2);
+                }
+            },
+            // Like with sh, word after command string is its $0, which
+            // dss has no use for, and rest are its arguments
+            "-c" => match args.next() {
+                Some(command) => {
+                    command_string = Some(command);
+                    args.next();
+                    break;
+                }
+                None => {
+                    write_to_stderr("dss: -c: option requires an argument\n")?;
+                    std::process::exit(2);
+                }
+            },
+            _ if arg.starts_with('-') => {
+                write_to_stderr(&format!("dss: {}: invalid option\n", arg))?;
+                std::process::exit(2);
+            }
+            // Words after script are its arguments
+            _ => {
+                script = Some(PathBuf::from(arg));
+                break;
+            }
+        }
+    }
 
-    engine.fire_on()?;
+    let mut profile = StartupProfile::new(profile_startup);
+    let mut engine = Engine::new(&mut profile);
+    engine.positional_params = args.collect();
+    engine.login = login;
+    engine.set_restricted(restricted);
+    // Children of shell are reaped as soon as they exit
+    interrupt::watch_children()?;
 
-    Ok(())
+    match (command_string, script, server_socket) {
+        _ if test_adapter => adapter::run(&mut engine)?,
+        (Some(command), _, _) => engine.execute_script(Path::new("-c"), &command)?,
+        (None, _, Some(socket)) => server::run(&mut engine, &socket)?,
+        (None, Some(path), None) => {
+            let contents = match std::fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(err) => {
+                    write_to_stderr(&format!("dss: {}: {}\n", path.display(), err))?;
+                    std::process::exit(127);
+                }
+            };
+            // Only scripts get summarized
+            if summary {
+                engine.summary = Some(ScriptSummary::default());
+            }
+            engine.execute_script(&path, &contents)?;
+        }
+        (None, None, None) => engine.fire_on(&mut profile, resume)?,
+    }
+
+    // Every way of running shell ends up here, for trap to run once
+    engine.run_exit_trap()?;
+
+    if let Some(summary) = &engine.summary {
+        write_to_stderr(&summary.report())?;
+        std::process::exit(summary.exit_code(engine.exit_code()));
+    }
+    std::process::exit(engine.exit_code());
 }
@@ -0,0 +1,55 @@
+// Command string given with `dss -c`, words after it become $0
+// and positional parameters
+
+mod common;
+
+use std::process::Output;
+
+fn run(args: &[&str]) -> Output {
+    common::output(common::dss().args(args))
+}
+
+#[test]
+fn test_command_string() {
+    let output = run(&[
+        "-c",
+        "echo hi && echo $1 $#; sh -c 'exit 3'",
+        "dss",
+        "a",
+        "b",
+    ]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hi\na 2\n");
+    assert_eq!(output.status.code(), Some(3));
+
+    let output = run(&["-c", "echo one\nexit 4\necho two"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "one\n");
+    assert_eq!(output.status.code(), Some(4));
+
+    // Commands killed by a signal only leave their status behind
+    let output = run(&["-c", "sh -c 'kill -9 $$'; echo $?"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "137\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+
+    let output = run(&["-c", "if true; then\necho 'a"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "dss: -c: syntax error: unexpected end of file\n"
+    );
+    assert_eq!(output.status.code(), Some(1));
+
+    // Only the command with a syntax error is skipped
+    let output = run(&["-c", "a && && b; echo c\nfi\necho e"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "c\ne\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "dss: syntax error near unexpected token `&&'\n\
+         dss: syntax error near unexpected token `fi'\n"
+    );
+
+    // Trap on EXIT runs once, after the last command
+    let output = run(&["-c", "trap 'echo trapped' EXIT; echo body"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "body\ntrapped\n");
+
+    let output = run(&["-c"]);
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,78 @@
+// Helpers for tests running the dss binary. Not every test file uses
+// all of them, each one of those is compiled as a crate of its own
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Child, Command, Output, Stdio},
+};
+
+pub const DSS: &str = env!("CARGO_BIN_EXE_dss");
+
+pub fn dss() -> Command {
+    Command::new(DSS)
+}
+
+// Runs command to its end without any input
+pub fn output(command: &mut Command) -> Output {
+    command
+        .stdin(Stdio::null())
+        .output()
+        .expect("dss should have started")
+}
+
+// Starts command with all of its standard streams piped
+pub fn spawn(command: &mut Command) -> Child {
+    command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("dss should have started")
+}
+
+// Runs command to its end with input written to its stdin
+pub fn output_with_input(command: &mut Command, input: &str) -> Output {
+    let mut child = spawn(command);
+
+    // dss might exit without reading all of input
+    let _ = child
+        .stdin
+        .take()
+        .expect("stdin should be piped")
+        .write_all(input.as_bytes());
+
+    child.wait_with_output().expect("dss should have exited")
+}
+
+// Directory of a test, removed along with its contents once dropped.
+// Tests of a file run in parallel in the same process, so each of
+// them gives a name of its own
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("dss_{}_test_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("temp dir should have been created");
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
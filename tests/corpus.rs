@@ -0,0 +1,98 @@
+// Golden tests for the shell as a whole. Every `name.dss` script in
+// tests/corpus is run non-interactively and its stdout compared
+// with `name.stdout`, and its exit status with `name.status` (0 if
+// there is none). Adding a case is just adding these files.
+//
+// Scripts get a directory of their own in $TMPDIR for files they
+// create, it shows up as `$TMPDIR` in stdout they are compared by.
+
+mod common;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use common::TempDir;
+
+struct Case {
+    script: PathBuf,
+    expected_stdout: String,
+    expected_status: i32,
+}
+
+fn load_cases(dir: &Path) -> Vec<Case> {
+    let mut scripts: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("corpus directory should be readable")
+        .map(|entry| entry.expect("corpus entry should be readable").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "dss"))
+        .collect();
+    scripts.sort();
+
+    scripts
+        .into_iter()
+        .map(|script| {
+            let expected_stdout = fs::read_to_string(script.with_extension("stdout"))
+                .unwrap_or_else(|_| panic!("{}: missing .stdout file", script.display()));
+            let expected_status = fs::read_to_string(script.with_extension("status"))
+                .map(|status| status.trim().parse().expect("status should be a number"))
+                .unwrap_or(0);
+
+            Case {
+                script,
+                expected_stdout,
+                expected_status,
+            }
+        })
+        .collect()
+}
+
+// Returns description of how the case failed, if it did
+fn run_case(case: &Case, dir: &Path) -> Option<String> {
+    let name = case.script.file_stem().unwrap().to_string_lossy();
+    let tmp_dir = TempDir::new(&format!("corpus_{}", name));
+    let output = common::output(
+        common::dss()
+            .arg(&case.script)
+            .current_dir(dir)
+            .env("TMPDIR", tmp_dir.path()),
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout)
+        .replace(&tmp_dir.path().display().to_string(), "$TMPDIR");
+    let status = output.status.code().unwrap_or(-1);
+
+    if stdout == case.expected_stdout && status == case.expected_status {
+        return None;
+    }
+
+    Some(format!(
+        "{}:\n--- expected status {}, stdout:\n{}--- got status {}, stdout:\n{}--- stderr:\n{}",
+        case.script.display(),
+        case.expected_status,
+        case.expected_stdout,
+        status,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    ))
+}
+
+#[test]
+fn test_corpus() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let cases = load_cases(&dir);
+    assert!(!cases.is_empty(), "corpus should not be empty");
+
+    let failures: Vec<String> = cases
+        .iter()
+        .filter_map(|case| run_case(case, &dir))
+        .collect();
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} corpus cases failed\n\n{}",
+        failures.len(),
+        cases.len(),
+        failures.join("\n")
+    );
+}
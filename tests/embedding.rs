@@ -0,0 +1,76 @@
+// dss used as a library, with builtins of application registered
+// on engine and commands run by it through `run_str`
+
+mod common;
+
+use common::TempDir;
+use dss::{
+    command::Assignment,
+    engine::{builtin::Io, profile::StartupProfile, Engine},
+};
+
+// Exit status of the builtin doing the assignment
+fn assign(engine: &mut Engine, word: &str) -> i32 {
+    let assignment = Assignment::parse(word).expect("expected a valid assignment word");
+    match engine.variables.assign(&assignment) {
+        Ok(()) => 0,
+        Err(_) => 1,
+    }
+}
+
+#[test]
+fn test_registered_builtins_run_in_shell() {
+    let mut engine = Engine::new(&mut StartupProfile::default());
+    engine.register_builtin(
+        "deploy",
+        |engine: &mut Engine, words: &[String], _: &mut Io| {
+            assign(engine, &format!("deployed={}", words[1..].join(",")))
+        },
+    );
+    engine.register_builtin("query", |_: &mut Engine, words: &[String], io: &mut Io| {
+        let _ = io.write_err(&format!("query: {}: no such table\n", words[1]));
+        1
+    });
+
+    engine
+        .run_str("env=staging\ndeploy web $env && query users || failed=1\n")
+        .unwrap();
+
+    assert_eq!(
+        engine.variables.get_str("deployed"),
+        Some("web,staging".into())
+    );
+    assert_eq!(engine.variables.get_str("failed"), Some("1".into()));
+}
+
+#[test]
+fn test_sandbox_runs_only_builtins() {
+    let mut engine = Engine::new(&mut StartupProfile::default());
+    engine.register_builtin(
+        "greet",
+        |engine: &mut Engine, words: &[String], _: &mut Io| {
+            assign(engine, &format!("greeted={}", words[1]))
+        },
+    );
+    engine.enable_sandbox();
+
+    let dir = TempDir::new("sandbox");
+    let file = dir.join("touched");
+    engine
+        .run_str(&format!(
+            "touch {} || external=failed\n\
+             (greeting=hi) || subshell=failed\n\
+             now=$(date) || substitution=failed\n\
+             greet world && declare -i n=1+1\n",
+            file.display()
+        ))
+        .unwrap();
+
+    assert!(!file.exists());
+    for name in ["external", "subshell", "substitution"] {
+        assert_eq!(engine.variables.get_str(name), Some("failed".into()));
+    }
+    assert_eq!(engine.variables.get_str("greeting"), None);
+    assert_eq!(engine.variables.get_str("greeted"), Some("world".into()));
+    assert_eq!(engine.variables.get_str("n"), Some("2".into()));
+}
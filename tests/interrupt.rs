@@ -0,0 +1,49 @@
+// Ctrl-C while the interactive shell is busy cancels what it is
+// running, without taking the shell down with it
+
+mod common;
+
+use std::{io::Write, thread, time::Duration};
+
+use nix::{
+    sys::signal::{kill, Signal},
+    unistd::Pid,
+};
+
+#[test]
+fn test_interrupt_cancels_rest_of_command_line() {
+    let mut shell = common::spawn(&mut common::dss());
+
+    let mut stdin = shell.stdin.take().unwrap();
+    stdin.write_all(b"sleep 1; echo after\n").unwrap();
+
+    // Only shell gets the signal, `sleep` still runs to its end
+    thread::sleep(Duration::from_millis(300));
+    kill(Pid::from_raw(shell.id() as i32), Signal::SIGINT).unwrap();
+
+    stdin.write_all(b"echo alive\n").unwrap();
+    drop(stdin);
+
+    let output = shell.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    assert!(stdout.contains("alive"), "{}", stdout);
+    assert!(!stdout.contains("after"), "{}", stdout);
+}
+
+#[test]
+fn test_commands_get_default_signal_actions() {
+    let output = common::output_with_input(
+        &mut common::dss(),
+        "sh -c 'kill -INT $$'\necho status: $?\nsh -c 'kill -QUIT $$'\necho status: $?\n",
+    );
+
+    // Interactive shell itself ignores SIGQUIT, commands it runs do not
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success());
+    let statuses: Vec<&str> = stdout
+        .lines()
+        .filter_map(|line| Some(line.split_once("status: ")?.1))
+        .collect();
+    assert_eq!(statuses, ["130", "131"], "{}", stdout);
+}
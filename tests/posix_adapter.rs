@@ -0,0 +1,49 @@
+// `dss --posix-test-adapter` reads script from stdin, DSS_TEST_*
+// variables set options, script name and where stderr goes
+
+mod common;
+
+use std::process::Output;
+
+// dss exits without reading script if adapter is set up wrong
+fn run_adapter(script: &str, envs: &[(&str, &str)]) -> Output {
+    common::output_with_input(
+        common::dss()
+            .arg("--posix-test-adapter")
+            .envs(envs.iter().copied()),
+        script,
+    )
+}
+
+#[test]
+fn test_adapter_runs_script_from_stdin() {
+    let output = run_adapter("echo one\nfalse || echo two\nexit 5\necho three\n", &[]);
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "one\ntwo\n");
+    assert_eq!(output.status.code(), Some(5));
+}
+
+#[test]
+fn test_adapter_modes() {
+    let output = run_adapter(
+        "echo /no/such/*.file\nls /no/such/dir\n",
+        &[
+            ("DSS_TEST_OPTIONS", "nullglob"),
+            ("DSS_TEST_STDERR", "stdout"),
+        ],
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("\n"), "{}", stdout);
+    assert!(stdout.contains("/no/such/dir"), "{}", stdout);
+    assert!(output.stderr.is_empty());
+
+    let output = run_adapter("if true\n", &[("DSS_TEST_NAME", "case.sh")]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "dss: case.sh: syntax error: unexpected end of file\n"
+    );
+
+    let output = run_adapter("echo\n", &[("DSS_TEST_STDERR", "file")]);
+    assert_eq!(output.status.code(), Some(2));
+}
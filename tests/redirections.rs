@@ -0,0 +1,40 @@
+// Files created by redirections, with umask dss is started with
+
+mod common;
+
+use std::{fs, os::unix::fs::PermissionsExt, process::Command};
+
+use common::TempDir;
+
+fn created_modes(umask: &str) -> Vec<u32> {
+    let dir = TempDir::new(&format!("redirections_{}", umask));
+
+    let script = dir.join("script.dss");
+    fs::write(
+        &script,
+        "echo hi > truncated\necho hi >> appended\ntrue <> read_write\n",
+    )
+    .unwrap();
+
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("umask {} && exec \"$0\" \"$1\"", umask))
+        .arg(common::DSS)
+        .arg(&script)
+        .current_dir(dir.path())
+        .status()
+        .expect("dss should have started");
+    assert!(status.success());
+
+    ["truncated", "appended", "read_write"]
+        .iter()
+        .map(|file| fs::metadata(dir.join(file)).unwrap().permissions().mode() & 0o777)
+        .collect()
+}
+
+#[test]
+fn test_created_files_honor_umask() {
+    assert_eq!(created_modes("022"), vec![0o644; 3]);
+    assert_eq!(created_modes("027"), vec![0o640; 3]);
+    assert_eq!(created_modes("000"), vec![0o666; 3]);
+}
@@ -0,0 +1,61 @@
+// `dss -r` refuses changing directory or PATH, running commands by
+// path and redirecting output, startup files are not held to it
+
+mod common;
+
+use std::{fs, process::Output};
+
+use common::TempDir;
+
+fn run_restricted(dir: &TempDir, command: &str) -> Output {
+    common::output(
+        common::dss()
+            .args(["-r", "-c", command])
+            .current_dir(dir.path()),
+    )
+}
+
+#[test]
+fn test_restricted_commands() {
+    let dir = TempDir::new("restricted");
+    let output = run_restricted(
+        &dir,
+        "cd /; pwd; PATH=/tmp; echo $PATH; /bin/echo by path; echo done",
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stdout.contains("by path"), "{}", stdout);
+    assert!(stdout.ends_with("done\n"), "{}", stdout);
+    assert_ne!(stdout.lines().next(), Some("/"));
+    assert_ne!(stdout.lines().nth(1), Some("/tmp"));
+    for forbidden in ["cd", "PATH", "/bin/echo"] {
+        assert!(
+            stderr.contains(&format!("dss: {}: restricted\n", forbidden)),
+            "{}",
+            stderr
+        );
+    }
+
+    let file = dir.join("file");
+    let output = run_restricted(&dir, &format!("echo hi > {}", file.display()));
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!file.exists());
+}
+
+#[test]
+fn test_startup_files_are_not_restricted() {
+    let home = TempDir::new("restricted_home");
+    fs::write(home.join(".dssrc"), "PATH=/usr/bin:/bin\n").unwrap();
+
+    let output = common::output_with_input(
+        common::dss()
+            .arg("-r")
+            .env("HOME", home.path())
+            .env_remove("DSS_RC"),
+        "PATH=/tmp\necho path: $PATH\n",
+    );
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("path: /usr/bin:/bin\n"), "{}", stdout);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("dss: PATH: restricted\n"));
+}
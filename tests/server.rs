@@ -0,0 +1,114 @@
+// `dss --server <socket>` runs each line a client sends as a
+// command, replying with its stdout, stderr and exit status
+
+mod common;
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::UnixStream,
+    path::Path,
+    process::Stdio,
+    thread,
+    time::Duration,
+};
+
+use common::TempDir;
+
+#[derive(Debug, PartialEq)]
+struct Reply {
+    stdout: String,
+    stderr: String,
+    status: i32,
+}
+
+fn connect(socket: &Path) -> UnixStream {
+    for _ in 0..50 {
+        if let Ok(stream) = UnixStream::connect(socket) {
+            return stream;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    panic!("server should have been listening on {}", socket.display());
+}
+
+fn read_section(reader: &mut impl BufRead, name: &str) -> String {
+    let mut header = String::new();
+    reader
+        .read_line(&mut header)
+        .expect("header should be readable");
+    let len = header
+        .trim_end()
+        .strip_prefix(&format!("{} ", name))
+        .and_then(|len| len.parse().ok())
+        .unwrap_or_else(|| panic!("expected {} header, got {:?}", name, header));
+
+    let mut content = vec![0; len];
+    reader
+        .read_exact(&mut content)
+        .expect("content should be readable");
+    String::from_utf8_lossy(&content).into_owned()
+}
+
+fn request(stream: &mut UnixStream, command: &str) -> Reply {
+    stream
+        .write_all(format!("{}\n", command).as_bytes())
+        .expect("request should have been written");
+
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let stdout = read_section(&mut reader, "stdout");
+    let stderr = read_section(&mut reader, "stderr");
+
+    let mut status = String::new();
+    reader.read_line(&mut status).unwrap();
+    let status = status
+        .trim_end()
+        .strip_prefix("status ")
+        .and_then(|status| status.parse().ok())
+        .expect("status should be a number");
+
+    Reply {
+        stdout,
+        stderr,
+        status,
+    }
+}
+
+#[test]
+fn test_server_keeps_state_between_commands() {
+    let dir = TempDir::new("server");
+    let socket = dir.join("dss.sock");
+    let mut server = common::dss()
+        .arg("--server")
+        .arg(&socket)
+        .stdin(Stdio::null())
+        .spawn()
+        .expect("dss should have started");
+
+    let mut stream = connect(&socket);
+    assert_eq!(
+        request(&mut stream, "cd /; greeting=hello"),
+        Reply {
+            stdout: "".into(),
+            stderr: "".into(),
+            status: 0
+        }
+    );
+    assert_eq!(
+        request(&mut stream, "echo $greeting $PWD").stdout,
+        "hello /\n"
+    );
+
+    let reply = request(&mut stream, "ls /no/such/dir");
+    assert!(reply.stderr.contains("/no/such/dir"), "{:?}", reply);
+    assert_eq!(reply.status, 2);
+    drop(stream);
+
+    // State is kept for the next client too
+    let mut stream = connect(&socket);
+    assert_eq!(request(&mut stream, "echo $greeting").stdout, "hello\n");
+    assert_eq!(request(&mut stream, "exit 3").status, 3);
+
+    let status = server.wait().expect("server should have exited");
+    assert_eq!(status.code(), Some(3));
+    assert!(!socket.exists());
+}
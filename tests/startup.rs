@@ -0,0 +1,49 @@
+// Startup files of user read before first prompt
+
+mod common;
+
+use std::{fs, os::unix::process::CommandExt, process::Command};
+
+use common::TempDir;
+
+// Shell with HOME set to home, and no rc file given
+fn dss(home: &TempDir) -> Command {
+    let mut command = common::dss();
+    command.env("HOME", home.path()).env_remove("DSS_RC");
+    command
+}
+
+// Value $FROM_PROFILE has at first prompt
+fn profile_value(command: &mut Command) -> String {
+    let output = common::output_with_input(command, "echo profile: $FROM_PROFILE\n");
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| Some(line.split_once("profile:")?.1.trim().to_string()))
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_login_shell_sources_profile() {
+    let home = TempDir::new("login");
+    fs::write(
+        home.join(".profile"),
+        "# read by login shells\nexport FROM_PROFILE=yes\n",
+    )
+    .unwrap();
+
+    assert_eq!(profile_value(dss(&home).arg0("-dss")), "yes");
+    assert_eq!(profile_value(dss(&home).arg("-l")), "yes");
+    assert_eq!(profile_value(&mut dss(&home)), "");
+}
+
+#[test]
+fn test_rc_file_is_sourced() {
+    let home = TempDir::new("rc");
+    fs::write(home.join(".dssrc"), "export FROM_PROFILE=dssrc\n").unwrap();
+    assert_eq!(profile_value(&mut dss(&home)), "dssrc");
+
+    // $DSS_RC is sourced instead
+    let rc = home.join("rc");
+    fs::write(&rc, "export FROM_PROFILE=rc\n").unwrap();
+    assert_eq!(profile_value(dss(&home).env("DSS_RC", &rc)), "rc");
+}
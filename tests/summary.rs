@@ -0,0 +1,26 @@
+// `dss --summary` lists commands of script with their status on
+// stderr once it is done, and fails if any of them did
+
+mod common;
+
+use std::fs;
+
+use common::TempDir;
+
+#[test]
+fn test_summary_of_script() {
+    let dir = TempDir::new("summary");
+    let script = dir.join("script.dss");
+    fs::write(&script, "echo one\n\nls /no/such/dir\necho two\n").unwrap();
+
+    let output = common::output(common::dss().arg("--summary").arg(&script));
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "one\ntwo\n");
+    assert!(stderr.contains("     3  failed "), "{}", stderr);
+    assert!(stderr.contains("     4  ok     "), "{}", stderr);
+    assert!(stderr.ends_with("  3 commands, 1 failed\n"), "{}", stderr);
+
+    // Last command succeeded, still the script fails
+    assert_eq!(output.status.code(), Some(1));
+}
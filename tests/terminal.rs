@@ -0,0 +1,140 @@
+// Foreground jobs of interactive shell get the terminal, which is
+// checked by running shell on a pseudo terminal of its own
+
+mod common;
+
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    os::unix::{io::FromRawFd, process::CommandExt},
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use nix::{pty::openpty, unistd::setsid};
+
+use common::TempDir;
+
+// Output shell wrote to terminal while running inputs, which are
+// typed one after another with a pause in between
+fn run_on_terminal(inputs: &[&str]) -> String {
+    run_command_on_terminal(common::dss(), inputs)
+}
+
+fn run_command_on_terminal(mut command: Command, inputs: &[&str]) -> String {
+    let pty = openpty(None, None).expect("pseudo terminal should have opened");
+
+    unsafe {
+        command
+            .stdin(Stdio::from_raw_fd(pty.slave))
+            .stdout(Stdio::from_raw_fd(libc::dup(pty.slave)))
+            .stderr(Stdio::from_raw_fd(libc::dup(pty.slave)))
+            .pre_exec(|| {
+                // Terminal becomes controlling one of shell
+                setsid()?;
+                if libc::ioctl(0, libc::TIOCSCTTY, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+    }
+    let mut shell = command.spawn().expect("dss should have started");
+    // Shell is left holding the only copies of slave side
+    drop(command);
+
+    let mut terminal = unsafe { File::from_raw_fd(pty.master) };
+    for (idx, input) in inputs.iter().enumerate() {
+        if idx > 0 {
+            thread::sleep(Duration::from_millis(300));
+        }
+        terminal.write_all(input.as_bytes()).unwrap();
+    }
+
+    // Reading fails once shell exits and slave side is closed
+    let mut output = vec![];
+    let mut buf = [0; 1024];
+    loop {
+        match terminal.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => output.extend_from_slice(&buf[..len]),
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        }
+    }
+
+    let status = shell.wait().unwrap();
+    let output = String::from_utf8_lossy(&output).into_owned();
+    assert!(status.success(), "{:?} {}", status, output);
+    output
+}
+
+#[test]
+fn test_foreground_job_owns_terminal() {
+    let output =
+        run_on_terminal(&["sh -c 'echo groups: $$ $(ps -o pgid= -o tpgid= -p $$)'\nexit\n"]);
+
+    // Command is in a group of its own, which is the foreground one
+    // while it runs
+    let groups: Vec<Vec<i32>> = output
+        .lines()
+        .filter_map(|line| line.split_once("groups: "))
+        .map(|(_, groups)| {
+            groups
+                .split_whitespace()
+                .map_while(|group| group.parse().ok())
+                .collect()
+        })
+        .filter(|groups: &Vec<i32>| groups.len() == 3)
+        .collect();
+    assert_eq!(groups.len(), 1, "{}", output);
+    assert_eq!(groups[0], [groups[0][0]; 3], "{}", output);
+}
+
+#[test]
+fn test_interrupt_abandons_line_being_typed() {
+    // Ctrl-C in the middle of a multiline command, and a Ctrl-\
+    // which shell ignores
+    let output = run_on_terminal(&[
+        "if true; then echo abandoned\n",
+        "\x03",
+        "\x1c",
+        "echo status: $?\nexit\n",
+    ]);
+
+    assert!(output.contains("status: 130"), "{}", output);
+    assert!(
+        !output.lines().any(|line| line == "abandoned"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn test_job_control_signals_stop_jobs_not_shell() {
+    // Ctrl-Z at the prompt is ignored, while command gets stopped
+    // till it is brought back to foreground
+    let output = run_on_terminal(&[
+        "\x1a",
+        "sh -c 'kill -TSTP $$; echo resumed'\necho status: $?\nfg\nexit\n",
+    ]);
+
+    assert!(output.contains("status: 148"), "{}", output);
+    assert!(output.lines().any(|line| line == "resumed"), "{}", output);
+}
+
+#[test]
+fn test_env_file_is_sourced() {
+    let dir = TempDir::new("env");
+    std::fs::write(dir.join("env"), "export FROM_ENV=yes\n").unwrap();
+
+    // Value of ENV is expanded
+    let mut command = common::dss();
+    command
+        .env("ENV", "${ENV_DIR}/${ENV_NAME}")
+        .env("ENV_DIR", dir.path())
+        .env("ENV_NAME", "env");
+    let output = run_command_on_terminal(command, &["echo from env: $FROM_ENV\nexit\n"]);
+
+    assert!(output.contains("from env: yes"), "{}", output);
+}
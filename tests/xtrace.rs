@@ -0,0 +1,31 @@
+// Commands traced with `set -x`, which go to stderr of the shell
+
+mod common;
+
+use std::fs;
+
+use common::TempDir;
+
+fn traced(script: &str) -> String {
+    let dir = TempDir::new("xtrace");
+    let path = dir.join("script.dss");
+    fs::write(&path, script).unwrap();
+
+    let output = common::output(common::dss().arg(&path));
+
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn test_commands_are_traced_after_expansion() {
+    assert_eq!(
+        traced("n='a b'\nset -x\necho $n \"$n\" > /dev/null\nm=$n\nset +x\necho $n > /dev/null\n"),
+        "+ echo a b 'a b'\n+ m='a b'\n+ set +x\n"
+    );
+
+    assert_eq!(
+        traced("set -x\nPS4='[$LINENO] '\ntrue | true\n"),
+        "+ PS4='[$LINENO] '\n[3] true\n[3] true\n"
+    );
+}